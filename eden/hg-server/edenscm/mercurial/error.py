@@ -28,6 +28,7 @@ from . import pycompat
 
 CertificateError = bindings.error.CertificateError
 CommitLookupError = bindings.error.CommitLookupError
+EdenApiError = bindings.error.EdenApiError
 HttpError = bindings.error.HttpError
 IndexedLogError = bindings.error.IndexedLogError
 MetaLogError = bindings.error.MetaLogError
@@ -14,13 +14,16 @@ use cpython_async::TStream;
 use cpython_ext::convert::Serde;
 use cpython_ext::{ExtractInner, ExtractInnerRef, PyPathBuf, ResultPyErrExt};
 use edenapi::{Builder, EdenApi};
-use edenapi_types::{CommitHashToLocationResponse, CommitLocationToHashResponse, CommitRevlogData};
+use edenapi_types::{
+    CommitHashToLocationResponse, CommitLocationToHashResponse, CommitRevlogData, FileEntry,
+};
 use progress::{NullProgressFactory, ProgressFactory};
 use pyconfigparser::config;
 use pyprogress::PyProgressFactory;
 use pyrevisionstore::{edenapifilestore, edenapitreestore};
 use revisionstore::{EdenApiFileStore, EdenApiTreeStore};
 
+use crate::cancel::cancellationtoken;
 use crate::pyext::EdenApiPyExt;
 use crate::stats::stats;
 
@@ -37,15 +40,25 @@ py_class!(pub class client |py| {
         _cls,
         config: config,
         ui: Option<PyObject> = None,
-        correlator: Option<String> = None
+        correlator: Option<String> = None,
+        retries: Option<usize> = None,
+        backoff_ms: Option<u64> = None
     ) -> PyResult<client> {
         let config = config.get_cfg(py);
-        let inner = Builder::from_config(&config)
+        let mut builder = Builder::from_config(&config)
             .map_pyerr(py)?
             .correlator(correlator)
-            .header("User-Agent", format!("EdenSCM/{}", version::VERSION))
-            .build()
-            .map_pyerr(py)?;
+            .header("User-Agent", format!("EdenSCM/{}", version::VERSION));
+
+        // Only override the values from config if they were passed in.
+        if retries.is_some() {
+            builder = builder.retries(retries);
+        }
+        if backoff_ms.is_some() {
+            builder = builder.backoff_ms(backoff_ms);
+        }
+
+        let inner = builder.build().map_pyerr(py)?;
 
         let progress = match ui {
             Some(ui) => PyProgressFactory::arc(py, ui)?,
@@ -70,16 +83,31 @@ py_class!(pub class client |py| {
         self.inner(py).clone().files_py(py, store, repo, keys, callback, progress)
     }
 
+    /// filesiter(repo: str, keys: [(path: str, node: bytes)], progress=None) -> [entry], stats
+    ///
+    /// Fetch file content, yielding each entry as soon as it is received
+    /// instead of waiting for the whole batch. Unlike `files`, the entries
+    /// are not written to a store, so the caller can do so incrementally.
+    def filesiter(
+        &self,
+        repo: String,
+        keys: Vec<(PyPathBuf, PyBytes)>,
+        callback: Option<PyObject> = None
+    ) -> PyResult<(TStream<anyhow::Result<Serde<FileEntry>>>, PyFuture)> {
+        self.inner(py).clone().files_iter_py(py, repo, keys, callback)
+    }
+
     def history(
         &self,
         store: PyObject,
         repo: String,
         keys: Vec<(PyPathBuf, PyBytes)>,
         length: Option<u32> = None,
-        callback: Option<PyObject> = None
+        callback: Option<PyObject> = None,
+        cancel: Option<cancellationtoken> = None
     ) -> PyResult<stats> {
         let progress = self.progress(py).clone();
-        self.inner(py).clone().history_py(py, store, repo, keys, length, callback, progress)
+        self.inner(py).clone().history_py(py, store, repo, keys, length, callback, progress, cancel)
     }
 
     def trees(
@@ -12,6 +12,7 @@
 
 use cpython::*;
 
+mod cancel;
 mod client;
 mod pyext;
 mod pytypes;
@@ -25,5 +26,6 @@ pub fn init_module(py: Python, package: &str) -> PyResult<PyModule> {
     let m = PyModule::new(py, &name)?;
     m.add_class::<client::client>(py)?;
     m.add_class::<stats::stats>(py)?;
+    m.add_class::<cancel::cancellationtoken>(py)?;
     Ok(m)
 }
@@ -5,7 +5,11 @@
  * GNU General Public License version 2.
  */
 
-use std::sync::Arc;
+use std::mem;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 
 use cpython::*;
 use futures::prelude::*;
@@ -16,14 +20,16 @@ use cpython_async::TStream;
 use cpython_ext::convert::Serde;
 use cpython_ext::{PyPathBuf, ResultPyErrExt};
 use dag_types::Location;
-use edenapi::{EdenApi, EdenApiBlocking, EdenApiError, Fetch, Stats};
+use edenapi::{EdenApi, EdenApiBlocking, EdenApiError, Fetch, Progress, ProgressCallback, Stats};
 use edenapi_types::{
     CommitHashToLocationResponse, CommitLocationToHashRequest, CommitLocationToHashResponse,
     CommitRevlogData, EdenApiServerError, FileEntry, HistoryEntry, TreeEntry,
 };
 use progress::{ProgressBar, ProgressFactory, Unit};
 use revisionstore::{HgIdMutableDeltaStore, HgIdMutableHistoryStore};
+use types::Key;
 
+use crate::cancel::cancellationtoken;
 use crate::pytypes::PyStats;
 use crate::stats::stats;
 use crate::util::{
@@ -72,6 +78,30 @@ pub trait EdenApiPyExt: EdenApi {
         stats::new(py, stats)
     }
 
+    fn files_iter_py(
+        self: Arc<Self>,
+        py: Python,
+        repo: String,
+        keys: Vec<(PyPathBuf, PyBytes)>,
+        callback: Option<PyObject>,
+    ) -> PyResult<(TStream<anyhow::Result<Serde<FileEntry>>>, PyFuture)> {
+        let keys = to_keys(py, &keys)?;
+        let callback = callback.map(wrap_callback);
+
+        let (files, stats) = py
+            .allow_threads(|| {
+                block_on_future(async move {
+                    let response = self.files(repo, keys, callback).await?;
+                    Ok::<_, EdenApiError>((response.entries, response.stats))
+                })
+            })
+            .map_pyerr(py)?;
+
+        let files_py = files.map_ok(Serde).map_err(Into::into);
+        let stats_py = PyFuture::new(py, stats.map_ok(PyStats))?;
+        Ok((files_py.into(), stats_py))
+    }
+
     fn history_py(
         self: Arc<Self>,
         py: Python,
@@ -81,12 +111,14 @@ pub trait EdenApiPyExt: EdenApi {
         length: Option<u32>,
         callback: Option<PyObject>,
         progress: Arc<dyn ProgressFactory>,
+        cancel: Option<cancellationtoken>,
     ) -> PyResult<stats> {
         let keys = to_keys(py, &keys)?;
         let store = as_historystore(py, store)?;
         let callback = callback.map(wrap_callback);
+        let cancel = cancel.map(|c| c.shared_flag(py));
 
-        let stats = py
+        let (stats, cancelled) = py
             .allow_threads(|| {
                 block_on_future(async move {
                     let prog = progress.bar(
@@ -94,13 +126,31 @@ pub trait EdenApiPyExt: EdenApi {
                         Some(keys.len() as u64),
                         Unit::Named("entries"),
                     )?;
-                    let response = self.history(repo, keys, length, callback).await?;
-                    write_history(response, store, prog.as_ref()).await
+                    match cancel {
+                        Some(cancel) => {
+                            let fetch = CancellableHistory {
+                                repo,
+                                length,
+                                store,
+                                cancel,
+                            };
+                            fetch.run(&*self, keys, callback, prog.as_ref()).await
+                        }
+                        None => {
+                            let response = self.history(repo, keys, length, callback).await?;
+                            let stats = write_history(response, store, prog.as_ref()).await?;
+                            Ok((stats, false))
+                        }
+                    }
                 })
             })
             .map_pyerr(py)?;
 
-        stats::new(py, stats)
+        if cancelled {
+            stats::cancelled(py, stats)
+        } else {
+            stats::new(py, stats)
+        }
     }
 
     fn trees_py(
@@ -303,3 +353,96 @@ async fn write_history(
     }
     response.stats.await
 }
+
+/// Keys requested at a time by a cancellable history fetch, so the
+/// cancellation token is polled between batches.
+const CANCELLABLE_HISTORY_BATCH_SIZE: usize = 1000;
+
+/// A history fetch that is split into batches of keys, checking before each
+/// batch whether it has been cancelled.
+struct CancellableHistory {
+    repo: String,
+    length: Option<u32>,
+    store: Arc<dyn HgIdMutableHistoryStore>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl CancellableHistory {
+    /// Returns the stats of the batches that were fetched, and whether the
+    /// fetch was cancelled before all of them were.
+    async fn run<T: EdenApi + ?Sized>(
+        self,
+        api: &T,
+        keys: Vec<Key>,
+        callback: Option<ProgressCallback>,
+        prog: &dyn ProgressBar,
+    ) -> Result<(Stats, bool), EdenApiError> {
+        let callback = callback.map(BatchProgress::new);
+        let mut total = Stats::default();
+        for batch in keys.chunks(CANCELLABLE_HISTORY_BATCH_SIZE) {
+            if self.cancel.load(Ordering::Relaxed) {
+                return Ok((total, true));
+            }
+            let batch_callback = callback.as_ref().map(|c| c.batch_callback());
+            let response = api
+                .history(
+                    self.repo.clone(),
+                    batch.to_vec(),
+                    self.length,
+                    batch_callback,
+                )
+                .await?;
+            let stats = write_history(response, self.store.clone(), prog).await?;
+            if let Some(callback) = &callback {
+                callback.batch_done();
+            }
+            add_batch_stats(&mut total, stats);
+        }
+        Ok((total, false))
+    }
+}
+
+/// Reports the progress of a fetch made in batches to a single callback, with
+/// each batch's progress added to that of the batches before it.
+#[derive(Clone)]
+struct BatchProgress {
+    // The callback, the progress of the finished batches and of the current one
+    inner: Arc<Mutex<(ProgressCallback, Progress, Progress)>>,
+}
+
+impl BatchProgress {
+    fn new(callback: ProgressCallback) -> Self {
+        let inner = (callback, Progress::default(), Progress::default());
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn batch_callback(&self) -> ProgressCallback {
+        let this = self.clone();
+        Box::new(move |progress| {
+            let mut inner = this.inner.lock().expect("lock poisoned");
+            let (callback, done, current) = &mut *inner;
+            *current = progress;
+            callback(*done + progress);
+        })
+    }
+
+    fn batch_done(&self) {
+        let mut inner = self.inner.lock().expect("lock poisoned");
+        let (_, done, current) = &mut *inner;
+        *done += mem::take(current);
+    }
+}
+
+/// Batches are fetched one after another, so their times add up. Latency is
+/// to the first response, so is that of the first batch.
+fn add_batch_stats(total: &mut Stats, batch: Stats) {
+    if total.requests == 0 {
+        total.latency = batch.latency;
+    }
+    total.downloaded += batch.downloaded;
+    total.uploaded += batch.uploaded;
+    total.requests += batch.requests;
+    total.time += batch.time;
+}
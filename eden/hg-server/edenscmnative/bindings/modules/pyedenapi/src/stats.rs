@@ -11,6 +11,7 @@ use edenapi::Stats;
 
 py_class!(pub class stats |py| {
     data stats: Stats;
+    data was_cancelled: bool;
 
     def __str__(&self) -> PyResult<String> {
         Ok(self.stats(py).to_string())
@@ -43,10 +44,22 @@ py_class!(pub class stats |py| {
     def bytes_per_second(&self) -> PyResult<f64> {
         Ok(self.stats(py).bytes_per_second())
     }
+
+    /// cancelled() -> bool
+    ///
+    /// Whether the fetch was cancelled before completing. The stats then only
+    /// cover the batches that completed before it was cancelled.
+    def cancelled(&self) -> PyResult<bool> {
+        Ok(*self.was_cancelled(py))
+    }
 });
 
 impl stats {
     pub fn new(py: Python, stats: Stats) -> PyResult<Self> {
-        Self::create_instance(py, stats)
+        Self::create_instance(py, stats, false)
+    }
+
+    pub fn cancelled(py: Python, stats: Stats) -> PyResult<Self> {
+        Self::create_instance(py, stats, true)
     }
 }
@@ -14,6 +14,9 @@ use taggederror_util::AnyhowEdenExt;
 py_exception!(error, CertificateError);
 py_exception!(error, CommitLookupError, exc::KeyError);
 py_exception!(error, HttpError);
+// Raised with args (message, status, url, curl_error), where the last three
+// are None if not known for the failure.
+py_exception!(error, EdenApiError, HttpError);
 py_exception!(error, IndexedLogError);
 py_exception!(error, MetaLogError);
 py_exception!(error, NonUTF8Path);
@@ -76,6 +79,7 @@ pub fn init_module(py: Python, package: &str) -> PyResult<PyModule> {
 
     m.add(py, "CertificateError", py.get_type::<CertificateError>())?;
     m.add(py, "CommitLookupError", py.get_type::<CommitLookupError>())?;
+    m.add(py, "EdenApiError", py.get_type::<EdenApiError>())?;
     m.add(py, "HttpError", py.get_type::<HttpError>())?;
     m.add(py, "IndexedLogError", py.get_type::<IndexedLogError>())?;
     m.add(py, "MetaLogError", py.get_type::<MetaLogError>())?;
@@ -162,25 +166,8 @@ fn register_error_handlers() {
                 py,
                 cpython_ext::Str::from(format!("{:?}", e)),
             ))
-        } else if let Some(edenapi::EdenApiError::Http(e)) =
-            e.downcast_ref::<edenapi::EdenApiError>()
-        {
-            match e {
-                http_client::HttpClientError::Tls(http_client::TlsError { source: e, .. }) => Some(
-                    PyErr::new::<TlsError, _>(py, cpython_ext::Str::from(e.to_string())),
-                ),
-                _ => Some(PyErr::new::<HttpError, _>(
-                    py,
-                    cpython_ext::Str::from(e.to_string()),
-                )),
-            }
-        } else if let Some(edenapi::EdenApiError::BadCertificate(e)) =
-            e.downcast_ref::<edenapi::EdenApiError>()
-        {
-            Some(PyErr::new::<CertificateError, _>(
-                py,
-                cpython_ext::Str::from(format!("{}", e)),
-            ))
+        } else if let Some(e) = e.downcast_ref::<edenapi::EdenApiError>() {
+            edenapi_error_handler(py, e)
         } else if e.is::<auth::X509Error>() {
             Some(PyErr::new::<CertificateError, _>(
                 py,
@@ -191,6 +178,44 @@ fn register_error_handlers() {
         }
     }
 
+    fn edenapi_error_handler(py: Python, e: &edenapi::EdenApiError) -> Option<PyErr> {
+        use http_client::HttpClientError;
+
+        let structured = |status: Option<u16>, url: Option<String>, curl_error: Option<String>| {
+            let message = cpython_ext::Str::from(e.to_string());
+            PyErr::new::<EdenApiError, _>(py, (message, status, url, curl_error))
+        };
+
+        match e {
+            edenapi::EdenApiError::Http(HttpClientError::Tls(http_client::TlsError {
+                source: e,
+                ..
+            })) => Some(PyErr::new::<TlsError, _>(
+                py,
+                cpython_ext::Str::from(e.to_string()),
+            )),
+            edenapi::EdenApiError::Http(HttpClientError::Curl(curl_error)) => Some(structured(
+                None,
+                None,
+                Some(curl_error.description().to_string()),
+            )),
+            edenapi::EdenApiError::Http(e) => Some(PyErr::new::<HttpError, _>(
+                py,
+                cpython_ext::Str::from(e.to_string()),
+            )),
+            edenapi::EdenApiError::HttpError { status, url, .. } => Some(structured(
+                Some(status.as_u16()),
+                Some(url.to_string()),
+                None,
+            )),
+            edenapi::EdenApiError::BadCertificate(e) => Some(PyErr::new::<CertificateError, _>(
+                py,
+                cpython_ext::Str::from(format!("{}", e)),
+            )),
+            _ => None,
+        }
+    }
+
     fn fallback_error_handler(py: Python, e: &error::Error, m: CommonMetadata) -> Option<PyErr> {
         TaggedExceptionData::create_instance(
             py,
@@ -33,6 +33,8 @@ pub struct Builder {
     max_history: Option<usize>,
     max_location_to_hash: Option<usize>,
     timeout: Option<Duration>,
+    retries: Option<usize>,
+    backoff_ms: Option<u64>,
     debug: bool,
     correlator: Option<String>,
     http_version: Option<HttpVersion>,
@@ -99,6 +101,14 @@ impl Builder {
             .map_err(|e| ConfigError::Malformed("edenapi.timeout".into(), e))?
             .map(Duration::from_secs);
 
+        let retries = config
+            .get_opt("edenapi", "retries")
+            .map_err(|e| ConfigError::Malformed("edenapi.retries".into(), e))?;
+
+        let backoff_ms = config
+            .get_opt("edenapi", "backoff-ms")
+            .map_err(|e| ConfigError::Malformed("edenapi.backoff-ms".into(), e))?;
+
         let debug = config
             .get_opt("edenapi", "debug")
             .map_err(|e| ConfigError::Malformed("edenapi.timeout".into(), e))?
@@ -133,6 +143,8 @@ impl Builder {
             max_history,
             max_location_to_hash,
             timeout,
+            retries,
+            backoff_ms,
             debug,
             correlator: None,
             http_version,
@@ -226,6 +238,19 @@ impl Builder {
         self
     }
 
+    /// Number of times a failed request will be retried before giving up.
+    pub fn retries(mut self, retries: Option<usize>) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Delay before the first retry of a failed request, in milliseconds.
+    /// The delay doubles with each subsequent retry.
+    pub fn backoff_ms(mut self, backoff_ms: Option<u64>) -> Self {
+        self.backoff_ms = backoff_ms;
+        self
+    }
+
     /// Unique identifier that will be logged by both the client and server for
     /// every request, allowing log entries on both sides to be correlated. Also
     /// allows correlating multiple requests that were made by the same instance
@@ -273,6 +298,8 @@ pub(crate) struct Config {
     pub(crate) max_history: Option<usize>,
     pub(crate) max_location_to_hash: Option<usize>,
     pub(crate) timeout: Option<Duration>,
+    pub(crate) retries: Option<usize>,
+    pub(crate) backoff_ms: Option<u64>,
     pub(crate) debug: bool,
     pub(crate) correlator: Option<String>,
     pub(crate) http_version: Option<HttpVersion>,
@@ -295,6 +322,8 @@ impl TryFrom<Builder> for Config {
             max_history,
             max_location_to_hash,
             timeout,
+            retries,
+            backoff_ms,
             debug,
             correlator,
             http_version,
@@ -328,6 +357,8 @@ impl TryFrom<Builder> for Config {
             max_history,
             max_location_to_hash,
             timeout,
+            retries,
+            backoff_ms,
             debug,
             correlator,
             http_version,
@@ -8,6 +8,8 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::{create_dir_all, File};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::{format_err, Context};
 use async_trait::async_trait;
@@ -33,7 +35,10 @@ use edenapi_types::{
     TreeRequest,
 };
 use hg_http::http_client;
-use http_client::{AsyncResponse, HttpClient, HttpClientError, Progress, Request};
+use http_client::{
+    AsyncResponse, HttpClient, HttpClientError, Progress, Request, Stats,
+    StatsFuture as HttpStatsFuture,
+};
 use types::{HgId, Key, RepoPathBuf};
 
 use crate::api::{EdenApi, ProgressCallback};
@@ -45,6 +50,9 @@ use crate::response::{Fetch, ResponseMeta};
 /// found in the repo's name will be percent-encoded before being used in URLs.
 const RESERVED_CHARS: &AsciiSet = &NON_ALPHANUMERIC.remove(b'_').remove(b'-').remove(b'.');
 
+/// Delay before the first retry of a failed fetch, if not configured.
+const DEFAULT_BACKOFF_MS: u64 = 100;
+
 mod paths {
     pub const HEALTH_CHECK: &str = "health_check";
     pub const FILES: &str = "files";
@@ -161,13 +169,23 @@ impl Client {
         let progress = progress.unwrap_or_else(|| Box::new(|_| ()));
         let n_requests = requests.len();
 
+        // All of the requests are for the same endpoint.
+        let url = match requests.first() {
+            Some(req) => req.url().clone(),
+            None => return Ok(Fetch::empty()),
+        };
+
+        if self.config.retries.unwrap_or_default() > 0 {
+            return self.fetch_raw_with_retries(requests, progress).await;
+        }
+
         let (mut responses, stats) = self.client.send_async_with_progress(requests, progress)?;
 
         let mut meta = Vec::with_capacity(n_requests);
         let mut streams = Vec::with_capacity(n_requests);
 
         while let Some(res) = responses.try_next().await? {
-            let res = raise_for_status(res).await?;
+            let res = raise_for_status(res, &url).await?;
 
             let response_meta = ResponseMeta::from(&res);
             tracing::debug!("{:?}", &response_meta);
@@ -187,6 +205,124 @@ impl Client {
         })
     }
 
+    /// Same as `fetch_raw`, but each request is sent on its own, so that a
+    /// request which fails with a retryable error before its response starts
+    /// streaming can be sent again after an exponential backoff, without
+    /// resending the requests that succeeded. Each request is retried up to
+    /// the configured number of times, and every failed attempt is counted
+    /// in the returned stats.
+    async fn fetch_raw_with_retries<T: DeserializeOwned + Send + 'static>(
+        &self,
+        requests: Vec<Request>,
+        progress: ProgressCallback,
+    ) -> Result<Fetch<T>, EdenApiError> {
+        let n_requests = requests.len();
+
+        // Each request only reports its own progress, so keep the latest
+        // progress of every request and report their sum to the caller.
+        let progress = Arc::new(Mutex::new((
+            vec![Progress::default(); n_requests],
+            progress,
+        )));
+
+        let mut sent = requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, req)| {
+                let progress = progress.clone();
+                self.send_with_retries(req, move |p| {
+                    let mut progress = progress.lock().expect("lock poisoned");
+                    let (latest, callback) = &mut *progress;
+                    latest[i] = p;
+                    callback(latest.iter().copied().sum());
+                })
+            })
+            .collect::<stream::FuturesUnordered<_>>();
+
+        let mut meta = Vec::with_capacity(n_requests);
+        let mut streams = Vec::with_capacity(n_requests);
+        let mut stats = Vec::with_capacity(n_requests);
+        let mut failed_attempts = 0;
+
+        while let Some((res, req_stats, failed)) = sent.try_next().await? {
+            let response_meta = ResponseMeta::from(&res);
+            tracing::debug!("{:?}", &response_meta);
+            meta.push(response_meta);
+
+            let entries = res.into_cbor_stream::<T>().err_into().boxed();
+            streams.push(entries);
+            stats.push(req_stats);
+            failed_attempts += failed;
+        }
+
+        let entries = stream::select_all(streams).boxed();
+        let stats = future::try_join_all(stats)
+            .err_into()
+            .map_ok(move |stats| {
+                let mut stats = combine_stats(stats);
+                stats.requests += failed_attempts;
+                stats
+            })
+            .boxed();
+
+        Ok(Fetch {
+            meta,
+            entries,
+            stats,
+        })
+    }
+
+    /// Send a single request, sending it again after an exponential backoff
+    /// whenever it fails with a retryable error, up to the configured number
+    /// of retries. Returns the response, its stats, and the number of failed
+    /// attempts.
+    async fn send_with_retries<P>(
+        &self,
+        mut req: Request,
+        progress: P,
+    ) -> Result<(AsyncResponse, HttpStatsFuture, usize), EdenApiError>
+    where
+        P: FnMut(Progress) + Clone + Send + 'static,
+    {
+        let url = req.url().clone();
+        let retries = self.config.retries.unwrap_or_default();
+        let mut backoff =
+            Duration::from_millis(self.config.backoff_ms.unwrap_or(DEFAULT_BACKOFF_MS));
+
+        let mut attempt = 0;
+        loop {
+            let retry = req.clone();
+            let (mut responses, stats) = self
+                .client
+                .send_async_with_progress(vec![req], progress.clone())?;
+
+            let res = match responses.try_next().await {
+                Ok(Some(res)) => raise_for_status(res, &url).await,
+                Ok(None) => Err(format_err!("No response received from {}", &url).into()),
+                Err(e) => Err(e.into()),
+            };
+
+            match res {
+                Ok(res) => return Ok((res, stats, attempt)),
+                Err(e) if e.is_retryable() && attempt < retries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "Retrying request to {} in {:?} (attempt {} of {}): {}",
+                        &url,
+                        backoff,
+                        attempt,
+                        retries,
+                        &e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    req = retry;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     /// Fetch data from the server.
     ///
     /// Concurrently performs all of the given HTTP requests, each of
@@ -594,7 +730,21 @@ fn split_into_batches<T>(
     }
 }
 
-async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiError> {
+/// Combine the stats of requests that were sent concurrently but separately,
+/// as if they had been sent together.
+fn combine_stats(stats: impl IntoIterator<Item = Stats>) -> Stats {
+    stats
+        .into_iter()
+        .fold(Stats::default(), |total, stats| Stats {
+            downloaded: total.downloaded + stats.downloaded,
+            uploaded: total.uploaded + stats.uploaded,
+            requests: total.requests + stats.requests,
+            time: total.time.max(stats.time),
+            latency: total.latency.max(stats.latency),
+        })
+}
+
+async fn raise_for_status(res: AsyncResponse, url: &Url) -> Result<AsyncResponse, EdenApiError> {
     if res.status.as_u16() < 400 {
         return Ok(res);
     }
@@ -603,6 +753,7 @@ async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiEr
     let message = String::from_utf8_lossy(&body).into_owned();
     Err(EdenApiError::HttpError {
         status: res.status,
+        url: url.clone(),
         message,
     })
 }
@@ -610,7 +761,13 @@ async fn raise_for_status(res: AsyncResponse) -> Result<AsyncResponse, EdenApiEr
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use futures::prelude::*;
+    use mockito::mock;
+    use url::Url;
+
+    use types::{HgId, Key, RepoPathBuf};
 
+    use crate::api::EdenApi;
     use crate::builder::Builder;
 
     #[test]
@@ -628,4 +785,37 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_retried_requests_in_stats() -> Result<()> {
+        // The first attempt fails with a server error, and the retry succeeds.
+        let failed = mock("POST", "/repo/files")
+            .with_status(503)
+            .expect(1)
+            .create();
+        let succeeded = mock("POST", "/repo/files")
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let server_url = Url::parse(&mockito::server_url())?;
+        let client = Builder::new()
+            .server_url(server_url)
+            .retries(Some(2))
+            .backoff_ms(Some(0))
+            .build()?;
+
+        let keys = vec![Key::new(RepoPathBuf::new(), HgId::null_id().clone())];
+        let fetch = client.files("repo".into(), keys, None).await?;
+        let entries = fetch.entries.try_collect::<Vec<_>>().await?;
+        let stats = fetch.stats.await?;
+
+        failed.assert();
+        succeeded.assert();
+
+        assert!(entries.is_empty());
+        assert_eq!(stats.requests, 2);
+
+        Ok(())
+    }
 }
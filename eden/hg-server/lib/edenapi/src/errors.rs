@@ -11,6 +11,7 @@ use auth::X509Error;
 use edenapi_types::{wire::WireToApiConversionError, EdenApiServerError};
 use http::status::StatusCode;
 use http_client::HttpClientError;
+use url::Url;
 
 #[derive(Debug, Error)]
 pub enum EdenApiError {
@@ -22,8 +23,12 @@ pub enum EdenApiError {
     BadCertificate(#[from] X509Error),
     #[error(transparent)]
     Http(#[from] HttpClientError),
-    #[error("Server reported an error ({status}): {message}")]
-    HttpError { status: StatusCode, message: String },
+    #[error("Server reported an error ({status}) for {url}: {message}")]
+    HttpError {
+        status: StatusCode,
+        url: Url,
+        message: String,
+    },
     #[error(transparent)]
     InvalidUrl(#[from] url::ParseError),
     #[error(transparent)]
@@ -34,6 +39,20 @@ pub enum EdenApiError {
     Other(#[from] anyhow::Error),
 }
 
+impl EdenApiError {
+    /// Whether the failure may be transient, so that sending the same
+    /// request again could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // TLS failures are usually due to the client's configuration.
+            Self::Http(HttpClientError::Tls(_)) => false,
+            Self::Http(_) => true,
+            Self::HttpError { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("No server URL specified")]
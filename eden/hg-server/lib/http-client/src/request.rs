@@ -60,8 +60,7 @@ impl fmt::Display for Method {
 
 /// A subset of the `Request` builder. Preserved in curl types.
 /// Expose the request in curl handler callback context.
-#[cfg_attr(test, derive(Clone))]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RequestContext {
     id: RequestId,
     url: Url,
@@ -76,8 +75,10 @@ pub struct RequestId(usize);
 
 /// A builder struct for HTTP requests, designed to be
 /// a more egonomic API for setting up a curl handle.
-#[cfg_attr(test, derive(Clone))]
-#[derive(Debug)]
+///
+/// A cloned request keeps the id of the original, so that a request
+/// that failed can be sent again as the same request.
+#[derive(Clone, Debug)]
 pub struct Request {
     ctx: RequestContext,
     headers: Vec<(String, String)>,
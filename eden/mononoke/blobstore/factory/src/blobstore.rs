@@ -5,7 +5,8 @@
  * GNU General Public License version 2.
  */
 
-use anyhow::{bail, Context, Error};
+use anyhow::{bail, Context, Error, Result};
+use async_trait::async_trait;
 use blobstore::{
     Blobstore, BlobstorePutOps, BlobstoreWithLink, DisabledBlob, ErrorKind, PutBehaviour,
     DEFAULT_PUT_BEHAVIOUR,
@@ -14,6 +15,7 @@ use blobstore_sync_queue::SqlBlobstoreSyncQueue;
 use cacheblob::CachelibBlobstoreOptions;
 use cached_config::ConfigStore;
 use chaosblob::{ChaosBlobstore, ChaosOptions};
+use context::CoreContext;
 use delayblob::{DelayOptions, DelayedBlobstore};
 use fbinit::FacebookInit;
 use fileblob::Fileblob;
@@ -24,8 +26,10 @@ use metaconfig_types::{
     BlobConfig, BlobstoreId, DatabaseConfig, MultiplexId, MultiplexedStoreType,
     ShardableRemoteDatabaseConfig,
 };
+use mononoke_types::BlobstoreBytes;
 use multiplexedblob::{
-    MultiplexedBlobstore, ScrubAction, ScrubBlobstore, ScrubHandler, ScrubOptions, ScrubWriteMostly,
+    BatchRepairOptions, BlobstoreBatchPut, MultiplexedBlobstore, ScrubAction, ScrubBlobstore,
+    ScrubHandler, ScrubOptions, ScrubWriteMostly,
 };
 use packblob::{PackBlob, PackOptions};
 use readonlyblob::ReadOnlyBlobstore;
@@ -35,7 +39,8 @@ use slog::Logger;
 use sql_construct::SqlConstructFromDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
 use sqlblob::{CountedSqlblob, Sqlblob};
-use std::num::{NonZeroU64, NonZeroUsize};
+use std::collections::{HashMap, HashSet};
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
 use std::sync::Arc;
 use std::time::Duration;
 use throttledblob::{ThrottleOptions, ThrottledBlob};
@@ -54,6 +59,10 @@ pub struct BlobstoreOptions {
     pub put_behaviour: PutBehaviour,
     pub scrub_options: Option<ScrubOptions>,
     pub sqlblob_mysql_options: MysqlOptions,
+    /// Puts per second allowed to each multiplex component, absent means unlimited
+    pub multiplex_write_limits: HashMap<BlobstoreId, NonZeroU32>,
+    /// Multiplex components that are written to but never read from
+    pub multiplex_write_only: HashSet<BlobstoreId>,
 }
 
 impl BlobstoreOptions {
@@ -80,6 +89,8 @@ impl BlobstoreOptions {
             // These are added via the builder methods
             scrub_options: None,
             sqlblob_mysql_options,
+            multiplex_write_limits: HashMap::new(),
+            multiplex_write_only: HashSet::new(),
         }
     }
 
@@ -123,6 +134,36 @@ impl BlobstoreOptions {
         }
     }
 
+    /// Batch scrub repairs of alias mappings to SQL components, this many to a batch
+    pub fn with_scrub_repair_batch_size(self, batch_size: NonZeroUsize) -> Self {
+        if let Some(mut scrub_options) = self.scrub_options {
+            // The stores are filled in for each multiplex as it is made
+            scrub_options.batch_repair = Some(BatchRepairOptions {
+                batch_size,
+                ..BatchRepairOptions::new(HashMap::new())
+            });
+            Self {
+                scrub_options: Some(scrub_options),
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Only scrub keys in `[start, end)`, where an empty end is unbounded
+    pub fn with_scrub_key_range(self, start: String, end: String) -> Self {
+        if let Some(mut scrub_options) = self.scrub_options {
+            scrub_options.key_range = Some((start, end));
+            Self {
+                scrub_options: Some(scrub_options),
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
     pub fn with_scrub_queue_peek_bound(self, queue_peek_bound_secs: u64) -> Self {
         if let Some(mut scrub_options) = self.scrub_options {
             scrub_options.queue_peek_bound = Some(Duration::from_secs(queue_peek_bound_secs));
@@ -134,6 +175,44 @@ impl BlobstoreOptions {
             self
         }
     }
+
+    /// Report scrub progress to the scrub handler every `interval_keys` keys, or
+    /// `interval` since the last report
+    pub fn with_scrub_progress(
+        self,
+        interval_keys: Option<NonZeroU64>,
+        interval: Option<Duration>,
+        expected_keys: Option<u64>,
+    ) -> Self {
+        if let Some(mut scrub_options) = self.scrub_options {
+            scrub_options.progress_interval_keys = interval_keys;
+            scrub_options.progress_interval = interval;
+            scrub_options.expected_keys = expected_keys;
+            Self {
+                scrub_options: Some(scrub_options),
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    pub fn with_multiplex_write_limits(
+        self,
+        multiplex_write_limits: HashMap<BlobstoreId, NonZeroU32>,
+    ) -> Self {
+        Self {
+            multiplex_write_limits,
+            ..self
+        }
+    }
+
+    pub fn with_multiplex_write_only(self, multiplex_write_only: HashSet<BlobstoreId>) -> Self {
+        Self {
+            multiplex_write_only,
+            ..self
+        }
+    }
 }
 
 /// Construct a blobstore according to the specification. The multiplexed blobstore
@@ -599,7 +678,7 @@ async fn make_blobstore_multiplexed<'a>(
         .scrub_options
         .as_ref()
         .map_or(ReadOnlyStorage(false), |v| {
-            ReadOnlyStorage(v.scrub_action != ScrubAction::Repair)
+            ReadOnlyStorage(v.scrub_action == ScrubAction::ReportOnly)
         });
 
     let mut applied_chaos = false;
@@ -620,22 +699,47 @@ async fn make_blobstore_multiplexed<'a>(
             }
 
             async move {
-                let store = make_blobstore_put_ops(
-                    fb,
-                    config,
-                    mysql_options,
-                    component_readonly,
+                let (store, batch_put) = if is_batch_repair_component(
+                    &config,
                     &blobstore_options,
-                    logger,
-                    config_store,
-                    scrub_handler,
+                    component_readonly,
                     component_sampler,
-                    Some(blobstoreid),
-                )
-                .watched(logger)
-                .await?;
+                ) {
+                    let store = Arc::new(
+                        make_sql_blobstore(
+                            fb,
+                            config,
+                            component_readonly,
+                            &blobstore_options,
+                            config_store,
+                        )
+                        .watched(logger)
+                        .await?,
+                    );
+                    let batch_put = Arc::new(SqlblobBatchPut(store.clone()));
+                    (
+                        store as Arc<dyn BlobstorePutOps>,
+                        Some(batch_put as Arc<dyn BlobstoreBatchPut>),
+                    )
+                } else {
+                    let store = make_blobstore_put_ops(
+                        fb,
+                        config,
+                        mysql_options,
+                        component_readonly,
+                        &blobstore_options,
+                        logger,
+                        config_store,
+                        scrub_handler,
+                        component_sampler,
+                        Some(blobstoreid),
+                    )
+                    .watched(logger)
+                    .await?;
+                    (store, None)
+                };
 
-                Result::<_, Error>::Ok((blobstoreid, store_type, store))
+                Result::<_, Error>::Ok((blobstoreid, store_type, store, batch_put))
             }
         }
     }))
@@ -649,10 +753,14 @@ async fn make_blobstore_multiplexed<'a>(
     )?;
 
     // For now, `partition` could do this, but this will be easier to extend when we introduce more store types
+    let mut batch_puts = HashMap::new();
     let (normal_components, write_mostly_components) = {
         let mut normal_components = vec![];
         let mut write_mostly_components = vec![];
-        for (blobstore_id, store_type, store) in components.into_iter() {
+        for (blobstore_id, store_type, store, batch_put) in components.into_iter() {
+            if let Some(batch_put) = batch_put {
+                batch_puts.insert(blobstore_id, batch_put);
+            }
             match store_type {
                 MultiplexedStoreType::Normal => normal_components.push((blobstore_id, store)),
                 MultiplexedStoreType::WriteMostly => {
@@ -664,37 +772,88 @@ async fn make_blobstore_multiplexed<'a>(
     };
 
     let blobstore = match &blobstore_options.scrub_options {
-        Some(scrub_options) => Arc::new(ScrubBlobstore::new(
-            multiplex_id,
-            normal_components,
-            write_mostly_components,
-            minimum_successful_writes,
-            Arc::new(queue),
-            scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
-                MononokeScubaSampleBuilder::new(fb, &table)
-            }),
-            multiplex_scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
-                MononokeScubaSampleBuilder::new(fb, &table)
-            }),
-            scuba_sample_rate,
-            scrub_options.clone(),
-            scrub_handler.clone(),
-        )) as Arc<dyn BlobstorePutOps>,
-        None => Arc::new(MultiplexedBlobstore::new(
-            multiplex_id,
-            normal_components,
-            write_mostly_components,
-            minimum_successful_writes,
-            Arc::new(queue),
-            scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
-                MononokeScubaSampleBuilder::new(fb, &table)
-            }),
-            multiplex_scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
-                MononokeScubaSampleBuilder::new(fb, &table)
-            }),
-            scuba_sample_rate,
-        )) as Arc<dyn BlobstorePutOps>,
+        Some(scrub_options) => {
+            let mut scrub_options = scrub_options.clone();
+            if let Some(batch_repair) = scrub_options.batch_repair.as_mut() {
+                batch_repair.stores = batch_puts;
+            }
+            let blobstore = ScrubBlobstore::new(
+                multiplex_id,
+                normal_components,
+                write_mostly_components,
+                minimum_successful_writes,
+                Arc::new(queue),
+                scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
+                    MononokeScubaSampleBuilder::new(fb, &table)
+                }),
+                multiplex_scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
+                    MononokeScubaSampleBuilder::new(fb, &table)
+                }),
+                scuba_sample_rate,
+                scrub_options,
+                scrub_handler.clone(),
+            )
+            .with_write_limits(&blobstore_options.multiplex_write_limits)
+            .with_write_only(&blobstore_options.multiplex_write_only);
+            // Lets whoever set the batch size flush the last batches once scrub is done
+            blobstore.register_batch_repairs();
+            Arc::new(blobstore) as Arc<dyn BlobstorePutOps>
+        }
+        None => Arc::new(
+            MultiplexedBlobstore::new(
+                multiplex_id,
+                normal_components,
+                write_mostly_components,
+                minimum_successful_writes,
+                Arc::new(queue),
+                scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
+                    MononokeScubaSampleBuilder::new(fb, &table)
+                }),
+                multiplex_scuba_table.map_or(MononokeScubaSampleBuilder::with_discard(), |table| {
+                    MononokeScubaSampleBuilder::new(fb, &table)
+                }),
+                scuba_sample_rate,
+            )
+            .with_write_limits(&blobstore_options.multiplex_write_limits)
+            .with_write_only(&blobstore_options.multiplex_write_only),
+        ) as Arc<dyn BlobstorePutOps>,
     };
 
     Ok(blobstore)
 }
+
+// Whether scrub repairs to this multiplex component are batched. Batch puts go straight
+// to its Sqlblob, so this is only done for components without wrappers for them to bypass.
+fn is_batch_repair_component(
+    config: &BlobConfig,
+    blobstore_options: &BlobstoreOptions,
+    readonly_storage: ReadOnlyStorage,
+    component_sampler: Option<&Arc<dyn ComponentSamplingHandler>>,
+) -> bool {
+    let batch_repair = blobstore_options
+        .scrub_options
+        .as_ref()
+        .map_or(false, |scrub_options| scrub_options.batch_repair.is_some());
+    batch_repair
+        && matches!(config, BlobConfig::Sqlite { .. } | BlobConfig::Mysql { .. })
+        && !readonly_storage.0
+        && component_sampler.is_none()
+        && !blobstore_options.throttle_options.has_throttle()
+        && !blobstore_options.chaos_options.has_chaos()
+        && !blobstore_options.delay_options.has_delay()
+}
+
+/// Scrub repairs batched to a SQL multiplex component
+#[derive(Debug)]
+struct SqlblobBatchPut(Arc<CountedSqlblob>);
+
+#[async_trait]
+impl BlobstoreBatchPut for SqlblobBatchPut {
+    async fn put_batch(
+        &self,
+        ctx: &CoreContext,
+        values: Vec<(String, BlobstoreBytes)>,
+    ) -> Result<()> {
+        self.0.as_inner().put_batch(ctx, values).await
+    }
+}
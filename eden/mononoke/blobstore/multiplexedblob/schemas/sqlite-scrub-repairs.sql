@@ -0,0 +1,16 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `scrub_repairs` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `blobstore_key` varchar NOT NULL,
+  `blobstore_id` INTEGER NOT NULL,
+  `is_repaired` BOOLEAN NOT NULL,
+  `add_timestamp` BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS `scrub_repairs_key` ON `scrub_repairs` (`blobstore_key`);
@@ -30,7 +30,7 @@ use std::{
     fmt,
     future::Future,
     hash::Hasher,
-    num::{NonZeroU64, NonZeroUsize},
+    num::{NonZeroU32, NonZeroU64, NonZeroUsize},
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
@@ -43,12 +43,17 @@ use tokio::time::timeout;
 use tunables::tunables;
 use twox_hash::XxHash;
 
-use crate::scrub::ScrubWriteMostly;
+use crate::metrics::{MultiplexMetrics, MultiplexMetricsRecorder};
+use crate::queue::{StoreBreakers, StoreHealth};
+use crate::scrub::{RepairCandidate, RepairSourcePolicy, ScrubHandler, ScrubWriteMostly};
+use crate::store_timeouts::StoreTimeouts;
+use crate::write_limits::WriteLimiters;
 
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
 const DEFAULT_IS_PRESENT_TIMEOUT_MS: i64 = 10000;
 
 type BlobstoresWithEntry = Vec<HashSet<BlobstoreId>>;
+type BlobstoreValues = Vec<BlobstoreGetData>;
 type BlobstoresReturnedNone = HashSet<BlobstoreId>;
 type BlobstoresReturnedError = HashMap<BlobstoreId, Error>;
 
@@ -58,12 +63,30 @@ pub enum ErrorKind {
     SomeFailedOthersNone(Arc<BlobstoresReturnedError>),
     #[error("All blobstores failed: {0:?}")]
     AllFailed(Arc<BlobstoresReturnedError>),
+    #[error("Fewer than {read_quorum} blobstores answered, failures: {errors:?}")]
+    QuorumUnavailable {
+        read_quorum: usize,
+        errors: Arc<BlobstoresReturnedError>,
+    },
+    #[error(
+        "No value was returned by {read_quorum} blobstores: {values:?}, {missing:?} do not have"
+    )]
+    QuorumFailed {
+        read_quorum: usize,
+        values: Arc<Vec<QuorumValue>>,
+        missing: Arc<BlobstoresReturnedNone>,
+    },
     // Errors below this point are from ScrubBlobstore only. If they include an
     // Option<BlobstoreBytes>, this implies that this error is recoverable
     #[error(
         "Different blobstores have different values for this item: {0:?} are grouped by content, {1:?} do not have"
     )]
-    ValueMismatch(Arc<BlobstoresWithEntry>, Arc<BlobstoresReturnedNone>),
+    ValueMismatch(
+        Arc<BlobstoresWithEntry>,
+        Arc<BlobstoresReturnedNone>,
+        // The value held by each group of blobstores, in the same order as the groups
+        Arc<BlobstoreValues>,
+    ),
     #[error("Some blobstores missing this item: {missing_main:?}")]
     SomeMissingItem {
         missing_main: Arc<BlobstoresReturnedNone>,
@@ -72,6 +95,75 @@ pub enum ErrorKind {
     },
     #[error("Multiple failures on put: {0:?}")]
     MultiplePutFailures(Arc<BlobstoresReturnedError>),
+    #[error("Put did not reach every blobstore, {written:?} have it, failures: {failed:?}")]
+    PutAllFailed {
+        written: Arc<Vec<BlobstoreId>>,
+        failed: Arc<BlobstoresReturnedError>,
+    },
+    #[error("Blobstore {blobstore_id} did not answer within {timeout:?}")]
+    StoreTimeout {
+        blobstore_id: BlobstoreId,
+        timeout: Duration,
+    },
+    #[error("Blobstore {0} was not asked as its circuit breaker is open")]
+    CircuitOpen(BlobstoreId),
+    #[error(
+        "Blobstore {blobstore_id} is not in multiplex {multiplex_id}, valid ids are {valid:?}"
+    )]
+    UnknownBlobstore {
+        blobstore_id: BlobstoreId,
+        multiplex_id: MultiplexId,
+        valid: Arc<Vec<BlobstoreId>>,
+    },
+    #[error("No blobstore has {0}, so there is nothing to heal it from")]
+    NothingToHealFrom(String),
+}
+
+/// One of the values returned during a quorum read, and the stores that returned it
+#[derive(Clone, Debug)]
+pub struct QuorumValue {
+    pub blobstores: HashSet<BlobstoreId>,
+    pub size: usize,
+    pub content_hash: u64,
+}
+
+/// What heal_key did to each inner store that did not already hold the chosen value
+#[derive(Debug, Default)]
+pub struct HealKeyReport {
+    /// Stores that were missing the key or held a different value, and now hold the chosen one
+    pub repaired: Vec<BlobstoreId>,
+    /// Stores that needed repair, but the put failed
+    pub failed: HashMap<BlobstoreId, Error>,
+    /// Stores that could not be read, so were left alone
+    pub unreadable: HashMap<BlobstoreId, Error>,
+}
+
+/// Whether one inner store holds a key, as found by is_present_per_store
+#[derive(Debug)]
+pub enum StorePresence {
+    Present,
+    Absent,
+    /// The store failed or timed out, so it is not known whether it holds the key
+    Unknown(Error),
+}
+
+/// Weighted acknowledgement for puts. A put succeeds once the stores that have acknowledged
+/// it carry at least `threshold` weight between them; the remaining puts carry on in the
+/// background. Stores absent from `weights` weigh 1, and write-only stores weigh nothing.
+#[derive(Clone, Debug)]
+pub struct WriteQuorum {
+    pub threshold: NonZeroU64,
+    pub weights: HashMap<BlobstoreId, u64>,
+}
+
+impl WriteQuorum {
+    pub fn new(threshold: NonZeroU64, weights: HashMap<BlobstoreId, u64>) -> Self {
+        Self { threshold, weights }
+    }
+
+    pub fn weight(&self, blobstore_id: BlobstoreId) -> u64 {
+        self.weights.get(&blobstore_id).copied().unwrap_or(1)
+    }
 }
 
 /// This handler is called on each successful put to underlying blobstore,
@@ -92,6 +184,7 @@ pub trait MultiplexedBlobstorePutHandler: Send + Sync {
     ) -> Result<()>;
 }
 
+#[derive(Clone)]
 pub struct MultiplexedBlobstoreBase {
     multiplex_id: MultiplexId,
     /// These are the "normal" blobstores, which are read from on `get`, and written to on `put`
@@ -107,6 +200,10 @@ pub struct MultiplexedBlobstoreBase {
     /// 2. When we're recording blobstore stats to Scuba on a `get` - in this case, the read executes
     ///    solely to gather statistics, and the result is discarded
     write_mostly_blobstores: Arc<[(BlobstoreId, Arc<dyn BlobstorePutOps>)]>,
+    /// Write-only blobstores take part in writes, but are never read from on `get` and do not
+    /// count towards `minimum_successful_writes`. Scrub still repairs them if they are missing
+    /// a key. This is for stores still being backfilled, that would otherwise give false misses.
+    write_only_blobstores: Arc<[(BlobstoreId, Arc<dyn BlobstorePutOps>)]>,
     /// `put` is considered successful if either this many `put` and `on_put` pairs succeeded or all puts were
     /// successful (regardless of whether `on_put`s were successful).
     /// This is meant to ensure that `put` fails if the data could end up lost (e.g. if a buggy experimental
@@ -116,6 +213,18 @@ pub struct MultiplexedBlobstoreBase {
     handler: Arc<dyn MultiplexedBlobstorePutHandler>,
     scuba: MononokeScubaSampleBuilder,
     scuba_sample_rate: NonZeroU64,
+    metrics: Arc<MultiplexMetricsRecorder>,
+    /// Puts to each inner store, including scrub repairs, wait for budget here
+    write_limiters: Arc<WriteLimiters>,
+    /// How long each inner store gets to answer. A get that runs out of time counts as a
+    /// miss, a put as a failed write.
+    timeouts: Arc<StoreTimeouts>,
+    /// If set, replaces `minimum_successful_writes` with a weighted threshold
+    write_quorum: Option<Arc<WriteQuorum>>,
+    /// Told about puts that fail after a put has already met its write quorum
+    background_write_failure_handler: Option<Arc<dyn ScrubHandler>>,
+    /// If set, puts go to this store and wait for it before going to the others
+    canonical_store: Option<BlobstoreId>,
 }
 
 impl std::fmt::Display for MultiplexedBlobstoreBase {
@@ -130,14 +239,58 @@ impl std::fmt::Display for MultiplexedBlobstoreBase {
             .iter()
             .map(|(id, store)| (*id, store.to_string()))
             .collect();
+        let write_only_blobstores: Vec<_> = self
+            .write_only_blobstores
+            .iter()
+            .map(|(id, store)| (*id, store.to_string()))
+            .collect();
         write!(
             f,
-            "Normal {:?}, write mostly {:?}",
-            blobstores, write_mostly_blobstores
+            "Normal {:?}, write mostly {:?}, write only {:?}",
+            blobstores, write_mostly_blobstores, write_only_blobstores
         )
     }
 }
 
+fn is_store_timeout(error: &Error) -> bool {
+    matches!(
+        error.downcast_ref::<ErrorKind>(),
+        Some(ErrorKind::StoreTimeout { .. })
+    )
+}
+
+// Background sessions only record puts for healing if some store is missing the value
+fn run_handlers_on_success(ctx: &CoreContext) -> bool {
+    !matches!(
+        ctx.session().session_class(),
+        SessionClass::Background | SessionClass::BackgroundUnlessTooSlow
+    )
+}
+
+async fn store_presence(
+    ctx: &CoreContext,
+    blobstore_id: BlobstoreId,
+    blobstore: &dyn BlobstorePutOps,
+    key: &str,
+    timeouts: &StoreTimeouts,
+) -> StorePresence {
+    let check_timeout = timeouts.get_timeout(blobstore_id);
+    match timeout(check_timeout, blobstore.is_present(ctx, key)).await {
+        Ok(Ok(BlobstoreIsPresent::Present)) => StorePresence::Present,
+        Ok(Ok(BlobstoreIsPresent::Absent)) => StorePresence::Absent,
+        Ok(Ok(BlobstoreIsPresent::ProbablyNotPresent(error))) | Ok(Err(error)) => {
+            StorePresence::Unknown(error)
+        }
+        Err(_) => StorePresence::Unknown(
+            ErrorKind::StoreTimeout {
+                blobstore_id,
+                timeout: check_timeout,
+            }
+            .into(),
+        ),
+    }
+}
+
 fn write_mostly_error(
     blobstores: &[(BlobstoreId, Arc<dyn BlobstorePutOps>)],
     errors: HashMap<BlobstoreId, Error>,
@@ -164,15 +317,131 @@ impl MultiplexedBlobstoreBase {
         scuba_sample_rate: NonZeroU64,
     ) -> Self {
         scuba.add_common_server_data();
+        let metrics = Arc::new(MultiplexMetricsRecorder::new(
+            blobstores
+                .iter()
+                .chain(write_mostly_blobstores.iter())
+                .map(|(id, _)| *id),
+        ));
 
         Self {
             multiplex_id,
             blobstores: blobstores.into(),
             write_mostly_blobstores: write_mostly_blobstores.into(),
+            write_only_blobstores: Arc::new([]),
             minimum_successful_writes,
             handler,
             scuba,
             scuba_sample_rate,
+            metrics,
+            write_limiters: Arc::new(WriteLimiters::default()),
+            timeouts: Arc::new(StoreTimeouts::with_default(REQUEST_TIMEOUT)),
+            write_quorum: None,
+            background_write_failure_handler: None,
+            canonical_store: None,
+        }
+    }
+
+    /// Limit the puts per second sent to individual inner stores. Stores absent from
+    /// `write_limits` are not limited. Puts over the limit wait until there is budget.
+    pub fn with_write_limits(self, write_limits: &HashMap<BlobstoreId, NonZeroU32>) -> Self {
+        Self {
+            write_limiters: Arc::new(WriteLimiters::new(write_limits)),
+            ..self
+        }
+    }
+
+    /// Give up on gets and puts to individual inner stores after the given time, so one
+    /// slow store can't stall the whole multiplex. Stores absent from the maps wait up to
+    /// the default request timeout.
+    pub fn with_store_timeouts(
+        self,
+        get_timeouts: &HashMap<BlobstoreId, Duration>,
+        put_timeouts: &HashMap<BlobstoreId, Duration>,
+    ) -> Self {
+        Self {
+            timeouts: Arc::new(StoreTimeouts::new(
+                REQUEST_TIMEOUT,
+                get_timeouts,
+                put_timeouts,
+            )),
+            ..self
+        }
+    }
+
+    /// Consider puts successful once the acknowledging stores carry `write_quorum.threshold`
+    /// weight, instead of after `minimum_successful_writes` stores. Puts that then fail in
+    /// the background are reported to `scrub_handler`, so they can be repaired.
+    pub fn with_write_quorum(
+        self,
+        write_quorum: WriteQuorum,
+        scrub_handler: Arc<dyn ScrubHandler>,
+    ) -> Self {
+        Self {
+            write_quorum: Some(Arc::new(write_quorum)),
+            background_write_failure_handler: Some(scrub_handler),
+            ..self
+        }
+    }
+
+    /// Write each put to `blobstore_id` first, and only start the puts to the other stores
+    /// once it has acknowledged, so a session reading from it sees its own writes at once.
+    /// The canonical store's write counts towards `minimum_successful_writes`, or its weight
+    /// towards the write quorum, like any other; the rest of the put then completes as usual.
+    /// If the canonical store fails the put fails, without writing to the other stores, even
+    /// if they alone could have met the quorum. An id not in this multiplex is ignored.
+    pub fn with_canonical_store(self, blobstore_id: BlobstoreId) -> Self {
+        Self {
+            canonical_store: Some(blobstore_id),
+            ..self
+        }
+    }
+
+    pub fn canonical_store(&self) -> Option<BlobstoreId> {
+        self.canonical_store
+    }
+
+    /// Treat the given normal or write-mostly stores as write-only, e.g. while a new
+    /// store is being backfilled. Ids not in this multiplex are ignored.
+    pub fn with_write_only(self, write_only: &HashSet<BlobstoreId>) -> Self {
+        let mut write_only_blobstores: Vec<_> = self.write_only_blobstores.to_vec();
+        let (write_only_normal, blobstores): (Vec<_>, Vec<_>) = self
+            .blobstores
+            .iter()
+            .cloned()
+            .partition(|(id, _)| write_only.contains(id));
+        let (write_only_write_mostly, write_mostly_blobstores): (Vec<_>, Vec<_>) = self
+            .write_mostly_blobstores
+            .iter()
+            .cloned()
+            .partition(|(id, _)| write_only.contains(id));
+        write_only_blobstores.extend(write_only_normal);
+        write_only_blobstores.extend(write_only_write_mostly);
+        Self {
+            blobstores: blobstores.into(),
+            write_mostly_blobstores: write_mostly_blobstores.into(),
+            write_only_blobstores: write_only_blobstores.into(),
+            ..self
+        }
+    }
+
+    /// Keep only the given stores, whatever kind they are. Used by scrub to compare a
+    /// subset of the multiplex, so keys held only by the other stores are not seen.
+    pub fn restricted_to(&self, blobstore_ids: &[BlobstoreId]) -> Self {
+        type Stores = Arc<[(BlobstoreId, Arc<dyn BlobstorePutOps>)]>;
+        let restrict = |stores: &Stores| -> Stores {
+            stores
+                .iter()
+                .filter(|(id, _)| blobstore_ids.contains(id))
+                .cloned()
+                .collect::<Vec<_>>()
+                .into()
+        };
+        Self {
+            blobstores: restrict(&self.blobstores),
+            write_mostly_blobstores: restrict(&self.write_mostly_blobstores),
+            write_only_blobstores: restrict(&self.write_only_blobstores),
+            ..self.clone()
         }
     }
 
@@ -180,22 +449,47 @@ impl MultiplexedBlobstoreBase {
         &self.multiplex_id
     }
 
+    /// Counts and latencies of the gets and puts sent to each inner store
+    pub fn multiplex_metrics(&self) -> MultiplexMetrics {
+        self.metrics.snapshot()
+    }
+
+    pub(crate) fn metrics_recorder(&self) -> &MultiplexMetricsRecorder {
+        self.metrics.as_ref()
+    }
+
+    pub(crate) fn write_limiters(&self) -> &WriteLimiters {
+        self.write_limiters.as_ref()
+    }
+
+    pub(crate) fn store_timeouts(&self) -> &StoreTimeouts {
+        self.timeouts.as_ref()
+    }
+
+    /// Each store is read under `store_key(blobstore_id)`, so stores that encode one logical
+    /// key differently are compared as holding the same key.
+    ///
+    /// Stores that time out are reported to `scrub_handler`, and are neither repaired nor
+    /// counted as failed unless every store timed out or failed.
     pub async fn scrub_get(
         &self,
         ctx: &CoreContext,
-        key: &str,
+        store_key: &(dyn Fn(BlobstoreId) -> String + Send + Sync),
         write_mostly: ScrubWriteMostly,
+        scrub_handler: &dyn ScrubHandler,
     ) -> Result<Option<BlobstoreGetData>, ErrorKind> {
         let mut scuba = self.scuba.clone();
         scuba.sampled(self.scuba_sample_rate);
 
         if write_mostly == ScrubWriteMostly::ScrubIfAbsent {
-            let mut results = join_all(multiplexed_get(
+            let mut results = join_all(multiplexed_get_per_store(
                 ctx,
                 self.write_mostly_blobstores.as_ref(),
-                key,
+                store_key,
                 OperationType::ScrubGet,
                 scuba.clone(),
+                self.metrics.clone(),
+                self.timeouts.clone(),
             ))
             .await;
             if let Some((_, Ok(success_return @ Some(_)))) = results.pop() {
@@ -209,24 +503,28 @@ impl MultiplexedBlobstoreBase {
         }
 
         let results = join_all(
-            multiplexed_get(
+            multiplexed_get_per_store(
                 ctx,
                 self.blobstores.as_ref(),
-                key,
+                store_key,
                 OperationType::ScrubGet,
                 scuba.clone(),
+                self.metrics.clone(),
+                self.timeouts.clone(),
             )
             .map(|f| f.map(|v| (false, v)).left_future())
             .chain(
                 match write_mostly {
                     ScrubWriteMostly::Scrub | ScrubWriteMostly::SkipMissing => Either::Left(
                         // Generate queries
-                        multiplexed_get(
+                        multiplexed_get_per_store(
                             ctx,
                             self.write_mostly_blobstores.as_ref(),
-                            key,
+                            store_key,
                             OperationType::ScrubGet,
                             scuba,
+                            self.metrics.clone(),
+                            self.timeouts.clone(),
                         )
                         .map(|f| f.map(|v| (true, v)).left_future()),
                     ),
@@ -244,21 +542,34 @@ impl MultiplexedBlobstoreBase {
         )
         .await;
 
-        let (successes, errors): (HashMap<_, _>, HashMap<_, _>) = results
+        let (successes, failures): (HashMap<_, _>, HashMap<_, _>) = results
             .into_iter()
             .partition_map(|(write_mostly_flag, (id, r))| match r {
                 Ok(v) => Either::Left((id, (write_mostly_flag, v))),
                 Err(v) => Either::Right((id, v)),
             });
+        let (timeouts, mut errors): (HashMap<_, _>, HashMap<_, _>) = failures
+            .into_iter()
+            .partition(|(_, error)| is_store_timeout(error));
+        let mut timed_out: Vec<_> = timeouts.keys().copied().collect();
+        timed_out.sort();
+        for blobstore_id in timed_out {
+            scrub_handler.on_timeout(ctx, blobstore_id, &store_key(blobstore_id));
+        }
 
         if successes.is_empty() {
+            errors.extend(timeouts);
             return Err(ErrorKind::AllFailed(errors.into()));
         }
 
         let mut all_values = HashMap::new();
         let mut missing_main = HashSet::new();
-        let mut missing_write_mostly = HashSet::new();
-        let mut last_get_data = None;
+        // Write-only stores are only checked for whether they need repair. Their values are
+        // never used, and they are treated like write-mostly stores as they are still being
+        // populated.
+        let mut missing_write_mostly = self
+            .scrub_missing_write_only(ctx, store_key, write_mostly)
+            .await;
 
         for (blobstore_id, (write_mostly_flag, value)) in successes.into_iter() {
             match value {
@@ -275,9 +586,9 @@ impl MultiplexedBlobstoreBase {
                     let content_hash = content_hash.finish();
                     all_values
                         .entry(content_hash)
-                        .or_insert_with(HashSet::new)
+                        .or_insert_with(|| (HashSet::new(), value))
+                        .0
                         .insert(blobstore_id);
-                    last_get_data = Some(value);
                 }
             }
         }
@@ -291,34 +602,478 @@ impl MultiplexedBlobstoreBase {
                 }
             }
             1 => {
+                let get_data = all_values.into_iter().next().map(|(_, (_, value))| value);
                 if missing_main.is_empty() && missing_write_mostly.is_empty() {
-                    Ok(last_get_data)
+                    Ok(get_data)
                 } else {
                     Err(ErrorKind::SomeMissingItem {
                         missing_main: Arc::new(missing_main),
                         missing_write_mostly: Arc::new(missing_write_mostly),
-                        value: last_get_data,
+                        value: get_data,
                     })
                 }
             }
             _ => {
-                let answered = all_values.into_iter().map(|(_, stores)| stores).collect();
+                let (answered, values): (Vec<_>, Vec<_>) =
+                    all_values.into_iter().map(|(_, group)| group).unzip();
                 let mut all_missing = HashSet::new();
                 all_missing.extend(missing_main.into_iter());
                 all_missing.extend(missing_write_mostly.into_iter());
                 Err(ErrorKind::ValueMismatch(
                     Arc::new(answered),
                     Arc::new(all_missing),
+                    Arc::new(values),
                 ))
             }
         }
     }
-}
 
-fn remap_timeout_result<O>(
-    timeout_or_result: Result<Result<O, Error>, tokio::time::error::Elapsed>,
-) -> Result<O, Error> {
-    timeout_or_result.unwrap_or_else(|_| Err(Error::msg("blobstore operation timeout")))
+    /// The write-only stores that do not have their `store_key`. As with write-mostly stores, only
+    /// ScrubWriteMostly::Scrub and SkipMissing read them, the other actions assume they are
+    /// missing.
+    async fn scrub_missing_write_only(
+        &self,
+        ctx: &CoreContext,
+        store_key: &(dyn Fn(BlobstoreId) -> String + Send + Sync),
+        write_mostly: ScrubWriteMostly,
+    ) -> HashSet<BlobstoreId> {
+        match write_mostly {
+            ScrubWriteMostly::Scrub | ScrubWriteMostly::SkipMissing => {
+                let mut scuba = self.scuba.clone();
+                scuba.sampled(self.scuba_sample_rate);
+                join_all(multiplexed_get_per_store(
+                    ctx,
+                    self.write_only_blobstores.as_ref(),
+                    store_key,
+                    OperationType::ScrubGet,
+                    scuba,
+                    self.metrics.clone(),
+                    self.timeouts.clone(),
+                ))
+                .await
+                .into_iter()
+                .filter_map(|(id, r)| matches!(r, Ok(None)).then(|| id))
+                .collect()
+            }
+            ScrubWriteMostly::PopulateIfAbsent | ScrubWriteMostly::ScrubIfAbsent => self
+                .write_only_blobstores
+                .iter()
+                .map(|(id, _store)| *id)
+                .collect(),
+        }
+    }
+
+    /// Get from a single inner store, normal, write-mostly or write-only, without consulting any others.
+    /// The value is returned as the store holds it. Intended for debugging divergence.
+    pub async fn get_from_store(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        blobstore_id: BlobstoreId,
+    ) -> Result<Option<BlobstoreGetData>, Error> {
+        let blobstore = self
+            .blobstores
+            .iter()
+            .chain(self.write_mostly_blobstores.iter())
+            .chain(self.write_only_blobstores.iter())
+            .find_map(|(id, store)| (*id == blobstore_id).then(|| store.clone()));
+        let blobstore = match blobstore {
+            Some(blobstore) => blobstore,
+            None => {
+                let mut valid: Vec<_> = self
+                    .blobstores
+                    .iter()
+                    .chain(self.write_mostly_blobstores.iter())
+                    .chain(self.write_only_blobstores.iter())
+                    .map(|(id, _)| *id)
+                    .collect();
+                valid.sort();
+                return Err(ErrorKind::UnknownBlobstore {
+                    blobstore_id,
+                    multiplex_id: self.multiplex_id,
+                    valid: Arc::new(valid),
+                }
+                .into());
+            }
+        };
+
+        let mut scuba = self.scuba.clone();
+        scuba.sampled(self.scuba_sample_rate);
+        let (_, result) = multiplexed_get_one(
+            ctx.clone(),
+            blobstore.as_ref(),
+            blobstore_id,
+            key,
+            OperationType::Get,
+            scuba,
+            self.metrics.as_ref(),
+            self.timeouts.as_ref(),
+        )
+        .await;
+        result
+    }
+
+    /// Get as normal, but if `health` is given ask unhealthy normal stores only once the
+    /// healthy ones have all missed, and record the outcome of each store asked. If
+    /// `breakers` is given, stores whose breaker is open are not asked.
+    pub(crate) async fn get_in_health_order(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        health: Option<Arc<StoreHealth>>,
+        breakers: Option<Arc<StoreBreakers>>,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let mut scuba = self.scuba.clone();
+        let blobstores = self.blobstores.clone();
+        let write_mostly_blobstores = self.write_mostly_blobstores.clone();
+        let metrics = self.metrics.clone();
+        let timeouts = self.timeouts.clone();
+        scuba.sampled(self.scuba_sample_rate);
+
+        blobstore_get(
+            ctx,
+            blobstores,
+            write_mostly_blobstores,
+            key,
+            scuba,
+            metrics,
+            timeouts,
+            health,
+            breakers,
+        )
+        .await
+    }
+
+    /// Get that only succeeds if at least read_quorum of the normal blobstores
+    /// return the same answer. Differing values are never resolved by picking one.
+    /// A store that times out has not answered.
+    pub async fn quorum_get(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        read_quorum: NonZeroUsize,
+    ) -> Result<Option<BlobstoreGetData>, ErrorKind> {
+        let read_quorum = read_quorum.get();
+        let mut scuba = self.scuba.clone();
+        scuba.sampled(self.scuba_sample_rate);
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::BlobGets);
+
+        let results = join_all(multiplexed_get(
+            ctx,
+            self.blobstores.as_ref(),
+            key,
+            OperationType::Get,
+            scuba,
+            self.metrics.clone(),
+            self.timeouts.clone(),
+        ))
+        .await;
+
+        let (successes, errors): (HashMap<_, _>, HashMap<_, _>) =
+            results.into_iter().partition_map(|(id, r)| match r {
+                Ok(v) => Either::Left((id, v)),
+                Err(v) => Either::Right((id, v)),
+            });
+
+        if successes.len() < read_quorum {
+            return Err(ErrorKind::QuorumUnavailable {
+                read_quorum,
+                errors: Arc::new(errors),
+            });
+        }
+
+        let mut all_values: HashMap<u64, (HashSet<BlobstoreId>, BlobstoreGetData)> = HashMap::new();
+        let mut missing = HashSet::new();
+        for (blobstore_id, value) in successes.into_iter() {
+            match value {
+                None => {
+                    missing.insert(blobstore_id);
+                }
+                Some(value) => {
+                    let mut content_hash = XxHash::with_seed(0);
+                    content_hash.write(value.as_raw_bytes());
+                    all_values
+                        .entry(content_hash.finish())
+                        .or_insert_with(|| (HashSet::new(), value))
+                        .0
+                        .insert(blobstore_id);
+                }
+            }
+        }
+
+        match all_values.len() {
+            // Every store that answered agrees the key is absent
+            0 => Ok(None),
+            1 if all_values.values().all(|(ids, _)| ids.len() >= read_quorum) => {
+                Ok(all_values.into_iter().next().map(|(_, (_, mut value))| {
+                    value.remove_ctime();
+                    value
+                }))
+            }
+            _ => {
+                let values = all_values
+                    .into_iter()
+                    .map(|(content_hash, (blobstores, value))| QuorumValue {
+                        blobstores,
+                        size: value.as_bytes().len(),
+                        content_hash,
+                    })
+                    .collect();
+                Err(ErrorKind::QuorumFailed {
+                    read_quorum,
+                    values: Arc::new(values),
+                    missing: Arc::new(missing),
+                })
+            }
+        }
+    }
+
+    /// Read `key` from every inner store, and write the value chosen by
+    /// `repair_source_policy` to each store that is missing it or holds a different value.
+    /// This is the single key form of a scrub repair, for fixing one known bad key.
+    /// Stores that can't be read are left alone.
+    pub async fn heal_key(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        repair_source_policy: &dyn RepairSourcePolicy,
+    ) -> Result<HealKeyReport, Error> {
+        let mut scuba = self.scuba.clone();
+        scuba.sampled(self.scuba_sample_rate);
+        let all_blobstores: Vec<_> = self
+            .blobstores
+            .iter()
+            .chain(self.write_mostly_blobstores.iter())
+            .chain(self.write_only_blobstores.iter())
+            .cloned()
+            .collect();
+
+        let results = join_all(multiplexed_get(
+            ctx,
+            &all_blobstores,
+            key,
+            OperationType::ScrubGet,
+            scuba.clone(),
+            self.metrics.clone(),
+            self.timeouts.clone(),
+        ))
+        .await;
+
+        let mut report = HealKeyReport::default();
+        let mut missing = HashSet::new();
+        let mut all_values: HashMap<u64, (HashSet<BlobstoreId>, BlobstoreGetData)> = HashMap::new();
+        for (blobstore_id, result) in results {
+            match result {
+                Err(error) => {
+                    report.unreadable.insert(blobstore_id, error);
+                }
+                Ok(None) => {
+                    missing.insert(blobstore_id);
+                }
+                Ok(Some(value)) => {
+                    let mut content_hash = XxHash::with_seed(0);
+                    content_hash.write(value.as_raw_bytes());
+                    all_values
+                        .entry(content_hash.finish())
+                        .or_insert_with(|| (HashSet::new(), value))
+                        .0
+                        .insert(blobstore_id);
+                }
+            }
+        }
+
+        // Sort so the candidates are offered to the policy in a stable order
+        let (answered, values): (BlobstoresWithEntry, BlobstoreValues) = all_values
+            .into_iter()
+            .map(|(_, group)| group)
+            .sorted_by_key(|(blobstores, _)| blobstores.iter().min().copied())
+            .unzip();
+        let candidates: Vec<_> = answered
+            .iter()
+            .zip(values.iter())
+            .map(|(blobstores, value)| RepairCandidate { blobstores, value })
+            .collect();
+        let chosen = match candidates.len() {
+            0 => return Err(ErrorKind::NothingToHealFrom(key.to_string()).into()),
+            1 => 0,
+            _ => match repair_source_policy.choose_source(key, &candidates) {
+                Some(chosen) if chosen < candidates.len() => chosen,
+                _ => {
+                    return Err(ErrorKind::ValueMismatch(
+                        Arc::new(answered),
+                        Arc::new(missing),
+                        Arc::new(values),
+                    )
+                    .into());
+                }
+            },
+        };
+
+        let needs_repair: HashSet<BlobstoreId> = answered
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != chosen)
+            .flat_map(|(_, blobstores)| blobstores.iter().copied())
+            .chain(missing.into_iter())
+            .collect();
+        let value = values[chosen].as_bytes();
+        let order = AtomicUsize::new(0);
+        let puts: FuturesUnordered<_> = all_blobstores
+            .iter()
+            .filter(|(blobstore_id, _)| needs_repair.contains(blobstore_id))
+            .map(|(blobstore_id, blobstore)| {
+                inner_put(
+                    ctx,
+                    scuba.clone(),
+                    &order,
+                    *blobstore_id,
+                    blobstore.as_ref(),
+                    key.to_owned(),
+                    value.clone(),
+                    Some(PutBehaviour::Overwrite),
+                    self.metrics.as_ref(),
+                    self.write_limiters.as_ref(),
+                    self.timeouts.as_ref(),
+                )
+            })
+            .collect();
+        let put_results: Vec<_> = puts.collect().await;
+        for (blobstore_id, result) in put_results {
+            match result {
+                Ok(_) => report.repaired.push(blobstore_id),
+                Err(error) => {
+                    report.failed.insert(blobstore_id, error);
+                }
+            }
+        }
+        report.repaired.sort();
+        Ok(report)
+    }
+
+    /// Write `value` to `key` only if no inner store has it, returning whether the write
+    /// happened.
+    ///
+    /// Stores can disagree on presence, e.g. after an earlier partial write. The key counts
+    /// as present if any store has it, so a value that only reached some stores is never
+    /// clobbered; healing copies it to the rest. If no store has the key but some could not
+    /// be checked, presence is unknown, so this fails without writing.
+    ///
+    /// Each store is written with `PutBehaviour::IfAbsent`, so a concurrent writer that
+    /// lands between the check and the write keeps its value in the stores it reached.
+    pub async fn put_if_absent(
+        &self,
+        ctx: &CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<bool> {
+        let checks = self
+            .blobstores
+            .iter()
+            .chain(self.write_mostly_blobstores.iter())
+            .chain(self.write_only_blobstores.iter())
+            .map(|(blobstore_id, blobstore)| {
+                let key = &key;
+                async move {
+                    let presence =
+                        store_presence(ctx, *blobstore_id, blobstore.as_ref(), key, &self.timeouts)
+                            .await;
+                    (*blobstore_id, presence)
+                }
+            });
+
+        let mut errors = HashMap::new();
+        for (blobstore_id, presence) in join_all(checks).await {
+            match presence {
+                StorePresence::Present => return Ok(false),
+                StorePresence::Absent => {}
+                StorePresence::Unknown(error) => {
+                    errors.insert(blobstore_id, error);
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ErrorKind::SomeFailedOthersNone(Arc::new(errors)).into());
+        }
+
+        self.put_impl(ctx, key, value, Some(PutBehaviour::IfAbsent))
+            .await?;
+        Ok(true)
+    }
+
+    /// Ask every inner store, normal, write-mostly or write-only, whether it holds `key`,
+    /// without fetching the value. Intended for finding which stores have diverged.
+    pub async fn is_present_per_store(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+    ) -> HashMap<BlobstoreId, StorePresence> {
+        let checks = self
+            .blobstores
+            .iter()
+            .chain(self.write_mostly_blobstores.iter())
+            .chain(self.write_only_blobstores.iter())
+            .map(|(blobstore_id, blobstore)| async move {
+                let presence =
+                    store_presence(ctx, *blobstore_id, blobstore.as_ref(), key, &self.timeouts)
+                        .await;
+                (*blobstore_id, presence)
+            });
+        join_all(checks).await.into_iter().collect()
+    }
+
+    /// Write `value` to `key` in every inner store, including write mostly and write only
+    /// ones, and wait for all of them rather than a quorum. Returns the stores written, or
+    /// `ErrorKind::PutAllFailed` with the stores written and failed if any store failed.
+    pub async fn put_all(
+        &self,
+        ctx: &CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<Vec<BlobstoreId>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::BlobPuts);
+
+        let mut handlers = Vec::new();
+        let mut failed = HashMap::new();
+        let mut puts = self.put_to_stores(ctx, key, value, None, &|_| true);
+        while let Some(result) = puts.next().await {
+            match result {
+                Ok(handler) => handlers.push(handler),
+                Err((blobstore_id, error)) => {
+                    failed.insert(blobstore_id, error);
+                }
+            }
+        }
+
+        // Stores that failed are healed from the ones recorded by their handlers
+        if run_handlers_on_success(ctx) || !failed.is_empty() {
+            for result in join_all(handlers).await {
+                if let Err((blobstore_id, error)) = result {
+                    failed.insert(blobstore_id, error);
+                }
+            }
+        }
+
+        let written: Vec<_> = self
+            .blobstores
+            .iter()
+            .chain(self.write_mostly_blobstores.iter())
+            .chain(self.write_only_blobstores.iter())
+            .map(|(blobstore_id, _)| *blobstore_id)
+            .filter(|blobstore_id| !failed.contains_key(blobstore_id))
+            .sorted()
+            .collect();
+        if failed.is_empty() {
+            Ok(written)
+        } else {
+            Err(ErrorKind::PutAllFailed {
+                written: Arc::new(written),
+                failed: Arc::new(failed),
+            }
+            .into())
+        }
+    }
 }
 
 pub async fn inner_put(
@@ -330,13 +1085,19 @@ pub async fn inner_put(
     key: String,
     value: BlobstoreBytes,
     put_behaviour: Option<PutBehaviour>,
+    metrics: &MultiplexMetricsRecorder,
+    write_limiters: &WriteLimiters,
+    timeouts: &StoreTimeouts,
 ) -> (BlobstoreId, Result<OverwriteStatus, Error>) {
     let size = value.len();
+    let put_timeout = timeouts.put_timeout(blobstore_id);
+    // Throttling happens before the timer starts, so it is not counted as store latency
+    write_limiters.until_ready(blobstore_id).await;
     let (pc, (stats, timeout_or_res)) = {
         let mut ctx = ctx.clone();
         let pc = ctx.fork_perf_counters();
         let ret = timeout(
-            REQUEST_TIMEOUT,
+            put_timeout,
             if let Some(put_behaviour) = put_behaviour {
                 blobstore.put_explicit(&ctx, key.clone(), value, put_behaviour)
             } else {
@@ -347,7 +1108,20 @@ pub async fn inner_put(
         .await;
         (pc, ret)
     };
-    let result = remap_timeout_result(timeout_or_res);
+    let result = match timeout_or_res {
+        Ok(result) => {
+            metrics.record_put(blobstore_id, stats.completion_time, result.is_ok());
+            result
+        }
+        Err(_) => {
+            metrics.record_put_timeout(blobstore_id, stats.completion_time);
+            Err(ErrorKind::StoreTimeout {
+                blobstore_id,
+                timeout: put_timeout,
+            }
+            .into())
+        }
+    };
     record_put_stats(
         &mut scuba,
         &pc,
@@ -370,36 +1144,97 @@ async fn blobstore_get<'a>(
     write_mostly_blobstores: Arc<[(BlobstoreId, Arc<dyn BlobstorePutOps>)]>,
     key: &'a str,
     scuba: MononokeScubaSampleBuilder,
+    metrics: Arc<MultiplexMetricsRecorder>,
+    timeouts: Arc<StoreTimeouts>,
+    health: Option<Arc<StoreHealth>>,
+    breakers: Option<Arc<StoreBreakers>>,
 ) -> Result<Option<BlobstoreGetData>, Error> {
     let is_logged = scuba.sampling().is_logged();
     let blobstores_count = blobstores.len() + write_mostly_blobstores.len();
 
+    // A store with an open breaker is not asked, and counts as having failed
+    let mut skipped: HashMap<BlobstoreId, Error> = HashMap::new();
+    let mut allowed = |stores: &[(BlobstoreId, Arc<dyn BlobstorePutOps>)]| -> Vec<_> {
+        stores
+            .iter()
+            .filter(|(blobstore_id, _)| {
+                let allow = breakers
+                    .as_ref()
+                    .map_or(true, |breakers| breakers.allow(ctx, *blobstore_id));
+                if !allow {
+                    skipped.insert(*blobstore_id, ErrorKind::CircuitOpen(*blobstore_id).into());
+                }
+                allow
+            })
+            .cloned()
+            .collect()
+    };
+    let allowed_blobstores = allowed(blobstores.as_ref());
+    let allowed_write_mostly_blobstores = allowed(write_mostly_blobstores.as_ref());
+
+    let (preferred_blobstores, fallback_blobstores) = match health.as_ref() {
+        Some(health) => health.read_order(&allowed_blobstores),
+        None => (allowed_blobstores, Vec::new()),
+    };
+
     let (stats, result) = {
         async move {
-            let mut errors = HashMap::new();
+            let mut errors = skipped;
             ctx.perf_counters()
                 .increment_counter(PerfCounterType::BlobGets);
 
+            let record_health = |health: Option<Arc<StoreHealth>>| {
+                let breakers = breakers.clone();
+                move |(blobstore_id, result): (BlobstoreId, Result<_, Error>)| {
+                    if let Some(health) = health {
+                        health.record(blobstore_id, result.is_ok());
+                    }
+                    if let Some(breakers) = breakers {
+                        breakers.record(ctx, blobstore_id, result.is_ok());
+                    }
+                    (blobstore_id, result)
+                }
+            };
             let main_requests: FuturesUnordered<_> = multiplexed_get(
                 ctx.clone(),
-                blobstores.as_ref(),
+                preferred_blobstores.as_ref(),
+                key.to_owned(),
+                OperationType::Get,
+                scuba.clone(),
+                metrics.clone(),
+                timeouts.clone(),
+            )
+            .map(|get| get.map(record_health(health.clone())))
+            .collect();
+            // Only asked once all the healthier stores have missed
+            let fallback_requests: FuturesUnordered<_> = multiplexed_get(
+                ctx.clone(),
+                fallback_blobstores.as_ref(),
                 key.to_owned(),
                 OperationType::Get,
                 scuba.clone(),
+                metrics.clone(),
+                timeouts.clone(),
             )
+            .map(|get| get.map(record_health(health.clone())))
             .collect();
             let write_mostly_requests: FuturesUnordered<_> = multiplexed_get(
                 ctx.clone(),
-                write_mostly_blobstores.as_ref(),
+                allowed_write_mostly_blobstores.as_ref(),
                 key.to_owned(),
                 OperationType::Get,
                 scuba,
+                metrics,
+                timeouts,
             )
+            .map(|get| get.map(record_health(None)))
             .collect();
 
             // `chain` here guarantees that `main_requests` is empty before it starts
-            // polling anything in `write_mostly_requests`
-            let mut requests = main_requests.chain(write_mostly_requests);
+            // polling anything in `fallback_requests`, and that before `write_mostly_requests`
+            let mut requests = main_requests
+                .chain(fallback_requests)
+                .chain(write_mostly_requests);
             while let Some(result) = requests.next().await {
                 match result {
                     (_, Ok(Some(mut value))) => {
@@ -413,6 +1248,8 @@ async fn blobstore_get<'a>(
                         value.remove_ctime();
                         return Ok(Some(value));
                     }
+                    // A store too slow to answer doesn't hold up the race, but it is still
+                    // a failure (tagged StoreTimeout), so a miss elsewhere checks the queue
                     (blobstore_id, Err(error)) => {
                         errors.insert(blobstore_id, error);
                     }
@@ -444,6 +1281,26 @@ fn spawn_stream_completion(s: impl StreamExt + Send + 'static) {
     tokio::spawn(s.for_each(|_| async {}));
 }
 
+// Like spawn_stream_completion, but tells scrub_handler about each store that failed
+fn spawn_background_completion<T>(
+    ctx: CoreContext,
+    key: String,
+    scrub_handler: Option<Arc<dyn ScrubHandler>>,
+    s: impl StreamExt<Item = Result<T, (BlobstoreId, Error)>> + Send + 'static,
+) {
+    match scrub_handler {
+        None => spawn_stream_completion(s),
+        Some(scrub_handler) => {
+            tokio::spawn(s.for_each(move |res| {
+                if let Err((blobstore_id, _)) = res {
+                    scrub_handler.on_background_write_failure(&ctx, blobstore_id, &key);
+                }
+                async {}
+            }));
+        }
+    }
+}
+
 struct Timeout;
 
 // Waits for select_next and timer if it's set, and returns
@@ -508,12 +1365,7 @@ impl Blobstore for MultiplexedBlobstoreBase {
         ctx: &'a CoreContext,
         key: &'a str,
     ) -> Result<Option<BlobstoreGetData>> {
-        let mut scuba = self.scuba.clone();
-        let blobstores = self.blobstores.clone();
-        let write_mostly_blobstores = self.write_mostly_blobstores.clone();
-        scuba.sampled(self.scuba_sample_rate);
-
-        blobstore_get(ctx, blobstores, write_mostly_blobstores, key, scuba).await
+        self.get_in_health_order(ctx, key, None, None).await
     }
 
     async fn is_present<'a>(
@@ -660,39 +1512,56 @@ impl Blobstore for MultiplexedBlobstoreBase {
 }
 
 impl MultiplexedBlobstoreBase {
-    // If put_behaviour is None, we we call inner BlobstorePutOps::put_with_status()
-    // If put_behaviour is Some, we we call inner BlobstorePutOps::put_explicit()
-    async fn put_impl<'a>(
-        &'a self,
-        ctx: &'a CoreContext,
+    fn quorum_weight(&self, blobstore_id: BlobstoreId) -> u64 {
+        match &self.write_quorum {
+            Some(write_quorum) => write_quorum.weight(blobstore_id),
+            None => 1,
+        }
+    }
+
+    // Starts a put of `value` to every inner store accepted by `filter`. Each put resolves to
+    // the on_put handler to run once its store has the value, and each handler to the store's
+    // quorum weight.
+    fn put_to_stores(
+        &self,
+        ctx: &CoreContext,
         key: String,
         value: BlobstoreBytes,
         put_behaviour: Option<PutBehaviour>,
-    ) -> Result<OverwriteStatus> {
+        filter: &dyn Fn(BlobstoreId) -> bool,
+    ) -> FuturesUnordered<
+        impl Future<
+                Output = Result<
+                    impl Future<Output = Result<u64, (BlobstoreId, Error)>> + Send + 'static,
+                    (BlobstoreId, Error),
+                >,
+            > + Send
+            + 'static,
+    > {
         let write_order = Arc::new(AtomicUsize::new(0));
         let operation_key = OperationKey::gen();
-        let mut needed_handlers: usize = self.minimum_successful_writes.into();
-        let run_handlers_on_success = !matches!(
-            ctx.session().session_class(),
-            SessionClass::Background | SessionClass::BackgroundUnlessTooSlow
-        );
-
-        let mut puts: FuturesUnordered<_> = self
-            .blobstores
+        self.blobstores
             .iter()
             .chain(self.write_mostly_blobstores.iter())
-            .cloned()
+            .map(|store| (self.quorum_weight(store.0), store))
+            .chain(self.write_only_blobstores.iter().map(|store| (0, store)))
+            .filter(|(_, (blobstore_id, _))| filter(*blobstore_id))
             .map({
-                |(blobstore_id, blobstore)| {
+                |(weight, (blobstore_id, blobstore))| {
+                    let blobstore_id = *blobstore_id;
                     cloned!(
                         self.handler,
+                        self.metrics,
+                        self.write_limiters,
+                        self.timeouts,
                         self.multiplex_id,
                         mut self.scuba,
                         mut ctx,
                         write_order,
                         key,
                         value,
-                        operation_key
+                        operation_key,
+                        blobstore
                     );
                     async move {
                         let blob_size = value.len() as u64;
@@ -705,6 +1574,9 @@ impl MultiplexedBlobstoreBase {
                             key.clone(),
                             value,
                             put_behaviour,
+                            metrics.as_ref(),
+                            write_limiters.as_ref(),
+                            timeouts.as_ref(),
                         )
                         .await;
                         res.map_err(|err| (blobstore_id, err))?;
@@ -723,18 +1595,40 @@ impl MultiplexedBlobstoreBase {
                                 )
                                 .await;
 
-                            res.map_err(|err| (blobstore_id, err))
+                            res.map(|()| weight).map_err(|err| (blobstore_id, err))
                         })
                     }
                 }
             })
-            .collect();
+            .collect()
+    }
+
+    // If put_behaviour is None, we we call inner BlobstorePutOps::put_with_status()
+    // If put_behaviour is Some, we we call inner BlobstorePutOps::put_explicit()
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        let mut needed_weight: u64 = match &self.write_quorum {
+            Some(write_quorum) => write_quorum.threshold.get(),
+            None => self.minimum_successful_writes.get() as u64,
+        };
+        let run_handlers_on_success = run_handlers_on_success(ctx);
 
-        if needed_handlers > puts.len() {
+        let available_weight: u64 = self
+            .blobstores
+            .iter()
+            .chain(self.write_mostly_blobstores.iter())
+            .map(|(blobstore_id, _)| self.quorum_weight(*blobstore_id))
+            .sum();
+        if needed_weight > available_weight {
             return Err(anyhow!(
                 "Not enough blobstores for configured put needs. Have {}, need {}",
-                puts.len(),
-                needed_handlers
+                available_weight,
+                needed_weight
             ));
         }
         let (stats, result) = {
@@ -749,6 +1643,38 @@ impl MultiplexedBlobstoreBase {
                 let mut handler_errors = HashMap::new();
                 let mut handlers = FuturesUnordered::new();
 
+                let mut puts = match self.canonical_store {
+                    Some(canonical_id) => {
+                        let mut canonical_put = self.put_to_stores(
+                            ctx,
+                            key.clone(),
+                            value.clone(),
+                            put_behaviour,
+                            &|blobstore_id| blobstore_id == canonical_id,
+                        );
+                        // Empty if the canonical store isn't part of this multiplex
+                        if let Some(res) = canonical_put.next().await {
+                            handlers.push(res.map_err(|(_, e)| e)?);
+                        }
+                        let puts = self.put_to_stores(
+                            ctx,
+                            key.clone(),
+                            value,
+                            put_behaviour,
+                            &|blobstore_id| blobstore_id != canonical_id,
+                        );
+                        // The canonical store was the only store
+                        if puts.is_empty() && !handlers.is_empty() {
+                            if run_handlers_on_success {
+                                spawn_stream_completion(handlers);
+                            }
+                            return Ok(OverwriteStatus::NotChecked);
+                        }
+                        puts
+                    }
+                    None => self.put_to_stores(ctx, key.clone(), value, put_behaviour, &|_| true),
+                };
+
                 while let Some(result) = select_next_with_timeout(
                     &mut puts,
                     &mut handlers,
@@ -782,14 +1708,22 @@ impl MultiplexedBlobstoreBase {
                             too_slow = true;
                             too_slow_signal.take();
                         }
-                        Ok(Right(Ok(()))) => {
-                            needed_handlers = needed_handlers.saturating_sub(1);
+                        Ok(Right(Ok(0))) => {
+                            // Write-only stores don't count towards minimum_successful_writes
+                        }
+                        Ok(Right(Ok(weight))) => {
+                            needed_weight = needed_weight.saturating_sub(weight);
                             // Can only get here if at least one handler has been run, therefore need to ensure all handlers
                             // run.
-                            if needed_handlers == 0 {
+                            if needed_weight == 0 {
                                 // Handlers were successful. Spawn off remaining puts and handler
                                 // writes, then done
-                                spawn_stream_completion(puts.and_then(|handler| handler));
+                                spawn_background_completion(
+                                    ctx.clone(),
+                                    key.clone(),
+                                    self.background_write_failure_handler.clone(),
+                                    puts.and_then(|handler| handler),
+                                );
                                 spawn_stream_completion(handlers);
                                 // Inner statuses can differ, don't attempt to return them
                                 return Ok(OverwriteStatus::NotChecked);
@@ -890,15 +1824,29 @@ async fn multiplexed_get_one<'a>(
     key: &'a str,
     operation: OperationType,
     mut scuba: MononokeScubaSampleBuilder,
+    metrics: &'a MultiplexMetricsRecorder,
+    timeouts: &'a StoreTimeouts,
 ) -> (BlobstoreId, Result<Option<BlobstoreGetData>, Error>) {
+    let get_timeout = timeouts.get_timeout(blobstore_id);
     let (pc, (stats, timeout_or_res)) = {
         let pc = ctx.fork_perf_counters();
-        let ret = timeout(REQUEST_TIMEOUT, blobstore.get(&ctx, key))
-            .timed()
-            .await;
+        let ret = timeout(get_timeout, blobstore.get(&ctx, key)).timed().await;
         (pc, ret)
     };
-    let result = remap_timeout_result(timeout_or_res);
+    let result = match timeout_or_res {
+        Ok(result) => {
+            metrics.record_get(blobstore_id, stats.completion_time, result.is_ok());
+            result
+        }
+        Err(_) => {
+            metrics.record_get_timeout(blobstore_id, stats.completion_time);
+            Err(ErrorKind::StoreTimeout {
+                blobstore_id,
+                timeout: get_timeout,
+            }
+            .into())
+        }
+    };
     record_get_stats(
         &mut scuba,
         &pc,
@@ -919,12 +1867,14 @@ fn multiplexed_get<'fut: 'iter, 'iter>(
     key: impl Borrow<str> + Clone + 'fut,
     operation: OperationType,
     scuba: MononokeScubaSampleBuilder,
+    metrics: Arc<MultiplexMetricsRecorder>,
+    timeouts: Arc<StoreTimeouts>,
 ) -> impl Iterator<
     Item = impl Future<Output = (BlobstoreId, Result<Option<BlobstoreGetData>, Error>)> + 'fut,
 > + 'iter {
     blobstores.iter().map(move |(blobstore_id, blobstore)| {
         let ctx = ctx.borrow().clone();
-        cloned!(blobstore, blobstore_id, key, scuba);
+        cloned!(blobstore, blobstore_id, key, scuba, metrics, timeouts);
         async move {
             multiplexed_get_one(
                 ctx,
@@ -933,6 +1883,39 @@ fn multiplexed_get<'fut: 'iter, 'iter>(
                 key.borrow(),
                 operation,
                 scuba,
+                metrics.as_ref(),
+                timeouts.as_ref(),
+            )
+            .await
+        }
+    })
+}
+
+// Like multiplexed_get, but asks each store for the key it holds the value under
+fn multiplexed_get_per_store<'fut: 'iter, 'iter>(
+    ctx: &'fut CoreContext,
+    blobstores: &'iter [(BlobstoreId, Arc<dyn BlobstorePutOps>)],
+    store_key: &'iter (dyn Fn(BlobstoreId) -> String + Send + Sync),
+    operation: OperationType,
+    scuba: MononokeScubaSampleBuilder,
+    metrics: Arc<MultiplexMetricsRecorder>,
+    timeouts: Arc<StoreTimeouts>,
+) -> impl Iterator<
+    Item = impl Future<Output = (BlobstoreId, Result<Option<BlobstoreGetData>, Error>)> + 'fut,
+> + 'iter {
+    blobstores.iter().map(move |(blobstore_id, blobstore)| {
+        let key = store_key(*blobstore_id);
+        cloned!(blobstore, blobstore_id, scuba, metrics, timeouts);
+        async move {
+            multiplexed_get_one(
+                ctx.clone(),
+                blobstore.as_ref(),
+                blobstore_id,
+                &key,
+                operation,
+                scuba,
+                metrics.as_ref(),
+                timeouts.as_ref(),
             )
             .await
         }
@@ -8,13 +8,23 @@
 #![deny(warnings)]
 
 pub mod base;
+pub mod metrics;
 pub mod queue;
 pub mod scrub;
+pub mod sql_scrub_handler;
+mod store_timeouts;
+mod write_limits;
 
+pub use crate::metrics::{MultiplexMetrics, StoreMetrics};
 pub use crate::queue::MultiplexedBlobstore;
 pub use crate::scrub::{
-    LoggingScrubHandler, ScrubAction, ScrubBlobstore, ScrubHandler, ScrubOptions, ScrubWriteMostly,
+    enumerate_scrub_keys, BatchRepairOptions, BlobstoreBatchPut, ContentHashRepairSourcePolicy,
+    DefaultRepairSourcePolicy, FnPerStoreKeyNormalizer, FnRepairSourcePolicy, FnScrubKeyNormalizer,
+    IdentityKeyNormalizer, LoggingScrubHandler, MajorityRepairSourcePolicy, RepairCandidate,
+    RepairSourcePolicy, ScrubAction, ScrubBlobstore, ScrubHandler, ScrubKeyNormalizer,
+    ScrubOptions, ScrubProgress, ScrubWriteMostly,
 };
+pub use crate::sql_scrub_handler::{ScrubRepairRecord, SqlScrubHandler};
 
 #[cfg(test)]
 mod test;
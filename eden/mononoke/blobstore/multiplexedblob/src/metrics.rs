@@ -0,0 +1,164 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use metaconfig_types::BlobstoreId;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Bucket 0 holds latencies under 1us, bucket i holds [2^(i-1), 2^i) us.
+// The last bucket also takes anything slower, 2^31us is well past REQUEST_TIMEOUT.
+const LATENCY_BUCKETS: usize = 32;
+
+#[derive(Default)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    fn record(&self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = (64 - micros.leading_zeros() as usize).min(LATENCY_BUCKETS - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Estimate is the upper bound of the bucket the quantile falls in
+    fn quantile(counts: &[u64; LATENCY_BUCKETS], q: f64) -> Option<Duration> {
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((total as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Duration::from_micros(1 << bucket));
+            }
+        }
+        None
+    }
+
+    fn snapshot(&self) -> [u64; LATENCY_BUCKETS] {
+        let mut counts = [0; LATENCY_BUCKETS];
+        for (count, bucket) in counts.iter_mut().zip(self.buckets.iter()) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+        counts
+    }
+}
+
+#[derive(Default)]
+struct StoreCounters {
+    gets: AtomicU64,
+    puts: AtomicU64,
+    errors: AtomicU64,
+    timeouts: AtomicU64,
+    get_latency: LatencyHistogram,
+    put_latency: LatencyHistogram,
+}
+
+/// Per inner store accounting for a multiplex. The set of stores is fixed at
+/// construction, so recording is lock free.
+pub(crate) struct MultiplexMetricsRecorder {
+    stores: HashMap<BlobstoreId, StoreCounters>,
+}
+
+impl MultiplexMetricsRecorder {
+    pub(crate) fn new(blobstore_ids: impl IntoIterator<Item = BlobstoreId>) -> Self {
+        Self {
+            stores: blobstore_ids
+                .into_iter()
+                .map(|id| (id, StoreCounters::default()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn record_get(&self, blobstore_id: BlobstoreId, latency: Duration, is_ok: bool) {
+        if let Some(counters) = self.stores.get(&blobstore_id) {
+            counters.gets.fetch_add(1, Ordering::Relaxed);
+            counters.get_latency.record(latency);
+            if !is_ok {
+                counters.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub(crate) fn record_put(&self, blobstore_id: BlobstoreId, latency: Duration, is_ok: bool) {
+        if let Some(counters) = self.stores.get(&blobstore_id) {
+            counters.puts.fetch_add(1, Ordering::Relaxed);
+            counters.put_latency.record(latency);
+            if !is_ok {
+                counters.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// A get that ran out of time, counted as a timeout rather than an error
+    pub(crate) fn record_get_timeout(&self, blobstore_id: BlobstoreId, latency: Duration) {
+        if let Some(counters) = self.stores.get(&blobstore_id) {
+            counters.gets.fetch_add(1, Ordering::Relaxed);
+            counters.get_latency.record(latency);
+            counters.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// A put that ran out of time, counted as a timeout rather than an error
+    pub(crate) fn record_put_timeout(&self, blobstore_id: BlobstoreId, latency: Duration) {
+        if let Some(counters) = self.stores.get(&blobstore_id) {
+            counters.puts.fetch_add(1, Ordering::Relaxed);
+            counters.put_latency.record(latency);
+            counters.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> MultiplexMetrics {
+        let stores = self
+            .stores
+            .iter()
+            .map(|(id, counters)| {
+                let get_latency = counters.get_latency.snapshot();
+                let put_latency = counters.put_latency.snapshot();
+                let metrics = StoreMetrics {
+                    gets: counters.gets.load(Ordering::Relaxed),
+                    puts: counters.puts.load(Ordering::Relaxed),
+                    errors: counters.errors.load(Ordering::Relaxed),
+                    timeouts: counters.timeouts.load(Ordering::Relaxed),
+                    get_p50: LatencyHistogram::quantile(&get_latency, 0.5),
+                    get_p95: LatencyHistogram::quantile(&get_latency, 0.95),
+                    put_p50: LatencyHistogram::quantile(&put_latency, 0.5),
+                    put_p95: LatencyHistogram::quantile(&put_latency, 0.95),
+                };
+                (*id, metrics)
+            })
+            .collect();
+        MultiplexMetrics { stores }
+    }
+}
+
+/// Point in time view of the operations on one inner store.
+/// Latency percentiles are estimated to within a power of two, and are None
+/// if there have been no operations of that kind.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreMetrics {
+    pub gets: u64,
+    pub puts: u64,
+    /// Failed gets and puts, not counting timeouts
+    pub errors: u64,
+    /// Gets and puts that did not finish within the store's timeout
+    pub timeouts: u64,
+    pub get_p50: Option<Duration>,
+    pub get_p95: Option<Duration>,
+    pub put_p50: Option<Duration>,
+    pub put_p95: Option<Duration>,
+}
+
+/// Point in time view of the operations on each inner store of a multiplex
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MultiplexMetrics {
+    pub stores: HashMap<BlobstoreId, StoreMetrics>,
+}
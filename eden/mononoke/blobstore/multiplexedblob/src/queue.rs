@@ -5,8 +5,13 @@
  * GNU General Public License version 2.
  */
 
-use crate::base::{ErrorKind, MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler};
-use anyhow::Result;
+use crate::base::{
+    ErrorKind, HealKeyReport, MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler,
+    StorePresence, WriteQuorum,
+};
+use crate::metrics::MultiplexMetrics;
+use crate::scrub::{DefaultRepairSourcePolicy, ScrubHandler};
+use anyhow::{Error, Result};
 use async_trait::async_trait;
 use blobstore::{
     Blobstore, BlobstoreGetData, BlobstoreIsPresent, BlobstorePutOps, OverwriteStatus, PutBehaviour,
@@ -18,9 +23,13 @@ use futures_stats::{FutureStats, TimedFutureExt};
 use metaconfig_types::{BlobstoreId, MultiplexId};
 use mononoke_types::{BlobstoreBytes, DateTime};
 use scuba_ext::MononokeScubaSampleBuilder;
+use slog::{info, warn};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::num::{NonZeroU64, NonZeroUsize};
-use std::sync::Arc;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tunables::tunables;
 
 const SYNC_QUEUE: &str = "mysql_sync_queue";
@@ -38,6 +47,239 @@ const SUCCESS: &str = "success";
 const ERROR: &str = "error";
 /// Was the blob found during the get/is_present operations?
 const BLOB_PRESENT: &str = "blob_present";
+/// Stores scoring below this are only read from once the healthier stores have missed
+const UNHEALTHY_SCORE: f64 = 0.5;
+
+/// Recent get outcomes of each inner store, decayed exponentially so that a store which
+/// stops failing recovers. Only used to order reads, a store is never skipped for its score.
+pub struct StoreHealth {
+    half_life: Duration,
+    outcomes: Mutex<HashMap<BlobstoreId, DecayedOutcomes>>,
+}
+
+#[derive(Clone, Copy)]
+struct DecayedOutcomes {
+    successes: f64,
+    failures: f64,
+    updated: Instant,
+}
+
+impl DecayedOutcomes {
+    fn decay_to(&mut self, now: Instant, half_life: Duration) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let factor = 0.5f64.powf(elapsed / half_life.as_secs_f64().max(f64::EPSILON));
+        self.successes *= factor;
+        self.failures *= factor;
+        self.updated = now;
+    }
+
+    // Starting from one success means quiet and unknown stores score as healthy
+    fn score(&self) -> f64 {
+        (self.successes + 1.0) / (self.successes + self.failures + 1.0)
+    }
+}
+
+impl StoreHealth {
+    pub fn new(half_life: Duration) -> Self {
+        Self {
+            half_life,
+            outcomes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, blobstore_id: BlobstoreId, success: bool) {
+        self.record_at(blobstore_id, success, Instant::now())
+    }
+
+    pub(crate) fn record_at(&self, blobstore_id: BlobstoreId, success: bool, now: Instant) {
+        let mut outcomes = self.outcomes.lock().expect("lock poisoned");
+        let outcome = outcomes
+            .entry(blobstore_id)
+            .or_insert_with(|| DecayedOutcomes {
+                successes: 0.0,
+                failures: 0.0,
+                updated: now,
+            });
+        outcome.decay_to(now, self.half_life);
+        if success {
+            outcome.successes += 1.0;
+        } else {
+            outcome.failures += 1.0;
+        }
+    }
+
+    /// From 1.0 for a store with no recent failures, towards 0.0 for one only failing
+    pub fn score(&self, blobstore_id: BlobstoreId) -> f64 {
+        self.score_at(blobstore_id, Instant::now())
+    }
+
+    pub(crate) fn score_at(&self, blobstore_id: BlobstoreId, now: Instant) -> f64 {
+        let outcomes = self.outcomes.lock().expect("lock poisoned");
+        outcomes.get(&blobstore_id).map_or(1.0, |outcome| {
+            let mut outcome = *outcome;
+            outcome.decay_to(now, self.half_life);
+            outcome.score()
+        })
+    }
+
+    /// Split stores into those to read first, healthiest first, and the unhealthy ones
+    /// to fall back to. If none are healthy they are all read first, as before scoring.
+    pub(crate) fn read_order<T: Clone>(
+        &self,
+        blobstores: &[(BlobstoreId, T)],
+    ) -> (Vec<(BlobstoreId, T)>, Vec<(BlobstoreId, T)>) {
+        let now = Instant::now();
+        let mut scored: Vec<_> = blobstores
+            .iter()
+            .map(|(id, store)| (self.score_at(*id, now), (*id, store.clone())))
+            .collect();
+        scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = scored
+            .into_iter()
+            .partition(|(score, _)| *score >= UNHEALTHY_SCORE);
+        let healthy: Vec<_> = healthy.into_iter().map(|(_, store)| store).collect();
+        let unhealthy: Vec<_> = unhealthy.into_iter().map(|(_, store)| store).collect();
+        if healthy.is_empty() {
+            (unhealthy, healthy)
+        } else {
+            (healthy, unhealthy)
+        }
+    }
+}
+
+/// Per store circuit breakers for gets. A store that fails `failure_threshold` gets in a
+/// row, within `window` of the first of them, is skipped by gets until `probe_interval`
+/// has passed. Then a single get is let through as a probe: if it succeeds the store is
+/// read from again, otherwise it is skipped for another interval.
+pub struct StoreBreakers {
+    failure_threshold: NonZeroUsize,
+    window: Duration,
+    probe_interval: Duration,
+    states: Mutex<HashMap<BlobstoreId, BreakerState>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BreakerState {
+    Closed {
+        consecutive_failures: usize,
+        first_failure: Instant,
+    },
+    Open {
+        until: Instant,
+    },
+    // A probe get is in flight. Another is let through if it never reports back,
+    // e.g. because it was dropped once a different store answered
+    Probing {
+        started: Instant,
+    },
+}
+
+impl StoreBreakers {
+    pub fn new(
+        failure_threshold: NonZeroUsize,
+        window: Duration,
+        probe_interval: Duration,
+    ) -> Self {
+        Self {
+            failure_threshold,
+            window,
+            probe_interval,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a get should be sent to the store
+    pub fn allow(&self, ctx: &CoreContext, blobstore_id: BlobstoreId) -> bool {
+        self.allow_at(ctx, blobstore_id, Instant::now())
+    }
+
+    pub(crate) fn allow_at(
+        &self,
+        ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        now: Instant,
+    ) -> bool {
+        let mut states = self.states.lock().expect("lock poisoned");
+        let state = match states.get_mut(&blobstore_id) {
+            Some(state) => state,
+            None => return true,
+        };
+        let probe = match *state {
+            BreakerState::Closed { .. } => return true,
+            BreakerState::Open { until } => now >= until,
+            BreakerState::Probing { started } => {
+                now.saturating_duration_since(started) >= self.probe_interval
+            }
+        };
+        if probe {
+            info!(
+                ctx.logger(),
+                "Probing blobstore {} with an open circuit breaker", blobstore_id
+            );
+            *state = BreakerState::Probing { started: now };
+        }
+        probe
+    }
+
+    pub fn record(&self, ctx: &CoreContext, blobstore_id: BlobstoreId, success: bool) {
+        self.record_at(ctx, blobstore_id, success, Instant::now())
+    }
+
+    pub(crate) fn record_at(
+        &self,
+        ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        success: bool,
+        now: Instant,
+    ) {
+        let mut states = self.states.lock().expect("lock poisoned");
+        if success {
+            if let Some(BreakerState::Open { .. }) | Some(BreakerState::Probing { .. }) =
+                states.remove(&blobstore_id)
+            {
+                info!(
+                    ctx.logger(),
+                    "Closed circuit breaker for blobstore {}, it has recovered", blobstore_id
+                );
+            }
+            return;
+        }
+
+        let state = states.entry(blobstore_id).or_insert(BreakerState::Closed {
+            consecutive_failures: 0,
+            first_failure: now,
+        });
+        let open = match state {
+            BreakerState::Closed {
+                consecutive_failures,
+                first_failure,
+            } => {
+                if *consecutive_failures == 0
+                    || now.saturating_duration_since(*first_failure) > self.window
+                {
+                    *consecutive_failures = 0;
+                    *first_failure = now;
+                }
+                *consecutive_failures += 1;
+                *consecutive_failures >= self.failure_threshold.get()
+            }
+            // Gets started before the breaker opened can still be finishing
+            BreakerState::Open { .. } => false,
+            BreakerState::Probing { .. } => true,
+        };
+        if open {
+            warn!(
+                ctx.logger(),
+                "Opened circuit breaker for blobstore {}, skipping it for {:?}",
+                blobstore_id,
+                self.probe_interval
+            );
+            *state = BreakerState::Open {
+                until: now + self.probe_interval,
+            };
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct MultiplexedBlobstore {
@@ -45,6 +287,12 @@ pub struct MultiplexedBlobstore {
     queue: Arc<dyn BlobstoreSyncQueue>,
     multiplex_scuba: MononokeScubaSampleBuilder,
     scuba_sample_rate: NonZeroU64,
+    /// If set, gets need this many normal blobstores to return the same value
+    read_quorum: Option<NonZeroUsize>,
+    /// If set, gets prefer the stores that have recently been succeeding
+    health: Option<Arc<StoreHealth>>,
+    /// If set, gets skip the stores that have been failing every request
+    breakers: Option<Arc<StoreBreakers>>,
 }
 
 impl MultiplexedBlobstore {
@@ -75,8 +323,185 @@ impl MultiplexedBlobstore {
             queue,
             multiplex_scuba,
             scuba_sample_rate,
+            read_quorum: None,
+            health: None,
+            breakers: None,
         }
     }
+
+    /// Require gets to be answered identically by read_quorum of the normal blobstores,
+    /// rather than returning the first value found
+    pub fn with_read_quorum(self, read_quorum: NonZeroUsize) -> Self {
+        Self {
+            read_quorum: Some(read_quorum),
+            ..self
+        }
+    }
+
+    /// Score each normal store on its recent gets, decaying with `half_life`, and read from
+    /// the unhealthy ones only after the healthy ones miss. Cuts tail latency while a
+    /// replica is flapping, without changing what a get can return.
+    pub fn with_health_scoring(self, half_life: Duration) -> Self {
+        Self {
+            health: Some(Arc::new(StoreHealth::new(half_life))),
+            ..self
+        }
+    }
+
+    /// Stop sending gets to a store once it has failed `failure_threshold` in a row within
+    /// `window`, probing it again after `probe_interval`. While skipped, a store counts as
+    /// having failed the get, so a dead replica adds no latency but a key it alone holds
+    /// is still looked up in the queue rather than reported missing.
+    pub fn with_circuit_breaker(
+        self,
+        failure_threshold: NonZeroUsize,
+        window: Duration,
+        probe_interval: Duration,
+    ) -> Self {
+        Self {
+            breakers: Some(Arc::new(StoreBreakers::new(
+                failure_threshold,
+                window,
+                probe_interval,
+            ))),
+            ..self
+        }
+    }
+
+    /// Limit the puts per second sent to individual inner stores. Stores absent from
+    /// `write_limits` are not limited.
+    pub fn with_write_limits(self, write_limits: &HashMap<BlobstoreId, NonZeroU32>) -> Self {
+        Self {
+            blobstore: Arc::new(
+                self.blobstore
+                    .as_ref()
+                    .clone()
+                    .with_write_limits(write_limits),
+            ),
+            ..self
+        }
+    }
+
+    /// Give up on gets and puts to individual inner stores after the given time. A get
+    /// that times out is a failed read, so a miss from the other stores is only trusted
+    /// if the healer queue has nothing for the key. A put that times out is a failed write.
+    pub fn with_store_timeouts(
+        self,
+        get_timeouts: &HashMap<BlobstoreId, Duration>,
+        put_timeouts: &HashMap<BlobstoreId, Duration>,
+    ) -> Self {
+        Self {
+            blobstore: Arc::new(
+                self.blobstore
+                    .as_ref()
+                    .clone()
+                    .with_store_timeouts(get_timeouts, put_timeouts),
+            ),
+            ..self
+        }
+    }
+
+    /// Treat the given stores as write-only. They receive puts, but are never read from
+    /// and do not count towards minimum_successful_writes. For onboarding a new store
+    /// before it has been backfilled.
+    pub fn with_write_only(self, write_only: &HashSet<BlobstoreId>) -> Self {
+        Self {
+            blobstore: Arc::new(self.blobstore.as_ref().clone().with_write_only(write_only)),
+            ..self
+        }
+    }
+
+    /// Consider puts successful once the acknowledging stores carry the quorum's threshold
+    /// weight. Puts still outstanding at that point carry on in the background, and any that
+    /// fail are reported to `scrub_handler`.
+    pub fn with_write_quorum(
+        self,
+        write_quorum: WriteQuorum,
+        scrub_handler: Arc<dyn ScrubHandler>,
+    ) -> Self {
+        Self {
+            blobstore: Arc::new(
+                self.blobstore
+                    .as_ref()
+                    .clone()
+                    .with_write_quorum(write_quorum, scrub_handler),
+            ),
+            ..self
+        }
+    }
+
+    /// Write each put to `blobstore_id` and wait for it before writing to the other stores,
+    /// so reads from that store see the session's writes at once. A failed write to it fails
+    /// the put, even when the other stores could have met the write quorum.
+    pub fn with_canonical_store(self, blobstore_id: BlobstoreId) -> Self {
+        Self {
+            blobstore: Arc::new(
+                self.blobstore
+                    .as_ref()
+                    .clone()
+                    .with_canonical_store(blobstore_id),
+            ),
+            ..self
+        }
+    }
+
+    /// Counts and latencies of the gets and puts sent to each inner store
+    pub fn multiplex_metrics(&self) -> MultiplexMetrics {
+        self.blobstore.multiplex_metrics()
+    }
+
+    /// Read a key from one inner store only, bypassing the fan-out. For ops tooling
+    /// investigating divergence between stores; the value is not healed or checked.
+    pub async fn get_from_store(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+        blobstore_id: BlobstoreId,
+    ) -> Result<Option<BlobstoreGetData>> {
+        self.blobstore.get_from_store(ctx, key, blobstore_id).await
+    }
+
+    /// Repair one known divergent key without running a whole scrub. The value held by
+    /// the most stores, or the one matching a content addressed key, is written to the
+    /// stores that are missing it or hold something else.
+    pub async fn heal_key(&self, ctx: &CoreContext, key: &str) -> Result<HealKeyReport> {
+        self.blobstore
+            .heal_key(ctx, key, &DefaultRepairSourcePolicy)
+            .await
+    }
+
+    /// Write `value` to `key` only if no inner store has it, returning whether the write
+    /// happened. A key present in any store counts as present.
+    pub async fn put_if_absent(
+        &self,
+        ctx: &CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<bool> {
+        self.blobstore.put_if_absent(ctx, key, value).await
+    }
+
+    /// Write `value` to `key` in every inner store and wait for all of them, for data that
+    /// must not be left partially replicated. Fails if any store could not be written,
+    /// with `ErrorKind::PutAllFailed` giving the outcome for each store.
+    pub async fn put_all(
+        &self,
+        ctx: &CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<Vec<BlobstoreId>> {
+        self.blobstore.put_all(ctx, key, value).await
+    }
+
+    /// Which inner stores hold `key`, checked without fetching the value. A store that
+    /// fails to answer is reported as `StorePresence::Unknown`, never as absent.
+    pub async fn is_present_per_store(
+        &self,
+        ctx: &CoreContext,
+        key: &str,
+    ) -> HashMap<BlobstoreId, StorePresence> {
+        self.blobstore.is_present_per_store(ctx, key).await
+    }
 }
 
 impl fmt::Display for MultiplexedBlobstore {
@@ -155,7 +580,19 @@ impl Blobstore for MultiplexedBlobstore {
         scuba.sampled(self.scuba_sample_rate);
 
         let (stats, result) = async {
-            let result = self.blobstore.get(ctx, key).await;
+            if let Some(read_quorum) = self.read_quorum {
+                // Nothing to resolve from the queue, disagreement is the error
+                return self
+                    .blobstore
+                    .quorum_get(ctx, key, read_quorum)
+                    .await
+                    .map_err(Error::from);
+            }
+
+            let result = self
+                .blobstore
+                .get_in_health_order(ctx, key, self.health.clone(), self.breakers.clone())
+                .await;
 
             match result {
                 Ok(value) => Ok(value),
@@ -183,7 +620,14 @@ impl Blobstore for MultiplexedBlobstore {
                             // Oh boy. If we found this on the queue but we didn't find it in the
                             // blobstores, it's possible that the content got written to the blobstore in
                             // the meantime. To account for this ... we have to check again.
-                            self.blobstore.get(ctx, key).await
+                            self.blobstore
+                                .get_in_health_order(
+                                    ctx,
+                                    key,
+                                    self.health.clone(),
+                                    self.breakers.clone(),
+                                )
+                                .await
                         }
                     } else {
                         return Err(error);
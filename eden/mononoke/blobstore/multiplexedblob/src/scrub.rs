@@ -7,35 +7,53 @@
 
 use crate::{
     base::{inner_put, ErrorKind, MultiplexedBlobstoreBase},
+    metrics::MultiplexMetricsRecorder,
     queue::MultiplexedBlobstore,
+    store_timeouts::StoreTimeouts,
+    write_limits::WriteLimiters,
 };
 
-use anyhow::Result;
+use anyhow::{format_err, Result};
 use async_trait::async_trait;
 use blobstore::{
-    Blobstore, BlobstoreGetData, BlobstoreIsPresent, BlobstoreMetadata, BlobstorePutOps,
-    OverwriteStatus, PutBehaviour,
+    Blobstore, BlobstoreGetData, BlobstoreIsPresent, BlobstoreKeyParam, BlobstoreKeySource,
+    BlobstoreMetadata, BlobstorePutOps, OverwriteStatus, PutBehaviour,
 };
 use blobstore_sync_queue::BlobstoreSyncQueue;
 use chrono::Duration as ChronoDuration;
 use context::CoreContext;
-use futures::stream::{FuturesUnordered, TryStreamExt};
+use futures::{
+    future::try_join_all,
+    stream::{FuturesUnordered, TryStreamExt},
+};
+use futures_stats::TimedFutureExt;
 use metaconfig_types::{BlobstoreId, MultiplexId};
-use mononoke_types::{BlobstoreBytes, Timestamp};
+use mononoke_types::{
+    hash::{Blake2, Context as HashContext},
+    BlobstoreBytes, Timestamp,
+};
 use once_cell::sync::Lazy;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::{info, warn};
+use std::borrow::Cow;
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::num::{NonZeroU64, NonZeroUsize};
-use std::sync::{atomic::AtomicUsize, Arc};
-use std::time::Duration;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
 use strum_macros::{EnumString, EnumVariantNames, IntoStaticStr};
+use tokio::time::timeout;
 
 static HEAL_MAX_BACKLOG: Lazy<Duration> =
     Lazy::new(|| Duration::from_secs(ChronoDuration::days(7).num_seconds() as u64));
 
+const DEFAULT_REPAIR_BATCH_SIZE: usize = 100;
+
 /// What to do when the ScrubBlobstore finds a problem
 #[derive(
     Debug,
@@ -53,6 +71,9 @@ pub enum ScrubAction {
     ReportOnly,
     /// Do repairs
     Repair,
+    /// Repair stores missing a key, but when stores hold differing values copy each
+    /// of them to the quarantine store for manual review instead of picking one
+    Quarantine,
 }
 
 // How to treat write mostly stores during the scrub
@@ -87,6 +108,171 @@ pub struct ScrubOptions {
     pub scrub_grace: Option<Duration>,
     pub scrub_action_on_missing_write_mostly: ScrubWriteMostly,
     pub queue_peek_bound: Option<Duration>,
+    pub repair_source_policy: Arc<dyn RepairSourcePolicy>,
+    /// Keys starting with any of these are read without being scrubbed,
+    /// for keys we know are intentionally absent from some stores
+    pub ignore_key_prefixes: Vec<String>,
+    /// If set, only these stores are compared and repaired, e.g. when bringing up
+    /// one new replica. Keys present only in the other stores are ignored.
+    pub restrict_to_stores: Option<Vec<BlobstoreId>>,
+    /// If set, only keys in `[start, end)` are scrubbed, so a scrub can be split
+    /// across workers by giving each a disjoint range. Keys outside the range are
+    /// read without being scrubbed or counted as scanned. An empty end leaves the
+    /// range unbounded above.
+    pub key_range: Option<(String, String)>,
+    /// Report progress to the scrub handler every this many keys scanned
+    pub progress_interval_keys: Option<NonZeroU64>,
+    /// Report progress to the scrub handler if this long has passed since the last report
+    pub progress_interval: Option<Duration>,
+    /// How many keys the scrub is expected to scan, if known. Used to estimate time remaining.
+    pub expected_keys: Option<u64>,
+    /// Maps each key to its logical form before scrub decides what to do with it, and
+    /// the logical key to the key each store holds it under
+    pub key_normalizer: Arc<dyn ScrubKeyNormalizer>,
+    /// Read-only store consulted only when no inner store has a key. If it has the
+    /// key, the value is repaired into the inner stores. It is never written to.
+    pub backup_store: Option<Arc<dyn Blobstore>>,
+    /// Where ScrubAction::Quarantine copies differing values. Required for that action.
+    pub quarantine_store: Option<Arc<dyn BlobstorePutOps>>,
+    /// If set, repairs of small keys to stores that support batch puts are grouped into
+    /// batches. A batch is written once full, so a scrub using this must call
+    /// BatchRepairOptions::flush or ScrubBlobstore::flush_repairs when done to write
+    /// the last ones.
+    pub batch_repair: Option<BatchRepairOptions>,
+}
+
+/// An inner store that can write several keys in one round trip
+#[async_trait]
+pub trait BlobstoreBatchPut: fmt::Debug + Send + Sync {
+    async fn put_batch(
+        &self,
+        ctx: &CoreContext,
+        values: Vec<(String, BlobstoreBytes)>,
+    ) -> Result<()>;
+}
+
+/// Which scrub repairs are batched. Batched repairs always overwrite, which is safe for
+/// keys like alias mappings whose value is determined by the key.
+#[derive(Clone, Debug)]
+pub struct BatchRepairOptions {
+    /// Stores that support batch puts. Other stores are repaired a key at a time.
+    pub stores: HashMap<BlobstoreId, Arc<dyn BlobstoreBatchPut>>,
+    /// Keys whose class, the key without its repo prefix, starts with one of these
+    /// are batched
+    pub key_classes: Vec<String>,
+    /// A store's batch is written once it holds this many repairs
+    pub batch_size: NonZeroUsize,
+    /// Batched repairs of the registered ScrubBlobstores, shared by all clones of these
+    /// options so that whoever made them can flush stores built from a clone
+    pub(crate) registered: Arc<Mutex<Vec<PendingRepairs>>>,
+}
+
+impl BatchRepairOptions {
+    /// Batch repairs of alias mappings to the given stores
+    pub fn new(stores: HashMap<BlobstoreId, Arc<dyn BlobstoreBatchPut>>) -> Self {
+        Self {
+            stores,
+            key_classes: vec!["alias.".to_string()],
+            batch_size: NonZeroUsize::new(DEFAULT_REPAIR_BATCH_SIZE)
+                .expect("batch size is not zero"),
+            registered: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Write the batched repairs that have not yet filled a batch, for every
+    /// ScrubBlobstore registered with a clone of these options
+    pub async fn flush(&self, ctx: &CoreContext) -> Result<()> {
+        let registered = self.registered.lock().expect("lock poisoned").clone();
+        try_join_all(registered.iter().map(|pending| pending.flush(ctx))).await?;
+        Ok(())
+    }
+
+    fn is_batched(&self, logical_key: &str) -> bool {
+        let key_class = match logical_key.split_once('.') {
+            Some((repo, rest)) if repo.starts_with("repo") => rest,
+            _ => logical_key,
+        };
+        self.key_classes
+            .iter()
+            .any(|class| key_class.starts_with(class.as_str()))
+    }
+}
+
+// Batched repairs waiting to be written, by store
+#[derive(Default)]
+struct RepairBatcher {
+    pending: Mutex<HashMap<BlobstoreId, Vec<(String, BlobstoreGetData)>>>,
+}
+
+// A ScrubBlobstore's batched repairs, with what is needed to write them
+#[derive(Clone)]
+pub(crate) struct PendingRepairs {
+    inner: Arc<MultiplexedBlobstoreBase>,
+    stores: HashMap<BlobstoreId, Arc<dyn BlobstoreBatchPut>>,
+    batcher: Arc<RepairBatcher>,
+    scrub_handler: Arc<dyn ScrubHandler>,
+    progress: Arc<ScrubProgressTracker>,
+}
+
+impl fmt::Debug for PendingRepairs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingRepairs")
+            .field("stores", &self.stores)
+            .finish()
+    }
+}
+
+impl PendingRepairs {
+    async fn flush(&self, ctx: &CoreContext) -> Result<()> {
+        let puts = self
+            .batcher
+            .take_all()
+            .into_iter()
+            .filter_map(|(id, batch)| {
+                let store = self.stores.get(&id)?;
+                Some(put_batch_and_mark_repaired(
+                    ctx,
+                    id,
+                    store.as_ref(),
+                    batch,
+                    self.scrub_handler.as_ref(),
+                    self.inner.metrics_recorder(),
+                    self.inner.write_limiters(),
+                    self.inner.store_timeouts(),
+                    self.progress.as_ref(),
+                ))
+            });
+        try_join_all(puts).await?;
+        Ok(())
+    }
+}
+
+impl RepairBatcher {
+    // Add a repair, returning the store's batch if it is now full. A key already
+    // waiting in the store's batch is not added again.
+    fn add(
+        &self,
+        blobstore_id: BlobstoreId,
+        key: &str,
+        value: &BlobstoreGetData,
+        batch_size: NonZeroUsize,
+    ) -> Option<Vec<(String, BlobstoreGetData)>> {
+        let mut pending = self.pending.lock().expect("lock poisoned");
+        let batch = pending.entry(blobstore_id).or_insert_with(Vec::new);
+        if batch.iter().any(|(pending_key, _)| pending_key == key) {
+            return None;
+        }
+        batch.push((key.to_owned(), value.clone()));
+        if batch.len() >= batch_size.get() {
+            pending.remove(&blobstore_id)
+        } else {
+            None
+        }
+    }
+
+    fn take_all(&self) -> HashMap<BlobstoreId, Vec<(String, BlobstoreGetData)>> {
+        std::mem::take(&mut *self.pending.lock().expect("lock poisoned"))
+    }
 }
 
 impl Default for ScrubOptions {
@@ -96,10 +282,351 @@ impl Default for ScrubOptions {
             scrub_grace: None,
             scrub_action_on_missing_write_mostly: ScrubWriteMostly::Scrub,
             queue_peek_bound: Some(*HEAL_MAX_BACKLOG),
+            repair_source_policy: Arc::new(DefaultRepairSourcePolicy),
+            ignore_key_prefixes: vec![],
+            restrict_to_stores: None,
+            key_range: None,
+            progress_interval_keys: None,
+            progress_interval: None,
+            expected_keys: None,
+            key_normalizer: Arc::new(IdentityKeyNormalizer),
+            backup_store: None,
+            quarantine_store: None,
+            batch_repair: None,
+        }
+    }
+}
+
+impl ScrubOptions {
+    /// Whether the logical key is one this scrub should check
+    pub fn key_in_range(&self, logical_key: &str) -> bool {
+        match &self.key_range {
+            Some((start, end)) => {
+                logical_key >= start.as_str() && (end.is_empty() || logical_key < end.as_str())
+            }
+            None => true,
+        }
+    }
+
+    /// The range to enumerate keys to scrub from a BlobstoreKeySource. Enumeration
+    /// ranges include their end key, so callers should still filter with `key_in_range`.
+    pub fn enumeration_range(&self) -> BlobstoreKeyParam {
+        match &self.key_range {
+            Some((start, end)) => (start.clone()..=end.clone()).into(),
+            None => (..).into(),
+        }
+    }
+}
+
+/// Enumerate the keys this scrub should check from `key_source`, in key order. Only
+/// `key_range` is listed, so workers given disjoint ranges each list just their own keys.
+pub async fn enumerate_scrub_keys(
+    ctx: &CoreContext,
+    key_source: &dyn BlobstoreKeySource,
+    scrub_options: &ScrubOptions,
+) -> Result<Vec<String>> {
+    let mut keys = vec![];
+    let mut range = scrub_options.enumeration_range();
+    loop {
+        let data = key_source.enumerate(ctx, &range).await?;
+        keys.extend(data.keys.into_iter().filter(|key| {
+            scrub_options.key_in_range(scrub_options.key_normalizer.normalize(key).as_ref())
+        }));
+        match data.next_token {
+            Some(next_token) => range = next_token,
+            None => break,
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+/// How far a scrub has got, as reported to ScrubHandler::on_progress
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScrubProgress {
+    pub keys_scanned: u64,
+    /// Inner stores repaired successfully
+    pub repairs_done: u64,
+    /// Inner stores that need repair but were not repaired, either because the
+    /// scrub action is ReportOnly or because the repair failed
+    pub repairs_pending: u64,
+    pub elapsed: Duration,
+    pub expected_keys: Option<u64>,
+}
+
+impl ScrubProgress {
+    /// Estimated time to scan the remaining expected keys at the rate so far
+    pub fn eta(&self) -> Option<Duration> {
+        let expected_keys = self.expected_keys?;
+        if self.keys_scanned == 0 {
+            return None;
+        }
+        let remaining = expected_keys.saturating_sub(self.keys_scanned);
+        let ratio = remaining as f64 / self.keys_scanned as f64;
+        Some(self.elapsed.mul_f64(ratio))
+    }
+}
+
+// Shared between clones of a ScrubBlobstore so progress covers the whole scrub
+#[derive(Debug)]
+struct ScrubProgressTracker {
+    started: Instant,
+    keys_scanned: AtomicU64,
+    repairs_done: AtomicU64,
+    repairs_pending: AtomicU64,
+    // Milliseconds after started that progress was last reported
+    last_report_ms: AtomicU64,
+}
+
+impl ScrubProgressTracker {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            keys_scanned: AtomicU64::new(0),
+            repairs_done: AtomicU64::new(0),
+            repairs_pending: AtomicU64::new(0),
+            last_report_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn record_repair(&self, is_repaired: bool) {
+        if is_repaired {
+            self.repairs_done.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.repairs_pending.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_would_repair(&self, count: usize) {
+        self.repairs_pending
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    // Count a scanned key, returning the progress so far if a report is due
+    fn record_key(&self, scrub_options: &ScrubOptions) -> Option<ScrubProgress> {
+        let keys_scanned = self.keys_scanned.fetch_add(1, Ordering::Relaxed) + 1;
+        let elapsed = self.started.elapsed();
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let due_by_keys = scrub_options
+            .progress_interval_keys
+            .map_or(false, |interval| keys_scanned % interval.get() == 0);
+        let due_by_time = scrub_options.progress_interval.map_or(false, |interval| {
+            let since_last_ms =
+                elapsed_ms.saturating_sub(self.last_report_ms.load(Ordering::Relaxed));
+            since_last_ms >= interval.as_millis() as u64
+        });
+        if !due_by_keys && !due_by_time {
+            return None;
+        }
+        self.last_report_ms.store(elapsed_ms, Ordering::Relaxed);
+        Some(ScrubProgress {
+            keys_scanned,
+            repairs_done: self.repairs_done.load(Ordering::Relaxed),
+            repairs_pending: self.repairs_pending.load(Ordering::Relaxed),
+            elapsed,
+            expected_keys: scrub_options.expected_keys,
+        })
+    }
+}
+
+/// One of the differing values found for a key, and the stores holding it
+pub struct RepairCandidate<'a> {
+    pub blobstores: &'a HashSet<BlobstoreId>,
+    pub value: &'a BlobstoreGetData,
+}
+
+/// Picks the authoritative value when the stores hold differing values for a key.
+/// The chosen value is then copied to the stores that disagree with it.
+pub trait RepairSourcePolicy: Send + Sync + fmt::Debug {
+    /// Returns the index into `candidates` of the value to repair from, or None
+    /// if no value can be trusted, in which case the key is left alone.
+    fn choose_source(&self, key: &str, candidates: &[RepairCandidate<'_>]) -> Option<usize>;
+}
+
+// Key types where the id is the blake2 hash of the stored blob itself.
+// Content is not here as its id is the hash of the file bytes, not of the blob.
+const BLOB_HASHED_KEY_TYPES: &[&str] = &[
+    "changeset",
+    "deletedmanifest",
+    "fastlogbatch",
+    "fileunode",
+    "fsnode",
+    "manifestunode",
+    "skeletonmanifest",
+];
+
+// Split a key like "repo0000.changeset.blake2.<hex>" into its type and hash
+fn parse_content_addressed_key(key: &str) -> Option<(&str, Blake2)> {
+    let (prefix, hash) = key.rsplit_once(".blake2.")?;
+    let key_type = prefix.rsplit('.').next()?;
+    if !BLOB_HASHED_KEY_TYPES.contains(&key_type) {
+        return None;
+    }
+    let hash = Blake2::from_str(hash).ok()?;
+    Some((key_type, hash))
+}
+
+/// Prefer the value whose hash matches the content addressed key.
+/// Makes no choice for other keys, or if it is not exactly one value that matches.
+#[derive(Debug)]
+pub struct ContentHashRepairSourcePolicy;
+
+impl RepairSourcePolicy for ContentHashRepairSourcePolicy {
+    fn choose_source(&self, key: &str, candidates: &[RepairCandidate<'_>]) -> Option<usize> {
+        let (key_type, expected) = parse_content_addressed_key(key)?;
+        let mut matching = candidates.iter().enumerate().filter(|(_, c)| {
+            let mut context = HashContext::new(key_type.as_bytes());
+            context.update(c.value.as_raw_bytes());
+            context.finish() == expected
+        });
+        match (matching.next(), matching.next()) {
+            (Some((index, _)), None) => Some(index),
+            _ => None,
+        }
+    }
+}
+
+/// Prefer the value held by the most stores. Makes no choice on a tie.
+#[derive(Debug)]
+pub struct MajorityRepairSourcePolicy;
+
+impl RepairSourcePolicy for MajorityRepairSourcePolicy {
+    fn choose_source(&self, _key: &str, candidates: &[RepairCandidate<'_>]) -> Option<usize> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut tied = false;
+        for (index, candidate) in candidates.iter().enumerate() {
+            let count = candidate.blobstores.len();
+            match best {
+                Some((_, best_count)) if count < best_count => {}
+                Some((_, best_count)) if count == best_count => tied = true,
+                _ => {
+                    best = Some((index, count));
+                    tied = false;
+                }
+            }
+        }
+        if tied {
+            None
+        } else {
+            best.map(|(index, _)| index)
+        }
+    }
+}
+
+/// Use the content hash for content addressed keys, and the majority otherwise
+#[derive(Debug)]
+pub struct DefaultRepairSourcePolicy;
+
+impl RepairSourcePolicy for DefaultRepairSourcePolicy {
+    fn choose_source(&self, key: &str, candidates: &[RepairCandidate<'_>]) -> Option<usize> {
+        if parse_content_addressed_key(key).is_some() {
+            ContentHashRepairSourcePolicy.choose_source(key, candidates)
+        } else {
+            MajorityRepairSourcePolicy.choose_source(key, candidates)
         }
     }
 }
 
+/// Adapts a closure into a RepairSourcePolicy
+pub struct FnRepairSourcePolicy<F>(pub F);
+
+impl<F> fmt::Debug for FnRepairSourcePolicy<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FnRepairSourcePolicy")
+    }
+}
+
+impl<F> RepairSourcePolicy for FnRepairSourcePolicy<F>
+where
+    F: Fn(&str, &[RepairCandidate<'_>]) -> Option<usize> + Send + Sync,
+{
+    fn choose_source(&self, key: &str, candidates: &[RepairCandidate<'_>]) -> Option<usize> {
+        (self.0)(key, candidates)
+    }
+}
+
+/// Maps a key as stored to the logical key it stands for, for stores whose keys carry
+/// a physical encoding such as a shard prefix. The logical key is what is matched
+/// against `ignore_key_prefixes` and `key_range`, and given to the `RepairSourcePolicy`.
+///
+/// Each store is read under `physical_key` for its id, so stores holding one logical key
+/// under different encodings are compared as holding the same key, and repairs write each
+/// store's physical key, never the normalized one. The sync queue records keys as they were
+/// put, so it is always looked up with the key scrub was given.
+pub trait ScrubKeyNormalizer: Send + Sync + fmt::Debug {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str>;
+
+    /// The key `blobstore_id` holds `logical_key` under. By default every store uses `key`,
+    /// the key scrub was given.
+    fn physical_key<'a>(
+        &self,
+        _blobstore_id: BlobstoreId,
+        _logical_key: &str,
+        key: &'a str,
+    ) -> Cow<'a, str> {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Leaves keys as they are
+#[derive(Debug)]
+pub struct IdentityKeyNormalizer;
+
+impl ScrubKeyNormalizer for IdentityKeyNormalizer {
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Adapts a closure into a ScrubKeyNormalizer. Every store is read and repaired under the
+/// key scrub was given.
+pub struct FnScrubKeyNormalizer<F>(pub F);
+
+impl<F> fmt::Debug for FnScrubKeyNormalizer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FnScrubKeyNormalizer")
+    }
+}
+
+impl<F> ScrubKeyNormalizer for FnScrubKeyNormalizer<F>
+where
+    F: Fn(&str) -> String + Send + Sync,
+{
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        Cow::Owned((self.0)(key))
+    }
+}
+
+/// Adapts a pair of closures into a ScrubKeyNormalizer for stores with their own key
+/// encodings. The first maps a key to its logical form, the second a store id and logical
+/// key to the key that store holds it under.
+pub struct FnPerStoreKeyNormalizer<F, P>(pub F, pub P);
+
+impl<F, P> fmt::Debug for FnPerStoreKeyNormalizer<F, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FnPerStoreKeyNormalizer")
+    }
+}
+
+impl<F, P> ScrubKeyNormalizer for FnPerStoreKeyNormalizer<F, P>
+where
+    F: Fn(&str) -> String + Send + Sync,
+    P: Fn(BlobstoreId, &str) -> String + Send + Sync,
+{
+    fn normalize<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        Cow::Owned((self.0)(key))
+    }
+
+    fn physical_key<'a>(
+        &self,
+        blobstore_id: BlobstoreId,
+        logical_key: &str,
+        _key: &'a str,
+    ) -> Cow<'a, str> {
+        Cow::Owned((self.1)(blobstore_id, logical_key))
+    }
+}
+
 pub fn default_scrub_handler() -> Arc<dyn ScrubHandler> {
     Arc::new(LoggingScrubHandler::new(false))
 }
@@ -114,6 +641,50 @@ pub trait ScrubHandler: Send + Sync + fmt::Debug {
         is_repaired: bool,
         meta: &BlobstoreMetadata,
     );
+
+    /// Called instead of on_repair when the scrub action is ReportOnly, with
+    /// all of the inner stores that a repair would have written the key to.
+    fn on_would_repair(
+        &self,
+        ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        meta: &BlobstoreMetadata,
+    ) {
+        for blobstore_id in blobstore_ids {
+            self.on_repair(ctx, *blobstore_id, key, false, meta);
+        }
+    }
+
+    /// Called with each of the differing values for a key when the scrub action is
+    /// Quarantine, once the value has been copied to `quarantine_key` in the
+    /// quarantine store, or has failed to be. The inner stores are left alone.
+    fn on_quarantine(
+        &self,
+        ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        _quarantine_key: &str,
+        _is_quarantined: bool,
+        meta: &BlobstoreMetadata,
+    ) {
+        for blobstore_id in blobstore_ids {
+            self.on_repair(ctx, *blobstore_id, key, false, meta);
+        }
+    }
+
+    /// Called when an inner store did not answer a scrub get within its timeout.
+    /// The store is left alone for this key, it is neither repaired nor counted as failed.
+    fn on_timeout(&self, _ctx: &CoreContext, _blobstore_id: BlobstoreId, _key: &str) {}
+
+    /// Called when a put to an inner store fails after the multiplexed put already met its
+    /// write quorum and returned success. The store is now missing the key.
+    fn on_background_write_failure(&self, ctx: &CoreContext, blobstore_id: BlobstoreId, key: &str) {
+        self.on_repair(ctx, blobstore_id, key, false, &BlobstoreMetadata::default());
+    }
+
+    /// Called periodically as keys are scanned, at the intervals set in ScrubOptions.
+    fn on_progress(&self, _ctx: &CoreContext, _progress: &ScrubProgress) {}
 }
 
 #[derive(Debug)]
@@ -150,16 +721,93 @@ impl ScrubHandler for LoggingScrubHandler {
             }
         }
     }
+
+    fn on_would_repair(
+        &self,
+        ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        _meta: &BlobstoreMetadata,
+    ) {
+        if !self.quiet {
+            info!(
+                ctx.logger(),
+                "scrub: would-repair blobstore_ids {:?} for {}", blobstore_ids, &key
+            );
+        }
+    }
+
+    fn on_quarantine(
+        &self,
+        ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        quarantine_key: &str,
+        is_quarantined: bool,
+        _meta: &BlobstoreMetadata,
+    ) {
+        if !self.quiet {
+            if is_quarantined {
+                warn!(
+                    ctx.logger(),
+                    "scrub: value from blobstore_ids {:?} for {} quarantined as {}",
+                    blobstore_ids,
+                    &key,
+                    &quarantine_key
+                );
+            } else {
+                warn!(
+                    ctx.logger(),
+                    "scrub: value from blobstore_ids {:?} for {} not quarantined",
+                    blobstore_ids,
+                    &key
+                );
+            }
+        }
+    }
+
+    fn on_timeout(&self, ctx: &CoreContext, blobstore_id: BlobstoreId, key: &str) {
+        if !self.quiet {
+            warn!(
+                ctx.logger(),
+                "scrub: blobstore_id {:?} timed out for {}", &blobstore_id, &key
+            );
+        }
+    }
+
+    // Progress is logged even when quiet, as it is one line per interval, not per key
+    fn on_progress(&self, ctx: &CoreContext, progress: &ScrubProgress) {
+        let eta = match progress.eta() {
+            Some(eta) => format!("{}s", eta.as_secs()),
+            None => "unknown".to_string(),
+        };
+        info!(
+            ctx.logger(),
+            "scrub: keys scanned {}{}, repairs done {}, repairs pending {}, elapsed {}s, eta {}",
+            progress.keys_scanned,
+            progress
+                .expected_keys
+                .map_or_else(String::new, |expected| format!("/{}", expected)),
+            progress.repairs_done,
+            progress.repairs_pending,
+            progress.elapsed.as_secs(),
+            eta,
+        );
+    }
 }
 
 #[derive(Clone)]
 pub struct ScrubBlobstore {
     inner: MultiplexedBlobstore,
+    // The inner stores that scrub compares, if restricted to a subset
+    restricted_inner: Option<Arc<MultiplexedBlobstoreBase>>,
     scrub_options: ScrubOptions,
     scuba: MononokeScubaSampleBuilder,
     scrub_stores: Arc<HashMap<BlobstoreId, Arc<dyn BlobstorePutOps>>>,
     queue: Arc<dyn BlobstoreSyncQueue>,
     scrub_handler: Arc<dyn ScrubHandler>,
+    progress: Arc<ScrubProgressTracker>,
+    repair_batcher: Arc<RepairBatcher>,
 }
 
 impl fmt::Display for ScrubBlobstore {
@@ -194,6 +842,7 @@ impl ScrubBlobstore {
         );
         Self {
             inner,
+            restricted_inner: None,
             scrub_options,
             scuba,
             scrub_stores: Arc::new(
@@ -204,8 +853,93 @@ impl ScrubBlobstore {
             ),
             queue,
             scrub_handler,
+            progress: Arc::new(ScrubProgressTracker::new()),
+            repair_batcher: Arc::new(RepairBatcher::default()),
+        }
+        .with_restriction()
+    }
+
+    // Recompute the restricted stores after the inner multiplex changes
+    fn with_restriction(self) -> Self {
+        let restricted_inner = self
+            .scrub_options
+            .restrict_to_stores
+            .as_ref()
+            .map(|ids| Arc::new(self.inner.blobstore.restricted_to(ids)));
+        Self {
+            restricted_inner,
+            ..self
+        }
+    }
+
+    /// Limit the puts per second sent to individual inner stores, for both normal puts
+    /// and scrub repairs. Stores absent from `write_limits` are not limited.
+    pub fn with_write_limits(self, write_limits: &HashMap<BlobstoreId, NonZeroU32>) -> Self {
+        Self {
+            inner: self.inner.with_write_limits(write_limits),
+            ..self
+        }
+        .with_restriction()
+    }
+
+    /// Give up on gets and puts to individual inner stores after the given time. Stores
+    /// that time out during a scrub are reported to the scrub handler, not repaired.
+    pub fn with_store_timeouts(
+        self,
+        get_timeouts: &HashMap<BlobstoreId, Duration>,
+        put_timeouts: &HashMap<BlobstoreId, Duration>,
+    ) -> Self {
+        Self {
+            inner: self.inner.with_store_timeouts(get_timeouts, put_timeouts),
+            ..self
+        }
+        .with_restriction()
+    }
+
+    /// Treat the given stores as write-only. They are not read from, but scrub still
+    /// repairs them if they are missing a key.
+    pub fn with_write_only(self, write_only: &HashSet<BlobstoreId>) -> Self {
+        Self {
+            inner: self.inner.with_write_only(write_only),
+            ..self
+        }
+        .with_restriction()
+    }
+
+    /// Have BatchRepairOptions::flush, on any clone of ScrubOptions::batch_repair, also
+    /// write this store's batched repairs. Call once the store is fully built, so the
+    /// flush uses its write limits and timeouts.
+    pub fn register_batch_repairs(&self) {
+        if let Some(pending) = self.pending_repairs() {
+            if let Some(batch_repair) = self.scrub_options.batch_repair.as_ref() {
+                batch_repair
+                    .registered
+                    .lock()
+                    .expect("lock poisoned")
+                    .push(pending);
+            }
         }
     }
+
+    /// Write the batched repairs that have not yet filled a batch. Nothing to do unless
+    /// ScrubOptions::batch_repair is set.
+    pub async fn flush_repairs(&self, ctx: &CoreContext) -> Result<()> {
+        match self.pending_repairs() {
+            Some(pending) => pending.flush(ctx).await,
+            None => Ok(()),
+        }
+    }
+
+    fn pending_repairs(&self) -> Option<PendingRepairs> {
+        let batch_repair = self.scrub_options.batch_repair.as_ref()?;
+        Some(PendingRepairs {
+            inner: self.inner.blobstore.clone(),
+            stores: batch_repair.stores.clone(),
+            batcher: self.repair_batcher.clone(),
+            scrub_handler: self.scrub_handler.clone(),
+            progress: self.progress.clone(),
+        })
+    }
 }
 
 impl fmt::Debug for ScrubBlobstore {
@@ -227,6 +961,10 @@ async fn put_and_mark_repaired(
     value: &BlobstoreGetData,
     scrub_handler: &dyn ScrubHandler,
     put_behaviour: PutBehaviour,
+    metrics: &MultiplexMetricsRecorder,
+    write_limiters: &WriteLimiters,
+    timeouts: &StoreTimeouts,
+    progress: &ScrubProgressTracker,
 ) -> Result<()> {
     let (_, res) = inner_put(
         ctx,
@@ -237,15 +975,210 @@ async fn put_and_mark_repaired(
         key.to_owned(),
         value.as_bytes().clone(),
         Some(put_behaviour),
+        metrics,
+        write_limiters,
+        timeouts,
     )
     .await;
     scrub_handler.on_repair(&ctx, id, key, res.is_ok(), value.as_meta());
+    progress.record_repair(res.is_ok());
     res.map(|_status| ())
 }
 
+// Write a batch of repairs to one store in a single put_batch. Each key in the batch
+// waits for write budget, and the batch as a whole gets the store's put timeout.
+async fn put_batch_and_mark_repaired(
+    ctx: &CoreContext,
+    id: BlobstoreId,
+    store: &dyn BlobstoreBatchPut,
+    batch: Vec<(String, BlobstoreGetData)>,
+    scrub_handler: &dyn ScrubHandler,
+    metrics: &MultiplexMetricsRecorder,
+    write_limiters: &WriteLimiters,
+    timeouts: &StoreTimeouts,
+    progress: &ScrubProgressTracker,
+) -> Result<()> {
+    let values = batch
+        .iter()
+        .map(|(key, value)| (key.clone(), value.as_bytes().clone()))
+        .collect();
+    let put_timeout = timeouts.put_timeout(id);
+    // Throttling happens before the timer starts, so it is not counted as store latency
+    for _ in &batch {
+        write_limiters.until_ready(id).await;
+    }
+    let (stats, timeout_or_res) = timeout(put_timeout, store.put_batch(ctx, values))
+        .timed()
+        .await;
+    let res = match timeout_or_res {
+        Ok(res) => {
+            metrics.record_put(id, stats.completion_time, res.is_ok());
+            res
+        }
+        Err(_) => {
+            metrics.record_put_timeout(id, stats.completion_time);
+            Err(ErrorKind::StoreTimeout {
+                blobstore_id: id,
+                timeout: put_timeout,
+            }
+            .into())
+        }
+    };
+    for (key, value) in &batch {
+        scrub_handler.on_repair(&ctx, id, key, res.is_ok(), value.as_meta());
+        progress.record_repair(res.is_ok());
+    }
+    res
+}
+
+// Either report or put the value to the stores that need it, depending on scrub_action.
+// Each store is written under its own store_key. If batch_repair is given, puts of batched keys to stores supporting batch puts are
+// added to their store's batch instead, and only made once the batch is full.
+async fn repair(
+    ctx: &CoreContext,
+    scuba: &MononokeScubaSampleBuilder,
+    mut needs_repair: HashMap<BlobstoreId, (PutBehaviour, &dyn BlobstorePutOps)>,
+    key: &str,
+    logical_key: &str,
+    store_key: &(dyn Fn(BlobstoreId) -> String + Send + Sync),
+    value: &BlobstoreGetData,
+    scrub_action: ScrubAction,
+    scrub_handler: &dyn ScrubHandler,
+    metrics: &MultiplexMetricsRecorder,
+    write_limiters: &WriteLimiters,
+    timeouts: &StoreTimeouts,
+    progress: &ScrubProgressTracker,
+    batch_repair: Option<(&BatchRepairOptions, &RepairBatcher)>,
+) -> Result<()> {
+    if needs_repair.is_empty() {
+        return Ok(());
+    }
+    if scrub_action == ScrubAction::ReportOnly {
+        // Never write, just tell the handler where the value would go
+        let mut ids: Vec<_> = needs_repair.keys().copied().collect();
+        ids.sort();
+        scrub_handler.on_would_repair(&ctx, &ids, key, value.as_meta());
+        progress.record_would_repair(ids.len());
+    } else {
+        let mut full_batches = Vec::new();
+        if let Some((options, batcher)) = batch_repair {
+            if options.is_batched(logical_key) {
+                needs_repair.retain(|id, _| match options.stores.get(id) {
+                    Some(store) => {
+                        let key = store_key(*id);
+                        if let Some(batch) = batcher.add(*id, &key, value, options.batch_size) {
+                            full_batches.push((*id, store.clone(), batch));
+                        }
+                        false
+                    }
+                    None => true,
+                });
+            }
+        }
+        let batch_puts = full_batches
+            .into_iter()
+            .map(|(id, store, batch)| async move {
+                put_batch_and_mark_repaired(
+                    ctx,
+                    id,
+                    store.as_ref(),
+                    batch,
+                    scrub_handler,
+                    metrics,
+                    write_limiters,
+                    timeouts,
+                    progress,
+                )
+                .await
+            });
+        try_join_all(batch_puts).await?;
+
+        // inner_put to the stores that need it.
+        let order = &AtomicUsize::new(0);
+        let repair_puts: FuturesUnordered<_> = needs_repair
+            .into_iter()
+            .map(|(id, (put_behaviour, store))| async move {
+                put_and_mark_repaired(
+                    ctx,
+                    scuba,
+                    order,
+                    id,
+                    store,
+                    &store_key(id),
+                    value,
+                    scrub_handler,
+                    put_behaviour,
+                    metrics,
+                    write_limiters,
+                    timeouts,
+                    progress,
+                )
+                .await
+            })
+            .collect();
+
+        repair_puts.try_for_each(|_| async { Ok(()) }).await?;
+    }
+    Ok(())
+}
+
+// Name under which a differing value for key is kept in the quarantine store. Keyed on
+// the value's hash, so rescrubbing the same divergence doesn't add more copies.
+fn quarantine_key(key: &str, value: &BlobstoreGetData) -> String {
+    let mut context = HashContext::new(b"scrub_quarantine");
+    context.update(value.as_raw_bytes());
+    format!("scrub_quarantine.{}.{}", key, context.finish())
+}
+
+// Copy each of the differing values for key to the quarantine store, leaving the
+// inner stores alone
+async fn quarantine(
+    ctx: &CoreContext,
+    scrub_options: &ScrubOptions,
+    key: &str,
+    candidates: &[RepairCandidate<'_>],
+    scrub_handler: &dyn ScrubHandler,
+    progress: &ScrubProgressTracker,
+) -> Result<()> {
+    let quarantine_store = scrub_options.quarantine_store.as_ref().ok_or_else(|| {
+        format_err!(
+            "Scrub action {:?} needs a quarantine store",
+            ScrubAction::Quarantine
+        )
+    })?;
+    let puts: FuturesUnordered<_> = candidates
+        .iter()
+        .map(|candidate| async move {
+            let quarantine_key = quarantine_key(key, candidate.value);
+            let res = quarantine_store
+                .put_explicit(
+                    ctx,
+                    quarantine_key.clone(),
+                    candidate.value.as_bytes().clone(),
+                    PutBehaviour::IfAbsent,
+                )
+                .await;
+            let mut ids: Vec<_> = candidate.blobstores.iter().copied().collect();
+            ids.sort();
+            scrub_handler.on_quarantine(
+                ctx,
+                &ids,
+                key,
+                &quarantine_key,
+                res.is_ok(),
+                candidate.value.as_meta(),
+            );
+            progress.record_would_repair(ids.len());
+            res.map(|_status| ())
+        })
+        .collect();
+    puts.try_for_each(|_| async { Ok(()) }).await
+}
+
 // Workaround for Blobstore returning a static lifetime future
 async fn blobstore_get(
     inner_blobstore: &MultiplexedBlobstoreBase,
+    scrub_blobstore: &MultiplexedBlobstoreBase,
     ctx: &CoreContext,
     key: &str,
     queue: &dyn BlobstoreSyncQueue,
@@ -253,12 +1186,80 @@ async fn blobstore_get(
     scrub_options: &ScrubOptions,
     scrub_handler: &dyn ScrubHandler,
     scuba: &MononokeScubaSampleBuilder,
+    progress: &ScrubProgressTracker,
+    repair_batcher: &RepairBatcher,
 ) -> Result<Option<BlobstoreGetData>> {
-    match inner_blobstore
-        .scrub_get(ctx, key, scrub_options.scrub_action_on_missing_write_mostly)
+    let logical_key = scrub_options.key_normalizer.normalize(key);
+    let logical_key = logical_key.as_ref();
+    let store_key = |blobstore_id: BlobstoreId| {
+        scrub_options
+            .key_normalizer
+            .physical_key(blobstore_id, logical_key, key)
+            .into_owned()
+    };
+    let batch_repair = scrub_options
+        .batch_repair
+        .as_ref()
+        .map(|options| (options, repair_batcher));
+    if !scrub_options.key_in_range(logical_key)
+        || scrub_options
+            .ignore_key_prefixes
+            .iter()
+            .any(|prefix| logical_key.starts_with(prefix.as_str()))
+    {
+        return inner_blobstore.get(ctx, key).await;
+    }
+
+    match scrub_blobstore
+        .scrub_get(
+            ctx,
+            &store_key,
+            scrub_options.scrub_action_on_missing_write_mostly,
+            scrub_handler,
+        )
         .await
     {
-        Ok(value) => return Ok(value),
+        Ok(None) => {
+            let backup_store = match scrub_options.backup_store.as_ref() {
+                Some(backup_store) => backup_store,
+                None => return Ok(None),
+            };
+            // A pending write will give the inner stores a value of their own
+            if !queue.get(ctx, key).await?.is_empty() {
+                return Ok(None);
+            }
+            let value = match backup_store.get(ctx, key).await? {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+            let needs_repair = scrub_stores
+                .iter()
+                .filter(|(id, _)| match scrub_options.restrict_to_stores.as_ref() {
+                    Some(ids) => ids.contains(id),
+                    None => true,
+                })
+                .map(|(id, s)| (*id, (PutBehaviour::Overwrite, s.as_ref())))
+                .collect();
+            repair(
+                ctx,
+                scuba,
+                needs_repair,
+                key,
+                logical_key,
+                &store_key,
+                &value,
+                scrub_options.scrub_action,
+                scrub_handler,
+                inner_blobstore.metrics_recorder(),
+                inner_blobstore.write_limiters(),
+                inner_blobstore.store_timeouts(),
+                progress,
+                batch_repair,
+            )
+            .await?;
+            Ok(Some(value))
+        }
+        Ok(value) => Ok(value),
         Err(error) => match error {
             ErrorKind::SomeFailedOthersNone(_) => {
                 // MultiplexedBlobstore returns Ok(None) here if queue is empty for the key
@@ -337,32 +1338,92 @@ async fn blobstore_get(
                     }
                 }
 
-                if scrub_options.scrub_action == ScrubAction::ReportOnly {
-                    for id in needs_repair.keys() {
-                        scrub_handler.on_repair(&ctx, *id, key, false, value.as_meta());
+                repair(
+                    ctx,
+                    scuba,
+                    needs_repair,
+                    key,
+                    logical_key,
+                    &store_key,
+                    &value,
+                    scrub_options.scrub_action,
+                    scrub_handler,
+                    inner_blobstore.metrics_recorder(),
+                    inner_blobstore.write_limiters(),
+                    inner_blobstore.store_timeouts(),
+                    progress,
+                    batch_repair,
+                )
+                .await?;
+                Ok(Some(value))
+            }
+            ErrorKind::ValueMismatch(ref answered, ref missing, ref values) => {
+                let candidates: Vec<_> = answered
+                    .iter()
+                    .zip(values.iter())
+                    .map(|(blobstores, value)| RepairCandidate { blobstores, value })
+                    .collect();
+
+                if scrub_options.scrub_action == ScrubAction::Quarantine {
+                    // A pending write could explain the divergence, leave it for the healer
+                    if !queue.get(ctx, key).await?.is_empty() {
+                        return Err(error.into());
                     }
-                } else {
-                    // inner_put to the stores that need it.
-                    let order = AtomicUsize::new(0);
-                    let repair_puts: FuturesUnordered<_> = needs_repair
-                        .into_iter()
-                        .map(|(id, (put_behaviour, store))| {
-                            put_and_mark_repaired(
-                                ctx,
-                                scuba,
-                                &order,
-                                id,
-                                store,
-                                key,
-                                &value,
-                                scrub_handler,
-                                put_behaviour,
-                            )
-                        })
-                        .collect();
-
-                    repair_puts.try_for_each(|_| async { Ok(()) }).await?;
+                    quarantine(
+                        ctx,
+                        scrub_options,
+                        key,
+                        &candidates,
+                        scrub_handler,
+                        progress,
+                    )
+                    .await?;
+                    return Err(error.into());
+                }
+
+                let chosen = match scrub_options
+                    .repair_source_policy
+                    .choose_source(logical_key, &candidates)
+                {
+                    Some(chosen) if chosen < candidates.len() => chosen,
+                    _ => return Err(error.into()),
+                };
+
+                // A pending write could explain the divergence, leave it for the healer
+                if !queue.get(ctx, key).await?.is_empty() {
+                    return Err(error.into());
                 }
+
+                let value = candidates[chosen].value.clone();
+                let needs_repair = candidates
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| *index != chosen)
+                    .flat_map(|(_, c)| c.blobstores.iter())
+                    .chain(missing.iter())
+                    .filter_map(|id| {
+                        scrub_stores
+                            .get(id)
+                            .map(|s| (*id, (PutBehaviour::Overwrite, s.as_ref())))
+                    })
+                    .collect();
+                repair(
+                    ctx,
+                    scuba,
+                    needs_repair,
+                    key,
+                    logical_key,
+                    &store_key,
+                    &value,
+                    scrub_options.scrub_action,
+                    scrub_handler,
+                    inner_blobstore.metrics_recorder(),
+                    inner_blobstore.write_limiters(),
+                    inner_blobstore.store_timeouts(),
+                    progress,
+                    batch_repair,
+                )
+                .await?;
                 Ok(Some(value))
             }
             _ => Err(error.into()),
@@ -377,8 +1438,11 @@ impl Blobstore for ScrubBlobstore {
         ctx: &'a CoreContext,
         key: &'a str,
     ) -> Result<Option<BlobstoreGetData>> {
-        blobstore_get(
+        let res = blobstore_get(
             self.inner.blobstore.as_ref(),
+            self.restricted_inner
+                .as_deref()
+                .unwrap_or_else(|| self.inner.blobstore.as_ref()),
             ctx,
             key,
             self.queue.as_ref(),
@@ -386,8 +1450,18 @@ impl Blobstore for ScrubBlobstore {
             &self.scrub_options,
             self.scrub_handler.as_ref(),
             &self.scuba,
+            self.progress.as_ref(),
+            self.repair_batcher.as_ref(),
         )
-        .await
+        .await;
+        // Keys outside the range belong to another worker's scrub
+        let logical_key = self.scrub_options.key_normalizer.normalize(key);
+        if self.scrub_options.key_in_range(logical_key.as_ref()) {
+            if let Some(progress) = self.progress.record_key(&self.scrub_options) {
+                self.scrub_handler.on_progress(ctx, &progress);
+            }
+        }
+        res
     }
 
     async fn is_present<'a>(
@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Error};
+use blobstore::BlobstoreMetadata;
+use context::CoreContext;
+use futures::{
+    channel::{mpsc, oneshot},
+    stream::StreamExt,
+};
+use metaconfig_types::BlobstoreId;
+use mononoke_types::Timestamp;
+use once_cell::sync::OnceCell;
+use slog::warn;
+use sql::{queries, Connection};
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+use sql_ext::SqlConnections;
+
+use crate::scrub::ScrubHandler;
+
+const WRITE_BUFFER_SIZE: usize = 1000;
+
+/// A durable record of one key that scrub found missing from an inner store.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScrubRepairRecord {
+    pub blobstore_key: String,
+    pub blobstore_id: BlobstoreId,
+    pub is_repaired: bool,
+    pub timestamp: Timestamp,
+}
+
+enum WriteRequest {
+    Record(ScrubRepairRecord),
+    Flush(oneshot::Sender<()>),
+}
+
+/// A ScrubHandler that audits every repair to a SQL table.
+///
+/// Records are batched and written in the background. A failure to write them
+/// is logged and otherwise ignored, so the audit store never blocks scrubbing.
+#[derive(Clone)]
+pub struct SqlScrubHandler {
+    write_connection: Arc<Connection>,
+    read_connection: Connection,
+    write_sender: mpsc::UnboundedSender<WriteRequest>,
+    write_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<WriteRequest>>>>,
+    worker_scheduled: Arc<OnceCell<()>>,
+}
+
+impl std::fmt::Debug for SqlScrubHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SqlScrubHandler").finish()
+    }
+}
+
+queries! {
+    write InsertRepairs(values: (
+        blobstore_key: String,
+        blobstore_id: BlobstoreId,
+        is_repaired: bool,
+        add_timestamp: Timestamp,
+    )) {
+        none,
+        "INSERT INTO scrub_repairs (blobstore_key, blobstore_id, is_repaired, add_timestamp)
+         VALUES {values}"
+    }
+
+    read GetRepairsByKey(key: String) -> (String, BlobstoreId, bool, Timestamp) {
+        "SELECT blobstore_key, blobstore_id, is_repaired, add_timestamp
+         FROM scrub_repairs
+         WHERE blobstore_key = {key}
+         ORDER BY id"
+    }
+}
+
+impl SqlConstruct for SqlScrubHandler {
+    const LABEL: &'static str = "scrub_repairs";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-scrub-repairs.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        let (write_sender, write_receiver) = mpsc::unbounded();
+        Self {
+            write_connection: Arc::new(connections.write_connection),
+            read_connection: connections.read_connection,
+            write_sender,
+            write_receiver: Arc::new(Mutex::new(Some(write_receiver))),
+            worker_scheduled: Arc::new(OnceCell::new()),
+        }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlScrubHandler {}
+
+impl SqlScrubHandler {
+    /// The worker is spawned on first use rather than at construction, as
+    /// construction may happen outside of a tokio runtime.
+    fn ensure_worker_scheduled(&self, ctx: &CoreContext) {
+        self.worker_scheduled.get_or_init(|| {
+            let receiver = self
+                .write_receiver
+                .lock()
+                .expect("lock poisoned")
+                .take()
+                .expect("worker already scheduled");
+            let write_connection = self.write_connection.clone();
+            let logger = ctx.logger().clone();
+            let batch_writes = receiver
+                .ready_chunks(WRITE_BUFFER_SIZE)
+                .for_each(move |batch| {
+                    let write_connection = write_connection.clone();
+                    let logger = logger.clone();
+                    async move {
+                        let mut records = Vec::new();
+                        let mut flushes = Vec::new();
+                        for request in batch {
+                            match request {
+                                WriteRequest::Record(record) => records.push(record),
+                                WriteRequest::Flush(sender) => flushes.push(sender),
+                            }
+                        }
+                        if let Err(e) = insert_records(&write_connection, records).await {
+                            warn!(logger, "scrub: failed to record repairs: {:?}", e);
+                        }
+                        for sender in flushes {
+                            // Ignoring the error, because receiver might have gone
+                            let _ = sender.send(());
+                        }
+                    }
+                });
+            tokio::spawn(batch_writes);
+        });
+    }
+
+    fn send(&self, ctx: &CoreContext, request: WriteRequest) -> bool {
+        self.ensure_worker_scheduled(ctx);
+        self.write_sender.unbounded_send(request).is_ok()
+    }
+
+    /// Wait until all records handled so far have been written, or have failed to be.
+    pub async fn flush(&self, ctx: &CoreContext) {
+        let (sender, receiver) = oneshot::channel();
+        if self.send(ctx, WriteRequest::Flush(sender)) {
+            let _ = receiver.await;
+        }
+    }
+
+    /// Fetch the repair records for a key, oldest first.
+    pub async fn get(&self, key: &str) -> Result<Vec<ScrubRepairRecord>, Error> {
+        let rows = GetRepairsByKey::query(&self.read_connection, &key.to_owned()).await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(blobstore_key, blobstore_id, is_repaired, timestamp)| ScrubRepairRecord {
+                    blobstore_key,
+                    blobstore_id,
+                    is_repaired,
+                    timestamp,
+                },
+            )
+            .collect())
+    }
+}
+
+async fn insert_records(
+    write_connection: &Connection,
+    records: Vec<ScrubRepairRecord>,
+) -> Result<(), Error> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let values: Vec<_> = records
+        .iter()
+        .map(|r| {
+            (
+                &r.blobstore_key,
+                &r.blobstore_id,
+                &r.is_repaired,
+                &r.timestamp,
+            )
+        })
+        .collect();
+    InsertRepairs::query(write_connection, &values[..])
+        .await
+        .with_context(|| format!("Failed to insert {} scrub repair records", records.len()))?;
+    Ok(())
+}
+
+impl ScrubHandler for SqlScrubHandler {
+    fn on_repair(
+        &self,
+        ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        key: &str,
+        is_repaired: bool,
+        _meta: &BlobstoreMetadata,
+    ) {
+        let record = ScrubRepairRecord {
+            blobstore_key: key.to_owned(),
+            blobstore_id,
+            is_repaired,
+            timestamp: Timestamp::now(),
+        };
+        if !self.send(ctx, WriteRequest::Record(record)) {
+            warn!(
+                ctx.logger(),
+                "scrub: could not record repair of blobstore_id {:?} for {}", blobstore_id, key
+            );
+        }
+    }
+}
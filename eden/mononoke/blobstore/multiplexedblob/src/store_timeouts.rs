@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use metaconfig_types::BlobstoreId;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Per inner store get and put timeouts for a multiplex. Stores without a
+/// timeout of their own use the default.
+pub(crate) struct StoreTimeouts {
+    default: Duration,
+    get_timeouts: HashMap<BlobstoreId, Duration>,
+    put_timeouts: HashMap<BlobstoreId, Duration>,
+}
+
+impl StoreTimeouts {
+    pub(crate) fn new(
+        default: Duration,
+        get_timeouts: &HashMap<BlobstoreId, Duration>,
+        put_timeouts: &HashMap<BlobstoreId, Duration>,
+    ) -> Self {
+        Self {
+            default,
+            get_timeouts: get_timeouts.clone(),
+            put_timeouts: put_timeouts.clone(),
+        }
+    }
+
+    pub(crate) fn with_default(default: Duration) -> Self {
+        Self::new(default, &HashMap::new(), &HashMap::new())
+    }
+
+    pub(crate) fn get_timeout(&self, blobstore_id: BlobstoreId) -> Duration {
+        self.get_timeouts
+            .get(&blobstore_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    pub(crate) fn put_timeout(&self, blobstore_id: BlobstoreId) -> Duration {
+        self.put_timeouts
+            .get(&blobstore_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
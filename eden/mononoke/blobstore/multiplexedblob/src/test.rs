@@ -16,16 +16,22 @@ use std::{
     time::{Duration, Instant, SystemTime},
 };
 
-use crate::base::{MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler};
-use crate::queue::MultiplexedBlobstore;
+use crate::base::{
+    ErrorKind, MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler, StorePresence, WriteQuorum,
+};
+use crate::queue::{MultiplexedBlobstore, StoreBreakers, StoreHealth};
 use crate::scrub::{
-    LoggingScrubHandler, ScrubAction, ScrubBlobstore, ScrubHandler, ScrubOptions, ScrubWriteMostly,
+    enumerate_scrub_keys, BatchRepairOptions, BlobstoreBatchPut, DefaultRepairSourcePolicy,
+    FnPerStoreKeyNormalizer, FnRepairSourcePolicy, FnScrubKeyNormalizer, IdentityKeyNormalizer,
+    LoggingScrubHandler, RepairCandidate, ScrubAction, ScrubBlobstore, ScrubHandler, ScrubOptions,
+    ScrubProgress, ScrubWriteMostly,
 };
+use crate::sql_scrub_handler::SqlScrubHandler;
 use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
 use blobstore::{
-    Blobstore, BlobstoreGetData, BlobstoreIsPresent, BlobstoreMetadata, BlobstorePutOps,
-    OverwriteStatus, PutBehaviour,
+    Blobstore, BlobstoreGetData, BlobstoreIsPresent, BlobstoreKeyParam, BlobstoreMetadata,
+    BlobstorePutOps, OverwriteStatus, PutBehaviour,
 };
 use blobstore_sync_queue::{
     BlobstoreSyncQueue, BlobstoreSyncQueueEntry, OperationKey, SqlBlobstoreSyncQueue,
@@ -41,10 +47,10 @@ use futures::{
     task::{Context, Poll},
 };
 use lock_ext::LockExt;
-use maplit::hashmap;
+use maplit::{hashmap, hashset};
 use memblob::Memblob;
 use metaconfig_types::{BlobstoreId, MultiplexId};
-use mononoke_types::{BlobstoreBytes, DateTime};
+use mononoke_types::{hash::Context as HashContext, BlobstoreBytes, DateTime};
 use nonzero_ext::nonzero;
 use readonlyblob::ReadOnlyBlobstore;
 use scuba_ext::MononokeScubaSampleBuilder;
@@ -238,6 +244,68 @@ impl MultiplexedBlobstorePutHandler for LogHandler {
     }
 }
 
+#[derive(Debug, Default)]
+struct RecordingScrubHandler {
+    repaired: Mutex<Vec<(BlobstoreId, String)>>,
+    would_repair: Mutex<Vec<(Vec<BlobstoreId>, String)>>,
+    timed_out: Mutex<Vec<(BlobstoreId, String)>>,
+    progress: Mutex<Vec<ScrubProgress>>,
+    quarantined: Mutex<Vec<(Vec<BlobstoreId>, String, String)>>,
+}
+
+impl ScrubHandler for RecordingScrubHandler {
+    fn on_repair(
+        &self,
+        _ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        key: &str,
+        _is_repaired: bool,
+        _meta: &BlobstoreMetadata,
+    ) {
+        self.repaired
+            .with(|repaired| repaired.push((blobstore_id, key.to_string())));
+    }
+
+    fn on_would_repair(
+        &self,
+        _ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        _meta: &BlobstoreMetadata,
+    ) {
+        self.would_repair
+            .with(|would_repair| would_repair.push((blobstore_ids.to_vec(), key.to_string())));
+    }
+
+    fn on_quarantine(
+        &self,
+        _ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        quarantine_key: &str,
+        is_quarantined: bool,
+        _meta: &BlobstoreMetadata,
+    ) {
+        assert!(is_quarantined);
+        self.quarantined.with(|quarantined| {
+            quarantined.push((
+                blobstore_ids.to_vec(),
+                key.to_string(),
+                quarantine_key.to_string(),
+            ))
+        });
+    }
+
+    fn on_timeout(&self, _ctx: &CoreContext, blobstore_id: BlobstoreId, key: &str) {
+        self.timed_out
+            .with(|timed_out| timed_out.push((blobstore_id, key.to_string())));
+    }
+
+    fn on_progress(&self, _ctx: &CoreContext, progress: &ScrubProgress) {
+        self.progress.with(|reports| reports.push(progress.clone()));
+    }
+}
+
 struct FailingPutHandler {}
 
 #[async_trait]
@@ -310,6 +378,17 @@ async fn scrub_none(
             scrub_grace: None,
             scrub_action_on_missing_write_mostly,
             queue_peek_bound: None,
+            repair_source_policy: Arc::new(DefaultRepairSourcePolicy),
+            ignore_key_prefixes: vec![],
+            restrict_to_stores: None,
+            key_range: None,
+            progress_interval_keys: None,
+            progress_interval: None,
+            expected_keys: None,
+            key_normalizer: Arc::new(IdentityKeyNormalizer),
+            backup_store: None,
+            quarantine_store: None,
+            batch_repair: None,
         },
         Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>,
     );
@@ -947,278 +1026,1832 @@ async fn multiplexed_blob_size(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
-async fn scrub_scenarios(fb: FacebookInit, scrub_action_on_missing_write_mostly: ScrubWriteMostly) {
+#[fbinit::test]
+async fn multiplexed_metrics(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
     borrowed!(ctx);
-    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
-    let scrub_handler = Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>;
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+
     let bid0 = BlobstoreId::new(0);
-    let bs0 = Arc::new(Tickable::new());
+    let bs0 = Arc::new(Memblob::default());
     let bid1 = BlobstoreId::new(1);
-    let bs1 = Arc::new(Tickable::new());
+    let bs1 = Arc::new(Memblob::default());
     let bid2 = BlobstoreId::new(2);
-    let bs2 = Arc::new(Tickable::new());
-    let bs = ScrubBlobstore::new(
+    let bs2 = Arc::new(ReadOnlyBlobstore::new(Memblob::default()));
+    // Needing all three writes means the put waits for every store before failing
+    let bs = MultiplexedBlobstore::new(
         MultiplexId::new(1),
-        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
-        vec![(bid2, bs2.clone())],
-        nonzero!(1usize),
-        queue.clone(),
+        vec![(bid0, bs0), (bid1, bs1), (bid2, bs2)],
+        vec![],
+        nonzero!(3usize),
+        queue,
         MononokeScubaSampleBuilder::with_discard(),
         MononokeScubaSampleBuilder::with_discard(),
         nonzero!(1u64),
-        ScrubOptions {
-            scrub_action: ScrubAction::ReportOnly,
-            scrub_grace: None,
-            scrub_action_on_missing_write_mostly,
-            queue_peek_bound: None,
-        },
-        scrub_handler.clone(),
     );
 
-    // non-existing key when one main blobstore failing
-    {
-        let k0 = "k0";
-
-        let mut get_fut = bs.get(ctx, k0).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-
-        bs0.tick(None);
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-
-        bs1.tick(Some("bs1 failed"));
-
-        bs2.tick(None);
-        assert_eq!(get_fut.await.unwrap(), None, "SomeNone + Err expected None");
+    assert!(bs
+        .put(ctx, "key".to_owned(), make_value("value"))
+        .await
+        .is_err());
+    // No store has the key, so every store is asked
+    assert!(bs.get(ctx, "other_key").await?.is_none());
+
+    let metrics = bs.multiplex_metrics();
+    assert_eq!(metrics.stores.len(), 3);
+    for (id, store) in metrics.stores.iter() {
+        assert_eq!(store.gets, 1);
+        assert_eq!(store.puts, 1);
+        assert_eq!(store.errors, if *id == bid2 { 1 } else { 0 });
+        assert!(store.get_p50.is_some());
+        assert!(store.get_p50 <= store.get_p95);
+        assert!(store.put_p50.is_some());
     }
+    Ok(())
+}
 
-    // non-existing key when one write mostly blobstore failing
-    {
-        let k0 = "k0";
-
-        let mut get_fut = bs.get(ctx, k0).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+#[fbinit::test]
+async fn multiplexed_write_limits(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
 
-        bs0.tick(None);
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    // Needing both writes means each put waits for the limited store
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(2usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    )
+    .with_write_limits(&hashmap! { bid1 => nonzero!(1u32) });
 
-        bs1.tick(None);
+    bs.put(ctx, "key0".to_owned(), make_value("value0")).await?;
+    // The first put used up this second's budget for bid1
+    let start = Instant::now();
+    bs.put(ctx, "key1".to_owned(), make_value("value1")).await?;
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert!(bs1.get(ctx, "key1").await?.is_some());
+    Ok(())
+}
 
-        bs2.tick(Some("bs1 failed"));
-        assert_eq!(get_fut.await.unwrap(), None, "SomeNone + Err expected None");
-    }
+#[fbinit::test]
+async fn multiplexed_write_only(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
 
-    // fail all but one store on put to make sure only one has the data
-    // only replica containing key fails on read.
-    {
-        let v1 = make_value("v1");
-        let k1 = "k1";
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let make_bs = |minimum_successful_writes| {
+        MultiplexedBlobstore::new(
+            MultiplexId::new(1),
+            vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+            vec![],
+            minimum_successful_writes,
+            queue.clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+            MononokeScubaSampleBuilder::with_discard(),
+            nonzero!(1u64),
+        )
+        .with_write_only(&hashset! { bid1 })
+    };
+    let bs = make_bs(nonzero!(1usize));
+
+    // A get ignores the write-only store, even when it holds the only copy
+    bs1.put(ctx, "only_write_only".to_owned(), make_value("value"))
+        .await?;
+    assert!(bs.get(ctx, "only_write_only").await?.is_none());
+    assert!(bs
+        .get_from_store(ctx, "only_write_only", bid1)
+        .await?
+        .is_some());
+
+    // Puts still reach the write-only store
+    bs.put(ctx, "key".to_owned(), make_value("value")).await?;
+    assert_eq!(
+        bs.get(ctx, "key").await?.map(|v| v.into_bytes()),
+        Some(make_value("value"))
+    );
+    assert!(bs1.get(ctx, "key").await?.is_some());
+
+    // The write-only store does not count towards the write quorum
+    let bs = make_bs(nonzero!(2usize));
+    assert!(bs
+        .put(ctx, "key2".to_owned(), make_value("value"))
+        .await
+        .is_err());
+    Ok(())
+}
 
-        let mut put_fut = bs
-            .put(ctx, k1.to_owned(), v1.clone())
-            .map_err(|_| ())
-            .boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
-        bs0.tick(None);
-        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
-        bs1.tick(Some("bs1 failed"));
-        bs2.tick(Some("bs2 failed"));
-        put_fut.await.unwrap();
+#[fbinit::test]
+async fn multiplexed_get_from_store(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
 
-        match queue.get(ctx, k1).await.unwrap().as_slice() {
-            [entry] => {
-                assert_eq!(entry.blobstore_id, bid0, "Queue bad");
-            }
-            _ => panic!("only one entry expected"),
-        }
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2)],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
 
-        let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+    // Diverge the stores underneath the multiplex
+    bs0.put(ctx, "key".to_owned(), make_value("value0")).await?;
+    bs1.put(ctx, "key".to_owned(), make_value("value1")).await?;
 
-        bs0.tick(Some("bs0 failed"));
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+    let get = |id| bs.get_from_store(ctx, "key", id);
+    assert_eq!(
+        get(bid0).await?.map(|v| v.into_bytes()),
+        Some(make_value("value0"))
+    );
+    assert_eq!(
+        get(bid1).await?.map(|v| v.into_bytes()),
+        Some(make_value("value1"))
+    );
+    assert!(get(bid2).await?.is_none());
 
-        bs1.tick(None);
-        bs2.tick(None);
-        assert!(get_fut.await.is_err(), "None/Err while replicating");
+    let err = get(BlobstoreId::new(3)).await.unwrap_err();
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::UnknownBlobstore { valid, .. }) => {
+            assert_eq!(valid.as_ref(), &vec![bid0, bid1, bid2]);
+        }
+        _ => panic!("unexpected error {:?}", err),
     }
+    Ok(())
+}
 
-    // all replicas fail
-    {
-        let k2 = "k2";
-
-        let mut get_fut = bs.get(ctx, k2).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-        bs0.tick(Some("bs0 failed"));
-        bs1.tick(Some("bs1 failed"));
-        bs2.tick(Some("bs1 failed"));
-        assert!(get_fut.await.is_err(), "Err/Err");
-    }
+#[fbinit::test]
+async fn multiplexed_heal_key(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
 
-    // Now replace bs1 & bs2 with an empty blobstore, and see the scrub work
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
     let bid1 = BlobstoreId::new(1);
-    let bs1 = Arc::new(Tickable::new());
+    let bs1 = Arc::new(Memblob::default());
     let bid2 = BlobstoreId::new(2);
-    let bs2 = Arc::new(Tickable::new());
-    let bs = ScrubBlobstore::new(
+    let bs2 = Arc::new(Memblob::default());
+    let bs = MultiplexedBlobstore::new(
         MultiplexId::new(1),
         vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
         vec![(bid2, bs2.clone())],
         nonzero!(1usize),
-        queue.clone(),
+        queue,
         MononokeScubaSampleBuilder::with_discard(),
         MononokeScubaSampleBuilder::with_discard(),
         nonzero!(1u64),
-        ScrubOptions {
-            scrub_action: ScrubAction::Repair,
-            scrub_grace: None,
-            scrub_action_on_missing_write_mostly,
-            queue_peek_bound: None,
-        },
-        scrub_handler.clone(),
+    );
+    let get = |id| bs.get_from_store(ctx, "key", id);
+
+    // No store has the key
+    let err = bs.heal_key(ctx, "key").await.unwrap_err();
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::NothingToHealFrom(key)) => assert_eq!(key, "key"),
+        _ => panic!("unexpected error {:?}", err),
+    }
+
+    // The majority value is copied over the odd one out and to the missing store
+    bs0.put(ctx, "key".to_owned(), make_value("good")).await?;
+    bs1.put(ctx, "key".to_owned(), make_value("good")).await?;
+    bs2.put(ctx, "key".to_owned(), make_value("bad")).await?;
+    let report = bs.heal_key(ctx, "key").await?;
+    assert_eq!(report.repaired, vec![bid2]);
+    assert!(report.failed.is_empty());
+    assert!(report.unreadable.is_empty());
+    assert_eq!(
+        get(bid2).await?.map(|v| v.into_bytes()),
+        Some(make_value("good"))
     );
 
-    // Non-existing key in all blobstores, new blobstore failing
-    {
-        let k0 = "k0";
-
-        let mut get_fut = bs.get(ctx, k0).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+    // Nothing to do once healed
+    let report = bs.heal_key(ctx, "key").await?;
+    assert!(report.repaired.is_empty());
+
+    // A tie can't be resolved, so nothing is written
+    bs0.put(ctx, "tie".to_owned(), make_value("value0")).await?;
+    bs1.put(ctx, "tie".to_owned(), make_value("value1")).await?;
+    let err = bs.heal_key(ctx, "tie").await.unwrap_err();
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::ValueMismatch(answered, missing, _)) => {
+            assert_eq!(answered.len(), 2);
+            assert_eq!(missing.as_ref(), &hashset! {bid2});
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+    assert!(bs2.get(ctx, "tie").await?.is_none());
+    Ok(())
+}
 
-        bs0.tick(None);
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+#[fbinit::test]
+async fn multiplexed_put_if_absent(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
 
-        bs1.tick(Some("bs1 failed"));
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2.clone())],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+    let put_if_absent = |key: &str, value| bs.put_if_absent(ctx, key.to_owned(), make_value(value));
 
-        bs2.tick(None);
-        assert_eq!(get_fut.await.unwrap(), None, "None/Err after replacement");
+    // Absent everywhere, so written everywhere
+    assert!(put_if_absent("key", "first").await?);
+    for store in &[&bs0, &bs1, &bs2] {
+        assert_eq!(
+            store.get(ctx, "key").await?.map(|v| v.into_bytes()),
+            Some(make_value("first"))
+        );
     }
 
-    // only replica containing key replaced after failure - DATA LOST
-    {
-        let k1 = "k1";
+    // A second write is refused and changes nothing
+    assert!(!put_if_absent("key", "second").await?);
+    assert_eq!(
+        bs0.get(ctx, "key").await?.map(|v| v.into_bytes()),
+        Some(make_value("first"))
+    );
 
-        let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-        bs0.tick(Some("bs0 failed"));
-        bs1.tick(None);
-        bs2.tick(None);
-        assert!(get_fut.await.is_err(), "Empty replacement against error");
-    }
+    // Stores disagree: present in one store counts as present, so nothing is written
+    bs2.put(ctx, "partial".to_owned(), make_value("old"))
+        .await?;
+    assert!(!put_if_absent("partial", "new").await?);
+    assert!(bs0.get(ctx, "partial").await?.is_none());
+    assert!(bs1.get(ctx, "partial").await?.is_none());
+    assert_eq!(
+        bs2.get(ctx, "partial").await?.map(|v| v.into_bytes()),
+        Some(make_value("old"))
+    );
+    Ok(())
+}
 
-    // One working replica after failure, queue lookback means scrub action not performed
-    {
-        // Create with different queue_peek_bound
-        let bs = ScrubBlobstore::new(
+#[fbinit::test]
+async fn multiplexed_put_all(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let make_multiplex = |bs1: Arc<dyn BlobstorePutOps>| -> Result<_> {
+        Ok(MultiplexedBlobstore::new(
             MultiplexId::new(1),
-            vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
-            vec![(bid2, bs2.clone())],
+            vec![(bid0, bs0.clone() as Arc<dyn BlobstorePutOps>), (bid1, bs1)],
+            vec![(bid2, bs2.clone() as Arc<dyn BlobstorePutOps>)],
             nonzero!(1usize),
-            queue.clone(),
+            Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?),
             MononokeScubaSampleBuilder::with_discard(),
             MononokeScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
-            ScrubOptions {
-                scrub_action: ScrubAction::Repair,
-                scrub_grace: None,
-                scrub_action_on_missing_write_mostly,
-                queue_peek_bound: Some(Duration::from_secs(7200)),
-            },
-            scrub_handler,
+        ))
+    };
+
+    // Every store acknowledges, so every store has the value
+    let bs = make_multiplex(bs1.clone())?;
+    let written = bs
+        .put_all(ctx, "key".to_owned(), make_value("value"))
+        .await?;
+    assert_eq!(written, vec![bid0, bid1, bid2]);
+    for store in &[&bs0, &bs1, &bs2] {
+        assert_eq!(
+            store.get(ctx, "key").await?.map(|v| v.into_bytes()),
+            Some(make_value("value"))
         );
-        let v1 = make_value("v1");
-        let k1 = "k1";
-        // Check there is an entry on the queue
+    }
+
+    // One store failing fails the put, even though a quorum was reached
+    let bs = make_multiplex(Arc::new(ReadOnlyBlobstore::new(Memblob::default())))?;
+    let err = bs
+        .put_all(ctx, "partial".to_owned(), make_value("value"))
+        .await
+        .unwrap_err();
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::PutAllFailed { written, failed }) => {
+            assert_eq!(written.as_ref(), &vec![bid0, bid2]);
+            assert_eq!(failed.keys().collect::<Vec<_>>(), vec![&bid1]);
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+    assert!(bs0.get(ctx, "partial").await?.is_some());
+    Ok(())
+}
+
+#[fbinit::test]
+async fn multiplexed_is_present_per_store(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Tickable::new());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2.clone())],
+        nonzero!(1usize),
+        Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?),
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+    bs0.put(ctx, "key".to_owned(), make_value("value")).await?;
+
+    let mut fut = bs.is_present_per_store(ctx, "key").boxed();
+    assert!(PollOnce::new(Pin::new(&mut fut)).await.is_pending());
+    bs1.tick(Some("bs1 failed"));
+    let presence = fut.await;
+
+    // A failing store is reported apart from the store that does not have the key
+    assert_eq!(presence.len(), 3);
+    assert!(matches!(presence[&bid0], StorePresence::Present));
+    assert!(matches!(presence[&bid1], StorePresence::Unknown(_)));
+    assert!(matches!(presence[&bid2], StorePresence::Absent));
+    Ok(())
+}
+
+#[fbinit::test]
+async fn multiplexed_read_quorum(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let make_multiplex = |read_quorum| {
+        MultiplexedBlobstore::new(
+            MultiplexId::new(1),
+            vec![
+                (bid0, bs0.clone() as Arc<dyn BlobstorePutOps>),
+                (bid1, bs1.clone() as Arc<dyn BlobstorePutOps>),
+                (bid2, bs2.clone() as Arc<dyn BlobstorePutOps>),
+            ],
+            vec![],
+            nonzero!(1usize),
+            queue.clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+            MononokeScubaSampleBuilder::with_discard(),
+            nonzero!(1u64),
+        )
+        .with_read_quorum(read_quorum)
+    };
+    let bs = make_multiplex(nonzero!(2usize));
+
+    // Absent everywhere
+    assert!(bs.get(ctx, "absent").await?.is_none());
+
+    // Two stores agree, the lagging one does not prevent the read
+    let v = make_value("value");
+    bs0.put(ctx, "k0".to_owned(), v.clone()).await?;
+    bs1.put(ctx, "k0".to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, "k0").await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+
+    // Only one store has it
+    bs0.put(ctx, "k1".to_owned(), v.clone()).await?;
+    match bs.get(ctx, "k1").await {
+        Err(e) => match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::QuorumFailed {
+                read_quorum,
+                values,
+                missing,
+            }) => {
+                assert_eq!(*read_quorum, 2);
+                assert_eq!(values.len(), 1);
+                assert_eq!(values[0].size, v.len());
+                assert_eq!(missing.as_ref(), &hashset! {bid1, bid2});
+            }
+            _ => panic!("Unexpected error {:?}", e),
+        },
+        Ok(value) => panic!("Unexpected value {:?}", value),
+    }
+
+    // Values differ, even though two stores agree one is not picked
+    bs0.put(ctx, "k2".to_owned(), v.clone()).await?;
+    bs1.put(ctx, "k2".to_owned(), v.clone()).await?;
+    bs2.put(ctx, "k2".to_owned(), make_value("other")).await?;
+    match bs.get(ctx, "k2").await {
+        Err(e) => match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::QuorumFailed { values, .. }) => assert_eq!(values.len(), 2),
+            _ => panic!("Unexpected error {:?}", e),
+        },
+        Ok(value) => panic!("Unexpected value {:?}", value),
+    }
+
+    // Not enough stores to ever reach quorum
+    let bs = make_multiplex(nonzero!(4usize));
+    match bs.get(ctx, "k0").await {
+        Err(e) => match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::QuorumUnavailable { read_quorum, .. }) => {
+                assert_eq!(*read_quorum, 4)
+            }
+            _ => panic!("Unexpected error {:?}", e),
+        },
+        Ok(value) => panic!("Unexpected value {:?}", value),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn store_health_decay() {
+    let bid0 = BlobstoreId::new(0);
+    let bid1 = BlobstoreId::new(1);
+    let bid2 = BlobstoreId::new(2);
+    let health = StoreHealth::new(Duration::from_secs(10));
+    let start = Instant::now();
+
+    for _ in 0..3 {
+        health.record_at(bid0, false, start);
+    }
+    health.record_at(bid1, true, start);
+    assert!(health.score_at(bid0, start) < 0.5);
+    assert_eq!(health.score_at(bid1, start), 1.0);
+    // Never seen counts as healthy
+    assert_eq!(health.score_at(bid2, start), 1.0);
+
+    let (preferred, fallback) = health.read_order(&[(bid0, ()), (bid1, ()), (bid2, ())]);
+    assert_eq!(
+        preferred.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![bid1, bid2]
+    );
+    assert_eq!(
+        fallback.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![bid0]
+    );
+
+    // Failures fade once the store goes quiet
+    let later = start + Duration::from_secs(60);
+    assert!(health.score_at(bid0, later) > 0.9);
+
+    // With nothing healthy, all are still read first
+    let (preferred, fallback) = health.read_order(&[(bid0, ())]);
+    assert_eq!(preferred.len(), 1);
+    assert!(fallback.is_empty());
+}
+
+#[fbinit::test]
+async fn multiplexed_health_scoring(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Tickable::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone() as Arc<dyn BlobstorePutOps>),
+            (bid1, bs1.clone() as Arc<dyn BlobstorePutOps>),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    )
+    .with_health_scoring(Duration::from_secs(3600));
+
+    // bs0 keeps failing
+    for _ in 0..3 {
+        let mut fut = bs.get(ctx, "absent").boxed();
+        assert!(PollOnce::new(Pin::new(&mut fut)).await.is_pending());
+        bs0.tick(Some("flapping"));
+        assert!(fut.await?.is_none());
+    }
+
+    // Found in the healthy store without waiting on bs0
+    bs1.put(ctx, "k1".to_owned(), make_value("v1")).await?;
+    assert!(bs.get(ctx, "k1").await?.is_some());
+    let metrics = bs.multiplex_metrics();
+    assert_eq!(metrics.stores[&bid0].gets, 3);
+
+    // Still read from bs0 when the healthy store misses
+    bs0.add_bytes("k2".to_owned(), make_value("v2"));
+    let mut fut = bs.get(ctx, "k2").boxed();
+    assert!(PollOnce::new(Pin::new(&mut fut)).await.is_pending());
+    bs0.tick(None);
+    let value = fut.await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(make_value("v2")));
+    Ok(())
+}
+
+#[fbinit::test]
+fn store_breaker_recovery(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bid0 = BlobstoreId::new(0);
+    let breakers = StoreBreakers::new(
+        nonzero!(2usize),
+        Duration::from_secs(10),
+        Duration::from_secs(60),
+    );
+    let start = Instant::now();
+
+    // Failures further apart than the window do not open the breaker
+    breakers.record_at(&ctx, bid0, false, start);
+    let failed = start + Duration::from_secs(20);
+    breakers.record_at(&ctx, bid0, false, failed);
+    assert!(breakers.allow_at(&ctx, bid0, failed));
+
+    // Two in a row do, and the store is skipped until the probe interval has passed
+    breakers.record_at(&ctx, bid0, false, failed + Duration::from_secs(1));
+    assert!(!breakers.allow_at(&ctx, bid0, failed + Duration::from_secs(1)));
+    assert!(!breakers.allow_at(&ctx, bid0, failed + Duration::from_secs(60)));
+
+    // A failed probe keeps the store skipped for another interval
+    let probe = failed + Duration::from_secs(61);
+    assert!(breakers.allow_at(&ctx, bid0, probe));
+    // Only one probe at a time
+    assert!(!breakers.allow_at(&ctx, bid0, probe));
+    breakers.record_at(&ctx, bid0, false, probe);
+    assert!(!breakers.allow_at(&ctx, bid0, probe + Duration::from_secs(59)));
+
+    // The store recovers, so the probe closes the breaker
+    let probe = probe + Duration::from_secs(60);
+    assert!(breakers.allow_at(&ctx, bid0, probe));
+    breakers.record_at(&ctx, bid0, true, probe);
+    assert!(breakers.allow_at(&ctx, bid0, probe));
+    // And the count of failures starts again
+    breakers.record_at(&ctx, bid0, false, probe);
+    assert!(breakers.allow_at(&ctx, bid0, probe));
+}
+
+#[fbinit::test]
+async fn multiplexed_circuit_breaker(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Tickable::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone() as Arc<dyn BlobstorePutOps>),
+            (bid1, bs1.clone() as Arc<dyn BlobstorePutOps>),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    )
+    .with_circuit_breaker(
+        nonzero!(2usize),
+        Duration::from_secs(3600),
+        Duration::from_secs(3600),
+    );
+
+    // bs0 is down
+    for _ in 0..2 {
+        let mut fut = bs.get(ctx, "absent").boxed();
+        assert!(PollOnce::new(Pin::new(&mut fut)).await.is_pending());
+        bs0.tick(Some("down"));
+        assert!(fut.await?.is_none());
+    }
+
+    // Its breaker is open, so gets no longer wait on it
+    let mut fut = bs.get(ctx, "absent").boxed();
+    match PollOnce::new(Pin::new(&mut fut)).await {
+        Poll::Ready(value) => assert!(value?.is_none()),
+        Poll::Pending => panic!("get waited on a store with an open breaker"),
+    }
+    bs1.put(ctx, "k1".to_owned(), make_value("v1")).await?;
+    assert!(bs.get(ctx, "k1").await?.is_some());
+    assert_eq!(bs.multiplex_metrics().stores[&bid0].gets, 2);
+    Ok(())
+}
+
+async fn scrub_scenarios(fb: FacebookInit, scrub_action_on_missing_write_mostly: ScrubWriteMostly) {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
+    let scrub_handler = Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>;
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Tickable::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Tickable::new());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Tickable::new());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2.clone())],
+        nonzero!(1usize),
+        queue.clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::ReportOnly,
+            scrub_grace: None,
+            scrub_action_on_missing_write_mostly,
+            queue_peek_bound: None,
+            repair_source_policy: Arc::new(DefaultRepairSourcePolicy),
+            ignore_key_prefixes: vec![],
+            restrict_to_stores: None,
+            key_range: None,
+            progress_interval_keys: None,
+            progress_interval: None,
+            expected_keys: None,
+            key_normalizer: Arc::new(IdentityKeyNormalizer),
+            backup_store: None,
+            quarantine_store: None,
+            batch_repair: None,
+        },
+        scrub_handler.clone(),
+    );
+
+    // non-existing key when one main blobstore failing
+    {
+        let k0 = "k0";
+
+        let mut get_fut = bs.get(ctx, k0).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
+        bs0.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
+        bs1.tick(Some("bs1 failed"));
+
+        bs2.tick(None);
+        assert_eq!(get_fut.await.unwrap(), None, "SomeNone + Err expected None");
+    }
+
+    // non-existing key when one write mostly blobstore failing
+    {
+        let k0 = "k0";
+
+        let mut get_fut = bs.get(ctx, k0).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
+        bs0.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
+        bs1.tick(None);
+
+        bs2.tick(Some("bs1 failed"));
+        assert_eq!(get_fut.await.unwrap(), None, "SomeNone + Err expected None");
+    }
+
+    // fail all but one store on put to make sure only one has the data
+    // only replica containing key fails on read.
+    {
+        let v1 = make_value("v1");
+        let k1 = "k1";
+
+        let mut put_fut = bs
+            .put(ctx, k1.to_owned(), v1.clone())
+            .map_err(|_| ())
+            .boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+        bs0.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+        bs1.tick(Some("bs1 failed"));
+        bs2.tick(Some("bs2 failed"));
+        put_fut.await.unwrap();
+
         match queue.get(ctx, k1).await.unwrap().as_slice() {
             [entry] => {
                 assert_eq!(entry.blobstore_id, bid0, "Queue bad");
             }
             _ => panic!("only one entry expected"),
         }
-        // bs1 and bs2 empty at this point
-        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
-        assert!(bs1.storage.with(|s| s.is_empty()));
-        assert!(bs2.storage.with(|s| s.is_empty()));
+
         let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
         assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-        // tick the gets
-        bs0.tick(None);
+
+        bs0.tick(Some("bs0 failed"));
         assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
         bs1.tick(None);
-        if scrub_action_on_missing_write_mostly != ScrubWriteMostly::PopulateIfAbsent {
-            // this read doesn't happen in this mode
-            bs2.tick(None);
-        }
-        // No repairs to tick, as its on queue within the peek lookback
+        bs2.tick(None);
+        assert!(get_fut.await.is_err(), "None/Err while replicating");
+    }
 
-        // Succeeds
-        assert_eq!(get_fut.await.unwrap().map(|v| v.into()), Some(v1.clone()));
+    // all replicas fail
+    {
+        let k2 = "k2";
 
-        // bs1 and bs2 still empty at this point. assumption is item on queue will be healed later.
-        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
-        assert!(bs1.storage.with(|s| s.is_empty()));
-        assert!(bs2.storage.with(|s| s.is_empty()));
+        let mut get_fut = bs.get(ctx, k2).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        bs0.tick(Some("bs0 failed"));
+        bs1.tick(Some("bs1 failed"));
+        bs2.tick(Some("bs1 failed"));
+        assert!(get_fut.await.is_err(), "Err/Err");
     }
 
-    // One working replica after failure.
-    {
-        let v1 = make_value("v1");
-        let k1 = "k1";
+    // Now replace bs1 & bs2 with an empty blobstore, and see the scrub work
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Tickable::new());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Tickable::new());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2.clone())],
+        nonzero!(1usize),
+        queue.clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            scrub_grace: None,
+            scrub_action_on_missing_write_mostly,
+            queue_peek_bound: None,
+            repair_source_policy: Arc::new(DefaultRepairSourcePolicy),
+            ignore_key_prefixes: vec![],
+            restrict_to_stores: None,
+            key_range: None,
+            progress_interval_keys: None,
+            progress_interval: None,
+            expected_keys: None,
+            key_normalizer: Arc::new(IdentityKeyNormalizer),
+            backup_store: None,
+            quarantine_store: None,
+            batch_repair: None,
+        },
+        scrub_handler.clone(),
+    );
+
+    // Non-existing key in all blobstores, new blobstore failing
+    {
+        let k0 = "k0";
+
+        let mut get_fut = bs.get(ctx, k0).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
+        bs0.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+
+        bs1.tick(Some("bs1 failed"));
+
+        bs2.tick(None);
+        assert_eq!(get_fut.await.unwrap(), None, "None/Err after replacement");
+    }
+
+    // only replica containing key replaced after failure - DATA LOST
+    {
+        let k1 = "k1";
+
+        let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        bs0.tick(Some("bs0 failed"));
+        bs1.tick(None);
+        bs2.tick(None);
+        assert!(get_fut.await.is_err(), "Empty replacement against error");
+    }
+
+    // One working replica after failure, queue lookback means scrub action not performed
+    {
+        // Create with different queue_peek_bound
+        let bs = ScrubBlobstore::new(
+            MultiplexId::new(1),
+            vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+            vec![(bid2, bs2.clone())],
+            nonzero!(1usize),
+            queue.clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+            MononokeScubaSampleBuilder::with_discard(),
+            nonzero!(1u64),
+            ScrubOptions {
+                scrub_action: ScrubAction::Repair,
+                scrub_grace: None,
+                scrub_action_on_missing_write_mostly,
+                queue_peek_bound: Some(Duration::from_secs(7200)),
+                repair_source_policy: Arc::new(DefaultRepairSourcePolicy),
+                ignore_key_prefixes: vec![],
+                restrict_to_stores: None,
+                key_range: None,
+                progress_interval_keys: None,
+                progress_interval: None,
+                expected_keys: None,
+                key_normalizer: Arc::new(IdentityKeyNormalizer),
+                backup_store: None,
+                quarantine_store: None,
+                batch_repair: None,
+            },
+            scrub_handler,
+        );
+        let v1 = make_value("v1");
+        let k1 = "k1";
+        // Check there is an entry on the queue
+        match queue.get(ctx, k1).await.unwrap().as_slice() {
+            [entry] => {
+                assert_eq!(entry.blobstore_id, bid0, "Queue bad");
+            }
+            _ => panic!("only one entry expected"),
+        }
+        // bs1 and bs2 empty at this point
+        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
+        assert!(bs1.storage.with(|s| s.is_empty()));
+        assert!(bs2.storage.with(|s| s.is_empty()));
+        let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        // tick the gets
+        bs0.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        bs1.tick(None);
+        if scrub_action_on_missing_write_mostly != ScrubWriteMostly::PopulateIfAbsent {
+            // this read doesn't happen in this mode
+            bs2.tick(None);
+        }
+        // No repairs to tick, as its on queue within the peek lookback
+
+        // Succeeds
+        assert_eq!(get_fut.await.unwrap().map(|v| v.into()), Some(v1.clone()));
+
+        // bs1 and bs2 still empty at this point. assumption is item on queue will be healed later.
+        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
+        assert!(bs1.storage.with(|s| s.is_empty()));
+        assert!(bs2.storage.with(|s| s.is_empty()));
+    }
+
+    // One working replica after failure.
+    {
+        let v1 = make_value("v1");
+        let k1 = "k1";
+
+        match queue.get(ctx, k1).await.unwrap().as_slice() {
+            [entry] => {
+                assert_eq!(entry.blobstore_id, bid0, "Queue bad");
+                queue
+                    .del(&ctx, &vec![entry.clone()])
+                    .await
+                    .expect("Could not delete scrub queue entry");
+            }
+            _ => panic!("only one entry expected"),
+        }
+
+        // bs1 and bs2 empty at this point
+        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
+        assert!(bs1.storage.with(|s| s.is_empty()));
+        assert!(bs2.storage.with(|s| s.is_empty()));
+
+        let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        // tick the gets
+        bs0.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        bs1.tick(None);
+        if scrub_action_on_missing_write_mostly != ScrubWriteMostly::PopulateIfAbsent {
+            // this read doesn't happen in this mode
+            bs2.tick(None);
+        }
+        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
+        // Tick the repairs
+        bs1.tick(None);
+        bs2.tick(None);
+
+        // Succeeds
+        assert_eq!(get_fut.await.unwrap().map(|v| v.into()), Some(v1.clone()));
+        // Now all populated.
+        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
+        assert_eq!(bs1.get_bytes(k1), Some(v1.clone()));
+        match scrub_action_on_missing_write_mostly {
+            ScrubWriteMostly::Scrub
+            | ScrubWriteMostly::PopulateIfAbsent
+            | ScrubWriteMostly::ScrubIfAbsent => {
+                assert_eq!(bs2.get_bytes(k1), Some(v1.clone()))
+            }
+            ScrubWriteMostly::SkipMissing => {
+                assert_eq!(bs2.get_bytes(k1), None)
+            }
+        }
+    }
+}
+
+#[fbinit::test]
+async fn scrubbed(fb: FacebookInit) {
+    scrub_scenarios(fb, ScrubWriteMostly::Scrub).await;
+    scrub_scenarios(fb, ScrubWriteMostly::SkipMissing).await;
+    scrub_scenarios(fb, ScrubWriteMostly::PopulateIfAbsent).await;
+}
+
+#[fbinit::test]
+async fn scrub_divergent_values(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone()),
+            (bid1, bs1.clone()),
+            (bid2, bs2.clone()),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            ..ScrubOptions::default()
+        },
+        Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>,
+    );
+
+    let good = make_value("good");
+    let bad = make_value("bad");
+
+    // Key that is not content addressed, the majority wins
+    {
+        let k = "k0";
+        bs0.put(ctx, k.to_owned(), good.clone()).await?;
+        bs1.put(ctx, k.to_owned(), good.clone()).await?;
+        bs2.put(ctx, k.to_owned(), bad.clone()).await?;
+
+        let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(good.clone()));
+        for store in &[&bs0, &bs1, &bs2] {
+            let value = store.get(ctx, k).await?.map(|v| v.into_bytes());
+            assert_eq!(value, Some(good.clone()));
+        }
+    }
+
+    // Content addressed key, the value matching the hash wins even in a minority
+    {
+        let mut context = HashContext::new(b"changeset");
+        context.update(good.as_bytes());
+        let k = format!("repo0000.changeset.blake2.{}", context.finish());
+        bs0.put(ctx, k.clone(), good.clone()).await?;
+        bs1.put(ctx, k.clone(), bad.clone()).await?;
+        bs2.put(ctx, k.clone(), bad.clone()).await?;
+
+        let value = bs.get(ctx, &k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(good.clone()));
+        for store in &[&bs0, &bs1, &bs2] {
+            let value = store.get(ctx, &k).await?.map(|v| v.into_bytes());
+            assert_eq!(value, Some(good.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_quarantines_divergent_values(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let quarantine_store = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone()),
+            (bid1, bs1.clone()),
+            (bid2, bs2.clone()),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Quarantine,
+            quarantine_store: Some(quarantine_store.clone()),
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    let good = make_value("good");
+    let bad = make_value("bad");
+
+    // The majority would win with Repair, but here no value is picked
+    let k = "k0";
+    bs0.put(ctx, k.to_owned(), good.clone()).await?;
+    bs1.put(ctx, k.to_owned(), good.clone()).await?;
+    bs2.put(ctx, k.to_owned(), bad.clone()).await?;
+
+    assert!(bs.get(ctx, k).await.is_err());
+    for (store, expected) in &[(&bs0, &good), (&bs1, &good), (&bs2, &bad)] {
+        let value = store.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value.as_ref(), Some(*expected));
+    }
+
+    // Both values are quarantined, each reported with the stores holding it
+    let mut quarantined = scrub_handler.quarantined.with(|q| q.clone());
+    quarantined.sort();
+    assert_eq!(quarantined.len(), 2);
+    let mut quarantined_values = vec![];
+    for (ids, key, quarantine_key) in quarantined {
+        assert_eq!(key, k);
+        let value = quarantine_store
+            .get(ctx, &quarantine_key)
+            .await?
+            .map(|v| v.into_bytes());
+        quarantined_values.push((ids, value));
+    }
+    quarantined_values.sort_by_key(|(ids, _)| ids.len());
+    assert_eq!(
+        quarantined_values,
+        vec![
+            (vec![bid2], Some(bad.clone())),
+            (vec![bid0, bid1], Some(good.clone())),
+        ]
+    );
+    assert!(scrub_handler.repaired.with(|r| r.is_empty()));
+
+    // Only missing, so repaired as usual
+    let k = "k1";
+    bs0.put(ctx, k.to_owned(), good.clone()).await?;
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(good.clone()));
+    for store in &[&bs1, &bs2] {
+        let value = store.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(good.clone()));
+    }
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_restricted_to_stores(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone()),
+            (bid1, bs1.clone()),
+            (bid2, bs2.clone()),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            restrict_to_stores: Some(vec![bid0, bid1]),
+            ..ScrubOptions::default()
+        },
+        Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>,
+    );
+
+    let good = make_value("good");
+    let bad = make_value("bad");
+
+    // Missing from one compared store, only that store is repaired
+    {
+        let k = "k0";
+        bs0.put(ctx, k.to_owned(), good.clone()).await?;
+
+        let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(good.clone()));
+        let value = bs1.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(good.clone()));
+        assert!(bs2.get(ctx, k).await?.is_none());
+    }
+
+    // Only in the excluded store, so ignored
+    {
+        let k = "k1";
+        bs2.put(ctx, k.to_owned(), good.clone()).await?;
+
+        assert!(bs.get(ctx, k).await?.is_none());
+        assert!(bs0.get(ctx, k).await?.is_none());
+        assert!(bs1.get(ctx, k).await?.is_none());
+    }
+
+    // Divergent value in the excluded store is left alone
+    {
+        let k = "k2";
+        bs0.put(ctx, k.to_owned(), good.clone()).await?;
+        bs1.put(ctx, k.to_owned(), good.clone()).await?;
+        bs2.put(ctx, k.to_owned(), bad.clone()).await?;
+
+        let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(good.clone()));
+        let value = bs2.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(bad));
+    }
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_report_only_would_repair(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone()),
+            (bid1, bs1.clone()),
+            (bid2, bs2.clone()),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::ReportOnly,
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    let k = "k0";
+    let v = make_value("value");
+    bs0.put(ctx, k.to_owned(), v.clone()).await?;
+
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v));
+
+    // The missing stores are reported, but nothing is written to them
+    assert_eq!(
+        scrub_handler.would_repair.with(|w| w.clone()),
+        vec![(vec![bid1, bid2], k.to_string())]
+    );
+    assert!(scrub_handler.repaired.with(|r| r.is_empty()));
+    assert!(bs1.get(ctx, k).await?.is_none());
+    assert!(bs2.get(ctx, k).await?.is_none());
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_repairs_from_backup_store(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let backup = Memblob::default();
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2.clone())],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            // Read-only, so any write to the backup fails the scrub
+            backup_store: Some(Arc::new(ReadOnlyBlobstore::new(backup.clone()))),
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    // Only the backup has the key, so every inner store is repaired from it
+    let k = "k0";
+    let v = make_value("value");
+    backup.put(ctx, k.to_owned(), v.clone()).await?;
+
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    for store in &[&bs0, &bs1, &bs2] {
+        let value = store.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(v.clone()));
+    }
+    let mut repaired = scrub_handler.repaired.with(|r| r.clone());
+    repaired.sort();
+    assert_eq!(
+        repaired,
+        vec![
+            (bid0, k.to_string()),
+            (bid1, k.to_string()),
+            (bid2, k.to_string()),
+        ]
+    );
+
+    // Missing everywhere, including the backup
+    assert!(bs.get(ctx, "k1").await?.is_none());
+    assert_eq!(scrub_handler.repaired.with(|r| r.len()), 3);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_reports_progress(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::ReportOnly,
+            progress_interval_keys: Some(nonzero!(2u64)),
+            expected_keys: Some(8),
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    // k1 is missing from bs1, so needs one repair
+    let v = make_value("value");
+    for k in &["k0", "k1", "k2", "k3", "k4"] {
+        bs0.put(ctx, k.to_string(), v.clone()).await?;
+        if *k != "k1" {
+            bs1.put(ctx, k.to_string(), v.clone()).await?;
+        }
+    }
+    for k in &["k0", "k1", "k2", "k3", "k4"] {
+        bs.get(ctx, k).await?;
+    }
+
+    // Reported every second key, not yet for the fifth
+    let progress = scrub_handler.progress.with(|p| p.clone());
+    let summary: Vec<_> = progress
+        .iter()
+        .map(|p| (p.keys_scanned, p.repairs_done, p.repairs_pending))
+        .collect();
+    assert_eq!(summary, vec![(2, 0, 1), (4, 0, 1)]);
+    assert!(progress.iter().all(|p| p.expected_keys == Some(8)));
+
+    Ok(())
+}
+
+#[test]
+fn scrub_progress_eta() {
+    let progress = ScrubProgress {
+        keys_scanned: 25,
+        repairs_done: 0,
+        repairs_pending: 0,
+        elapsed: Duration::from_secs(10),
+        expected_keys: Some(100),
+    };
+    // 75 keys left at 2.5 keys per second
+    assert_eq!(progress.eta(), Some(Duration::from_secs(30)));
+    assert_eq!(
+        ScrubProgress {
+            expected_keys: None,
+            ..progress.clone()
+        }
+        .eta(),
+        None
+    );
+    assert_eq!(
+        ScrubProgress {
+            keys_scanned: 0,
+            ..progress
+        }
+        .eta(),
+        None
+    );
+}
+
+#[fbinit::test]
+async fn scrub_sql_handler_records_repairs(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(SqlScrubHandler::with_sqlite_in_memory()?);
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone()),
+            (bid1, bs1.clone()),
+            (bid2, bs2.clone()),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    let k = "k0";
+    let v = make_value("value");
+    bs0.put(ctx, k.to_owned(), v.clone()).await?;
+
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v));
+
+    // One row per repaired store, written once the batch is flushed
+    scrub_handler.flush(ctx).await;
+    let mut recorded: Vec<_> = scrub_handler
+        .get(k)
+        .await?
+        .into_iter()
+        .map(|r| (r.blobstore_id, r.blobstore_key, r.is_repaired))
+        .collect();
+    recorded.sort();
+    assert_eq!(
+        recorded,
+        vec![(bid1, k.to_string(), true), (bid2, k.to_string(), true)]
+    );
+    assert!(scrub_handler.get("other").await?.is_empty());
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_ignore_key_prefixes(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            ignore_key_prefixes: vec!["repo0000.alias.".to_string()],
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    let v = make_value("value");
+
+    // Ignored key is still readable, but not repaired or reported
+    let k = "repo0000.alias.sha1.0000";
+    bs0.put(ctx, k.to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    assert!(bs1.get(ctx, k).await?.is_none());
+    assert!(scrub_handler.repaired.with(|r| r.is_empty()));
+    assert!(scrub_handler.would_repair.with(|w| w.is_empty()));
+
+    // Other keys are scrubbed as usual
+    let k = "repo0000.content.blake2.0000";
+    bs0.put(ctx, k.to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    assert_eq!(bs1.get(ctx, k).await?.map(|v| v.into_bytes()), Some(v));
+    assert_eq!(
+        scrub_handler.repaired.with(|r| r.clone()),
+        vec![(bid1, k.to_string())]
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_key_range(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let scrub_options = ScrubOptions {
+        scrub_action: ScrubAction::Repair,
+        key_range: Some(("repo0000.b".to_string(), "repo0000.d".to_string())),
+        progress_interval_keys: Some(nonzero!(1u64)),
+        ..ScrubOptions::default()
+    };
+    assert_eq!(
+        scrub_options.enumeration_range(),
+        BlobstoreKeyParam::from("repo0000.b".to_string()..="repo0000.d".to_string())
+    );
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        scrub_options.clone(),
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+
+    let v = make_value("value");
+
+    // Keys before the start and at the exclusive end are read but left untouched
+    for k in &["repo0000.a", "repo0000.d"] {
+        bs0.put(ctx, k.to_string(), v.clone()).await?;
+        let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(v.clone()));
+        assert!(bs1.get(ctx, k).await?.is_none());
+    }
+    assert!(scrub_handler.repaired.with(|r| r.is_empty()));
+    assert!(scrub_handler.would_repair.with(|w| w.is_empty()));
+
+    // Keys from the start up to the end are scrubbed
+    for k in &["repo0000.b", "repo0000.c.0000"] {
+        bs0.put(ctx, k.to_string(), v.clone()).await?;
+        let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+        assert_eq!(value, Some(v.clone()));
+        assert_eq!(
+            bs1.get(ctx, k).await?.map(|v| v.into_bytes()),
+            Some(v.clone())
+        );
+    }
+    assert_eq!(
+        scrub_handler.repaired.with(|r| r.clone()),
+        vec![
+            (bid1, "repo0000.b".to_string()),
+            (bid1, "repo0000.c.0000".to_string())
+        ]
+    );
+
+    // Only the keys in range count as scanned
+    let scanned: Vec<_> = scrub_handler
+        .progress
+        .with(|p| p.iter().map(|p| p.keys_scanned).collect());
+    assert_eq!(scanned, vec![1, 2]);
+
+    // Enumeration lists just the range, without its exclusive end
+    assert_eq!(
+        enumerate_scrub_keys(ctx, bs0.as_ref(), &scrub_options).await?,
+        vec!["repo0000.b".to_string(), "repo0000.c.0000".to_string()]
+    );
+
+    Ok(())
+}
+
+// Batch put to a Memblob that records the keys of each batch it is sent
+#[derive(Debug)]
+struct RecordingBatchPut {
+    store: Arc<Memblob>,
+    batches: Mutex<Vec<Vec<String>>>,
+}
+
+#[async_trait]
+impl BlobstoreBatchPut for RecordingBatchPut {
+    async fn put_batch(
+        &self,
+        ctx: &CoreContext,
+        values: Vec<(String, BlobstoreBytes)>,
+    ) -> Result<()> {
+        self.batches
+            .with(|b| b.push(values.iter().map(|(key, _)| key.clone()).collect()));
+        for (key, value) in values {
+            self.store.put(ctx, key, value).await?;
+        }
+        Ok(())
+    }
+}
+
+#[fbinit::test]
+async fn scrub_batch_alias_repair(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let batch_put = Arc::new(RecordingBatchPut {
+        store: bs1.clone(),
+        batches: Mutex::new(vec![]),
+    });
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let batch_repair = BatchRepairOptions {
+        batch_size: nonzero!(2usize),
+        ..BatchRepairOptions::new(
+            hashmap! {bid1 => batch_put.clone() as Arc<dyn BlobstoreBatchPut>},
+        )
+    };
+    let scrub_options = ScrubOptions {
+        scrub_action: ScrubAction::Repair,
+        batch_repair: Some(batch_repair.clone()),
+        ..ScrubOptions::default()
+    };
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        scrub_options,
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
+    bs.register_batch_repairs();
+
+    let v = make_value("value");
+    let keys = [
+        "repo0000.alias.sha1.aaa",
+        "repo0000.alias.sha1.bbb",
+        "repo0000.other",
+        "repo0000.alias.sha1.ccc",
+    ];
+    for k in &keys {
+        bs0.put(ctx, k.to_string(), v.clone()).await?;
+    }
+
+    // The first alias key waits for a full batch
+    assert_eq!(
+        bs.get(ctx, keys[0]).await?.map(|v| v.into_bytes()),
+        Some(v.clone())
+    );
+    assert!(bs1.get(ctx, keys[0]).await?.is_none());
+
+    // Scrubbing the same key again doesn't add it to the batch twice
+    bs.get(ctx, keys[0]).await?;
+    assert!(batch_put.batches.with(|b| b.is_empty()));
+
+    // The second fills the batch, so both are put together
+    bs.get(ctx, keys[1]).await?;
+    assert_eq!(
+        batch_put.batches.with(|b| b.clone()),
+        vec![vec![keys[0].to_string(), keys[1].to_string()]]
+    );
+
+    // Other keys are repaired immediately
+    bs.get(ctx, keys[2]).await?;
+    assert!(bs1.get(ctx, keys[2]).await?.is_some());
+
+    // A partial batch is only put on flush, which the options the store was built
+    // from can do
+    bs.get(ctx, keys[3]).await?;
+    assert!(bs1.get(ctx, keys[3]).await?.is_none());
+    batch_repair.flush(ctx).await?;
+    assert_eq!(batch_put.batches.with(|b| b.len()), 2);
+
+    // Nothing is left to flush
+    bs.flush_repairs(ctx).await?;
+    assert_eq!(batch_put.batches.with(|b| b.len()), 2);
+
+    for k in &keys {
+        assert_eq!(
+            bs1.get(ctx, k).await?.map(|v| v.into_bytes()),
+            Some(v.clone())
+        );
+    }
+    let mut repaired = scrub_handler.repaired.with(|r| r.clone());
+    repaired.sort();
+    let mut expected: Vec<_> = keys.iter().map(|k| (bid1, k.to_string())).collect();
+    expected.sort();
+    assert_eq!(repaired, expected);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn scrub_key_normalizer(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    // Keys are sharded as "<shard>/<logical key>"
+    let strip_shard = |key: &str| match key.split_once('/') {
+        Some((_shard, logical)) => logical.to_string(),
+        None => key.to_string(),
+    };
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            ignore_key_prefixes: vec!["repo0000.alias.".to_string()],
+            key_normalizer: Arc::new(FnScrubKeyNormalizer(strip_shard)),
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
 
-        match queue.get(ctx, k1).await.unwrap().as_slice() {
-            [entry] => {
-                assert_eq!(entry.blobstore_id, bid0, "Queue bad");
-                queue
-                    .del(&ctx, &vec![entry.clone()])
-                    .await
-                    .expect("Could not delete scrub queue entry");
-            }
-            _ => panic!("only one entry expected"),
-        }
+    let v = make_value("value");
+
+    // The prefix is matched against the logical key
+    let k = "07/repo0000.alias.sha1.0000";
+    bs0.put(ctx, k.to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    assert!(bs1.get(ctx, k).await?.is_none());
+    assert!(scrub_handler.repaired.with(|r| r.is_empty()));
+
+    // Repairs write the physical key
+    let k = "07/repo0000.content.blake2.0000";
+    bs0.put(ctx, k.to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, k).await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    assert_eq!(bs1.get(ctx, k).await?.map(|v| v.into_bytes()), Some(v));
+    assert_eq!(
+        scrub_handler.repaired.with(|r| r.clone()),
+        vec![(bid1, k.to_string())]
+    );
 
-        // bs1 and bs2 empty at this point
-        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
-        assert!(bs1.storage.with(|s| s.is_empty()));
-        assert!(bs2.storage.with(|s| s.is_empty()));
+    Ok(())
+}
 
-        let mut get_fut = bs.get(ctx, k1).map_err(|_| ()).boxed();
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-        // tick the gets
-        bs0.tick(None);
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-        bs1.tick(None);
-        if scrub_action_on_missing_write_mostly != ScrubWriteMostly::PopulateIfAbsent {
-            // this read doesn't happen in this mode
-            bs2.tick(None);
+#[fbinit::test]
+async fn scrub_per_store_keys(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    // Each store prefixes keys with its own shard, "a/<logical key>" or "b/<logical key>"
+    let strip_shard = |key: &str| match key.split_once('/') {
+        Some((_shard, logical)) => logical.to_string(),
+        None => key.to_string(),
+    };
+    let shard = move |blobstore_id: BlobstoreId, logical: &str| {
+        if blobstore_id == bid0 {
+            format!("a/{}", logical)
+        } else {
+            format!("b/{}", logical)
         }
-        assert_eq!(PollOnce::new(Pin::new(&mut get_fut)).await, Poll::Pending);
-        // Tick the repairs
-        bs1.tick(None);
-        bs2.tick(None);
+    };
+    // Trust store 0 when the stores differ
+    let prefer_bid0 = move |_key: &str, candidates: &[RepairCandidate<'_>]| {
+        candidates
+            .iter()
+            .position(|candidate| candidate.blobstores.contains(&bid0))
+    };
+    let bs = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        queue.clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            repair_source_policy: Arc::new(FnRepairSourcePolicy(prefer_bid0)),
+            key_normalizer: Arc::new(FnPerStoreKeyNormalizer(strip_shard, shard)),
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    );
 
-        // Succeeds
-        assert_eq!(get_fut.await.unwrap().map(|v| v.into()), Some(v1.clone()));
-        // Now all populated.
-        assert_eq!(bs0.get_bytes(k1), Some(v1.clone()));
-        assert_eq!(bs1.get_bytes(k1), Some(v1.clone()));
-        match scrub_action_on_missing_write_mostly {
-            ScrubWriteMostly::Scrub
-            | ScrubWriteMostly::PopulateIfAbsent
-            | ScrubWriteMostly::ScrubIfAbsent => {
-                assert_eq!(bs2.get_bytes(k1), Some(v1.clone()))
-            }
-            ScrubWriteMostly::SkipMissing => {
-                assert_eq!(bs2.get_bytes(k1), None)
-            }
-        }
-    }
-}
+    let v = make_value("value");
+    let other = make_value("other");
+
+    // Both stores hold the key under their own encoding, so nothing diverges
+    bs0.put(ctx, "a/k1".to_owned(), v.clone()).await?;
+    bs1.put(ctx, "b/k1".to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, "a/k1").await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    assert!(scrub_handler.repaired.with(|r| r.is_empty()));
+
+    // Repairs write the store's own physical key
+    bs0.put(ctx, "a/k2".to_owned(), v.clone()).await?;
+    let value = bs.get(ctx, "a/k2").await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(v.clone()));
+    assert_eq!(
+        bs1.get(ctx, "b/k2").await?.map(|v| v.into_bytes()),
+        Some(v.clone())
+    );
+    assert!(bs1.get(ctx, "a/k2").await?.is_none());
+    assert_eq!(
+        scrub_handler.repaired.with(|r| r.clone()),
+        vec![(bid1, "b/k2".to_string())]
+    );
 
-#[fbinit::test]
-async fn scrubbed(fb: FacebookInit) {
-    scrub_scenarios(fb, ScrubWriteMostly::Scrub).await;
-    scrub_scenarios(fb, ScrubWriteMostly::SkipMissing).await;
-    scrub_scenarios(fb, ScrubWriteMostly::PopulateIfAbsent).await;
+    // The queue holds the key as it was put, so a pending write there leaves the divergence
+    // to the healer
+    bs0.put(ctx, "a/k3".to_owned(), v.clone()).await?;
+    bs1.put(ctx, "b/k3".to_owned(), other.clone()).await?;
+    let entry = BlobstoreSyncQueueEntry {
+        blobstore_key: "a/k3".to_string(),
+        blobstore_id: bid0,
+        multiplex_id: MultiplexId::new(1),
+        timestamp: DateTime::now(),
+        id: None,
+        operation_key: OperationKey::gen(),
+        blob_size: None,
+    };
+    queue.add(ctx, entry).await?;
+    assert!(bs.get(ctx, "a/k3").await.is_err());
+    assert_eq!(
+        bs1.get(ctx, "b/k3").await?.map(|v| v.into_bytes()),
+        Some(other)
+    );
+    assert_eq!(scrub_handler.repaired.with(|r| r.len()), 1);
+
+    Ok(())
 }
 
 #[fbinit::test]
@@ -1754,6 +3387,176 @@ async fn needed_writes_bad_config(fb: FacebookInit) {
     }
 }
 
+#[fbinit::test]
+async fn write_quorum(fb: FacebookInit) {
+    let bs0 = Arc::new(Tickable::new());
+    let bs1 = Arc::new(Tickable::new());
+    let bs2 = Arc::new(Tickable::new());
+
+    let log = Arc::new(LogHandler::new());
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let make_bs = |threshold| {
+        MultiplexedBlobstoreBase::new(
+            MultiplexId::new(1),
+            vec![
+                (BlobstoreId::new(0), bs0.clone()),
+                (BlobstoreId::new(1), bs1.clone()),
+            ],
+            vec![(BlobstoreId::new(2), bs2.clone())],
+            nonzero!(1usize),
+            log.clone(),
+            MononokeScubaSampleBuilder::with_discard(),
+            nonzero!(1u64),
+        )
+        .with_write_quorum(
+            WriteQuorum::new(threshold, hashmap! { BlobstoreId::new(0) => 2 }),
+            scrub_handler.clone(),
+        )
+    };
+
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+
+    // The heavy store alone meets the quorum, and the other puts finish in the background
+    {
+        let bs = make_bs(nonzero!(2u64));
+        let v0 = make_value("v0");
+        let k0 = "k0";
+        let mut put_fut = bs
+            .put(ctx, k0.to_owned(), v0.clone())
+            .map_err(|_| ())
+            .boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+
+        bs0.tick(None);
+        assert!(put_fut.await.is_ok(), "Put failed with quorum met");
+        assert_eq!(bs0.get_bytes(k0), Some(v0.clone()));
+        assert_eq!(bs1.get_bytes(k0), None);
+
+        bs1.tick(Some("Error"));
+        bs2.tick(None);
+        while bs2.get_bytes(k0).is_none() || scrub_handler.repaired.with(|r| r.is_empty()) {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(bs2.get_bytes(k0), Some(v0));
+        scrub_handler
+            .repaired
+            .with(|repaired| assert_eq!(repaired, &vec![(BlobstoreId::new(1), k0.to_owned())]));
+        log.clear();
+    }
+
+    // Two light stores don't add up to the quorum
+    {
+        let bs = make_bs(nonzero!(3u64));
+        let v1 = make_value("v1");
+        let k1 = "k1";
+        let mut put_fut = bs
+            .put(ctx, k1.to_owned(), v1.clone())
+            .map_err(|_| ())
+            .boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+
+        bs1.tick(None);
+        bs2.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+
+        bs0.tick(Some("Error"));
+        assert!(put_fut.await.is_err(), "Put succeeded without quorum");
+        log.clear();
+    }
+
+    // A quorum heavier than all the stores together can never be met
+    {
+        let bs = make_bs(nonzero!(5u64));
+        assert!(
+            bs.put(ctx, "k2".to_owned(), make_value("v2"))
+                .await
+                .is_err(),
+            "Put succeeded despite not enough blobstore weight"
+        );
+    }
+}
+
+#[fbinit::test]
+async fn canonical_store(fb: FacebookInit) {
+    let bs0 = Arc::new(Tickable::new());
+    let bs1 = Arc::new(Tickable::new());
+    let bs2 = Arc::new(Tickable::new());
+
+    let log = Arc::new(LogHandler::new());
+    let bs = MultiplexedBlobstoreBase::new(
+        MultiplexId::new(1),
+        vec![
+            (BlobstoreId::new(0), bs0.clone()),
+            (BlobstoreId::new(1), bs1.clone()),
+        ],
+        vec![(BlobstoreId::new(2), bs2.clone())],
+        nonzero!(2usize),
+        log.clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    )
+    .with_canonical_store(BlobstoreId::new(1));
+    let pending = |store: &Tickable<(BlobstoreBytes, u64)>| store.queue.with(|q| q.len());
+
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+
+    // Only the canonical store is written until it acknowledges
+    {
+        let v0 = make_value("v0");
+        let k0 = "k0";
+        let mut put_fut = bs
+            .put(ctx, k0.to_owned(), v0.clone())
+            .map_err(|_| ())
+            .boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+        assert_eq!((pending(&bs0), pending(&bs1), pending(&bs2)), (0, 1, 0));
+
+        bs1.tick(None);
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+        assert_eq!(bs1.get_bytes(k0), Some(v0.clone()));
+        assert_eq!((pending(&bs0), pending(&bs2)), (1, 1));
+        log.log
+            .with(|log| assert_eq!(log, &vec![(BlobstoreId::new(1), k0.to_owned())]));
+
+        // The canonical store counts towards minimum_successful_writes
+        bs2.tick(None);
+        assert!(put_fut.await.is_ok(), "Put failed with enough writes");
+        assert_eq!(bs2.get_bytes(k0), Some(v0.clone()));
+        assert_eq!(bs0.get_bytes(k0), None);
+
+        // The last store is written in the background
+        bs0.tick(None);
+        while log.log.with(|log| log.len()) < 3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(bs0.get_bytes(k0), Some(v0));
+        log.clear();
+    }
+
+    // A failed canonical write fails the put without writing to the other stores
+    {
+        let v1 = make_value("v1");
+        let k1 = "k1";
+        let mut put_fut = bs
+            .put(ctx, k1.to_owned(), v1.clone())
+            .map_err(|_| ())
+            .boxed();
+        assert_eq!(PollOnce::new(Pin::new(&mut put_fut)).await, Poll::Pending);
+
+        bs1.tick(Some("Error"));
+        assert!(
+            put_fut.await.is_err(),
+            "Put succeeded without the canonical store"
+        );
+        assert_eq!((pending(&bs0), pending(&bs2)), (0, 0));
+        assert_eq!(bs0.get_bytes(k1), None);
+        assert_eq!(bs2.get_bytes(k1), None);
+        log.log.with(|log| assert!(log.is_empty()));
+    }
+}
+
 #[fbinit::test]
 async fn no_handlers(fb: FacebookInit) {
     let bs0 = Arc::new(Tickable::new());
@@ -2013,3 +3816,120 @@ async fn test_dont_wait_for_slowest_blobstore_in_background_mode(fb: FacebookIni
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn multiplexed_store_timeouts(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory()?);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(DelayBlobstore::new(Duration::from_secs(60)));
+    let store_timeouts = hashmap! { bid1 => Duration::from_millis(100) };
+    // Needing both writes means the put can't complete without the slow store
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone() as Arc<dyn BlobstorePutOps>),
+            (bid1, bs1.clone() as Arc<dyn BlobstorePutOps>),
+        ],
+        vec![],
+        nonzero!(2usize),
+        queue.clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    )
+    .with_store_timeouts(&store_timeouts, &store_timeouts);
+
+    // The slow store is a miss, not an error, when the healer queue has nothing for the key
+    let start = Instant::now();
+    assert!(bs.get(ctx, "absent").await?.is_none());
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // but if the key is queued the slow store may be the one holding it
+    let entry = BlobstoreSyncQueueEntry {
+        blobstore_key: "queued".to_string(),
+        blobstore_id: bid1,
+        multiplex_id: MultiplexId::new(1),
+        timestamp: DateTime::now(),
+        id: None,
+        operation_key: OperationKey::gen(),
+        blob_size: None,
+    };
+    queue.add(ctx, entry).await?;
+    let tunables = MononokeTunables::default();
+    tunables.update_bools(&hashmap! {
+        "multiplex_blobstore_get_do_queue_lookup".to_string() => true
+    });
+    let res = with_tunables_async(tunables, bs.get(ctx, "queued").boxed()).await;
+    assert!(res.is_err());
+
+    // and a failed write
+    let start = Instant::now();
+    let err = bs
+        .put(ctx, "key".to_owned(), make_value("value"))
+        .await
+        .expect_err("put to the slow store should time out");
+    assert!(start.elapsed() < Duration::from_secs(10));
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::StoreTimeout { blobstore_id, .. }) => assert_eq!(*blobstore_id, bid1),
+        _ => panic!("unexpected err {:?}", err),
+    }
+
+    // A presence check that times out leaves presence unknown, so nothing is written
+    let start = Instant::now();
+    let err = bs
+        .put_if_absent(ctx, "new".to_owned(), make_value("value"))
+        .await
+        .expect_err("the slow store's presence is unknown");
+    assert!(start.elapsed() < Duration::from_secs(10));
+    match err.downcast_ref::<ErrorKind>() {
+        Some(ErrorKind::SomeFailedOthersNone(errors)) => {
+            assert_eq!(errors.keys().collect::<Vec<_>>(), vec![&bid1])
+        }
+        _ => panic!("unexpected err {:?}", err),
+    }
+    assert!(bs0.get(ctx, "new").await?.is_none());
+
+    let metrics = bs.multiplex_metrics();
+    // One get each for the absent key and the put, and the queued key is read twice
+    assert_eq!(metrics.stores[&bid1].timeouts, 4);
+    assert_eq!(metrics.stores[&bid1].errors, 0);
+    assert_eq!(metrics.stores[&bid0].timeouts, 0);
+
+    // Scrub reports the slow store rather than repairing it
+    let scrub_handler = Arc::new(RecordingScrubHandler::default());
+    let scrub = ScrubBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone() as Arc<dyn BlobstorePutOps>),
+            (bid1, bs1.clone() as Arc<dyn BlobstorePutOps>),
+        ],
+        vec![],
+        nonzero!(1usize),
+        queue,
+        MononokeScubaSampleBuilder::with_discard(),
+        MononokeScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+        ScrubOptions {
+            scrub_action: ScrubAction::Repair,
+            ..ScrubOptions::default()
+        },
+        scrub_handler.clone() as Arc<dyn ScrubHandler>,
+    )
+    .with_store_timeouts(&store_timeouts, &store_timeouts);
+
+    bs0.put(ctx, "k0".to_owned(), make_value("v0")).await?;
+    let value = scrub.get(ctx, "k0").await?.map(|v| v.into_bytes());
+    assert_eq!(value, Some(make_value("v0")));
+    assert_eq!(
+        scrub_handler.timed_out.with(|timed_out| timed_out.clone()),
+        vec![(bid1, "k0".to_owned())]
+    );
+    assert!(scrub_handler.repaired.with(|repaired| repaired.is_empty()));
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use governor::{
+    clock::DefaultClock,
+    state::{direct::NotKeyed, InMemoryState},
+    Jitter, Quota, RateLimiter,
+};
+use metaconfig_types::BlobstoreId;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+static JITTER_MAX: Duration = Duration::from_millis(5);
+
+/// Per inner store put rate limits for a multiplex. Stores without a limit are
+/// never throttled.
+#[derive(Default)]
+pub(crate) struct WriteLimiters {
+    limiters: HashMap<BlobstoreId, RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
+}
+
+impl WriteLimiters {
+    /// `write_limits` is the number of puts per second allowed for each store
+    pub(crate) fn new(write_limits: &HashMap<BlobstoreId, NonZeroU32>) -> Self {
+        Self {
+            limiters: write_limits
+                .iter()
+                .map(|(id, puts_s)| (*id, RateLimiter::direct(Quota::per_second(*puts_s))))
+                .collect(),
+        }
+    }
+
+    /// Wait until the store has budget for another put
+    pub(crate) async fn until_ready(&self, blobstore_id: BlobstoreId) {
+        if let Some(limiter) = self.limiters.get(&blobstore_id) {
+            limiter
+                .until_ready_with_jitter(Jitter::up_to(JITTER_MAX))
+                .await;
+        }
+    }
+}
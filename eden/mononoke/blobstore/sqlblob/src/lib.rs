@@ -399,6 +399,50 @@ impl Sqlblob {
             bail!("key does not exist");
         }
     }
+
+    /// Write several keys, overwriting any present. Values small enough to be stored
+    /// inline are written with one query per shard, larger ones are put a key at a time.
+    pub async fn put_batch(
+        &self,
+        ctx: &CoreContext,
+        values: Vec<(String, BlobstoreBytes)>,
+    ) -> Result<()> {
+        if let Some((key, _)) = values
+            .iter()
+            .find(|(key, _)| key.as_bytes().len() > MAX_KEY_SIZE)
+        {
+            bail!("Key {} exceeded max key size {}", key, MAX_KEY_SIZE);
+        }
+
+        let (inline, chunked): (Vec<_>, Vec<_>) = values.into_iter().partition(|(_, value)| {
+            self.allow_inline_put && (value.len() as u64) <= MAX_INLINE_LEN
+        });
+
+        let ctime = current_ctime()?;
+        let rows: Vec<_> = inline
+            .into_iter()
+            .map(|(key, value)| {
+                let chunk_id =
+                    base64::encode_config(value.as_bytes().as_ref(), base64::STANDARD_NO_PAD);
+                (key, ctime, chunk_id, 0, ChunkingMethod::InlineBase64)
+            })
+            .collect();
+        self.data_store.put_many(&rows).await?;
+
+        let chunked_puts: FuturesUnordered<_> = chunked
+            .into_iter()
+            .map(|(key, value)| self.put_explicit(ctx, key, value, PutBehaviour::Overwrite))
+            .collect();
+        chunked_puts.try_for_each(|_| async { Ok(()) }).await
+    }
+}
+
+fn current_ctime() -> Result<i64> {
+    let ctime = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(offset) => offset.as_secs().try_into(),
+        Err(negative) => negative.duration().as_secs().try_into().map(|v: i64| -v),
+    }?;
+    Ok(ctime)
 }
 
 impl fmt::Debug for Sqlblob {
@@ -505,12 +549,7 @@ impl BlobstorePutOps for Sqlblob {
         };
 
         let put_fut = async {
-            let ctime = {
-                match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-                    Ok(offset) => offset.as_secs().try_into(),
-                    Err(negative) => negative.duration().as_secs().try_into().map(|v: i64| -v),
-                }
-            }?;
+            let ctime = current_ctime()?;
             let (chunk_key, chunk_count, chunk_gen_insert_shard_id) = match chunking_method {
                 ChunkingMethod::ByContentHashBlake2 => {
                     let chunk_key = {
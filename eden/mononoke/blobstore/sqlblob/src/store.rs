@@ -11,7 +11,7 @@ use anyhow::{bail, format_err, Error};
 use bytes::BytesMut;
 use cached_config::ConfigHandle;
 use futures::{
-    future::TryFutureExt,
+    future::{self, TryFutureExt},
     stream::{self, Stream},
 };
 use sql::{queries, Connection};
@@ -99,6 +99,17 @@ queries! {
         ) VALUES {values}"
     }
 
+    write ReplaceData(values: (id: &str, ctime: i64, chunk_id: &str, chunk_count: u32, chunking_method: ChunkingMethod)) {
+        none,
+        "REPLACE INTO data (
+            id
+            , creation_time
+            , chunk_id
+            , chunk_count
+            , chunking_method
+        ) VALUES {values}"
+    }
+
     write DeleteData(id: &str) {
         none,
         "DELETE FROM data WHERE id = {id}"
@@ -288,6 +299,36 @@ impl DataSqlStore {
         Ok(())
     }
 
+    // Write several rows, overwriting any already present, with one query per shard
+    pub(crate) async fn put_many(
+        &self,
+        rows: &[(String, i64, String, u32, ChunkingMethod)],
+    ) -> Result<(), Error> {
+        let mut by_shard: HashMap<usize, Vec<_>> = HashMap::new();
+        for (key, ctime, chunk_id, chunk_count, chunking_method) in rows {
+            by_shard.entry(self.shard(key)).or_default().push((
+                key.as_str(),
+                *ctime,
+                chunk_id.as_str(),
+                *chunk_count,
+                *chunking_method,
+            ));
+        }
+
+        let puts = by_shard.into_iter().map(|(shard_id, rows)| async move {
+            self.delay.delay(shard_id).await;
+            let rows: Vec<_> = rows
+                .iter()
+                .map(|(key, ctime, chunk_id, chunk_count, chunking_method)| {
+                    (key, ctime, chunk_id, chunk_count, chunking_method)
+                })
+                .collect();
+            ReplaceData::query(&self.write_connection[shard_id], &rows[..]).await
+        });
+        future::try_join_all(puts).await?;
+        Ok(())
+    }
+
     pub(crate) async fn unlink(&self, key: &str) -> Result<(), Error> {
         let shard_id = self.shard(key);
 
@@ -162,6 +162,44 @@ async fn overwrite(fb: FacebookInit) -> Result<(), Error> {
     .await
 }
 
+#[fbinit::test]
+async fn put_batch(fb: FacebookInit) -> Result<(), Error> {
+    test_chunking_methods(fb, DEFAULT_PUT_BEHAVIOUR, |ctx, bs, _| async move {
+        borrowed!(ctx);
+        // Sizes either side of the inline limit, so both write paths are covered
+        let values: Vec<_> = [1, 32, MAX_INLINE_LEN, 255, 512]
+            .iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let mut bytes = vec![0u8; *size as usize];
+                thread_rng().fill_bytes(&mut bytes);
+                (
+                    format!("put_batch_test_{}", i),
+                    BlobstoreBytes::from_bytes(Bytes::from(bytes)),
+                )
+            })
+            .collect();
+
+        // A key already present is overwritten
+        let (first_key, _) = &values[0];
+        bs.put(ctx, first_key.clone(), BlobstoreBytes::from_bytes("old"))
+            .await?;
+
+        bs.as_inner().put_batch(ctx, values.clone()).await?;
+
+        for (key, value) in values {
+            assert_eq!(
+                bs.get(ctx, &key).await?.map(|get| get.into_bytes()),
+                Some(value),
+                "{}",
+                key,
+            );
+        }
+        Ok(())
+    })
+    .await
+}
+
 #[fbinit::test]
 async fn dedup(fb: FacebookInit) -> Result<(), Error> {
     test_chunking_methods(fb, DEFAULT_PUT_BEHAVIOUR, |ctx, bs, _| async move {
@@ -6,6 +6,7 @@
  */
 
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Error, Result};
@@ -20,7 +21,7 @@ use cross_repo_sync::CHANGE_XREPO_MAPPING_EXTRA;
 use derived_data::BonsaiDerived;
 use futures::compat::Stream01CompatExt;
 use futures::future::{self, try_join};
-use futures::stream::{self, StreamExt, TryStreamExt};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
 use futures_ext::FbStreamExt;
 use hooks::{CrossRepoPushSource, HookManager};
 use metaconfig_types::{BookmarkAttrs, InfinitepushParams, PushrebaseParams};
@@ -31,9 +32,9 @@ use scribe_commit_queue::{self, ChangedFilesInfo, LogToScribe};
 use skeleton_manifest::RootSkeletonManifestId;
 use tunables::tunables;
 
-use crate::hook_running::run_hooks;
+use crate::hook_running::run_hooks_with_concurrency;
 use crate::restrictions::{BookmarkKind, BookmarkMoveAuthorization};
-use crate::BookmarkMovementError;
+use crate::{BookmarkMovementError, ServiceWriteDenial};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(crate) enum AdditionalChangesets {
@@ -45,6 +46,18 @@ pub(crate) enum AdditionalChangesets {
     },
 }
 
+/// How many changesets a bookmark move would run hooks on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AffectedChangesetsCount {
+    /// Number of affected changesets, up to the hooks additional changesets
+    /// limit on top of any new changesets.
+    pub count: usize,
+    /// Counting stopped at the limit, so there are more than `count`.
+    pub limit_reached: bool,
+    /// Some of the affected changesets, new changesets first.
+    pub sample: Vec<ChangesetId>,
+}
+
 pub(crate) struct AffectedChangesets {
     /// Changesets that are being added to the repository and to this bookmark.
     new_changesets: HashMap<ChangesetId, BonsaiChangeset>,
@@ -56,6 +69,74 @@ pub(crate) struct AffectedChangesets {
     additional_changesets: Option<HashSet<BonsaiChangeset>>,
 }
 
+fn additional_changesets_limit() -> usize {
+    match tunables().get_hooks_additional_changesets_limit() {
+        limit if limit > 0 => limit as usize,
+        _ => std::usize::MAX,
+    }
+}
+
+/// Whether hooks are run at all for a bookmark move. This matches the
+/// checks in `AffectedChangesets::check_hooks`.
+pub(crate) fn hooks_would_run(
+    hook_manager: &HookManager,
+    bookmark: &BookmarkName,
+    reason: BookmarkUpdateReason,
+    kind: BookmarkKind,
+    auth: &BookmarkMoveAuthorization<'_>,
+) -> bool {
+    auth == &BookmarkMoveAuthorization::User
+        && kind == BookmarkKind::Public
+        && !(reason == BookmarkUpdateReason::Push && tunables().get_disable_hooks_on_plain_push())
+        && hook_manager.hooks_exist_for_bookmark(bookmark)
+}
+
+/// Stream the changesets in the additional changeset range: ancestors of
+/// `head` but not of `base` or any of the `hooks_skip_ancestors_of`
+/// bookmarks for the named bookmark. None if there is no range.
+async fn additional_changesets_range(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+    bookmark_attrs: &BookmarkAttrs,
+    bookmark: &BookmarkName,
+    additional_changesets: AdditionalChangesets,
+) -> Result<Option<BoxStream<'static, Result<ChangesetId, Error>>>, Error> {
+    let (head, base) = match additional_changesets {
+        AdditionalChangesets::None => return Ok(None),
+        AdditionalChangesets::Ancestors(head) => (head, None),
+        AdditionalChangesets::Range { head, base } => (head, Some(base)),
+    };
+
+    let mut exclude_bookmarks: HashSet<_> = bookmark_attrs
+        .select(bookmark)
+        .map(|attr| attr.params().hooks_skip_ancestors_of.iter())
+        .flatten()
+        .cloned()
+        .collect();
+    exclude_bookmarks.remove(bookmark);
+
+    let mut excludes: HashSet<_> = stream::iter(exclude_bookmarks)
+        .map(|bookmark| repo.bookmarks().get(ctx.clone(), &bookmark))
+        .buffered(100)
+        .try_filter_map(|maybe_cs_id| async move { Ok(maybe_cs_id) })
+        .try_collect()
+        .await?;
+    excludes.extend(base);
+
+    let range = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+        ctx.clone(),
+        &repo.get_changeset_fetcher(),
+        lca_hint.clone(),
+        vec![head],
+        excludes.into_iter().collect(),
+    )
+    .compat()
+    .yield_periodically()
+    .boxed();
+    Ok(Some(range))
+}
+
 impl AffectedChangesets {
     pub(crate) fn new() -> Self {
         Self {
@@ -116,49 +197,27 @@ impl AffectedChangesets {
             return Ok(());
         }
 
-        let (head, base) = match additional_changesets {
-            AdditionalChangesets::None => {
+        let range = match additional_changesets_range(
+            ctx,
+            repo,
+            lca_hint,
+            bookmark_attrs,
+            bookmark,
+            additional_changesets,
+        )
+        .await?
+        {
+            Some(range) => range.try_filter(|bcs_id| {
+                let exists = self.new_changesets.contains_key(bcs_id);
+                future::ready(!exists)
+            }),
+            None => {
                 self.additional_changesets = Some(HashSet::new());
                 return Ok(());
             }
-            AdditionalChangesets::Ancestors(head) => (head, None),
-            AdditionalChangesets::Range { head, base } => (head, Some(base)),
         };
 
-        let mut exclude_bookmarks: HashSet<_> = bookmark_attrs
-            .select(bookmark)
-            .map(|attr| attr.params().hooks_skip_ancestors_of.iter())
-            .flatten()
-            .cloned()
-            .collect();
-        exclude_bookmarks.remove(bookmark);
-
-        let mut excludes: HashSet<_> = stream::iter(exclude_bookmarks)
-            .map(|bookmark| repo.bookmarks().get(ctx.clone(), &bookmark))
-            .buffered(100)
-            .try_filter_map(|maybe_cs_id| async move { Ok(maybe_cs_id) })
-            .try_collect()
-            .await?;
-        excludes.extend(base);
-
-        let range = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
-            ctx.clone(),
-            &repo.get_changeset_fetcher(),
-            lca_hint.clone(),
-            vec![head],
-            excludes.into_iter().collect(),
-        )
-        .compat()
-        .yield_periodically()
-        .try_filter(|bcs_id| {
-            let exists = self.new_changesets.contains_key(bcs_id);
-            future::ready(!exists)
-        });
-
-        let limit = match tunables().get_hooks_additional_changesets_limit() {
-            limit if limit > 0 => limit as usize,
-            _ => std::usize::MAX,
-        };
+        let limit = additional_changesets_limit();
 
         let additional_changesets = if tunables().get_run_hooks_on_additional_changesets() {
             let bonsais = range
@@ -213,6 +272,88 @@ impl AffectedChangesets {
         Ok(())
     }
 
+    /// Count the changesets that hooks would run on for a bookmark move,
+    /// without loading them or running anything. Additional changesets are
+    /// only counted if hooks are run on them, and counting stops once there
+    /// are more than the hooks additional changesets limit.
+    pub(crate) async fn count_affected_changesets(
+        &self,
+        ctx: &CoreContext,
+        repo: &BlobRepo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        bookmark_attrs: &BookmarkAttrs,
+        bookmark: &BookmarkName,
+        additional_changesets: AdditionalChangesets,
+        sample_size: usize,
+    ) -> Result<AffectedChangesetsCount, Error> {
+        let mut new_changesets: Vec<_> = self
+            .new_changesets
+            .keys()
+            .copied()
+            .chain(
+                self.source_changesets
+                    .iter()
+                    .map(|bcs| bcs.get_changeset_id()),
+            )
+            .collect();
+        new_changesets.sort();
+        let mut count = new_changesets.len();
+        let mut sample: Vec<_> = new_changesets.into_iter().take(sample_size).collect();
+
+        if !tunables().get_run_hooks_on_additional_changesets() {
+            return Ok(AffectedChangesetsCount {
+                count,
+                limit_reached: false,
+                sample,
+            });
+        }
+
+        let limit = additional_changesets_limit();
+        let mut limit_reached = false;
+        if let Some(range) = additional_changesets_range(
+            ctx,
+            repo,
+            lca_hint,
+            bookmark_attrs,
+            bookmark,
+            additional_changesets,
+        )
+        .await?
+        {
+            let range = range.try_filter(|bcs_id| {
+                let exists = self.new_changesets.contains_key(bcs_id);
+                future::ready(!exists)
+            });
+            // Hooks fail once there are more than `limit` additional
+            // changesets, so look at one more to tell if that would happen.
+            let (additional_count, additional_sample) = range
+                .take(limit.saturating_add(1))
+                .try_fold(
+                    (0usize, Vec::new()),
+                    |(count, mut sample), bcs_id| async move {
+                        if count < limit && sample.len() < sample_size {
+                            sample.push(bcs_id);
+                        }
+                        Ok((count + 1, sample))
+                    },
+                )
+                .await?;
+            limit_reached = additional_count > limit;
+            count += additional_count.min(limit);
+            sample.extend(
+                additional_sample
+                    .into_iter()
+                    .take(sample_size.saturating_sub(sample.len())),
+            );
+        }
+
+        Ok(AffectedChangesetsCount {
+            count,
+            limit_reached,
+            sample,
+        })
+    }
+
     fn is_empty(&self) -> bool {
         self.new_changesets.is_empty()
             && self.source_changesets.is_empty()
@@ -245,6 +386,7 @@ impl AffectedChangesets {
         auth: &BookmarkMoveAuthorization<'_>,
         additional_changesets: AdditionalChangesets,
         cross_repo_push_source: CrossRepoPushSource,
+        hook_concurrency: Option<NonZeroUsize>,
     ) -> Result<(), BookmarkMovementError> {
         self.check_extras(
             ctx,
@@ -283,6 +425,7 @@ impl AffectedChangesets {
             auth,
             additional_changesets,
             cross_repo_push_source,
+            hook_concurrency,
         )
         .await?;
 
@@ -430,6 +573,7 @@ impl AffectedChangesets {
         auth: &BookmarkMoveAuthorization<'_>,
         additional_changesets: AdditionalChangesets,
         cross_repo_push_source: CrossRepoPushSource,
+        hook_concurrency: Option<NonZeroUsize>,
     ) -> Result<(), BookmarkMovementError> {
         if auth == &BookmarkMoveAuthorization::User && kind == BookmarkKind::Public {
             if reason == BookmarkUpdateReason::Push && tunables().get_disable_hooks_on_plain_push()
@@ -480,13 +624,14 @@ impl AffectedChangesets {
                 }
 
                 if !self.is_empty() {
-                    run_hooks(
+                    run_hooks_with_concurrency(
                         ctx,
                         hook_manager,
                         bookmark,
                         self.iter(),
                         pushvars,
                         cross_repo_push_source,
+                        hook_concurrency,
                     )
                     .await?;
                 }
@@ -528,6 +673,7 @@ impl AffectedChangesets {
                     return Err(BookmarkMovementError::PermissionDeniedServicePath {
                         service_name: service_name.clone(),
                         path: path.clone(),
+                        reason: ServiceWriteDenial::PathNotPermitted,
                     });
                 }
             }
@@ -0,0 +1,36 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks_types::BookmarkName;
+use context::CoreContext;
+
+use crate::restrictions::BookmarkMoveAuthorization;
+use crate::update::BookmarkUpdateTargets;
+
+/// A bookmark move that has been committed.
+pub struct BookmarkMoveAuditEvent<'a> {
+    pub bookmark: &'a BookmarkName,
+    /// Who moved the bookmark. For user moves the user's identity is in the
+    /// core context passed alongside the event.
+    pub auth: &'a BookmarkMoveAuthorization<'a>,
+    pub targets: &'a BookmarkUpdateTargets,
+    pub reason: BookmarkUpdateReason,
+    /// Whether the new target is a descendant of (or the same as) the old one
+    pub fast_forward: bool,
+}
+
+/// Receives an event for every bookmark move once its transaction has
+/// committed, so deployments can keep an audit trail in their preferred sink.
+#[async_trait]
+pub trait BookmarkMoveAuditor: Send + Sync {
+    /// Record the move. The bookmark has already moved, so errors are logged
+    /// but do not fail the move.
+    async fn record(&self, ctx: &CoreContext, event: &BookmarkMoveAuditEvent<'_>) -> Result<()>;
+}
@@ -147,6 +147,7 @@ impl<'op> CreateBookmarkOp<'op> {
                 &self.auth,
                 AdditionalChangesets::Ancestors(self.target),
                 self.cross_repo_push_source,
+                None,
             )
             .await?;
 
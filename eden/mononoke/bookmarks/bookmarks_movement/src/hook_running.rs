@@ -6,13 +6,16 @@
  */
 
 use std::collections::HashMap;
+use std::iter;
+use std::num::NonZeroUsize;
 
 use anyhow::{anyhow, Context, Result};
 use bookmarks_types::BookmarkName;
 use bytes::Bytes;
 use context::CoreContext;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use futures_stats::TimedFutureExt;
-use hooks::{CrossRepoPushSource, HookManager, HookOutcome};
+use hooks::{CrossRepoPushSource, HookManager, HookOutcome, HookRejection};
 use mononoke_types::BonsaiChangeset;
 use tunables::tunables;
 
@@ -58,6 +61,44 @@ fn take_n_changeset_ids<'a>(
         .collect()
 }
 
+/// Run hooks on up to `concurrency` changesets at once, in changeset id order so
+/// that the reported rejections are stable. Stops at the first changeset that is
+/// rejected, returning only its rejections.
+async fn run_hooks_bounded<'a>(
+    ctx: &CoreContext,
+    hook_manager: &HookManager,
+    bookmark: &BookmarkName,
+    changesets: impl Iterator<Item = &'a BonsaiChangeset>,
+    pushvars: Option<&HashMap<String, Bytes>>,
+    cross_repo_push_source: CrossRepoPushSource,
+    concurrency: NonZeroUsize,
+) -> Result<Vec<HookRejection>> {
+    let mut changesets: Vec<_> = changesets.collect();
+    changesets.sort_by_key(|bcs| bcs.get_changeset_id());
+
+    let mut outcomes = stream::iter(changesets)
+        .map(|bcs| {
+            hook_manager.run_hooks_for_bookmark(
+                ctx,
+                iter::once(bcs),
+                bookmark,
+                pushvars,
+                cross_repo_push_source,
+            )
+        })
+        .buffered(concurrency.get());
+    while let Some(outcomes) = outcomes.try_next().await? {
+        let rejections: Vec<_> = outcomes
+            .into_iter()
+            .filter_map(HookOutcome::into_rejection)
+            .collect();
+        if !rejections.is_empty() {
+            return Ok(rejections);
+        }
+    }
+    Ok(Vec::new())
+}
+
 pub async fn run_hooks(
     ctx: &CoreContext,
     hook_manager: &HookManager,
@@ -65,6 +106,29 @@ pub async fn run_hooks(
     changesets: impl Iterator<Item = &BonsaiChangeset> + Clone,
     pushvars: Option<&HashMap<String, Bytes>>,
     cross_repo_push_source: CrossRepoPushSource,
+) -> Result<(), BookmarkMovementError> {
+    run_hooks_with_concurrency(
+        ctx,
+        hook_manager,
+        bookmark,
+        changesets,
+        pushvars,
+        cross_repo_push_source,
+        None,
+    )
+    .await
+}
+
+/// As `run_hooks`, but if `concurrency` is set, changesets are checked that many at
+/// a time and the first rejected changeset stops the rest from being checked.
+pub(crate) async fn run_hooks_with_concurrency(
+    ctx: &CoreContext,
+    hook_manager: &HookManager,
+    bookmark: &BookmarkName,
+    changesets: impl Iterator<Item = &BonsaiChangeset> + Clone,
+    pushvars: Option<&HashMap<String, Bytes>>,
+    cross_repo_push_source: CrossRepoPushSource,
+    concurrency: Option<NonZeroUsize>,
 ) -> Result<(), BookmarkMovementError> {
     if cross_repo_push_source == CrossRepoPushSource::PushRedirected {
         if tunables().get_disable_running_hooks_in_pushredirected_repo() {
@@ -103,16 +167,40 @@ pub async fn run_hooks(
         return Ok(());
     }
 
-    let (stats, outcomes) = hook_manager
-        .run_hooks_for_bookmark(&ctx, changesets, bookmark, pushvars, cross_repo_push_source)
-        .timed()
-        .await;
-    let outcomes = outcomes.with_context(|| format!("Failed to run hooks for {}", bookmark))?;
-
-    let rejections: Vec<_> = outcomes
-        .into_iter()
-        .filter_map(HookOutcome::into_rejection)
-        .collect();
+    let (stats, rejections) = async {
+        match concurrency {
+            Some(concurrency) => {
+                run_hooks_bounded(
+                    ctx,
+                    hook_manager,
+                    bookmark,
+                    changesets,
+                    pushvars,
+                    cross_repo_push_source,
+                    concurrency,
+                )
+                .await
+            }
+            None => hook_manager
+                .run_hooks_for_bookmark(
+                    &ctx,
+                    changesets,
+                    bookmark,
+                    pushvars,
+                    cross_repo_push_source,
+                )
+                .await
+                .map(|outcomes| {
+                    outcomes
+                        .into_iter()
+                        .filter_map(HookOutcome::into_rejection)
+                        .collect()
+                }),
+        }
+    }
+    .timed()
+    .await;
+    let rejections = rejections.with_context(|| format!("Failed to run hooks for {}", bookmark))?;
 
     ctx.scuba()
         .clone()
@@ -7,6 +7,8 @@
 
 #![deny(warnings)]
 
+use std::fmt;
+
 use bookmarks_types::BookmarkName;
 use itertools::Itertools;
 use mononoke_types::{ChangesetId, MPath};
@@ -14,6 +16,7 @@ use pushrebase::PushrebaseError;
 use thiserror::Error;
 
 mod affected_changesets;
+mod audit;
 mod create;
 mod delete;
 #[cfg(fbcode_build)]
@@ -28,11 +31,13 @@ mod update;
 pub use hooks::{CrossRepoPushSource, HookRejection};
 pub use pushrebase::PushrebaseOutcome;
 
-pub use crate::affected_changesets::log_commits_to_scribe;
+pub use crate::affected_changesets::{log_commits_to_scribe, AffectedChangesetsCount};
+pub use crate::audit::{BookmarkMoveAuditEvent, BookmarkMoveAuditor};
 pub use crate::create::CreateBookmarkOp;
 pub use crate::delete::DeleteBookmarkOp;
 pub use crate::hook_running::run_hooks;
 pub use crate::pushrebase_onto::{get_pushrebase_hooks, PushrebaseOntoBookmarkOp};
+pub use crate::restrictions::BookmarkMoveAuthorization;
 pub use crate::update::{BookmarkUpdatePolicy, BookmarkUpdateTargets, UpdateBookmarkOp};
 
 /// An error encountered during an attempt to move a bookmark.
@@ -50,14 +55,19 @@ pub enum BookmarkMovementError {
         bookmark: BookmarkName,
     },
 
-    #[error("Service '{service_name}' is not permitted to move '{bookmark}'")]
+    #[error("Service '{service_name}' is not permitted to move '{bookmark}' ({reason})")]
     PermissionDeniedServiceBookmark {
         service_name: String,
         bookmark: BookmarkName,
+        reason: ServiceWriteDenial,
     },
 
-    #[error("Service '{service_name}' is not permitted to modify path '{path}'")]
-    PermissionDeniedServicePath { service_name: String, path: MPath },
+    #[error("Service '{service_name}' is not permitted to modify path '{path}' ({reason})")]
+    PermissionDeniedServicePath {
+        service_name: String,
+        path: MPath,
+        reason: ServiceWriteDenial,
+    },
 
     #[error(
         "Invalid scratch bookmark: {bookmark} (scratch bookmarks must match pattern {pattern})"
@@ -80,9 +90,26 @@ pub enum BookmarkMovementError {
     )]
     ScratchBookmarksDisabled { bookmark: BookmarkName },
 
+    #[error("Scratch bookmark '{bookmark}' cannot be updated with bundle replay data")]
+    ScratchBookmarkBundleReplay { bookmark: BookmarkName },
+
+    #[error(
+        "Scratch bookmark '{bookmark}' cannot be moved to {changeset_id}, which needs a git mapping entry"
+    )]
+    ScratchBookmarkGitMapping {
+        bookmark: BookmarkName,
+        changeset_id: ChangesetId,
+    },
+
     #[error("Bookmark transaction failed")]
     TransactionFailed,
 
+    #[error("Bookmark move was cancelled before it was committed")]
+    Cancelled,
+
+    #[error("Hook configuration changed from version {expected} to {current}, retry the move")]
+    HookConfigChanged { expected: u64, current: u64 },
+
     #[error("Hooks failed:\n{}", describe_hook_rejections(.0.as_slice()))]
     HookFailure(Vec<HookRejection>),
 
@@ -129,6 +156,76 @@ pub enum BookmarkMovementError {
     Error(#[from] anyhow::Error),
 }
 
+/// The service write restriction that denied a service-initiated bookmark
+/// move.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ServiceWriteDenial {
+    /// There are no write restrictions configured for the service, so it
+    /// may not write anything.
+    ServiceNotConfigured,
+
+    /// The bookmark is not one of the service's permitted bookmarks, and
+    /// does not match its permitted bookmark regex (if any).
+    BookmarkNotPermitted {
+        permitted_bookmark_regex: Option<String>,
+    },
+
+    /// A modified path is not covered by the service's permitted path
+    /// prefixes.
+    PathNotPermitted,
+}
+
+impl fmt::Display for ServiceWriteDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServiceNotConfigured => {
+                write!(f, "no write restrictions are configured for the service")
+            }
+            Self::BookmarkNotPermitted {
+                permitted_bookmark_regex: Some(regex),
+            } => write!(
+                f,
+                "bookmark is not a permitted bookmark and does not match pattern {}",
+                regex
+            ),
+            Self::BookmarkNotPermitted {
+                permitted_bookmark_regex: None,
+            } => write!(f, "bookmark is not a permitted bookmark"),
+            Self::PathNotPermitted => {
+                write!(f, "path is not covered by the permitted path prefixes")
+            }
+        }
+    }
+}
+
+impl BookmarkMovementError {
+    /// The changesets rejected by hooks, in the order they were first
+    /// rejected.  Empty if this is not a hook failure.
+    pub fn rejected_changesets(&self) -> Vec<ChangesetId> {
+        match self {
+            Self::HookFailure(rejections) => rejections
+                .iter()
+                .map(|rejection| rejection.cs_id)
+                .unique()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The names of the hooks that rejected changesets, in the order they
+    /// first rejected one.  Empty if this is not a hook failure.
+    pub fn rejecting_hooks(&self) -> Vec<&str> {
+        match self {
+            Self::HookFailure(rejections) => rejections
+                .iter()
+                .map(|rejection| rejection.hook_name.as_str())
+                .unique()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
 pub fn describe_hook_rejections(rejections: &[HookRejection]) -> String {
     rejections
         .iter()
@@ -140,3 +237,61 @@ pub fn describe_hook_rejections(rejections: &[HookRejection]) -> String {
         })
         .join("\n")
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hooks::HookRejectionInfo;
+    use mononoke_types_mocks::changesetid::{ONES_CSID, TWOS_CSID};
+
+    fn rejection(hook_name: &str, cs_id: ChangesetId) -> HookRejection {
+        HookRejection {
+            hook_name: hook_name.to_string(),
+            cs_id,
+            reason: HookRejectionInfo::new("rejected"),
+        }
+    }
+
+    #[test]
+    fn test_hook_failure_details() {
+        let error = BookmarkMovementError::HookFailure(vec![
+            rejection("block_commit_message", ONES_CSID),
+            rejection("limit_filesize", ONES_CSID),
+            rejection("block_commit_message", TWOS_CSID),
+        ]);
+        assert_eq!(error.rejected_changesets(), vec![ONES_CSID, TWOS_CSID]);
+        assert_eq!(
+            error.rejecting_hooks(),
+            vec!["block_commit_message", "limit_filesize"]
+        );
+
+        let error = BookmarkMovementError::TransactionFailed;
+        assert!(error.rejected_changesets().is_empty());
+        assert!(error.rejecting_hooks().is_empty());
+    }
+
+    #[test]
+    fn test_service_write_denial_message() {
+        let error = BookmarkMovementError::PermissionDeniedServiceBookmark {
+            service_name: "svc".to_string(),
+            bookmark: BookmarkName::new("main").unwrap(),
+            reason: ServiceWriteDenial::BookmarkNotPermitted {
+                permitted_bookmark_regex: Some("^svc/.*$".to_string()),
+            },
+        };
+        assert_eq!(
+            error.to_string(),
+            "Service 'svc' is not permitted to move 'main' (bookmark is not a permitted bookmark and does not match pattern ^svc/.*$)"
+        );
+
+        let error = BookmarkMovementError::PermissionDeniedServiceBookmark {
+            service_name: "svc".to_string(),
+            bookmark: BookmarkName::new("main").unwrap(),
+            reason: ServiceWriteDenial::ServiceNotConfigured,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Service 'svc' is not permitted to move 'main' (no write restrictions are configured for the service)"
+        );
+    }
+}
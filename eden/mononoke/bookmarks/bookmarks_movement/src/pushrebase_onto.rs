@@ -144,6 +144,7 @@ impl<'op> PushrebaseOntoBookmarkOp<'op> {
                 &self.auth,
                 AdditionalChangesets::None,
                 self.cross_repo_push_source,
+                None,
             )
             .await?;
 
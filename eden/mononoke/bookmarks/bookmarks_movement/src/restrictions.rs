@@ -15,11 +15,11 @@ use metaconfig_types::{
 use mononoke_types::ChangesetId;
 use reachabilityindex::LeastCommonAncestorsHint;
 
-use crate::BookmarkMovementError;
+use crate::{BookmarkMovementError, ServiceWriteDenial};
 
 /// How authorization for the bookmark move should be determined.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub(crate) enum BookmarkMoveAuthorization<'params> {
+pub enum BookmarkMoveAuthorization<'params> {
     /// The bookmark move has been initiated by a user. The user's identity in
     /// the core context should be used to check permission, and hooks must be
     /// run.
@@ -59,10 +59,11 @@ impl<'params> BookmarkMoveAuthorization<'params> {
                 // TODO: Check using ctx.identities, and deny if neither are provided.
             }
             BookmarkMoveAuthorization::Service(service_name, scs_params) => {
-                if !scs_params.service_write_bookmark_permitted(service_name, bookmark) {
+                if let Some(reason) = service_bookmark_denial(scs_params, service_name, bookmark) {
                     return Err(BookmarkMovementError::PermissionDeniedServiceBookmark {
                         service_name: service_name.clone(),
                         bookmark: bookmark.clone(),
+                        reason,
                     });
                 }
             }
@@ -71,6 +72,27 @@ impl<'params> BookmarkMoveAuthorization<'params> {
     }
 }
 
+/// Determine which service write restriction, if any, prevents the named
+/// service from moving the bookmark.
+pub(crate) fn service_bookmark_denial(
+    scs_params: &SourceControlServiceParams,
+    service_name: &str,
+    bookmark: &BookmarkName,
+) -> Option<ServiceWriteDenial> {
+    if scs_params.service_write_bookmark_permitted(service_name, bookmark) {
+        return None;
+    }
+    match scs_params.service_write_restrictions.get(service_name) {
+        None => Some(ServiceWriteDenial::ServiceNotConfigured),
+        Some(restrictions) => Some(ServiceWriteDenial::BookmarkNotPermitted {
+            permitted_bookmark_regex: restrictions
+                .permitted_bookmark_regex
+                .as_ref()
+                .map(|regex| regex.as_str().to_string()),
+        }),
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) enum BookmarkKind {
     Scratch,
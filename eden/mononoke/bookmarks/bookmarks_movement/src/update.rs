@@ -6,10 +6,13 @@
  */
 
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use blobrepo::BlobRepo;
+use bonsai_git_mapping::extract_git_sha1_from_bonsai_extra;
 use bookmarks::{BookmarkUpdateReason, BundleReplay};
 use bookmarks_types::BookmarkName;
 use bytes::Bytes;
@@ -23,8 +26,10 @@ use reachabilityindex::LeastCommonAncestorsHint;
 use repo_read_write_status::RepoReadWriteFetcher;
 
 use crate::affected_changesets::{
-    find_draft_ancestors, log_bonsai_commits_to_scribe, AdditionalChangesets, AffectedChangesets,
+    find_draft_ancestors, hooks_would_run, log_bonsai_commits_to_scribe, AdditionalChangesets,
+    AffectedChangesets, AffectedChangesetsCount,
 };
+use crate::audit::{BookmarkMoveAuditEvent, BookmarkMoveAuditor};
 use crate::repo_lock::check_repo_lock;
 use crate::restrictions::{BookmarkKind, BookmarkKindRestrictions, BookmarkMoveAuthorization};
 use crate::BookmarkMovementError;
@@ -44,8 +49,17 @@ pub enum BookmarkUpdatePolicy {
     /// of the old target).
     FastForwardOnly,
 
+    /// Allow fast-forward moves, and moves back to an ancestor of the old
+    /// target.  Used to roll a bookmark back to a known-good commit.
+    AncestorOrFastForward,
+
     /// Allow any update that is permitted for the bookmark by repo config.
     AnyPermittedByConfig,
+
+    /// Allow non-fast-forward moves for authorized services.  Moves made
+    /// on behalf of a user are still fast-forward only, unless the bookmark
+    /// is exempted by repo config.
+    AllowNonFastForwardForAuthorized,
 }
 
 impl BookmarkUpdatePolicy {
@@ -57,17 +71,32 @@ impl BookmarkUpdatePolicy {
         bookmark_attrs: &BookmarkAttrs,
         bookmark: &BookmarkName,
         targets: &BookmarkUpdateTargets,
+        auth: &BookmarkMoveAuthorization<'_>,
     ) -> Result<(), BookmarkMovementError> {
-        let fast_forward_only = match self {
-            Self::FastForwardOnly => true,
-            Self::AnyPermittedByConfig => bookmark_attrs.is_fast_forward_only(&bookmark),
+        let fast_forward_only = match (self, auth) {
+            (Self::FastForwardOnly, _) | (Self::AncestorOrFastForward, _) => true,
+            (Self::AnyPermittedByConfig, _)
+            | (Self::AllowNonFastForwardForAuthorized, BookmarkMoveAuthorization::User) => {
+                bookmark_attrs.is_fast_forward_only(&bookmark)
+            }
+            // The service has already been checked as permitted to move this
+            // bookmark by `check_authorized`.
+            (Self::AllowNonFastForwardForAuthorized, BookmarkMoveAuthorization::Service(..)) => {
+                false
+            }
         };
         if fast_forward_only && targets.old != targets.new {
             // Check that this move is a fast-forward move.
             let is_ancestor = lca_hint
                 .is_ancestor(ctx, &repo.get_changeset_fetcher(), targets.old, targets.new)
                 .await?;
-            if !is_ancestor {
+            // Rollbacks must still move along the old target's history.
+            let is_rollback = !is_ancestor
+                && *self == Self::AncestorOrFastForward
+                && lca_hint
+                    .is_ancestor(ctx, &repo.get_changeset_fetcher(), targets.new, targets.old)
+                    .await?;
+            if !is_ancestor && !is_rollback {
                 return Err(BookmarkMovementError::NonFastForwardMove {
                     from: targets.old,
                     to: targets.new,
@@ -78,6 +107,34 @@ impl BookmarkUpdatePolicy {
     }
 }
 
+/// Scratch bookmark updates are not replayed and do not populate the git
+/// mapping, so reject updates that expect either of those to happen.
+fn check_scratch_update(
+    bookmark: &BookmarkName,
+    bundle_replay: Option<&dyn BundleReplay>,
+    pushrebase_params: &PushrebaseParams,
+    new_changesets: &HashMap<ChangesetId, BonsaiChangeset>,
+) -> Result<(), BookmarkMovementError> {
+    if bundle_replay.is_some() {
+        return Err(BookmarkMovementError::ScratchBookmarkBundleReplay {
+            bookmark: bookmark.clone(),
+        });
+    }
+    if pushrebase_params.populate_git_mapping {
+        for (cs_id, bcs) in new_changesets {
+            let git_sha1 = extract_git_sha1_from_bonsai_extra(bcs.extra())
+                .with_context(|| format!("Failed to extract Git Sha1 from {}", cs_id))?;
+            if git_sha1.is_some() {
+                return Err(BookmarkMovementError::ScratchBookmarkGitMapping {
+                    bookmark: bookmark.clone(),
+                    changeset_id: *cs_id,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 pub struct UpdateBookmarkOp<'op> {
     bookmark: &'op BookmarkName,
     targets: BookmarkUpdateTargets,
@@ -90,6 +147,10 @@ pub struct UpdateBookmarkOp<'op> {
     pushvars: Option<&'op HashMap<String, Bytes>>,
     bundle_replay: Option<&'op dyn BundleReplay>,
     log_new_public_commits_to_scribe: bool,
+    hook_concurrency: Option<NonZeroUsize>,
+    cancelled: Option<Arc<AtomicBool>>,
+    auditor: Option<&'op dyn BookmarkMoveAuditor>,
+    expected_hook_config_version: Option<u64>,
 }
 
 #[must_use = "UpdateBookmarkOp must be run to have an effect"]
@@ -112,6 +173,10 @@ impl<'op> UpdateBookmarkOp<'op> {
             pushvars: None,
             bundle_replay: None,
             log_new_public_commits_to_scribe: false,
+            hook_concurrency: None,
+            cancelled: None,
+            auditor: None,
+            expected_hook_config_version: None,
         }
     }
 
@@ -166,6 +231,114 @@ impl<'op> UpdateBookmarkOp<'op> {
         self
     }
 
+    /// Run hooks on at most this many affected changesets at a time, stopping
+    /// at the first rejected changeset. By default hooks run on all of them at
+    /// once and every rejection is reported.
+    pub fn with_hook_concurrency(mut self, hook_concurrency: NonZeroUsize) -> Self {
+        self.hook_concurrency = Some(hook_concurrency);
+        self
+    }
+
+    /// Abandon the update if `cancelled` is set, e.g. because the client
+    /// disconnected. The flag is checked between the permission checks, hook
+    /// runs and transaction setup, but never once the transaction commit has
+    /// started, so a cancelled update leaves the bookmark untouched.
+    pub fn with_cancellation(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    /// Record the move with `auditor` once the transaction has committed.
+    /// Nothing is recorded for no-op updates or if the transaction fails.
+    pub fn with_auditor(mut self, auditor: &'op dyn BookmarkMoveAuditor) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+
+    /// Only move the bookmark if the hook manager's configuration is still at
+    /// `version`, so that a move checked by a client against hook config that
+    /// has since been reconfigured fails with `HookConfigChanged` and can be
+    /// retried.
+    pub fn with_expected_hook_config_version(mut self, version: u64) -> Self {
+        self.expected_hook_config_version = Some(version);
+        self
+    }
+
+    fn check_hook_config_version(
+        &self,
+        hook_manager: &HookManager,
+    ) -> Result<(), BookmarkMovementError> {
+        match self.expected_hook_config_version {
+            Some(expected) if expected != hook_manager.config_version() => {
+                Err(BookmarkMovementError::HookConfigChanged {
+                    expected,
+                    current: hook_manager.config_version(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), BookmarkMovementError> {
+        match &self.cancelled {
+            Some(cancelled) if cancelled.load(Ordering::Relaxed) => {
+                Err(BookmarkMovementError::Cancelled)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether this update leaves the bookmark where it is and has no other
+    /// side effects, so there is no need to open a transaction for it.
+    fn is_noop(&self, pushrebase_params: &PushrebaseParams) -> bool {
+        self.targets.old == self.targets.new
+            && self.bundle_replay.is_none()
+            && self.affected_changesets.new_changesets().is_empty()
+            // Populating the git mapping may also backfill ancestors.
+            && !pushrebase_params.populate_git_mapping
+    }
+
+    /// Work out how many changesets this update would run hooks on, so
+    /// clients can warn before a large move. Only the bookmark kind is
+    /// checked, nothing is run, and the bookmark is not moved. If no hooks
+    /// would run, the count is zero. At most `sample_size` of the changesets
+    /// are returned.
+    pub async fn preflight(
+        &self,
+        ctx: &'op CoreContext,
+        repo: &'op BlobRepo,
+        lca_hint: &'op Arc<dyn LeastCommonAncestorsHint>,
+        infinitepush_params: &'op InfinitepushParams,
+        bookmark_attrs: &'op BookmarkAttrs,
+        hook_manager: &'op HookManager,
+        sample_size: usize,
+    ) -> Result<AffectedChangesetsCount, BookmarkMovementError> {
+        let kind = self
+            .kind_restrictions
+            .check_kind(infinitepush_params, self.bookmark)?;
+        if !hooks_would_run(hook_manager, self.bookmark, self.reason, kind, &self.auth) {
+            return Ok(AffectedChangesetsCount::default());
+        }
+
+        let count = self
+            .affected_changesets
+            .count_affected_changesets(
+                ctx,
+                repo,
+                lca_hint,
+                bookmark_attrs,
+                self.bookmark,
+                AdditionalChangesets::Range {
+                    head: self.targets.new,
+                    base: self.targets.old,
+                },
+                sample_size,
+            )
+            .await
+            .context("Failed to count affected changesets")?;
+        Ok(count)
+    }
+
     pub async fn run(
         mut self,
         ctx: &'op CoreContext,
@@ -177,6 +350,8 @@ impl<'op> UpdateBookmarkOp<'op> {
         hook_manager: &'op HookManager,
         repo_read_write_fetcher: &'op RepoReadWriteFetcher,
     ) -> Result<(), BookmarkMovementError> {
+        self.check_hook_config_version(hook_manager)?;
+
         let kind = self
             .kind_restrictions
             .check_kind(infinitepush_params, self.bookmark)?;
@@ -184,6 +359,22 @@ impl<'op> UpdateBookmarkOp<'op> {
         self.auth
             .check_authorized(ctx, bookmark_attrs, self.bookmark)
             .await?;
+        self.check_cancelled()?;
+
+        if self.is_noop(pushrebase_params) {
+            check_repo_lock(repo_read_write_fetcher, kind, self.pushvars).await?;
+            // The transaction would have failed if the bookmark isn't where
+            // the caller expects, so skipping it must fail the same way.
+            let current = repo.get_bonsai_bookmark(ctx.clone(), self.bookmark).await?;
+            if current != Some(self.targets.old) {
+                return Err(BookmarkMovementError::TransactionFailed);
+            }
+            ctx.scuba()
+                .clone()
+                .add("bookmark", self.bookmark.to_string())
+                .log_with_msg("Skipping no-op bookmark update", None);
+            return Ok(());
+        }
 
         self.update_policy
             .check_update_permitted(
@@ -193,8 +384,10 @@ impl<'op> UpdateBookmarkOp<'op> {
                 bookmark_attrs,
                 &self.bookmark,
                 &self.targets,
+                &self.auth,
             )
             .await?;
+        self.check_cancelled()?;
 
         self.affected_changesets
             .check_restrictions(
@@ -214,16 +407,41 @@ impl<'op> UpdateBookmarkOp<'op> {
                     base: self.targets.old,
                 },
                 self.cross_repo_push_source,
+                self.hook_concurrency,
             )
             .await?;
+        self.check_cancelled()?;
 
         check_repo_lock(repo_read_write_fetcher, kind, self.pushvars).await?;
+        self.check_cancelled()?;
+
+        // Only worked out when auditing, the update policy may not need it.
+        let fast_forward = match self.auditor {
+            Some(_) if self.targets.old != self.targets.new => {
+                lca_hint
+                    .is_ancestor(
+                        ctx,
+                        &repo.get_changeset_fetcher(),
+                        self.targets.old,
+                        self.targets.new,
+                    )
+                    .await?
+            }
+            _ => true,
+        };
 
         let mut txn = repo.update_bookmark_transaction(ctx.clone());
         let txn_hook;
 
         let commits_to_log = match kind {
             BookmarkKind::Scratch => {
+                check_scratch_update(
+                    self.bookmark,
+                    self.bundle_replay,
+                    pushrebase_params,
+                    self.affected_changesets.new_changesets(),
+                )?;
+
                 // TODO: remove this once hg->mononoke migration is done
                 // as we won't need any syncing between hg and mononoke then.
                 #[cfg(fbcode_build)]
@@ -308,6 +526,8 @@ impl<'op> UpdateBookmarkOp<'op> {
             }
         };
 
+        // Last chance to back out: dropping the transaction discards it.
+        self.check_cancelled()?;
         let ok = match txn_hook {
             Some(txn_hook) => txn.commit_with_hook(txn_hook).await?,
             None => txn.commit().await?,
@@ -316,6 +536,25 @@ impl<'op> UpdateBookmarkOp<'op> {
             return Err(BookmarkMovementError::TransactionFailed);
         }
 
+        if let Some(auditor) = self.auditor {
+            let event = BookmarkMoveAuditEvent {
+                bookmark: self.bookmark,
+                auth: &self.auth,
+                targets: &self.targets,
+                reason: self.reason,
+                fast_forward,
+            };
+            if let Err(err) = auditor.record(ctx, &event).await {
+                ctx.scuba()
+                    .clone()
+                    .add("bookmark", self.bookmark.to_string())
+                    .log_with_msg(
+                        "Failed to record bookmark move audit event",
+                        Some(format!("{:#}", err)),
+                    );
+            }
+        }
+
         if self.log_new_public_commits_to_scribe {
             log_bonsai_commits_to_scribe(
                 ctx,
@@ -332,3 +571,510 @@ impl<'op> UpdateBookmarkOp<'op> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use blobstore::Loadable;
+    use bookmarks::{BookmarkUpdateLog, RawBundleReplayData};
+    use fbinit::FacebookInit;
+    use hooks::{ChangesetHook, FileContentManager, HookExecution, HookRejectionInfo};
+    use hooks_content_stores::InMemoryFileContentManager;
+    use maplit::hashmap;
+    use metaconfig_types::{
+        BookmarkOrRegex, HgsqlName, HookConfig, HookManagerParams, RepoReadOnly,
+    };
+    use scuba_ext::MononokeScubaSampleBuilder;
+    use skiplist::SkiplistIndex;
+    use tests_utils::{bookmark, drawdag::create_from_dag};
+    use tunables::{with_tunables_async, MononokeTunables};
+
+    #[test]
+    fn test_scratch_update_with_bundle_replay() -> Result<()> {
+        let bookmark = BookmarkName::new("scratch/bookmark")?;
+        let pushrebase_params = PushrebaseParams::default();
+        let new_changesets = HashMap::new();
+        let bundle_replay = RawBundleReplayData {
+            bundle_handle: "handle".to_string(),
+            commit_timestamps_json: "{}".to_string(),
+        };
+
+        assert!(matches!(
+            check_scratch_update(
+                &bookmark,
+                Some(&bundle_replay),
+                &pushrebase_params,
+                &new_changesets,
+            ),
+            Err(BookmarkMovementError::ScratchBookmarkBundleReplay { .. })
+        ));
+        assert!(check_scratch_update(&bookmark, None, &pushrebase_params, &new_changesets).is_ok());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_ancestor_or_fast_forward_policy(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(
+            &ctx,
+            &repo,
+            r##"
+                A-B-C
+                   \
+                    D
+            "##,
+        )
+        .await?;
+        let book = BookmarkName::new("book")?;
+        let lca_hint = SkiplistIndex::new();
+        let bookmark_attrs = BookmarkAttrs::new(fb, vec![]).await?;
+
+        let check = |old: &str, new: &str| {
+            let targets = BookmarkUpdateTargets {
+                old: mapping[old],
+                new: mapping[new],
+            };
+            let ctx = &ctx;
+            let repo = &repo;
+            let lca_hint = &lca_hint;
+            let bookmark_attrs = &bookmark_attrs;
+            let book = &book;
+            async move {
+                BookmarkUpdatePolicy::AncestorOrFastForward
+                    .check_update_permitted(
+                        ctx,
+                        repo,
+                        lca_hint,
+                        bookmark_attrs,
+                        book,
+                        &targets,
+                        &BookmarkMoveAuthorization::User,
+                    )
+                    .await
+            }
+        };
+
+        // Fast-forward
+        assert!(check("B", "C").await.is_ok());
+        // Rollback to an ancestor
+        assert!(check("C", "A").await.is_ok());
+        // Sideways
+        assert!(matches!(
+            check("C", "D").await,
+            Err(BookmarkMovementError::NonFastForwardMove { .. })
+        ));
+        Ok(())
+    }
+
+    /// What every UpdateBookmarkOp::run needs besides the repo.
+    struct UpdateFixture {
+        lca_hint: Arc<dyn LeastCommonAncestorsHint>,
+        infinitepush_params: InfinitepushParams,
+        pushrebase_params: PushrebaseParams,
+        bookmark_attrs: BookmarkAttrs,
+        hook_manager: HookManager,
+        repo_read_write_fetcher: RepoReadWriteFetcher,
+    }
+
+    impl UpdateFixture {
+        async fn new(fb: FacebookInit) -> Result<Self> {
+            Ok(Self {
+                lca_hint: Arc::new(SkiplistIndex::new()),
+                infinitepush_params: InfinitepushParams::default(),
+                pushrebase_params: PushrebaseParams::default(),
+                bookmark_attrs: BookmarkAttrs::new(fb, vec![]).await?,
+                hook_manager: HookManager::new(
+                    fb,
+                    Box::new(InMemoryFileContentManager::new()),
+                    HookManagerParams {
+                        disable_acl_checker: true,
+                        ..Default::default()
+                    },
+                    MononokeScubaSampleBuilder::with_discard(),
+                    "repo".to_string(),
+                )
+                .await?,
+                repo_read_write_fetcher: RepoReadWriteFetcher::new(
+                    None,
+                    RepoReadOnly::ReadWrite,
+                    HgsqlName("repo".to_string()),
+                ),
+            })
+        }
+
+        async fn run<'op>(
+            &'op self,
+            ctx: &'op CoreContext,
+            repo: &'op BlobRepo,
+            op: UpdateBookmarkOp<'op>,
+        ) -> Result<(), BookmarkMovementError> {
+            op.run(
+                ctx,
+                repo,
+                &self.lca_hint,
+                &self.infinitepush_params,
+                &self.pushrebase_params,
+                &self.bookmark_attrs,
+                &self.hook_manager,
+                &self.repo_read_write_fetcher,
+            )
+            .await
+        }
+
+        async fn preflight<'op>(
+            &'op self,
+            ctx: &'op CoreContext,
+            repo: &'op BlobRepo,
+            op: UpdateBookmarkOp<'op>,
+            tunables: MononokeTunables,
+        ) -> Result<AffectedChangesetsCount, BookmarkMovementError> {
+            let preflight = op.preflight(
+                ctx,
+                repo,
+                &self.lca_hint,
+                &self.infinitepush_params,
+                &self.bookmark_attrs,
+                &self.hook_manager,
+                2,
+            );
+            with_tunables_async(tunables, Box::pin(preflight)).await
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_noop_update_skips_transaction(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(&ctx, &repo, "A-B").await?;
+        let book = BookmarkName::new("book")?;
+        bookmark(&ctx, &repo, "book").set_to(mapping["B"]).await?;
+        let fixture = UpdateFixture::new(fb).await?;
+
+        let log_entries = || {
+            repo.bookmark_update_log()
+                .count_further_bookmark_log_entries(ctx.clone(), 0, None)
+        };
+        let before = log_entries().await?;
+
+        let noop = |name: &str| {
+            UpdateBookmarkOp::new(
+                &book,
+                BookmarkUpdateTargets {
+                    old: mapping[name],
+                    new: mapping[name],
+                },
+                BookmarkUpdatePolicy::FastForwardOnly,
+                BookmarkUpdateReason::TestMove,
+            )
+        };
+
+        fixture.run(&ctx, &repo, noop("B")).await?;
+
+        // No transaction was committed, so nothing was logged
+        assert_eq!(log_entries().await?, before);
+        assert_eq!(
+            repo.get_bonsai_bookmark(ctx.clone(), &book).await?,
+            Some(mapping["B"])
+        );
+
+        // The bookmark is at B, not A, so this fails like the transaction would
+        assert!(matches!(
+            fixture.run(&ctx, &repo, noop("A")).await,
+            Err(BookmarkMovementError::TransactionFailed)
+        ));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_cancelled_update_does_not_move_bookmark(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(&ctx, &repo, "A-B").await?;
+        let book = BookmarkName::new("book")?;
+        bookmark(&ctx, &repo, "book").set_to(mapping["A"]).await?;
+        let fixture = UpdateFixture::new(fb).await?;
+
+        let op = UpdateBookmarkOp::new(
+            &book,
+            BookmarkUpdateTargets {
+                old: mapping["A"],
+                new: mapping["B"],
+            },
+            BookmarkUpdatePolicy::FastForwardOnly,
+            BookmarkUpdateReason::TestMove,
+        )
+        .with_cancellation(Arc::new(AtomicBool::new(true)));
+        let res = fixture.run(&ctx, &repo, op).await;
+
+        assert!(matches!(res, Err(BookmarkMovementError::Cancelled)));
+        assert_eq!(
+            repo.get_bonsai_bookmark(ctx.clone(), &book).await?,
+            Some(mapping["A"])
+        );
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_hook_config_changed_does_not_move_bookmark(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(&ctx, &repo, "A-B").await?;
+        let book = BookmarkName::new("book")?;
+        bookmark(&ctx, &repo, "book").set_to(mapping["A"]).await?;
+        let mut fixture = UpdateFixture::new(fb).await?;
+
+        // The client saw the config before it was reconfigured
+        let seen_version = fixture.hook_manager.config_version();
+        fixture
+            .hook_manager
+            .set_hooks_for_bookmark(BookmarkOrRegex::Bookmark(book.clone()), vec![]);
+
+        let update = |expected_version: u64| {
+            let op = UpdateBookmarkOp::new(
+                &book,
+                BookmarkUpdateTargets {
+                    old: mapping["A"],
+                    new: mapping["B"],
+                },
+                BookmarkUpdatePolicy::FastForwardOnly,
+                BookmarkUpdateReason::TestMove,
+            )
+            .with_expected_hook_config_version(expected_version);
+            fixture.run(&ctx, &repo, op)
+        };
+
+        assert!(matches!(
+            update(seen_version).await,
+            Err(BookmarkMovementError::HookConfigChanged { .. })
+        ));
+        assert_eq!(
+            repo.get_bonsai_bookmark(ctx.clone(), &book).await?,
+            Some(mapping["A"])
+        );
+
+        // Retrying against the current config succeeds
+        update(fixture.hook_manager.config_version()).await?;
+        assert_eq!(
+            repo.get_bonsai_bookmark(ctx.clone(), &book).await?,
+            Some(mapping["B"])
+        );
+        Ok(())
+    }
+
+    fn hook_tunables(run_hooks_on_additional_changesets: bool, limit: i64) -> MononokeTunables {
+        let tunables = MononokeTunables::default();
+        tunables.update_bools(&hashmap! {
+            "run_hooks_on_additional_changesets".to_string() => run_hooks_on_additional_changesets,
+        });
+        tunables.update_ints(&hashmap! {
+            "hooks_additional_changesets_limit".to_string() => limit,
+        });
+        tunables
+    }
+
+    #[fbinit::test]
+    async fn test_preflight_counts_range(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(&ctx, &repo, "A-B-C-D").await?;
+        let book = BookmarkName::new("book")?;
+        bookmark(&ctx, &repo, "book").set_to(mapping["A"]).await?;
+        let mut fixture = UpdateFixture::new(fb).await?;
+        fixture
+            .hook_manager
+            .set_hooks_for_bookmark(BookmarkOrRegex::Bookmark(book.clone()), vec![]);
+
+        let log_entries = || {
+            repo.bookmark_update_log()
+                .count_further_bookmark_log_entries(ctx.clone(), 0, None)
+        };
+        let before = log_entries().await?;
+
+        let update = |book| {
+            UpdateBookmarkOp::new(
+                book,
+                BookmarkUpdateTargets {
+                    old: mapping["A"],
+                    new: mapping["D"],
+                },
+                BookmarkUpdatePolicy::FastForwardOnly,
+                BookmarkUpdateReason::TestMove,
+            )
+        };
+
+        let count = fixture
+            .preflight(&ctx, &repo, update(&book), hook_tunables(true, 0))
+            .await?;
+        assert_eq!(count.count, 3);
+        assert!(!count.limit_reached);
+        // Newest first
+        assert_eq!(count.sample, vec![mapping["D"], mapping["C"]]);
+
+        // Hooks may run on exactly as many changesets as the limit
+        let count = fixture
+            .preflight(&ctx, &repo, update(&book), hook_tunables(true, 3))
+            .await?;
+        assert_eq!(count.count, 3);
+        assert!(!count.limit_reached);
+
+        let count = fixture
+            .preflight(&ctx, &repo, update(&book), hook_tunables(true, 2))
+            .await?;
+        assert_eq!(count.count, 2);
+        assert!(count.limit_reached);
+
+        // Hooks are not run on the additional changesets
+        let count = fixture
+            .preflight(&ctx, &repo, update(&book), hook_tunables(false, 0))
+            .await?;
+        assert_eq!(count, AffectedChangesetsCount::default());
+
+        // No hooks are configured for this bookmark
+        let other = BookmarkName::new("other")?;
+        let count = fixture
+            .preflight(&ctx, &repo, update(&other), hook_tunables(true, 0))
+            .await?;
+        assert_eq!(count, AffectedChangesetsCount::default());
+
+        // The bookmark did not move
+        assert_eq!(log_entries().await?, before);
+        assert_eq!(
+            repo.get_bonsai_bookmark(ctx.clone(), &book).await?,
+            Some(mapping["A"])
+        );
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct RecordingAuditor {
+        events: std::sync::Mutex<Vec<(BookmarkName, ChangesetId, ChangesetId, bool)>>,
+    }
+
+    #[async_trait]
+    impl BookmarkMoveAuditor for RecordingAuditor {
+        async fn record(
+            &self,
+            _ctx: &CoreContext,
+            event: &BookmarkMoveAuditEvent<'_>,
+        ) -> Result<()> {
+            assert_eq!(event.auth, &BookmarkMoveAuthorization::User);
+            self.events.lock().unwrap().push((
+                event.bookmark.clone(),
+                event.targets.old,
+                event.targets.new,
+                event.fast_forward,
+            ));
+            Ok(())
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_auditor_records_committed_moves(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(&ctx, &repo, "A-B-C").await?;
+        let book = BookmarkName::new("book")?;
+        bookmark(&ctx, &repo, "book").set_to(mapping["A"]).await?;
+        let fixture = UpdateFixture::new(fb).await?;
+        let auditor = RecordingAuditor::default();
+
+        let update = |old: &str, new: &str| {
+            let op = UpdateBookmarkOp::new(
+                &book,
+                BookmarkUpdateTargets {
+                    old: mapping[old],
+                    new: mapping[new],
+                },
+                BookmarkUpdatePolicy::AnyPermittedByConfig,
+                BookmarkUpdateReason::TestMove,
+            )
+            .with_auditor(&auditor);
+            fixture.run(&ctx, &repo, op)
+        };
+
+        update("A", "B").await?;
+        update("B", "A").await?;
+        // The bookmark is at A, not C, so the transaction fails
+        assert!(matches!(
+            update("C", "B").await,
+            Err(BookmarkMovementError::TransactionFailed)
+        ));
+
+        assert_eq!(
+            *auditor.events.lock().unwrap(),
+            vec![
+                (book.clone(), mapping["A"], mapping["B"], true),
+                (book.clone(), mapping["B"], mapping["A"], false),
+            ]
+        );
+        Ok(())
+    }
+
+    struct RejectAll;
+
+    #[async_trait]
+    impl ChangesetHook for RejectAll {
+        async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+            &'this self,
+            _ctx: &'ctx CoreContext,
+            _bookmark: &BookmarkName,
+            _changeset: &'cs BonsaiChangeset,
+            _content_manager: &'fetcher dyn FileContentManager,
+            _cross_repo_push_source: CrossRepoPushSource,
+        ) -> Result<HookExecution> {
+            Ok(HookExecution::Rejected(HookRejectionInfo::new("rejected")))
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_hook_concurrency_stops_at_first_rejection(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let mapping = create_from_dag(&ctx, &repo, "A-B-C").await?;
+        let book = BookmarkName::new("book")?;
+        bookmark(&ctx, &repo, "book").set_to(mapping["A"]).await?;
+
+        let mut new_changesets = HashMap::new();
+        for name in &["B", "C"] {
+            let bcs = mapping[*name].load(&ctx, repo.blobstore()).await?;
+            new_changesets.insert(mapping[*name], bcs);
+        }
+
+        let mut fixture = UpdateFixture::new(fb).await?;
+        fixture.hook_manager.register_changeset_hook(
+            "reject_all",
+            Box::new(RejectAll),
+            HookConfig::default(),
+        );
+        fixture.hook_manager.set_hooks_for_bookmark(
+            BookmarkOrRegex::Bookmark(book.clone()),
+            vec!["reject_all".to_string()],
+        );
+
+        let op = UpdateBookmarkOp::new(
+            &book,
+            BookmarkUpdateTargets {
+                old: mapping["A"],
+                new: mapping["C"],
+            },
+            BookmarkUpdatePolicy::FastForwardOnly,
+            BookmarkUpdateReason::TestMove,
+        )
+        .with_new_changesets(new_changesets)
+        .with_hook_concurrency(NonZeroUsize::new(1).unwrap());
+        let res = fixture.run(&ctx, &repo, op).await;
+
+        // Only the first changeset in id order is reported
+        let first = std::cmp::min(mapping["B"], mapping["C"]);
+        match res {
+            Err(BookmarkMovementError::HookFailure(rejections)) => {
+                assert_eq!(rejections.len(), 1);
+                assert_eq!(rejections[0].cs_id, first);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        Ok(())
+    }
+}
@@ -59,6 +59,8 @@ pub const WRITE_BYTES_ARG: &str = "blobstore-write-bytes-s";
 pub const READ_BURST_BYTES_ARG: &str = "blobstore-read-burst-bytes-s";
 pub const WRITE_BURST_BYTES_ARG: &str = "blobstore-write-burst-bytes-s";
 pub const BLOBSTORE_BYTES_MIN_THROTTLE_ARG: &str = "blobstore-bytes-min-throttle";
+pub const BLOBSTORE_MULTIPLEX_WRITE_QPS_ARG: &str = "blobstore-multiplex-write-qps";
+pub const BLOBSTORE_MULTIPLEX_WRITE_ONLY_ARG: &str = "blobstore-multiplex-write-only";
 pub const READ_CHAOS_ARG: &str = "blobstore-read-chaos-rate";
 pub const WRITE_CHAOS_ARG: &str = "blobstore-write-chaos-rate";
 pub const WRITE_ZSTD_ARG: &str = "blobstore-write-zstd";
@@ -69,6 +71,8 @@ pub const BLOBSTORE_SCRUB_ACTION_ARG: &str = "blobstore-scrub-action";
 pub const BLOBSTORE_SCRUB_GRACE_ARG: &str = "blobstore-scrub-grace";
 pub const BLOBSTORE_SCRUB_WRITE_MOSTLY_MISSING_ARG: &str = "blobstore-scrub-write-mostly-missing";
 pub const BLOBSTORE_SCRUB_QUEUE_PEEK_BOUND_ARG: &str = "blobstore-scrub-queue-peek";
+pub const BLOBSTORE_SCRUB_REPAIR_BATCH_SIZE_ARG: &str = "blobstore-scrub-repair-batch-size";
+pub const BLOBSTORE_SCRUB_KEY_RANGE_ARG: &str = "blobstore-scrub-key-range";
 pub const PUT_MEAN_DELAY_SECS_ARG: &str = "blobstore-put-mean-delay-secs";
 pub const PUT_STDDEV_DELAY_SECS_ARG: &str = "blobstore-put-stddev-delay-secs";
 pub const GET_MEAN_DELAY_SECS_ARG: &str = "blobstore-get-mean-delay-secs";
@@ -654,6 +658,26 @@ impl MononokeAppBuilder {
                 .required(false)
                 .help("Write QPS limit to ThrottledBlob"),
         )
+        .arg(
+            Arg::with_name(BLOBSTORE_MULTIPLEX_WRITE_QPS_ARG)
+                .long(BLOBSTORE_MULTIPLEX_WRITE_QPS_ARG)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .value_name("BLOBSTORE_ID:QPS")
+                .help("Limit puts to a multiplex component, including scrub repairs. May be repeated for several components"),
+        )
+        .arg(
+            Arg::with_name(BLOBSTORE_MULTIPLEX_WRITE_ONLY_ARG)
+                .long(BLOBSTORE_MULTIPLEX_WRITE_ONLY_ARG)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .value_name("BLOBSTORE_ID")
+                .help("Write to a multiplex component but never read from it, e.g. while it is backfilled. May be repeated for several components"),
+        )
         .arg(
             Arg::with_name(WRITE_BYTES_ARG)
                 .long(WRITE_BYTES_ARG)
@@ -821,10 +845,27 @@ impl MononokeAppBuilder {
                 scrub_action_on_missing_write_mostly_arg =
                     scrub_action_on_missing_write_mostly_arg.default_value(default.into());
             }
+            let scrub_repair_batch_size_arg =
+                Arg::with_name(BLOBSTORE_SCRUB_REPAIR_BATCH_SIZE_ARG)
+                    .long(BLOBSTORE_SCRUB_REPAIR_BATCH_SIZE_ARG)
+                    .takes_value(true)
+                    .required(false)
+                    .requires(BLOBSTORE_SCRUB_ACTION_ARG)
+                    .help("Batch repairs of alias mappings to SQL stores, this many to a batch. The last partial batches are written when the scrub completes.");
+            let scrub_key_range_arg = Arg::with_name(BLOBSTORE_SCRUB_KEY_RANGE_ARG)
+                .long(BLOBSTORE_SCRUB_KEY_RANGE_ARG)
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["START", "END"])
+                .required(false)
+                .requires(BLOBSTORE_SCRUB_ACTION_ARG)
+                .help("Only scrub keys from START up to but excluding END, so a scrub can be split across workers by giving each a disjoint range. Other keys are read without being scrubbed or counted. An empty END leaves the range unbounded above.");
             app.arg(scrub_action_arg)
                 .arg(scrub_grace_arg)
                 .arg(scrub_action_on_missing_write_mostly_arg)
                 .arg(scrub_queue_peek_bound_arg)
+                .arg(scrub_repair_batch_size_arg)
+                .arg(scrub_key_range_arg)
         } else {
             app
         }
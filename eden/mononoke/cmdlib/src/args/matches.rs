@@ -6,7 +6,7 @@
  */
 
 use std::borrow::Borrow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::num::{NonZeroU32, NonZeroUsize};
 use std::path::PathBuf;
@@ -34,7 +34,7 @@ use blobstore_factory::{
     PutBehaviour, ScrubAction, ScrubWriteMostly, ThrottleOptions,
 };
 use environment::{Caching, MononokeEnvironment};
-use metaconfig_types::PackFormat;
+use metaconfig_types::{BlobstoreId, PackFormat};
 use observability::{DynamicLevelDrain, ObservabilityContext};
 use repo_factory::ReadOnlyStorage;
 use scuba_ext::MononokeScubaSampleBuilder;
@@ -49,9 +49,11 @@ use crate::log;
 use super::parse_config_spec_to_path;
 use super::{
     app::{
-        ArgType, MononokeAppData, BLOBSTORE_BYTES_MIN_THROTTLE_ARG, BLOBSTORE_PUT_BEHAVIOUR_ARG,
-        BLOBSTORE_SCRUB_ACTION_ARG, BLOBSTORE_SCRUB_GRACE_ARG,
-        BLOBSTORE_SCRUB_QUEUE_PEEK_BOUND_ARG, BLOBSTORE_SCRUB_WRITE_MOSTLY_MISSING_ARG,
+        ArgType, MononokeAppData, BLOBSTORE_BYTES_MIN_THROTTLE_ARG,
+        BLOBSTORE_MULTIPLEX_WRITE_ONLY_ARG, BLOBSTORE_MULTIPLEX_WRITE_QPS_ARG,
+        BLOBSTORE_PUT_BEHAVIOUR_ARG, BLOBSTORE_SCRUB_ACTION_ARG, BLOBSTORE_SCRUB_GRACE_ARG,
+        BLOBSTORE_SCRUB_KEY_RANGE_ARG, BLOBSTORE_SCRUB_QUEUE_PEEK_BOUND_ARG,
+        BLOBSTORE_SCRUB_REPAIR_BATCH_SIZE_ARG, BLOBSTORE_SCRUB_WRITE_MOSTLY_MISSING_ARG,
         CACHELIB_ATTEMPT_ZSTD_ARG, CRYPTO_PATH_REGEX_ARG, DERIVE_REMOTELY, DERIVE_REMOTELY_TIER,
         DISABLE_TUNABLES, ENABLE_MCROUTER, GET_MEAN_DELAY_SECS_ARG, GET_STDDEV_DELAY_SECS_ARG,
         LOCAL_CONFIGERATOR_PATH_ARG, LOGVIEW_ADDITIONAL_LEVEL_FILTER, LOGVIEW_CATEGORY,
@@ -560,6 +562,15 @@ fn parse_blobstore_options(
         .transpose()
         .context("Provided qps is not u32")?;
 
+    let multiplex_write_limits = parse_multiplex_write_limits(matches)?;
+    let multiplex_write_only = matches
+        .values_of(BLOBSTORE_MULTIPLEX_WRITE_ONLY_ARG)
+        .into_iter()
+        .flatten()
+        .map(|id| Ok(BlobstoreId::new(id.parse()?)))
+        .collect::<Result<HashSet<_>, Error>>()
+        .context("Provided blobstore id is not u64")?;
+
     let read_bytes: Option<NonZeroUsize> = matches
         .value_of(READ_BYTES_ARG)
         .map(|v| v.parse())
@@ -680,7 +691,9 @@ fn parse_blobstore_options(
         blobstore_put_behaviour,
         parse_sqlblob_mysql_options(matches, app_data)
             .context("Failed to parse sqlblob MySQL options")?,
-    );
+    )
+    .with_multiplex_write_limits(multiplex_write_limits)
+    .with_multiplex_write_only(multiplex_write_only);
 
     let blobstore_options = if arg_types.contains(&ArgType::Scrub) {
         let scrub_action = matches
@@ -709,6 +722,28 @@ fn parse_blobstore_options(
         if let Some(v) = scrub_queue_peek_bound {
             blobstore_options = blobstore_options.with_scrub_queue_peek_bound(v)
         }
+        let scrub_repair_batch_size: Option<NonZeroUsize> = matches
+            .value_of(BLOBSTORE_SCRUB_REPAIR_BATCH_SIZE_ARG)
+            .map(|v| v.parse())
+            .transpose()
+            .context("Provided batch size is not a non-zero usize")?;
+        if let Some(v) = scrub_repair_batch_size {
+            blobstore_options = blobstore_options.with_scrub_repair_batch_size(v)
+        }
+        if let Some(mut range) = matches.values_of(BLOBSTORE_SCRUB_KEY_RANGE_ARG) {
+            // clap has already checked there are two values
+            let start = range.next().unwrap_or_default().to_string();
+            let end = range.next().unwrap_or_default().to_string();
+            if !end.is_empty() && start >= end {
+                bail!(
+                    "--{} start {:?} must be before its end {:?}",
+                    BLOBSTORE_SCRUB_KEY_RANGE_ARG,
+                    start,
+                    end
+                );
+            }
+            blobstore_options = blobstore_options.with_scrub_key_range(start, end)
+        }
         blobstore_options
     } else {
         blobstore_options
@@ -717,6 +752,27 @@ fn parse_blobstore_options(
     Ok(blobstore_options)
 }
 
+fn parse_multiplex_write_limits(
+    matches: &ArgMatches<'_>,
+) -> Result<HashMap<BlobstoreId, NonZeroU32>, Error> {
+    let mut write_limits = HashMap::new();
+    for v in matches
+        .values_of(BLOBSTORE_MULTIPLEX_WRITE_QPS_ARG)
+        .into_iter()
+        .flatten()
+    {
+        let (id, qps) = v
+            .split_once(':')
+            .ok_or_else(|| format_err!("Expected BLOBSTORE_ID:QPS, got {}", v))?;
+        let id = BlobstoreId::new(id.parse().context("Provided blobstore id is not u64")?);
+        let qps: NonZeroU32 = qps.parse().context("Provided qps is not u32")?;
+        if write_limits.insert(id, qps).is_some() {
+            bail!("Multiple write limits given for blobstore {}", id);
+        }
+    }
+    Ok(write_limits)
+}
+
 fn parse_norm_distribution(
     matches: &ArgMatches,
     mean_key: &str,
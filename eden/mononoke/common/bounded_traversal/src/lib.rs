@@ -20,7 +20,10 @@ mod dag;
 pub use dag::bounded_traversal_dag;
 
 mod stream;
-pub use stream::{bounded_traversal_stream, bounded_traversal_stream2, limited_by_key_shardable};
+pub use stream::{
+    bounded_traversal_stream, bounded_traversal_stream2, limited_by_key_shardable,
+    limited_by_key_shardable_with_frontier, Frontier,
+};
 
 mod ordered_stream;
 pub use ordered_stream::{
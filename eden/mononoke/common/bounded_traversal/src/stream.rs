@@ -72,14 +72,50 @@ where
     })
 }
 
+/// The queue of items waiting to be scheduled by `limited_by_key_shardable_with_frontier`.
+/// The order in which items are popped determines the traversal order.
+pub trait Frontier<In> {
+    /// Add a newly unfolded child
+    fn push(&mut self, item: In);
+    /// Return an item that was popped but could not be scheduled yet, so
+    /// that it is retried ahead of other items
+    fn push_retry(&mut self, item: In);
+    fn pop(&mut self) -> Option<In>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Children are pushed to and popped from the front, so the most recently
+/// unfolded items are scheduled first.
+impl<In> Frontier<In> for VecDeque<In> {
+    fn push(&mut self, item: In) {
+        self.push_front(item)
+    }
+
+    fn push_retry(&mut self, item: In) {
+        self.push_front(item)
+    }
+
+    fn pop(&mut self) -> Option<In> {
+        self.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+}
+
 /// This function is similar to `bounded_traversal_stream` but:
 ///   - prevents items with duplicate keys executing concurrently
 ///   - allows an item to have no stream output by returning None
-///   - optionally allows to restrict the number of keys executing concurrently by a ShardKey
+///   - optionally allows to restrict the number of keys executing concurrently by a ShardKey,
+///     to at most the usize returned with it by `key_fn`, which must not be 0
 pub fn limited_by_key_shardable<In, InsInit, Ins, Out, Unfold, UFut, UErr, Key, KeyFn, ShardKey>(
     scheduled_max: usize,
     init: InsInit,
-    mut unfold: Unfold,
+    unfold: Unfold,
     key_fn: KeyFn,
 ) -> impl Stream<Item = Result<Out, UErr>>
 where
@@ -91,7 +127,37 @@ where
     KeyFn: Fn(&In) -> (&Key, Option<(ShardKey, usize)>),
     ShardKey: Clone + Eq + Hash,
 {
-    let mut unscheduled = VecDeque::from_iter(init);
+    limited_by_key_shardable_with_frontier(scheduled_max, VecDeque::from_iter(init), unfold, key_fn)
+}
+
+/// As `limited_by_key_shardable`, but the caller provides the `Frontier`
+/// holding the unscheduled items, already populated with the roots.
+pub fn limited_by_key_shardable_with_frontier<
+    In,
+    F,
+    Ins,
+    Out,
+    Unfold,
+    UFut,
+    UErr,
+    Key,
+    KeyFn,
+    ShardKey,
+>(
+    scheduled_max: usize,
+    mut unscheduled: F,
+    mut unfold: Unfold,
+    key_fn: KeyFn,
+) -> impl Stream<Item = Result<Out, UErr>>
+where
+    F: Frontier<In>,
+    Unfold: FnMut(In) -> UFut,
+    UFut: Future<Output = (Key, Option<ShardKey>, Result<Option<(Out, Ins)>, UErr>)>,
+    Ins: IntoIterator<Item = In>,
+    Key: Clone + Eq + Hash,
+    KeyFn: Fn(&In) -> (&Key, Option<(ShardKey, usize)>),
+    ShardKey: Clone + Eq + Hash,
+{
     let mut scheduled = FuturesUnordered::new();
     let mut waiting_for_key: HashMap<Key, VecDeque<_>> = HashMap::new();
     let mut waiting_for_shard: HashMap<ShardKey, (usize, VecDeque<_>)> = HashMap::new();
@@ -103,9 +169,8 @@ where
             }
 
             while scheduled.len() < scheduled_max && !unscheduled.is_empty() {
-                for item in unscheduled
-                    .drain(..std::cmp::min(unscheduled.len(), scheduled_max - scheduled.len()))
-                {
+                let to_schedule = std::cmp::min(unscheduled.len(), scheduled_max - scheduled.len());
+                for item in (0..to_schedule).filter_map(|_| unscheduled.pop()) {
                     let (key, shard_info) = key_fn(&item);
                     if let Some(inflight) = waiting_for_key.get_mut(key) {
                         // Exact duplicate, it needs to wait
@@ -114,8 +179,10 @@ where
                     }
 
                     if let Some((shard_key, max_per_shard)) = shard_info {
+                        // Nothing could ever take a place on the shard to release the queue
+                        assert!(max_per_shard > 0, "max_per_shard must be greater than 0");
                         let (inflight, queued) = waiting_for_shard.entry(shard_key).or_default();
-                        if *inflight > max_per_shard {
+                        if *inflight >= max_per_shard {
                             // Shard is too busy, so queue more
                             queued.push_back(item);
                             continue;
@@ -130,22 +197,28 @@ where
             }
 
             if let Some((key, shard_key, unfolded)) = ready!(scheduled.poll_next_unpin(cx)) {
+                let mut rescheduled_key = false;
                 if let Some((key, mut queue)) = waiting_for_key.remove_entry(&key) {
                     if let Some(item) = queue.pop_front() {
                         let unfolded = unfold(item);
                         scheduled.push(unfolded);
+                        rescheduled_key = true;
                     }
-                    if !queue.is_empty() {
+                    // Keep the key while its duplicate is in flight
+                    if rescheduled_key {
                         waiting_for_key.insert(key, queue);
                     }
                 }
 
-                if let Some(shard_key) = shard_key {
+                // A duplicate key is on the same shard, so when one was scheduled
+                // above it takes over this item's place on the shard. The place is
+                // given up whether or not the unfold succeeded.
+                if let Some(shard_key) = shard_key.filter(|_| !rescheduled_key) {
                     if let Some((inflight, queue)) = waiting_for_shard.get_mut(&shard_key) {
                         *inflight = inflight.saturating_sub(1);
                         if let Some(item) = queue.pop_front() {
                             // Don't directly schedule as could be a duplicate key
-                            unscheduled.push_front(item);
+                            unscheduled.push_retry(item);
                         }
                     }
                 }
@@ -153,7 +226,7 @@ where
                 if let Some((out, children)) = unfolded? {
                     // there is output on this unfold
                     for child in children {
-                        unscheduled.push_front(child);
+                        unscheduled.push(child);
                     }
                     return Poll::Ready(Some(Ok(out)));
                 }
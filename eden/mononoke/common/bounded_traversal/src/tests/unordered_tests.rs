@@ -5,11 +5,14 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Error;
 use cloned::cloned;
-use futures::future::FutureExt;
+use futures::future::{self, FutureExt};
 use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use maplit::hashmap;
 use pretty_assertions::assert_eq;
@@ -18,7 +21,7 @@ use tokio::task::yield_now;
 use super::utils::{StateLog, Tick};
 use crate::{
     bounded_traversal, bounded_traversal_dag, bounded_traversal_stream, bounded_traversal_stream2,
-    limited_by_key_shardable,
+    limited_by_key_shardable, limited_by_key_shardable_with_frontier, Frontier,
 };
 
 // Tree for test purposes
@@ -540,6 +543,50 @@ async fn test_limited_by_key_shardable_ticks() -> Result<(), Error> {
     .await
 }
 
+// Drains oldest items first, giving a breadth first traversal
+struct FifoFrontier(VecDeque<Tree>);
+
+impl Frontier<Tree> for FifoFrontier {
+    fn push(&mut self, item: Tree) {
+        self.0.push_back(item)
+    }
+
+    fn push_retry(&mut self, item: Tree) {
+        self.0.push_front(item)
+    }
+
+    fn pop(&mut self) -> Option<Tree> {
+        self.0.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[tokio::test]
+async fn test_limited_by_key_shardable_frontier_order() -> Result<(), Error> {
+    let unfold = |Tree { id, children }| {
+        future::ready((id, None::<()>, Ok::<_, Error>(Some((id, children)))))
+    };
+
+    // Default frontier schedules the most recently unfolded items first
+    let order: Vec<usize> =
+        limited_by_key_shardable(1, Some(build_tree()), unfold, |item| (&item.id, None))
+            .try_collect()
+            .await?;
+    assert_eq!(order, vec![0, 2, 4, 3, 1, 5]);
+
+    let frontier = FifoFrontier(VecDeque::from(vec![build_tree()]));
+    let order: Vec<usize> =
+        limited_by_key_shardable_with_frontier(1, frontier, unfold, |item| (&item.id, None))
+            .try_collect()
+            .await?;
+    assert_eq!(order, vec![0, 1, 2, 5, 3, 4]);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_limited_by_key_shardable_duplicate_ticks() -> Result<(), Error> {
     check_duplicate_stream_unfold_ticks(|tree, tick, log| {
@@ -585,6 +632,86 @@ async fn test_limited_by_key_shardable_duplicate_ticks_sharded() -> Result<(), E
     .await
 }
 
+#[tokio::test]
+async fn test_limited_by_key_shardable_max_per_shard() -> Result<(), Error> {
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_active = Arc::new(AtomicUsize::new(0));
+    let mut leaves: Vec<_> = (1..10).map(Tree::leaf).collect();
+    leaves.push(Tree::leaf(2));
+    leaves.push(Tree::leaf(3));
+
+    let ids: BTreeSet<usize> = limited_by_key_shardable(
+        10, // This has a wide executor
+        Some(Tree::new(0, leaves)),
+        {
+            cloned!(active, max_active);
+            move |Tree { id, children }| {
+                cloned!(active, max_active);
+                async move {
+                    let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now_active, Ordering::SeqCst);
+                    yield_now().await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    // Failures must still give up their place on the shard
+                    let res = if id % 2 == 1 {
+                        Err(Error::msg(format!("failed {}", id)))
+                    } else {
+                        Ok(Some((id, children)))
+                    };
+                    (id, Some(()), res)
+                }
+            }
+        },
+        |item| (&item.id, Some(((), 2))),
+    )
+    .filter_map(|res| future::ready(res.ok()))
+    .collect()
+    .await;
+
+    assert_eq!(ids, (0..10).step_by(2).collect::<BTreeSet<_>>());
+    assert_eq!(max_active.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_limited_by_key_shardable_drains_shard_queue() -> Result<(), Error> {
+    // One place on the shard, so the leaves queue up behind each other. Unfolds without
+    // output must still give up their place, or the queued leaves would never run.
+    let leaves: Vec<_> = (1..10).map(Tree::leaf).collect();
+    let traverse = limited_by_key_shardable(
+        10,
+        Some(Tree::new(0, leaves)),
+        |Tree { id, children }| async move {
+            yield_now().await;
+            let out = if id % 3 == 2 {
+                None
+            } else {
+                Some((id, children))
+            };
+            (id, Some(()), Ok::<_, Error>(out))
+        },
+        |item| (&item.id, Some(((), 1))),
+    )
+    .try_collect::<BTreeSet<usize>>();
+
+    let ids = tokio::time::timeout(Duration::from_secs(10), traverse).await??;
+    assert_eq!(ids, (0..10).filter(|id| id % 3 != 2).collect());
+    Ok(())
+}
+
+#[tokio::test]
+#[should_panic(expected = "max_per_shard must be greater than 0")]
+async fn test_limited_by_key_shardable_zero_per_shard() {
+    let _ = limited_by_key_shardable(
+        1,
+        Some(Tree::leaf(0)),
+        |Tree { id, children }| future::ready((id, Some(()), Ok::<_, Error>(Some((id, children))))),
+        |item| (&item.id, Some(((), 0))),
+    )
+    .try_collect::<Vec<usize>>()
+    .await;
+}
+
 #[tokio::test]
 async fn test_limited_by_key_shardable_parents() -> Result<(), Error> {
     check_stream_unfold_parents(|tree, tick, log| {
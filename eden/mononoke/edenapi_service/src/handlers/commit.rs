@@ -21,12 +21,12 @@ use std::time::Duration;
 use blobstore::Loadable;
 use edenapi_types::{
     wire::WireCommitHashToLocationRequestBatch, AnyFileContentId, AnyId, Batch, BonsaiFileChange,
-    CommitGraphEntry, CommitGraphRequest, CommitHashLookupRequest, CommitHashLookupResponse,
-    CommitHashToLocationResponse, CommitLocationToHashRequest, CommitLocationToHashRequestBatch,
-    CommitLocationToHashResponse, CommitMutationsRequest, CommitMutationsResponse,
-    CommitRevlogData, CommitRevlogDataRequest, EphemeralPrepareRequest, EphemeralPrepareResponse,
-    FetchSnapshotRequest, FetchSnapshotResponse, UploadBonsaiChangesetRequest,
-    UploadHgChangesetsRequest, UploadToken, UploadTokensResponse,
+    CommitBonsaiMappingRequest, CommitBonsaiMappingResponse, CommitGraphEntry, CommitGraphRequest,
+    CommitHashLookupRequest, CommitHashLookupResponse, CommitHashToLocationResponse,
+    CommitLocationToHashRequest, CommitLocationToHashRequestBatch, CommitLocationToHashResponse,
+    CommitMutationsRequest, CommitMutationsResponse, CommitRevlogData, CommitRevlogDataRequest,
+    EphemeralPrepareRequest, EphemeralPrepareResponse, FetchSnapshotRequest, FetchSnapshotResponse,
+    UploadBonsaiChangesetRequest, UploadHgChangesetsRequest, UploadToken, UploadTokensResponse,
 };
 use ephemeral_blobstore::BubbleId;
 use mercurial_types::{HgChangesetId, HgNodeHash};
@@ -571,3 +571,46 @@ impl EdenApiHandler for CommitMutationsHandler {
         Ok(stream::iter(mutations).boxed())
     }
 }
+
+async fn bonsai_mapping(
+    repo: HgRepoContext,
+    hgid: HgId,
+) -> Result<CommitBonsaiMappingResponse, Error> {
+    let hg_cs_id = HgChangesetId::new(HgNodeHash::from(hgid));
+    let bonsai = repo.get_bonsai_from_hg(hg_cs_id).await?;
+    Ok(CommitBonsaiMappingResponse {
+        hgid,
+        bonsai: bonsai.map(Into::into),
+    })
+}
+
+/// Look up the bonsai changeset ids for a batch of hg commits. Every
+/// requested commit gets an entry in the response; commits without a
+/// known mapping are returned with no bonsai id.
+pub struct CommitBonsaiMappingHandler;
+
+#[async_trait]
+impl EdenApiHandler for CommitBonsaiMappingHandler {
+    type Request = CommitBonsaiMappingRequest;
+    type Response = CommitBonsaiMappingResponse;
+
+    const HTTP_METHOD: hyper::Method = hyper::Method::POST;
+    const API_METHOD: EdenApiMethod = EdenApiMethod::CommitBonsaiMapping;
+    const ENDPOINT: &'static str = "/commit/bonsai_mapping";
+
+    async fn handler(
+        repo: HgRepoContext,
+        _path: Self::PathExtractor,
+        _query: Self::QueryStringExtractor,
+        request: Self::Request,
+    ) -> HandlerResult<'async_trait, Self::Response> {
+        let mappings = request
+            .hgids
+            .into_iter()
+            .map(move |hgid| bonsai_mapping(repo.clone(), hgid));
+
+        Ok(stream::iter(mappings)
+            .buffer_unordered(MAX_CONCURRENT_FETCHES_PER_REQUEST)
+            .boxed())
+    }
+}
@@ -81,6 +81,7 @@ pub enum EdenApiMethod {
     CommitGraph,
     DownloadFile,
     CommitMutations,
+    CommitBonsaiMapping,
 }
 
 impl fmt::Display for EdenApiMethod {
@@ -110,6 +111,7 @@ impl fmt::Display for EdenApiMethod {
             Self::FetchSnapshot => "fetch_snapshot",
             Self::DownloadFile => "download_file",
             Self::CommitMutations => "commit_mutations",
+            Self::CommitBonsaiMapping => "commit_bonsai_mapping",
         };
         write!(f, "{}", name)
     }
@@ -294,6 +296,7 @@ pub fn build_router(ctx: ServerContext) -> Router {
         Handlers::setup::<commit::GraphHandler>(route);
         Handlers::setup::<files::DownloadFileHandler>(route);
         Handlers::setup::<commit::CommitMutationsHandler>(route);
+        Handlers::setup::<commit::CommitBonsaiMappingHandler>(route);
         route
             .get("/:repo/capabilities")
             .with_path_extractor::<capabilities::CapabilitiesParams>()
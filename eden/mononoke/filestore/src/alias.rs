@@ -9,13 +9,13 @@ use anyhow::{Error, Result};
 use bytes::Bytes;
 use futures::{
     future::{self, Future, TryFutureExt},
-    stream::Stream,
+    stream::{Stream, TryStreamExt},
 };
 use mononoke_types::hash;
 
 use crate::expected_size::ExpectedSize;
 use crate::incremental_hash::{
-    GitSha1IncrementalHasher, Sha1IncrementalHasher, Sha256IncrementalHasher,
+    GitSha1IncrementalHasher, Hasher, Sha1IncrementalHasher, Sha256IncrementalHasher,
 };
 use crate::multiplexer::Multiplexer;
 use crate::streamhash::hash_stream;
@@ -82,3 +82,27 @@ where
 
     Ok(aliases.await?)
 }
+
+/// Count and hash a stream without an expected size, so that content which is shorter or longer
+/// than its metadata says can still be compared against it. The git sha1 isn't computed, as it
+/// needs the size up front.
+pub async fn size_and_hashes<S>(chunks: S) -> Result<(u64, hash::Sha1, hash::Sha256), Error>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Send,
+{
+    let (size, sha1, sha256) = chunks
+        .try_fold(
+            (
+                0u64,
+                Sha1IncrementalHasher::new(),
+                Sha256IncrementalHasher::new(),
+            ),
+            |(size, mut sha1, mut sha256), bytes| {
+                sha1.update(&bytes);
+                sha256.update(&bytes);
+                future::ok((size + bytes.len() as u64, sha1, sha256))
+            },
+        )
+        .await?;
+    Ok((size, sha1.finish(), sha256.finish()))
+}
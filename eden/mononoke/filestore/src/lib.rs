@@ -36,6 +36,7 @@ mod prepare;
 mod rechunk;
 mod streamhash;
 
+pub use alias::size_and_hashes;
 pub use fetch::Range;
 pub use fetch_key::{Alias, AliasBlob, FetchKey};
 pub use rechunk::{force_rechunk, rechunk};
@@ -209,6 +210,17 @@ pub async fn get_metadata_readonly<B: Blobstore>(
     }
 }
 
+/// Compute the metadata for the content from its bytes, ignoring and not updating any stored
+/// metadata. This will return None if the content does not exist. Useful to check that stored
+/// metadata and aliases are correct.
+pub async fn compute_metadata<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Option<ContentMetadata>, Error> {
+    metadata::compute_metadata(blobstore, ctx, content_id).await
+}
+
 /// Return true if the given key exists. A successful return means the key definitely
 /// either exists or doesn't; an error means the existence could not be determined.
 pub async fn exists<B: Blobstore>(
@@ -75,15 +75,45 @@ pub async fn get_metadata_readonly<B: Blobstore>(
         })
 }
 
+/// Computes the metadata for a ContentId from the file contents, ignoring any stored metadata.
+/// Returns None if the content does not exist. The computed metadata is not stored.
+pub async fn compute_metadata<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Option<ContentMetadata>, Error> {
+    match compute_metadata_from_content(blobstore, ctx, content_id).await {
+        Ok(metadata) => Ok(Some(metadata)),
+        Err(RebuildBackmappingError::NotFound(_)) => Ok(None),
+        Err(e @ RebuildBackmappingError::InternalError(..)) => Err(e.into()),
+    }
+}
+
 /// If the metadata is missing, we can rebuild it on the fly, since all that's needed to do so
 /// is the file contents. This can happen if we successfully stored a file, but failed to store
-/// its metadata. To rebuild the metadata, we peek at the content in the blobstore to get
-/// its size, then produce a stream of its contents and compute aliases over it. Finally, store
-/// the metadata, and return it.
+/// its metadata. Rebuild the metadata from the contents, then store it, and return it.
 async fn rebuild_metadata<B: Blobstore>(
     blobstore: &B,
     ctx: &CoreContext,
     content_id: ContentId,
+) -> Result<ContentMetadata, RebuildBackmappingError> {
+    let metadata = compute_metadata_from_content(blobstore, ctx, content_id).await?;
+
+    let blob = metadata.clone().into_blob();
+
+    blob.store(ctx, blobstore)
+        .await
+        .map_err(|e| RebuildBackmappingError::InternalError(content_id, e))?;
+
+    Ok(metadata)
+}
+
+/// To compute the metadata, we peek at the content in the blobstore to get its size, then
+/// produce a stream of its contents and compute aliases over it.
+async fn compute_metadata_from_content<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
 ) -> Result<ContentMetadata, RebuildBackmappingError> {
     use RebuildBackmappingError::*;
 
@@ -110,20 +140,11 @@ async fn rebuild_metadata<B: Blobstore>(
         .redeem(total_size)
         .map_err(|e| InternalError(content_id, e))?;
 
-
-    let metadata = ContentMetadata {
+    Ok(ContentMetadata {
         total_size,
         content_id,
         sha1,
         sha256,
         git_sha1,
-    };
-
-    let blob = metadata.clone().into_blob();
-
-    blob.store(ctx, blobstore)
-        .await
-        .map_err(|e| InternalError(content_id, e))?;
-
-    Ok(metadata)
+    })
 }
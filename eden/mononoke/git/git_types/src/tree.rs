@@ -12,7 +12,10 @@ use std::fmt::{self, Display};
 use std::io::{self, Write};
 
 use ::manifest::Entry;
-use mononoke_types::{hash::RichGitSha1, MPathElement};
+use mononoke_types::{
+    hash::{GitSha1, RichGitSha1},
+    MPathElement,
+};
 
 use crate::errors::ErrorKind;
 use crate::mode;
@@ -34,7 +37,12 @@ impl TreeHandle {
     }
 
     pub fn blobstore_key(&self) -> String {
-        format!("git.tree.{}", self.oid)
+        Self::blobstore_key_for_sha1(&self.oid.sha1())
+    }
+
+    /// Key the tree is stored under, for callers that only know its object id
+    pub fn blobstore_key_for_sha1(sha1: &GitSha1) -> String {
+        format!("git.tree.{}", sha1)
     }
 }
 
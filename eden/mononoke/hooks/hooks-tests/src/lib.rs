@@ -1566,3 +1566,28 @@ async fn test_load_disabled_hooks_hook_does_not_exist(fb: FacebookInit) {
         _ => assert!(false, "Unexpected err type"),
     };
 }
+
+#[fbinit::test]
+async fn test_config_version_follows_config(fb: FacebookInit) {
+    let config = |value: &str| HookConfig {
+        strings: hashmap! {String::from("key") => String::from(value)},
+        ..Default::default()
+    };
+    let bookmark = BookmarkName::new("bm1").unwrap();
+
+    let mut hm1 = hook_manager_inmem(fb).await;
+    hm1.register_changeset_hook("hook1", always_accepting_changeset_hook(), config("a"));
+    hm1.set_hooks_for_bookmark(bookmark.clone().into(), vec!["hook1".into()]);
+
+    // Same number of hooks, but a different config
+    let mut hm2 = hook_manager_inmem(fb).await;
+    hm2.register_changeset_hook("hook1", always_accepting_changeset_hook(), config("b"));
+    hm2.set_hooks_for_bookmark(bookmark.clone().into(), vec!["hook1".into()]);
+    assert_ne!(hm1.config_version(), hm2.config_version());
+
+    // The same config, set up in a different order
+    let mut hm3 = hook_manager_inmem(fb).await;
+    hm3.set_hooks_for_bookmark(bookmark.into(), vec!["hook1".into()]);
+    hm3.register_changeset_hook("hook1", always_accepting_changeset_hook(), config("a"));
+    assert_eq!(hm1.config_version(), hm3.config_version());
+}
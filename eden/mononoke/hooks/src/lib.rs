@@ -33,6 +33,7 @@ use futures::{
 };
 use futures_stats::TimedFutureExt;
 pub use hooks_content_stores::{FileContentManager, PathContent};
+use itertools::Itertools;
 use metaconfig_types::{BookmarkOrRegex, HookBypass, HookConfig, HookManagerParams};
 use mononoke_types::{BasicFileChange, BonsaiChangeset, ChangesetId, MPath};
 use permission_checker::{ArcMembershipChecker, MembershipCheckerBuilder};
@@ -40,9 +41,10 @@ use regex::Regex;
 use scuba::builder::ServerData;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::debug;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fmt;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 use std::str;
 
 /// Manages hooks and allows them to be installed and uninstalled given a name
@@ -59,6 +61,7 @@ pub struct HookManager {
     scuba: MononokeScubaSampleBuilder,
     all_hooks_bypassed: bool,
     scuba_bypassed_commits: MononokeScubaSampleBuilder,
+    config_version: u64,
 }
 
 impl HookManager {
@@ -98,7 +101,7 @@ impl HookManager {
             );
 
 
-        Ok(HookManager {
+        let mut hook_manager = HookManager {
             repo_name,
             hooks,
             bookmark_hooks: HashMap::new(),
@@ -109,7 +112,10 @@ impl HookManager {
             scuba,
             all_hooks_bypassed: hook_manager_params.all_hooks_bypassed,
             scuba_bypassed_commits,
-        })
+            config_version: 0,
+        };
+        hook_manager.update_config_version();
+        Ok(hook_manager)
     }
 
     pub fn register_changeset_hook(
@@ -120,6 +126,7 @@ impl HookManager {
     ) {
         self.hooks
             .insert(hook_name.to_string(), Hook::from_changeset(hook, config));
+        self.update_config_version();
     }
 
     pub fn register_file_hook(
@@ -130,6 +137,7 @@ impl HookManager {
     ) {
         self.hooks
             .insert(hook_name.to_string(), Hook::from_file(hook, config));
+        self.update_config_version();
     }
 
     pub fn set_hooks_for_bookmark(&mut self, bookmark: BookmarkOrRegex, hooks: Vec<String>) {
@@ -141,6 +149,28 @@ impl HookManager {
                 self.regex_hooks.push((regex.into_inner(), hooks));
             }
         }
+        self.update_config_version();
+    }
+
+    /// The version of the hook configuration. This is a hash of the registered
+    /// hooks with their configs and of the hooks set for each bookmark, so
+    /// managers loaded from the same config by the same build agree on it.
+    pub fn config_version(&self) -> u64 {
+        self.config_version
+    }
+
+    fn update_config_version(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        for (hook_name, hook) in self.hooks.iter().sorted_by_key(|(name, _)| *name) {
+            hook_name.hash(&mut hasher);
+            hook.hash_config(&mut hasher);
+        }
+        hash_sorted(&self.bookmark_hooks, &mut hasher);
+        for (regex, hooks) in &self.regex_hooks {
+            regex.as_str().hash(&mut hasher);
+            hooks.hash(&mut hasher);
+        }
+        self.config_version = hasher.finish();
     }
 
     pub(crate) fn get_reviewers_perm_checker(&self) -> ArcMembershipChecker {
@@ -403,6 +433,20 @@ impl Hook {
         }
     }
 
+    fn hash_config<H: Hasher>(&self, state: &mut H) {
+        let (kind, config) = match self {
+            Self::Changeset(_, config) => ("changeset", config),
+            Self::File(_, config) => ("file", config),
+        };
+        kind.hash(state);
+        // The bypass has no maps, so its debug output is stable
+        format!("{:?}", config.bypass).hash(state);
+        hash_sorted(&config.strings, state);
+        hash_sorted(&config.ints, state);
+        hash_sorted(&config.string_lists, state);
+        hash_sorted(&config.int_lists, state);
+    }
+
     pub fn get_futures<'a: 'cs, 'cs>(
         &'a self,
         ctx: &'a CoreContext,
@@ -447,6 +491,11 @@ impl Hook {
     }
 }
 
+/// Hash a map in key order, as its iteration order isn't stable
+fn hash_sorted<K: Hash + Ord, V: Hash + Ord, H: Hasher>(map: &HashMap<K, V>, state: &mut H) {
+    map.iter().sorted().collect::<Vec<_>>().hash(state);
+}
+
 #[async_trait]
 pub trait ChangesetHook: Send + Sync {
     async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
@@ -9,7 +9,9 @@
 
 pub mod types;
 
+mod progress;
 mod queue;
+pub use progress::{MegarepoRequestPhase, MegarepoRequestProgress};
 pub use queue::{AsyncMethodRequestQueue, ClaimedBy, RequestId};
 
 pub mod tokens {
@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{anyhow, Error};
+use requests_table::ProgressRecord;
+use std::fmt;
+use std::str::FromStr;
+
+/// Coarse stage a long-running megarepo request is in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MegarepoRequestPhase {
+    /// Validating the request and creating move commits for each source
+    Planning,
+    /// Merging the moved sources together
+    Merging,
+    /// Deriving data for the result and moving the target bookmark
+    Committing,
+}
+
+impl fmt::Display for MegarepoRequestPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MegarepoRequestPhase::*;
+
+        let s = match self {
+            Planning => "planning",
+            Merging => "merging",
+            Committing => "committing",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for MegarepoRequestPhase {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use MegarepoRequestPhase::*;
+
+        match s {
+            "planning" => Ok(Planning),
+            "merging" => Ok(Merging),
+            "committing" => Ok(Committing),
+            _ => Err(anyhow!("unknown megarepo request phase: {}", s)),
+        }
+    }
+}
+
+/// How far a long-running megarepo request has got. Workers store the latest
+/// value in the requests table, so that whoever polls the request can see it
+/// advance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MegarepoRequestProgress {
+    pub phase: MegarepoRequestPhase,
+    /// Number of sources that have been moved into place so far
+    pub sources_merged: usize,
+    /// Number of files in the sources moved so far
+    pub files_processed: usize,
+}
+
+impl MegarepoRequestProgress {
+    pub fn new(phase: MegarepoRequestPhase) -> Self {
+        Self {
+            phase,
+            sources_merged: 0,
+            files_processed: 0,
+        }
+    }
+}
+
+impl fmt::Display for MegarepoRequestProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.phase, self.sources_merged, self.files_processed
+        )
+    }
+}
+
+impl FromStr for MegarepoRequestProgress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(phase), Some(sources_merged), Some(files_processed), None) => Ok(Self {
+                phase: phase.parse()?,
+                sources_merged: sources_merged.parse()?,
+                files_processed: files_processed.parse()?,
+            }),
+            _ => Err(anyhow!("malformed megarepo request progress: {}", s)),
+        }
+    }
+}
+
+impl From<MegarepoRequestProgress> for ProgressRecord {
+    fn from(progress: MegarepoRequestProgress) -> Self {
+        ProgressRecord(progress.to_string())
+    }
+}
+
+impl TryFrom<ProgressRecord> for MegarepoRequestProgress {
+    type Error = Error;
+
+    fn try_from(record: ProgressRecord) -> Result<Self, Self::Error> {
+        record.0.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_record_roundtrip() -> Result<(), Error> {
+        let progress = MegarepoRequestProgress {
+            phase: MegarepoRequestPhase::Merging,
+            sources_merged: 3,
+            files_processed: 1200,
+        };
+        let record: ProgressRecord = progress.into();
+        assert_eq!(record.0, "merging:3:1200");
+        assert_eq!(MegarepoRequestProgress::try_from(record)?, progress);
+
+        assert!("merging:3".parse::<MegarepoRequestProgress>().is_err());
+        assert!("sleeping:0:0".parse::<MegarepoRequestProgress>().is_err());
+        Ok(())
+    }
+}
@@ -25,6 +25,7 @@ use sql_construct::SqlConstruct;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::progress::MegarepoRequestProgress;
 use crate::types::{
     BlobstoreKeyWrapper, MegarepoAsynchronousRequestParams, MegarepoAsynchronousRequestResult,
     Request, ThriftParams, Token,
@@ -186,6 +187,38 @@ impl AsyncMethodRequestQueue {
         Ok(self.table.update_in_progress_timestamp(ctx, req_id).await?)
     }
 
+    /// Store the latest progress of a request that's being worked on. Like
+    /// update_in_progress_timestamp, returns false if the request is no
+    /// longer in progress.
+    pub async fn update_progress(
+        &self,
+        ctx: &CoreContext,
+        req_id: &RequestId,
+        progress: MegarepoRequestProgress,
+    ) -> Result<bool, MegarepoError> {
+        Ok(self
+            .table
+            .update_progress(ctx, req_id, &progress.into())
+            .await?)
+    }
+
+    /// Get the last progress reported for the request this token refers to,
+    /// or None if no progress has been reported yet.
+    pub async fn progress<T: Token>(
+        &self,
+        ctx: &CoreContext,
+        token: T,
+    ) -> Result<Option<MegarepoRequestProgress>, MegarepoError> {
+        let (row_id, _target) = token.to_db_id_and_target()?;
+        let req_id = RequestId(row_id, RequestType(T::R::NAME.to_owned()));
+        match self.table.get_progress(ctx, &req_id).await? {
+            Some(record) => Ok(Some(
+                MegarepoRequestProgress::try_from(record).map_err(MegarepoError::internal)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
     pub async fn find_abandoned_requests(
         &self,
         ctx: &CoreContext,
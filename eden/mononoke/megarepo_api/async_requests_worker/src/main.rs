@@ -8,6 +8,7 @@
 #![feature(async_closure)]
 
 mod methods;
+mod result_cache;
 mod worker;
 
 use std::sync::atomic::{AtomicBool, Ordering};
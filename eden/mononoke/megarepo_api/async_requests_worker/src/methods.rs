@@ -14,12 +14,16 @@
 
 use anyhow::anyhow;
 use async_requests::types::{MegarepoAsynchronousRequestParams, MegarepoAsynchronousRequestResult};
+use async_requests::MegarepoRequestProgress;
 use context::CoreContext;
-use megarepo_api::MegarepoApi;
+use megarepo_api::{AddSyncTargetPlan, MegarepoApi};
 use megarepo_error::MegarepoError;
 use mononoke_types::ChangesetId;
 use source_control as thrift;
 use std::collections::HashMap;
+use std::io;
+
+use crate::result_cache::RequestResultCache;
 
 async fn megarepo_sync_changeset(
     ctx: &CoreContext,
@@ -50,6 +54,7 @@ async fn megarepo_add_sync_target(
     ctx: &CoreContext,
     megarepo_api: &MegarepoApi,
     params: thrift::MegarepoAddTargetParams,
+    progress: &(dyn Fn(MegarepoRequestProgress) + Send + Sync),
 ) -> Result<thrift::MegarepoAddTargetResponse, MegarepoError> {
     let config = params.config_with_new_target;
     let mut changesets_to_merge = HashMap::new();
@@ -58,7 +63,7 @@ async fn megarepo_add_sync_target(
         changesets_to_merge.insert(s, cs_id);
     }
     let cs_id = megarepo_api
-        .add_sync_target(&ctx, config, changesets_to_merge, params.message)
+        .add_sync_target(&ctx, config, changesets_to_merge, params.message, progress)
         .await?
         .as_ref()
         .into();
@@ -68,6 +73,47 @@ async fn megarepo_add_sync_target(
     })
 }
 
+async fn megarepo_add_sync_target_dry_run(
+    ctx: &CoreContext,
+    megarepo_api: &MegarepoApi,
+    params: thrift::MegarepoAddTargetParams,
+) -> Result<thrift::MegarepoAddTargetResponse, MegarepoError> {
+    let config = params.config_with_new_target;
+    let mut changesets_to_merge = HashMap::new();
+    for (s, cs_id) in params.changesets_to_merge {
+        let cs_id = ChangesetId::from_bytes(cs_id).map_err(MegarepoError::request)?;
+        changesets_to_merge.insert(s, cs_id);
+    }
+    let plan = megarepo_api
+        .plan_add_sync_target(&ctx, config, changesets_to_merge)
+        .await?;
+    Ok(thrift::MegarepoAddTargetResponse {
+        plan: Some(plan_into_thrift(plan)),
+        ..Default::default()
+    })
+}
+
+fn plan_into_thrift(plan: AddSyncTargetPlan) -> thrift::MegarepoAddTargetPlan {
+    thrift::MegarepoAddTargetPlan {
+        source_file_counts: plan
+            .source_file_counts
+            .into_iter()
+            .map(|(source, count)| (source.0, count as i64))
+            .collect(),
+        file_count: plan.file_count as i64,
+        conflicts: plan
+            .conflicts
+            .into_iter()
+            .map(|conflict| thrift::MegarepoPathConflict {
+                path: conflict.path.to_string(),
+                sources: conflict.sources.into_iter().map(|s| s.0).collect(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
 async fn megarepo_add_branching_sync_target(
     ctx: &CoreContext,
     megarepo_api: &MegarepoApi,
@@ -146,47 +192,147 @@ async fn megarepo_remerge_source(
     })
 }
 
+/// How a request computation ended, so the worker knows whether to retry it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ComputeStatus {
+    Success,
+    /// The request failed in a way that won't change if it's retried, e.g.
+    /// malformed params.
+    PermanentFailure,
+    /// The request failed due to a likely transient problem, e.g. a storage
+    /// timeout, and may succeed if it's retried.
+    RetryableFailure,
+}
+
+/// Storage and network timeouts are transient, so internal errors caused by
+/// them are marked as retryable. Request errors are always permanent.
+fn classify_error(error: MegarepoError) -> MegarepoError {
+    match error {
+        MegarepoError::InternalError(internal) => {
+            let is_timeout = internal.0.chain().any(|cause| {
+                cause.is::<tokio::time::error::Elapsed>()
+                    || cause
+                        .downcast_ref::<io::Error>()
+                        .map_or(false, |e| e.kind() == io::ErrorKind::TimedOut)
+            });
+            if is_timeout {
+                MegarepoError::RetryableError(internal)
+            } else {
+                MegarepoError::InternalError(internal)
+            }
+        }
+        error => error,
+    }
+}
+
+/// Converts a method result, noting how the computation ended.
+fn into_request_result<R>(
+    result: Result<R, MegarepoError>,
+) -> (MegarepoAsynchronousRequestResult, ComputeStatus)
+where
+    Result<R, MegarepoError>: Into<MegarepoAsynchronousRequestResult>,
+{
+    let result = result.map_err(classify_error);
+    let status = match &result {
+        Ok(_) => ComputeStatus::Success,
+        Err(e) if e.is_retryable() => ComputeStatus::RetryableFailure,
+        Err(_) => ComputeStatus::PermanentFailure,
+    };
+    (result.into(), status)
+}
+
 /// Given the request params dispatches the request to the right processing
 /// funtion and returns the computation result. This function doesn't return
 /// `Result` as both successfull computation and error are part of
 /// `MegarepoAsynchronousRequestResult` structure.
+///
+/// If this worker recently computed the same params successfully, the cached
+/// result is returned instead. Errors are never cached, so failed requests are
+/// retried.
+///
+/// The returned status tells the caller whether a failure is worth retrying.
+///
+/// Methods that support it report how far they've got to `progress`.
 pub(crate) async fn megarepo_async_request_compute(
     ctx: &CoreContext,
     megarepo_api: &MegarepoApi,
+    cache: &RequestResultCache,
     params: MegarepoAsynchronousRequestParams,
-) -> MegarepoAsynchronousRequestResult {
+    progress: &(dyn Fn(MegarepoRequestProgress) + Send + Sync),
+) -> (MegarepoAsynchronousRequestResult, ComputeStatus) {
+    let params_id = *params.handle();
+    if let Some(result) = cache.get(&params_id) {
+        ctx.scuba()
+            .clone()
+            .log_with_msg("Returning cached result", None);
+        return (result, ComputeStatus::Success);
+    }
+
+    let (result, status) =
+        megarepo_async_request_dispatch(ctx, megarepo_api, params, progress).await;
+    if status == ComputeStatus::Success {
+        cache.insert(params_id, result.clone());
+    }
+    (result, status)
+}
+
+async fn megarepo_async_request_dispatch(
+    ctx: &CoreContext,
+    megarepo_api: &MegarepoApi,
+    params: MegarepoAsynchronousRequestParams,
+    progress: &(dyn Fn(MegarepoRequestProgress) + Send + Sync),
+) -> (MegarepoAsynchronousRequestResult, ComputeStatus) {
     match params.into() {
+        megarepo_types_thrift::MegarepoAsynchronousRequestParams::megarepo_add_target_params(params) if params.plan_only.unwrap_or(false) || params.dry_run.unwrap_or(false) => {
+            into_request_result(megarepo_add_sync_target_dry_run(ctx, megarepo_api, params).await)
+        }
         megarepo_types_thrift::MegarepoAsynchronousRequestParams::megarepo_add_target_params(params) => {
-            megarepo_add_sync_target(ctx, megarepo_api, params)
-                .await
-                .into()
+            into_request_result(megarepo_add_sync_target(ctx, megarepo_api, params, progress).await)
         }
         megarepo_types_thrift::MegarepoAsynchronousRequestParams::megarepo_add_branching_target_params(params) => {
-            megarepo_add_branching_sync_target(ctx, megarepo_api, params)
-                .await
-                .into()
+            into_request_result(megarepo_add_branching_sync_target(ctx, megarepo_api, params).await)
         }
         megarepo_types_thrift::MegarepoAsynchronousRequestParams::megarepo_change_target_params(params) => {
-            megarepo_change_target_config(ctx, megarepo_api, params)
-                .await
-                .into()
+            into_request_result(megarepo_change_target_config(ctx, megarepo_api, params).await)
         }
         megarepo_types_thrift::MegarepoAsynchronousRequestParams::megarepo_remerge_source_params(params) => {
-            megarepo_remerge_source(ctx, megarepo_api, params)
-                .await
-                .into()
+            into_request_result(megarepo_remerge_source(ctx, megarepo_api, params).await)
         }
         megarepo_types_thrift::MegarepoAsynchronousRequestParams::megarepo_sync_changeset_params(params) => {
-            megarepo_sync_changeset(ctx, megarepo_api, params)
-                .await
-                .into()
+            into_request_result(megarepo_sync_changeset(ctx, megarepo_api, params).await)
         }
         megarepo_types_thrift::MegarepoAsynchronousRequestParams::UnknownField(union_tag) => {
-            Err::<thrift::MegarepoRemergeSourceResponse, _>(MegarepoError::internal(anyhow!(
+            into_request_result(Err::<thrift::MegarepoRemergeSourceResponse, _>(MegarepoError::internal(anyhow!(
                 "this type of reuqest (MegarepoAsynchronousRequestParams tag {}) not supported by this worker!", union_tag
-            )))
-            .into()
+            ))))
 
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_classify_error() {
+        let elapsed =
+            tokio::time::timeout(Duration::from_millis(1), futures::future::pending::<()>())
+                .await
+                .unwrap_err();
+        let timeout = MegarepoError::internal(
+            anyhow::Error::from(elapsed).context("while loading changeset"),
+        );
+        assert!(classify_error(timeout).is_retryable());
+
+        let io_timeout = || io::Error::new(io::ErrorKind::TimedOut, "connection timed out");
+        assert!(classify_error(MegarepoError::internal(io_timeout())).is_retryable());
+
+        let internal = MegarepoError::internal(anyhow!("corrupt manifest"));
+        assert!(!classify_error(internal).is_retryable());
+
+        // Request errors won't get better on a retry, whatever their cause
+        assert!(!classify_error(MegarepoError::request(io_timeout())).is_retryable());
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! A bounded in-memory cache of successful request results.
+//!
+//! The cache is local to one worker process and starts out empty, so it does
+//! not help after the worker restarts or when another worker takes a request
+//! over. It only saves redoing the work when this worker is given params it
+//! completed recently, e.g. when a client enqueues the same request again.
+//! Only successful results are cached, so failed requests are always retried.
+
+use async_requests::types::{
+    MegarepoAsynchronousRequestParamsId, MegarepoAsynchronousRequestResult,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_RESULT_CACHE_CAPACITY: usize = 1000;
+pub const DEFAULT_RESULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+struct CachedResult {
+    result: MegarepoAsynchronousRequestResult,
+    inserted: Instant,
+}
+
+struct Inner {
+    results: HashMap<MegarepoAsynchronousRequestParamsId, CachedResult>,
+    // Insertion order, oldest first, for eviction
+    order: VecDeque<MegarepoAsynchronousRequestParamsId>,
+}
+
+pub struct RequestResultCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl RequestResultCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::new(Inner {
+                results: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// The stored result for these params, if there is one that has not expired.
+    pub fn get(
+        &self,
+        params_id: &MegarepoAsynchronousRequestParamsId,
+    ) -> Option<MegarepoAsynchronousRequestResult> {
+        let mut inner = self.inner.lock().expect("lock poisoned");
+        match inner.results.get(params_id) {
+            Some(cached) if cached.inserted.elapsed() < self.ttl => Some(cached.result.clone()),
+            Some(_) => {
+                inner.results.remove(params_id);
+                inner.order.retain(|id| id != params_id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store a successful result, evicting the oldest entry if full.
+    pub fn insert(
+        &self,
+        params_id: MegarepoAsynchronousRequestParamsId,
+        result: MegarepoAsynchronousRequestResult,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().expect("lock poisoned");
+        let cached = CachedResult {
+            result,
+            inserted: Instant::now(),
+        };
+        if inner.results.insert(params_id, cached).is_some() {
+            inner.order.retain(|id| id != &params_id);
+        }
+        inner.order.push_back(params_id);
+        while inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.results.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_requests::types::MegarepoAsynchronousRequestParams;
+
+    fn result() -> MegarepoAsynchronousRequestResult {
+        MegarepoAsynchronousRequestResult::from_thrift(Default::default())
+    }
+
+    #[test]
+    fn test_result_cache_bounded() {
+        let cache = RequestResultCache::new(2, DEFAULT_RESULT_CACHE_TTL);
+        let ids: Vec<_> = (0..3)
+            .map(|i| MegarepoAsynchronousRequestParamsId::from_byte_array([i; 32]))
+            .collect();
+
+        for id in &ids {
+            cache.insert(*id, result());
+        }
+        // The oldest entry was evicted
+        assert_eq!(cache.get(&ids[0]), None);
+        assert_eq!(cache.get(&ids[1]), Some(result()));
+        assert_eq!(cache.get(&ids[2]), Some(result()));
+    }
+
+    #[test]
+    fn test_result_cache_expires() {
+        let cache = RequestResultCache::new(2, Duration::from_secs(0));
+        let id = *MegarepoAsynchronousRequestParams::from_thrift(Default::default()).handle();
+        cache.insert(id, result());
+        assert_eq!(cache.get(&id), None);
+    }
+}
@@ -13,9 +13,13 @@
 //! One important consideration to keep in mind - worker executes request "at least once"
 //! but not exactly once i.e. the same request might be executed a few times.
 
-use crate::methods::megarepo_async_request_compute;
+use crate::methods::{megarepo_async_request_compute, ComputeStatus};
+use crate::result_cache::{
+    RequestResultCache, DEFAULT_RESULT_CACHE_CAPACITY, DEFAULT_RESULT_CACHE_TTL,
+};
 use async_requests::{
-    types::MegarepoAsynchronousRequestParams, AsyncMethodRequestQueue, ClaimedBy, RequestId,
+    types::MegarepoAsynchronousRequestParams, AsyncMethodRequestQueue, ClaimedBy,
+    MegarepoRequestProgress, RequestId,
 };
 use async_stream::try_stream;
 use cloned::cloned;
@@ -32,7 +36,7 @@ use megarepo_error::MegarepoError;
 use mononoke_types::{RepositoryId, Timestamp};
 use slog::{debug, error, info};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 const DEQUEUE_STREAM_SLEEP_TIME: u64 = 1000;
@@ -40,11 +44,17 @@ const DEQUEUE_STREAM_SLEEP_TIME: u64 = 1000;
 // if it hasn't updated inprogress timestamp
 const ABANDONED_REQUEST_THRESHOLD_SECS: i64 = 5 * 60;
 const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// How many times a request failing with a retryable error is computed
+/// before its error is saved as the result.
+const MAX_COMPUTE_ATTEMPTS: usize = 5;
+/// Delay before the first retry, doubled for each retry after that.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
 
 #[derive(Clone)]
 pub struct AsyncMethodRequestWorker {
     megarepo: Arc<MegarepoApi>,
     name: String,
+    result_cache: Arc<RequestResultCache>,
 }
 
 impl AsyncMethodRequestWorker {
@@ -53,7 +63,14 @@ impl AsyncMethodRequestWorker {
     /// in the queue table so it's possible to find out which instance is working on
     /// a given task (for debugging purposes).
     pub fn new(megarepo: Arc<MegarepoApi>, name: String) -> Self {
-        Self { megarepo, name }
+        Self {
+            megarepo,
+            name,
+            result_cache: Arc::new(RequestResultCache::new(
+                DEFAULT_RESULT_CACHE_CAPACITY,
+                DEFAULT_RESULT_CACHE_TTL,
+            )),
+        }
     }
 
     /// Start async request worker.
@@ -209,14 +226,54 @@ impl AsyncMethodRequestWorker {
 
         let ctx = self.prepare_ctx(&ctx, &req_id, &target);
 
-        // Do the actual work.
-        let work_fut = megarepo_async_request_compute(&ctx, &self.megarepo, params);
+        // The latest progress reported by the method. The keep-alive loop
+        // saves it together with the inprogress timestamp.
+        let progress = Arc::new(Mutex::new(None));
+        let report_progress = {
+            cloned!(progress);
+            move |p: MegarepoRequestProgress| {
+                *progress.lock().expect("lock poisoned") = Some(p);
+            }
+        };
+
+        // Do the actual work, retrying transient failures with backoff.
+        let work_fut = async {
+            let mut delay = INITIAL_RETRY_DELAY;
+            let mut attempt = 1;
+            loop {
+                let (result, status) = megarepo_async_request_compute(
+                    &ctx,
+                    &self.megarepo,
+                    &self.result_cache,
+                    params.clone(),
+                    &report_progress,
+                )
+                .await;
+                if status != ComputeStatus::RetryableFailure || attempt >= MAX_COMPUTE_ATTEMPTS {
+                    break result;
+                }
+                info!(
+                    ctx.logger(),
+                    "[{}] attempt {} failed with a retryable error, retrying in {:?}",
+                    &req_id.0,
+                    attempt,
+                    delay,
+                );
+                ctx.scuba()
+                    .clone()
+                    .add("attempt", attempt)
+                    .log_with_msg("Retrying request", None);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        };
 
         // Start the loop that would keep saying that request is still being
         // processed
         let (keep_alive, keep_alive_abort_handle) = abortable({
             cloned!(ctx, req_id, queue);
-            async move { Self::keep_alive_loop(&ctx, &req_id, &queue).await }
+            async move { Self::keep_alive_loop(&ctx, &req_id, &queue, &progress).await }
         });
 
         let keep_alive = tokio::spawn(keep_alive);
@@ -293,12 +350,20 @@ impl AsyncMethodRequestWorker {
         ctx: &CoreContext,
         req_id: &RequestId,
         queue: &AsyncMethodRequestQueue,
+        progress: &Mutex<Option<MegarepoRequestProgress>>,
     ) {
         loop {
             let mut scuba = ctx.scuba().clone();
             ctx.perf_counters().insert_perf_counters(&mut scuba);
 
-            let res = queue.update_in_progress_timestamp(&ctx, &req_id).await;
+            let latest_progress = *progress.lock().expect("lock poisoned");
+            let res = match latest_progress {
+                Some(latest_progress) => {
+                    scuba.add("progress", latest_progress.to_string());
+                    queue.update_progress(&ctx, &req_id, latest_progress).await
+                }
+                None => queue.update_in_progress_timestamp(&ctx, &req_id).await,
+            };
             match res {
                 Ok(res) => {
                     // Weren't able to update inprogress timestamp - that probably means
@@ -22,7 +22,7 @@ mod oss;
 mod test_impl;
 mod verification;
 
-pub use verification::verify_config;
+pub use verification::{validate_config, verify_config, ConfigValidationError};
 
 #[cfg(fbcode_build)]
 pub use facebook::CfgrMononokeMegarepoConfigs;
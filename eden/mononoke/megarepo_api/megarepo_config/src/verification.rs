@@ -9,21 +9,124 @@ use anyhow::{anyhow, Result};
 use context::CoreContext;
 use megarepo_configs::types::SyncTargetConfig;
 use slog::warn;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+/// A problem with a config that can be found from the config alone,
+/// without looking at the contents of any repo
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigValidationError {
+    /// More than one source has this source name
+    DuplicateSourceName(String),
+    /// More than one source maps files under this target prefix, either as
+    /// their default prefix or as an override destination
+    PrefixConflict {
+        prefix: String,
+        sources: Vec<String>,
+    },
+    /// A linkfile path that is written more than once, or that is also a
+    /// prefix files are mapped under
+    PathOverlap { path: String, sources: Vec<String> },
+}
+
+impl fmt::Display for ConfigValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateSourceName(source_name) => {
+                write!(f, "Non-unique source name: {}", source_name)
+            }
+            Self::PrefixConflict { prefix, sources } => write!(
+                f,
+                "Prefix '{}' is used by more than one source: {}",
+                prefix,
+                sources.join(", ")
+            ),
+            Self::PathOverlap { path, sources } => write!(
+                f,
+                "Linkfile '{}' overlaps other paths of sources: {}",
+                path,
+                sources.join(", ")
+            ),
+        }
+    }
+}
 
 /// Verify the config
 pub fn verify_config(ctx: &CoreContext, config: &SyncTargetConfig) -> Result<()> {
     verify_unique_source_names(ctx, config)
 }
 
-fn verify_unique_source_names(ctx: &CoreContext, config: &SyncTargetConfig) -> Result<()> {
+/// Run every check that can be made on the config alone and return all the
+/// problems found, rather than failing on the first. This includes the checks
+/// made by verify_config, as well as path conflicts that would otherwise only
+/// make a request fail once the sources are merged.
+pub fn validate_config(config: &SyncTargetConfig) -> Vec<ConfigValidationError> {
+    let mut errors: Vec<_> = duplicate_source_names(config)
+        .into_iter()
+        .map(ConfigValidationError::DuplicateSourceName)
+        .collect();
+
+    // Target prefixes and linkfiles, with the sources that write them
+    let mut prefixes: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut linkfiles: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for src in &config.sources {
+        let mapping = &src.mapping;
+        let src_prefixes = std::iter::once(&mapping.default_prefix)
+            .chain(mapping.overrides.values().flatten())
+            .map(|prefix| prefix.trim_matches('/'))
+            .collect::<BTreeSet<_>>();
+        for prefix in src_prefixes {
+            prefixes.entry(prefix).or_default().insert(&src.source_name);
+        }
+        for link in mapping.linkfiles.keys() {
+            linkfiles
+                .entry(link.trim_matches('/'))
+                .or_default()
+                .insert(&src.source_name);
+        }
+    }
+
+    for (prefix, sources) in &prefixes {
+        if sources.len() > 1 {
+            errors.push(ConfigValidationError::PrefixConflict {
+                prefix: prefix.to_string(),
+                sources: sources.iter().map(|s| s.to_string()).collect(),
+            });
+        }
+    }
+    for (path, mut sources) in linkfiles {
+        let is_prefix = match prefixes.get(path) {
+            Some(prefix_sources) => {
+                sources.extend(prefix_sources);
+                true
+            }
+            None => false,
+        };
+        if is_prefix || sources.len() > 1 {
+            errors.push(ConfigValidationError::PathOverlap {
+                path: path.to_string(),
+                sources: sources.iter().map(|s| s.to_string()).collect(),
+            });
+        }
+    }
+
+    errors
+}
+
+// Source names used by more than one source
+fn duplicate_source_names(config: &SyncTargetConfig) -> BTreeSet<String> {
     let mut seen = HashSet::new();
-    let mut seen_more_than_once = HashSet::new();
+    let mut seen_more_than_once = BTreeSet::new();
     config.sources.iter().for_each(|src| {
         if !seen.insert(&src.source_name) {
-            seen_more_than_once.insert(&src.source_name);
+            seen_more_than_once.insert(src.source_name.clone());
         }
     });
+    seen_more_than_once
+}
+
+fn verify_unique_source_names(ctx: &CoreContext, config: &SyncTargetConfig) -> Result<()> {
+    let seen_more_than_once = duplicate_source_names(config);
 
     if !seen_more_than_once.is_empty() {
         warn!(
@@ -119,4 +222,40 @@ mod verification_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_validate_config() {
+        assert_eq!(validate_config(&get_good_cfg()), vec![]);
+
+        let mut cfg = get_good_cfg();
+        cfg.sources[1].mapping.default_prefix = s("pre/fix1/");
+        let linkfiles = &mut cfg.sources[1].mapping.linkfiles;
+        linkfiles.insert(s("link/source_1"), s("link_target_2"));
+        // Also an override destination of source1
+        linkfiles.insert(s("copiedfile"), s("link_target_3"));
+        let both = vec![s("source1"), s("source2")];
+        assert_eq!(
+            validate_config(&cfg),
+            vec![
+                ConfigValidationError::PrefixConflict {
+                    prefix: s("pre/fix1"),
+                    sources: both.clone(),
+                },
+                ConfigValidationError::PathOverlap {
+                    path: s("copiedfile"),
+                    sources: both.clone(),
+                },
+                ConfigValidationError::PathOverlap {
+                    path: s("link/source_1"),
+                    sources: both,
+                },
+            ]
+        );
+
+        cfg.sources[1].source_name = s("source1");
+        assert_eq!(
+            validate_config(&cfg)[0],
+            ConfigValidationError::DuplicateSourceName(s("source1"))
+        );
+    }
 }
@@ -59,6 +59,10 @@ pub enum MegarepoError {
     RequestError(#[source] RequestError),
     #[error("{0}")]
     InternalError(#[source] InternalError),
+    /// An internal error that is likely transient, e.g. a storage timeout,
+    /// so the request may succeed if it is tried again
+    #[error("{0}")]
+    RetryableError(#[source] InternalError),
 }
 
 impl MegarepoError {
@@ -69,6 +73,16 @@ impl MegarepoError {
     pub fn request(e: impl Into<Error>) -> Self {
         Self::RequestError(RequestError::from(e.into()))
     }
+
+    pub fn retryable(e: impl Into<Error>) -> Self {
+        Self::RetryableError(InternalError::from(e.into()))
+    }
+
+    /// Whether trying the request again may succeed. Request errors are
+    /// permanent, as are internal errors not known to be transient.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RetryableError(_))
+    }
 }
 
 /// By default, let's treat errors as internal
@@ -78,6 +92,7 @@ impl From<Error> for MegarepoError {
             Ok(megarepo_error) => match megarepo_error {
                 Self::RequestError(e) => Self::RequestError(e),
                 Self::InternalError(e) => Self::InternalError(e),
+                Self::RetryableError(e) => Self::RetryableError(e),
             },
             Err(orig) => Self::internal(orig),
         }
@@ -124,7 +139,7 @@ impl From<MegarepoError> for scs_thrift::MegarepoAsynchronousRequestError {
                 reason: format!("{}", e),
                 ..Default::default()
             }),
-            MegarepoError::InternalError(error) => {
+            MegarepoError::InternalError(error) | MegarepoError::RetryableError(error) => {
                 let reason = error.to_string();
                 let backtrace = error
                     .backtrace()
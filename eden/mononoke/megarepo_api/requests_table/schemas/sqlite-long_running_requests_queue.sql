@@ -18,7 +18,8 @@
   `ready_at` bigint(20) DEFAULT NULL,
   `polled_at` bigint(20) DEFAULT NULL,
   `status` VARCHAR(32) NOT NULL, -- enum('new','inprogress','ready','polled') NOT NULL DEFAULT 'new',
-  `claimed_by` VARCHAR(255) NULL
+  `claimed_by` VARCHAR(255) NULL,
+  `progress` VARCHAR(255) NULL
 );
 
 CREATE INDEX IF NOT EXISTS `request_status` ON long_running_request_queue (`status`, `request_type`);
@@ -18,7 +18,8 @@ mod types;
 
 pub use crate::store::SqlLongRunningRequestsQueue;
 pub use crate::types::{
-    BlobstoreKey, ClaimedBy, LongRunningRequestEntry, RequestId, RequestStatus, RequestType, RowId,
+    BlobstoreKey, ClaimedBy, LongRunningRequestEntry, ProgressRecord, RequestId, RequestStatus,
+    RequestType, RowId,
 };
 
 /// A queue of long-running requests
@@ -77,6 +78,22 @@ pub trait LongRunningRequestsQueue: Send + Sync {
         req_id: &RequestId,
     ) -> Result<bool>;
 
+    /// Store the latest progress record of an in-progress request. This
+    /// also updates the inprogress_last_updated_at timestamp.
+    async fn update_progress(
+        &self,
+        ctx: &CoreContext,
+        req_id: &RequestId,
+        progress: &ProgressRecord,
+    ) -> Result<bool>;
+
+    /// Get the last progress record stored for a request, if any
+    async fn get_progress(
+        &self,
+        ctx: &CoreContext,
+        req_id: &RequestId,
+    ) -> Result<Option<ProgressRecord>>;
+
     /// Find requests that have "inprogress" status but which timestamp
     /// hasn't been updated after `abandoned_timestamp`.
     async fn find_abandoned_requests(
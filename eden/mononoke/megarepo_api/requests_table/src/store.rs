@@ -16,7 +16,8 @@ use sql_ext::SqlConnections;
 
 use crate::LongRunningRequestsQueue;
 use crate::{
-    BlobstoreKey, ClaimedBy, LongRunningRequestEntry, RequestId, RequestStatus, RequestType, RowId,
+    BlobstoreKey, ClaimedBy, LongRunningRequestEntry, ProgressRecord, RequestId, RequestStatus,
+    RequestType, RowId,
 };
 
 queries! {
@@ -179,6 +180,25 @@ queries! {
         "
     }
 
+    write UpdateProgress(
+        id: RowId,
+        request_type: RequestType,
+        inprogress_last_updated_at: Timestamp,
+        progress: ProgressRecord,
+    ) {
+        none,
+        "UPDATE long_running_request_queue
+         SET inprogress_last_updated_at = {inprogress_last_updated_at}, progress = {progress}
+         WHERE id = {id} AND request_type = {request_type} AND status = 'inprogress'
+        "
+    }
+
+    read GetProgress(id: RowId, request_type: RequestType) -> (Option<ProgressRecord>,) {
+        "SELECT progress
+        FROM long_running_request_queue
+        WHERE id = {id} AND request_type = {request_type}"
+    }
+
     write MarkRequestReady(id: RowId, request_type: RequestType, ready_at: Timestamp, result_blobstore_key: BlobstoreKey) {
         none,
         "UPDATE long_running_request_queue
@@ -404,6 +424,37 @@ impl LongRunningRequestsQueue for SqlLongRunningRequestsQueue {
         Ok(res.affected_rows() > 0)
     }
 
+    async fn update_progress(
+        &self,
+        _ctx: &CoreContext,
+        req_id: &RequestId,
+        progress: &ProgressRecord,
+    ) -> Result<bool> {
+        let res = UpdateProgress::query(
+            &self.connections.write_connection,
+            &req_id.0,
+            &req_id.1,
+            &Timestamp::now(),
+            progress,
+        )
+        .await?;
+        Ok(res.affected_rows() > 0)
+    }
+
+    async fn get_progress(
+        &self,
+        _ctx: &CoreContext,
+        req_id: &RequestId,
+    ) -> Result<Option<ProgressRecord>> {
+        let rows = GetProgress::query(
+            &self.connections.read_master_connection,
+            &req_id.0,
+            &req_id.1,
+        )
+        .await?;
+        Ok(rows.into_iter().next().and_then(|(progress,)| progress))
+    }
+
     async fn find_abandoned_requests(
         &self,
         _ctx: &CoreContext,
@@ -712,4 +763,49 @@ mod test {
 
         Ok(())
     }
+
+    #[fbinit::test]
+    async fn test_update_progress(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let queue = SqlLongRunningRequestsQueue::with_sqlite_in_memory()?;
+        let request_type = RequestType("type".to_string());
+        let id = queue
+            .add_request(
+                &ctx,
+                &request_type,
+                &RepositoryId::new(0),
+                &BookmarkName::new("book")?,
+                &BlobstoreKey("key".to_string()),
+            )
+            .await?;
+        let req_id = RequestId(id, request_type);
+
+        // Progress can't be reported before the request is claimed
+        let progress = ProgressRecord("planning".to_string());
+        assert!(!queue.update_progress(&ctx, &req_id, &progress).await?);
+        assert_eq!(queue.get_progress(&ctx, &req_id).await?, None);
+
+        queue
+            .claim_and_get_new_request(&ctx, &ClaimedBy("me".to_string()), &[RepositoryId::new(0)])
+            .await?;
+        assert!(queue.update_progress(&ctx, &req_id, &progress).await?);
+        assert_eq!(queue.get_progress(&ctx, &req_id).await?, Some(progress));
+
+        // The last record wins, and it's kept after the request is ready
+        let progress = ProgressRecord("merging".to_string());
+        assert!(queue.update_progress(&ctx, &req_id, &progress).await?);
+        assert!(
+            queue
+                .mark_ready(&ctx, &req_id, BlobstoreKey("result".to_string()))
+                .await?
+        );
+        assert!(
+            !queue
+                .update_progress(&ctx, &req_id, &ProgressRecord("committing".to_string()))
+                .await?
+        );
+        assert_eq!(queue.get_progress(&ctx, &req_id).await?, Some(progress));
+
+        Ok(())
+    }
 }
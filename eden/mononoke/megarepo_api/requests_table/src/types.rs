@@ -92,6 +92,7 @@ macro_rules! mysql_string_newtype {
 mysql_string_newtype!(BlobstoreKey);
 mysql_string_newtype!(RequestType);
 mysql_string_newtype!(ClaimedBy);
+mysql_string_newtype!(ProgressRecord);
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, mysql::OptTryFromRowField)]
 pub enum RequestStatus {
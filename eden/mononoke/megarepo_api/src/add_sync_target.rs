@@ -6,17 +6,45 @@
  */
 
 use crate::common::MegarepoOp;
+use async_requests::{MegarepoRequestPhase, MegarepoRequestProgress};
 use bookmarks::BookmarkName;
 use context::CoreContext;
 use derived_data_utils::derived_data_utils;
-use futures::{future, stream::FuturesUnordered, TryFutureExt, TryStreamExt};
+use futures::{
+    future,
+    stream::{self, FuturesUnordered},
+    TryFutureExt, TryStreamExt,
+};
 use megarepo_config::{verify_config, MononokeMegarepoConfigs, SyncTargetConfig};
 use megarepo_error::MegarepoError;
 use megarepo_mapping::SourceName;
-use mononoke_api::{Mononoke, RepoContext};
+use mononoke_api::{Mononoke, MononokePath, RepoContext};
 use mononoke_types::ChangesetId;
 use mutable_renames::MutableRenames;
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::Arc,
+};
+
+/// What add_sync_target would produce for a config, worked out without
+/// creating any commits.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AddSyncTargetPlan {
+    /// Number of files each source puts in the target, including linkfiles
+    pub source_file_counts: BTreeMap<SourceName, usize>,
+    /// Number of distinct files in the merged target
+    pub file_count: usize,
+    /// Paths that would make the real run fail
+    pub conflicts: Vec<PathConflict>,
+}
+
+/// A target path that more than one source writes, or that one source
+/// writes as a file while another writes files under it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathConflict {
+    pub path: MononokePath,
+    pub sources: Vec<SourceName>,
+}
 
 // Create a new sync target given a config.
 // After this command finishes it creates
@@ -40,6 +68,7 @@ pub struct AddSyncTarget<'a> {
     pub megarepo_configs: &'a Arc<dyn MononokeMegarepoConfigs>,
     pub mononoke: &'a Arc<Mononoke>,
     pub mutable_renames: &'a Arc<MutableRenames>,
+    pub progress: Option<&'a (dyn Fn(MegarepoRequestProgress) + Send + Sync)>,
 }
 
 impl<'a> MegarepoOp for AddSyncTarget<'a> {
@@ -58,6 +87,23 @@ impl<'a> AddSyncTarget<'a> {
             megarepo_configs,
             mononoke,
             mutable_renames,
+            progress: None,
+        }
+    }
+
+    /// Report how far run() has got to `progress`. It's called once for
+    /// each phase, so it should be cheap and must not block.
+    pub fn with_progress(
+        mut self,
+        progress: &'a (dyn Fn(MegarepoRequestProgress) + Send + Sync),
+    ) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    fn report_progress(&self, progress: MegarepoRequestProgress) {
+        if let Some(report) = self.progress {
+            report(progress);
         }
     }
 
@@ -89,6 +135,8 @@ impl<'a> AddSyncTarget<'a> {
             return Ok(already_done);
         }
 
+        self.report_progress(MegarepoRequestProgress::new(MegarepoRequestPhase::Planning));
+
         // First let's create commit on top of all source commits that
         // move all files in a correct place
         let moved_commits = self
@@ -102,6 +150,17 @@ impl<'a> AddSyncTarget<'a> {
             .await?;
         scuba.log_with_msg("Created move commits", None);
 
+        let sources_merged = moved_commits.len();
+        let files_processed = moved_commits
+            .iter()
+            .map(|(_, css)| css.moved.file_changes_map().len())
+            .sum();
+        self.report_progress(MegarepoRequestProgress {
+            phase: MegarepoRequestPhase::Merging,
+            sources_merged,
+            files_processed,
+        });
+
         // Now let's merge all the moved commits together
         let top_merge_cs_id = self
             .create_merge_commits(
@@ -119,6 +178,11 @@ impl<'a> AddSyncTarget<'a> {
             Some(format!("{}", top_merge_cs_id)),
         );
 
+        self.report_progress(MegarepoRequestProgress {
+            phase: MegarepoRequestPhase::Committing,
+            sources_merged,
+            files_processed,
+        });
 
         // add_sync_target might need to derive a lot of data, and it takes a long time to
         // do it. We don't have any resumability, so if it fails for any reason, then we'd
@@ -172,6 +236,37 @@ impl<'a> AddSyncTarget<'a> {
         Ok(top_merge_cs_id)
     }
 
+    // Works out where each source's files land in the target and whether
+    // they conflict, without writing anything to the repo. Unlike run()
+    // conflicts are reported rather than failing.
+    pub async fn plan(
+        self,
+        ctx: &CoreContext,
+        sync_target_config: SyncTargetConfig,
+        changesets_to_merge: BTreeMap<SourceName, ChangesetId>,
+    ) -> Result<AddSyncTargetPlan, MegarepoError> {
+        verify_config(ctx, &sync_target_config).map_err(MegarepoError::request)?;
+
+        let this = &self;
+        let changesets_to_merge = &changesets_to_merge;
+        let source_paths = stream::iter(sync_target_config.sources.iter().map(Ok))
+            .map_ok(|source| async move {
+                let source_repo = this.find_repo_by_id(ctx, source.repo_id).await?;
+                let changeset_id = this
+                    .validate_changeset_to_merge(ctx, &source_repo, source, changesets_to_merge)
+                    .await?;
+                let paths = this
+                    .paths_in_target_belonging_to_source(ctx, source, changeset_id)
+                    .await?;
+                Ok::<_, MegarepoError>((SourceName(source.source_name.clone()), paths))
+            })
+            .try_buffer_unordered(10)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(plan_from_source_paths(source_paths))
+    }
+
     // If that add_sync_target() call was successful, but failed to send
     // successful result to the client (e.g. network issues) then
     // client will retry a request. We need to detect this situation and
@@ -226,3 +321,51 @@ impl<'a> AddSyncTarget<'a> {
         Ok(Some(cs_id))
     }
 }
+
+fn plan_from_source_paths(
+    source_paths: Vec<(SourceName, HashSet<MononokePath>)>,
+) -> AddSyncTargetPlan {
+    let mut source_file_counts = BTreeMap::new();
+    let mut file_sources: BTreeMap<MononokePath, BTreeSet<SourceName>> = BTreeMap::new();
+    let mut dir_sources: BTreeMap<MononokePath, BTreeSet<SourceName>> = BTreeMap::new();
+    for (source_name, paths) in source_paths {
+        source_file_counts.insert(source_name.clone(), paths.len());
+        for path in paths {
+            // The root is the parent of everything, it can't conflict
+            for dir in path.prefixes().filter(|dir| dir.as_mpath().is_some()) {
+                dir_sources
+                    .entry(dir)
+                    .or_default()
+                    .insert(source_name.clone());
+            }
+            file_sources
+                .entry(path)
+                .or_default()
+                .insert(source_name.clone());
+        }
+    }
+
+    let mut conflicts = vec![];
+    for (path, sources) in &file_sources {
+        let mut sources = sources.clone();
+        let is_also_dir = match dir_sources.get(path) {
+            Some(dir_sources) => {
+                sources.extend(dir_sources.iter().cloned());
+                true
+            }
+            None => false,
+        };
+        if is_also_dir || sources.len() > 1 {
+            conflicts.push(PathConflict {
+                path: path.clone(),
+                sources: sources.into_iter().collect(),
+            });
+        }
+    }
+
+    AddSyncTargetPlan {
+        source_file_counts,
+        file_count: file_sources.len(),
+        conflicts,
+    }
+}
@@ -5,7 +5,7 @@
  * GNU General Public License version 2.
  */
 
-use crate::add_sync_target::AddSyncTarget;
+use crate::add_sync_target::{AddSyncTarget, PathConflict};
 use crate::megarepo_test_utils::{MegarepoTest, SyncTargetConfigBuilder};
 use crate::sync_changeset::SyncChangeset;
 use anyhow::Error;
@@ -16,6 +16,7 @@ use maplit::{btreemap, hashmap};
 use megarepo_config::MononokeMegarepoConfigs;
 use megarepo_config::Target;
 use megarepo_mapping::{CommitRemappingState, SourceName, REMAPPING_STATE_FILE};
+use mononoke_api::MononokePath;
 use mononoke_types::{FileType, MPath};
 use std::sync::Arc;
 use tests_utils::{
@@ -786,3 +787,88 @@ async fn test_add_sync_target_repeat_same_request(fb: FacebookInit) -> Result<()
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn test_add_sync_target_plan(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mut test = MegarepoTest::new(&ctx).await?;
+    let target: Target = test.target("target".to_string());
+
+    let first_source_name = SourceName::new("source_1");
+    let second_source_name = SourceName::new("source_2");
+    let version = "version_1".to_string();
+    // Both sources are put under source_1/
+    SyncTargetConfigBuilder::new(test.repo_id(), target.clone(), version.clone())
+        .source_builder(first_source_name.clone())
+        .set_prefix_bookmark_to_source_name()
+        .build_source()?
+        .source_builder(second_source_name.clone())
+        .default_prefix("source_1")
+        .bookmark("source_2")
+        .build_source()?
+        .build(&mut test.configs_storage);
+
+    let first_source_cs_id = CreateCommitContext::new_root(&ctx, &test.blobrepo)
+        .add_file("first", "first")
+        .add_file("dir", "dir")
+        .commit()
+        .await?;
+    bookmark(&ctx, &test.blobrepo, first_source_name.to_string())
+        .set_to(first_source_cs_id)
+        .await?;
+
+    let second_source_cs_id = CreateCommitContext::new_root(&ctx, &test.blobrepo)
+        .add_file("first", "other first")
+        .add_file("dir/file", "file")
+        .add_file("second", "second")
+        .commit()
+        .await?;
+    bookmark(&ctx, &test.blobrepo, second_source_name.to_string())
+        .set_to(second_source_cs_id)
+        .await?;
+
+    let configs_storage: Arc<dyn MononokeMegarepoConfigs> = Arc::new(test.configs_storage.clone());
+    let sync_target_config =
+        test.configs_storage
+            .get_config_by_version(ctx.clone(), target.clone(), version.clone())?;
+
+    let add_sync_target =
+        AddSyncTarget::new(&configs_storage, &test.mononoke, &test.mutable_renames);
+    let plan = add_sync_target
+        .plan(
+            &ctx,
+            sync_target_config,
+            btreemap! {
+                first_source_name.clone() => first_source_cs_id,
+                second_source_name.clone() => second_source_cs_id,
+            },
+        )
+        .await?;
+
+    assert_eq!(
+        plan.source_file_counts,
+        btreemap! {
+            first_source_name.clone() => 2,
+            second_source_name.clone() => 3,
+        }
+    );
+    assert_eq!(plan.file_count, 4);
+    assert_eq!(
+        plan.conflicts,
+        vec![
+            PathConflict {
+                path: MononokePath::new(Some(MPath::new("source_1/dir")?)),
+                sources: vec![first_source_name.clone(), second_source_name.clone()],
+            },
+            PathConflict {
+                path: MononokePath::new(Some(MPath::new("source_1/first")?)),
+                sources: vec![first_source_name, second_source_name],
+            },
+        ]
+    );
+
+    // Nothing was created
+    assert!(resolve_cs_id(&ctx, &test.blobrepo, "target").await.is_err());
+
+    Ok(())
+}
@@ -10,17 +10,19 @@
 
 use add_branching_sync_target::AddBranchingSyncTarget;
 use add_sync_target::AddSyncTarget;
-use anyhow::{bail, Error};
+use anyhow::{anyhow, bail, Error};
 use async_once_cell::AsyncOnceCell;
-use async_requests::AsyncMethodRequestQueue;
+use async_requests::{AsyncMethodRequestQueue, MegarepoRequestProgress};
 use blobstore::Blobstore;
 use change_target_config::ChangeTargetConfig;
+use common::{find_target_bookmark_and_value, find_target_sync_config};
 use context::CoreContext;
 use environment::MononokeEnvironment;
 use futures::future::try_join_all;
 use megarepo_config::{
-    CfgrMononokeMegarepoConfigs, MononokeMegarepoConfigs, MononokeMegarepoConfigsOptions,
-    SyncConfigVersion, SyncTargetConfig, Target, TestMononokeMegarepoConfigs,
+    validate_config, CfgrMononokeMegarepoConfigs, ConfigValidationError, MononokeMegarepoConfigs,
+    MononokeMegarepoConfigsOptions, SyncConfigVersion, SyncTargetConfig, Target,
+    TestMononokeMegarepoConfigs,
 };
 use megarepo_error::MegarepoError;
 use megarepo_mapping::{MegarepoMapping, SourceName};
@@ -57,6 +59,8 @@ mod remerge_source;
 mod remerge_source_test;
 mod sync_changeset;
 
+pub use add_sync_target::{AddSyncTargetPlan, PathConflict};
+
 /// A cache for AsyncMethodRequestQueue instances
 #[derive(Clone)]
 struct Cache<K: Clone + Eq + Hash, V: Clone> {
@@ -379,19 +383,78 @@ impl MegarepoApi {
         res
     }
 
+    /// Returns the config currently applied to the target, without changing
+    /// anything, along with the commit the target's bookmark points to. That
+    /// commit's remapping state records which config version is applied.
+    pub async fn read_target_config(
+        &self,
+        ctx: &CoreContext,
+        target: &Target,
+    ) -> Result<(SyncTargetConfig, ChangesetId), MegarepoError> {
+        let target_repo_id = RepositoryId::new(target.repo_id.try_into().unwrap());
+        let target_repo = self
+            .mononoke
+            .repo_by_id_bypass_acl_check(ctx.clone(), target_repo_id)
+            .await
+            .map_err(MegarepoError::internal)?
+            .ok_or_else(|| MegarepoError::request(anyhow!("repo not found {}", target_repo_id)))?;
+
+        let (_, cs_id) = find_target_bookmark_and_value(ctx, &target_repo, target).await?;
+        let (_, config) = find_target_sync_config(
+            ctx,
+            target_repo.blob_repo(),
+            cs_id,
+            target,
+            &self.megarepo_configs,
+        )
+        .await?;
+
+        Ok((config, cs_id))
+    }
+
+    /// Checks a config for problems that can be found from the config alone,
+    /// e.g. duplicate source names or sources sharing a prefix. All problems
+    /// are returned rather than failing on the first, and nothing is stored,
+    /// so proposed configs can be checked before add_sync_target is called.
+    pub fn validate_config(
+        &self,
+        ctx: &CoreContext,
+        sync_target_config: &SyncTargetConfig,
+    ) -> Vec<ConfigValidationError> {
+        let ctx = self.prepare_ctx(
+            ctx,
+            sync_target_config.target.clone(),
+            Some(sync_target_config.version.clone()),
+            "validate_config",
+        );
+        let errors = validate_config(sync_target_config);
+        ctx.scuba()
+            .clone()
+            .add("error_count", errors.len())
+            .log_with_msg("Success", None);
+        errors
+    }
+
     /// Adds new sync target. Returs the commit hash of newly created target's head.
+    /// To see what it would merge without writing anything, use
+    /// `plan_add_sync_target` instead.
+    ///
+    /// `progress` is called as the operation moves from one phase to the
+    /// next.
     pub async fn add_sync_target(
         &self,
         ctx: &CoreContext,
         sync_target_config: SyncTargetConfig,
         changesets_to_merge: HashMap<String, ChangesetId>,
         message: Option<String>,
+        progress: &(dyn Fn(MegarepoRequestProgress) + Send + Sync),
     ) -> Result<ChangesetId, MegarepoError> {
         let mutable_renames = self
             .mutable_renames(ctx, &sync_target_config.target)
             .await?;
         let add_sync_target =
-            AddSyncTarget::new(&self.megarepo_configs, &self.mononoke, &mutable_renames);
+            AddSyncTarget::new(&self.megarepo_configs, &self.mononoke, &mutable_renames)
+                .with_progress(progress);
 
         let changesets_to_merge = changesets_to_merge
             .into_iter()
@@ -406,6 +469,53 @@ impl MegarepoApi {
             .await
     }
 
+    /// Works out what add_sync_target would merge for this config, without
+    /// creating any commits: the files each source contributes, and any
+    /// paths that conflict between sources.
+    pub async fn plan_add_sync_target(
+        &self,
+        ctx: &CoreContext,
+        sync_target_config: SyncTargetConfig,
+        changesets_to_merge: HashMap<String, ChangesetId>,
+    ) -> Result<AddSyncTargetPlan, MegarepoError> {
+        let mutable_renames = self
+            .mutable_renames(ctx, &sync_target_config.target)
+            .await?;
+        let add_sync_target =
+            AddSyncTarget::new(&self.megarepo_configs, &self.mononoke, &mutable_renames);
+
+        let changesets_to_merge = changesets_to_merge
+            .into_iter()
+            .map(|(source, cs_id)| (SourceName(source), cs_id))
+            .collect();
+
+        let ctx = self.prepare_ctx(
+            ctx,
+            sync_target_config.target.clone(),
+            Some(sync_target_config.version.clone()),
+            "plan_add_sync_target",
+        );
+        ctx.scuba().clone().log_with_msg("Started", None);
+        let res = add_sync_target
+            .plan(&ctx, sync_target_config, changesets_to_merge)
+            .await;
+        match &res {
+            Ok(plan) => {
+                ctx.scuba()
+                    .clone()
+                    .add("file_count", plan.file_count)
+                    .add("conflict_count", plan.conflicts.len())
+                    .log_with_msg("Success", None);
+            }
+            Err(err) => {
+                ctx.scuba()
+                    .clone()
+                    .log_with_msg("Failed", Some(format!("{:#?}", err)));
+            }
+        }
+        res
+    }
+
     pub async fn add_branching_sync_target(
         &self,
         ctx: &CoreContext,
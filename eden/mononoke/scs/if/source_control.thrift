@@ -997,6 +997,14 @@ struct MegarepoAddTargetParams {
   /// A message to be used in the commit description
   /// If not provided, service will generate commit description
   3: optional string message;
+  /// If set, nothing is written to the repo: no commits are created, the
+  /// target bookmark is not created and the config is not recorded. Same as
+  /// `plan_only`: the response carries a plan of the merge instead.
+  4: optional bool dry_run;
+  /// If set, no commits are created at all. The response carries a plan of
+  /// the merge (per-source file counts and path conflicts) and its `cs_id`
+  /// is left empty.
+  5: optional bool plan_only;
 }
 
 /// Params for megarepo_add_sync_target method
@@ -1048,6 +1056,19 @@ struct MegarepoSyncChangesetParams {
   4: megarepo_configs.ChangesetId target_location;
 }
 
+/// Params for megarepo_read_target_config method
+struct MegarepoReadTargetConfigParams {
+  /// Target whose current config should be read
+  1: megarepo_configs.Target target;
+}
+
+/// Params for megarepo_validate_config method
+struct MegarepoValidateConfigParams {
+  /// Config to check. It is not stored, and its target doesn't need to
+  /// exist yet.
+  1: megarepo_configs.SyncTargetConfig config;
+}
+
 /// Params for megarepo_re_merge_source method
 struct MegarepoRemergeSourceParams {
   /// Source which needs remerging
@@ -1278,10 +1299,44 @@ struct RepoListHgManifestResponse {
 
 struct MegarepoAddConfigResponse {}
 
+struct MegarepoReadTargetConfigResponse {
+  /// The config currently applied to the target
+  1: megarepo_configs.SyncTargetConfig config;
+  /// The commit the target's bookmark points to. Its remapping state
+  /// records the config version above.
+  2: megarepo_configs.ChangesetId cs_id;
+}
+
+struct MegarepoValidateConfigResponse {
+  /// Problems found in the config, empty if it is valid
+  1: list<string> errors;
+}
+
+/// A target path that more than one source would write, or that one
+/// source would write as a file while another writes files under it
+struct MegarepoPathConflict {
+  1: string path;
+  2: list<string> sources;
+}
+
+/// What megarepo_add_sync_target would merge, computed without creating
+/// any commits
+struct MegarepoAddTargetPlan {
+  /// Number of files each source puts in the target, including linkfiles
+  1: map<string, i64> source_file_counts;
+  /// Number of distinct files in the merged target
+  2: i64 file_count;
+  /// Paths that would make the real call fail
+  3: list<MegarepoPathConflict> conflicts;
+}
+
 struct MegarepoAddTargetResponse {
   /// A new position of the target bookmark
-  /// after the "sync changeset" operaton finished
+  /// after the "sync changeset" operaton finished.
+  /// Empty when only a plan was requested.
   1: megarepo_configs.ChangesetId cs_id;
+  /// Set when the request had `plan_only` or `dry_run`
+  2: optional MegarepoAddTargetPlan plan;
 }
 
 union MegarepoAddTargetResult {
@@ -1646,6 +1701,20 @@ service SourceControlService extends fb303.FacebookService {
     1: MegarepoAddConfigParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Read the config currently applied to a target, and the commit its
+  /// bookmark points to. Read-only: nothing is enqueued or changed.
+  MegarepoReadTargetConfigResponse megarepo_read_target_config(
+    1: MegarepoReadTargetConfigParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
+  /// Check a config for problems that would make megarepo_add_sync_target
+  /// fail, such as duplicate source names, conflicting prefixes or unknown
+  /// repos. Only the config is checked: no repo contents are read and
+  /// nothing is stored.
+  MegarepoValidateConfigResponse megarepo_validate_config(
+    1: MegarepoValidateConfigParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Add a new target to the list of known targets and set its
   /// initial SyncTargetConfig value
   MegarepoAddTargetToken megarepo_add_sync_target(
@@ -91,7 +91,7 @@ impl From<MegarepoError> for ServiceError {
                 reason: format!("{}", e),
                 ..Default::default()
             }),
-            MegarepoError::InternalError(error) => {
+            MegarepoError::InternalError(error) | MegarepoError::RetryableError(error) => {
                 let reason = error.to_string();
                 let backtrace = error
                     .backtrace()
@@ -226,6 +226,8 @@ impl_into_thrift_error!(service::FileDiffExn);
 impl_into_thrift_error!(service::CommitLookupXrepoExn);
 impl_into_thrift_error!(service::RepoListHgManifestExn);
 impl_into_thrift_error!(service::MegarepoAddSyncTargetConfigExn);
+impl_into_thrift_error!(service::MegarepoReadTargetConfigExn);
+impl_into_thrift_error!(service::MegarepoValidateConfigExn);
 impl_into_thrift_error!(service::MegarepoAddSyncTargetExn);
 impl_into_thrift_error!(service::MegarepoAddSyncTargetPollExn);
 impl_into_thrift_error!(service::MegarepoAddBranchingSyncTargetExn);
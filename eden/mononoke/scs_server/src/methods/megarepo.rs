@@ -22,10 +22,8 @@ use crate::errors;
 use crate::source_control_impl::SourceControlServiceImpl;
 
 impl SourceControlServiceImpl {
-    fn verify_repos_by_config(
-        &self,
-        config: &SyncTargetConfig,
-    ) -> Result<(), errors::ServiceError> {
+    // Repo ids the config refers to that aren't known to this server
+    fn unknown_repo_ids_in_config(&self, config: &SyncTargetConfig) -> Vec<i64> {
         let known_repo_ids: HashSet<RepositoryId> =
             self.mononoke.known_repo_ids().into_iter().collect();
 
@@ -36,16 +34,24 @@ impl SourceControlServiceImpl {
             repo_ids_in_cfg
         };
 
-        for repo_id_in_cfg in repo_ids_in_cfg {
-            if !known_repo_ids.contains(&RepositoryId::new(repo_id_in_cfg as i32)) {
-                return Err(errors::ServiceError::from(errors::repo_not_found(format!(
-                    "{}",
-                    repo_id_in_cfg
-                ))));
-            }
-        }
+        repo_ids_in_cfg
+            .into_iter()
+            .filter(|repo_id_in_cfg| {
+                !known_repo_ids.contains(&RepositoryId::new(*repo_id_in_cfg as i32))
+            })
+            .collect()
+    }
 
-        Ok(())
+    fn verify_repos_by_config(
+        &self,
+        config: &SyncTargetConfig,
+    ) -> Result<(), errors::ServiceError> {
+        match self.unknown_repo_ids_in_config(config).first() {
+            Some(repo_id_in_cfg) => Err(errors::ServiceError::from(errors::repo_not_found(
+                format!("{}", repo_id_in_cfg),
+            ))),
+            None => Ok(()),
+        }
     }
 
     async fn check_write_allowed(
@@ -120,6 +126,55 @@ impl SourceControlServiceImpl {
         })
     }
 
+    pub(crate) async fn megarepo_read_target_config(
+        &self,
+        ctx: CoreContext,
+        params: thrift::MegarepoReadTargetConfigParams,
+    ) -> Result<thrift::MegarepoReadTargetConfigResponse, errors::ServiceError> {
+        let target_repo_id = RepositoryId::new(params.target.repo_id.try_into().unwrap());
+        // Check that we are allowed to read the target repo
+        self.mononoke
+            .repo_by_id(ctx.clone(), target_repo_id)
+            .await
+            .map_err(errors::invalid_request)?
+            .ok_or_else(|| errors::invalid_request(anyhow!("repo not found {}", target_repo_id)))?;
+
+        let (config, cs_id) = self
+            .megarepo_api
+            .read_target_config(&ctx, &params.target)
+            .await?;
+
+        Ok(thrift::MegarepoReadTargetConfigResponse {
+            config,
+            cs_id: cs_id.as_ref().into(),
+            ..Default::default()
+        })
+    }
+
+    pub(crate) async fn megarepo_validate_config(
+        &self,
+        ctx: CoreContext,
+        params: thrift::MegarepoValidateConfigParams,
+    ) -> Result<thrift::MegarepoValidateConfigResponse, errors::ServiceError> {
+        let config = params.config;
+        let mut errors: Vec<String> = self
+            .megarepo_api
+            .validate_config(&ctx, &config)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        errors.extend(
+            self.unknown_repo_ids_in_config(&config)
+                .into_iter()
+                .map(|repo_id| format!("Repo not found: {}", repo_id)),
+        );
+
+        Ok(thrift::MegarepoValidateConfigResponse {
+            errors,
+            ..Default::default()
+        })
+    }
+
     pub(crate) async fn megarepo_add_sync_target(
         &self,
         ctx: CoreContext,
@@ -374,6 +374,8 @@ impl AddScubaParams for thrift::MegarepoAddTargetParams {
             self.config_with_new_target.version.clone(),
         );
         scuba.add("param_megarepo_message", self.message.clone());
+        scuba.add("param_megarepo_dry_run", self.dry_run.unwrap_or(false));
+        scuba.add("param_megarepo_plan_only", self.plan_only.unwrap_or(false));
         report_megarepo_target(&self.config_with_new_target.target, scuba, Reported::Param);
     }
 }
@@ -391,3 +393,16 @@ impl AddScubaParams for thrift::MegarepoAddConfigParams {
         report_megarepo_target(&self.new_config.target, scuba, Reported::Param);
     }
 }
+
+impl AddScubaParams for thrift::MegarepoReadTargetConfigParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        report_megarepo_target(&self.target, scuba, Reported::Param);
+    }
+}
+
+impl AddScubaParams for thrift::MegarepoValidateConfigParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_megarepo_version", self.config.version.clone());
+        report_megarepo_target(&self.config.target, scuba, Reported::Param);
+    }
+}
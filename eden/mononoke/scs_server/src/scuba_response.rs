@@ -93,6 +93,18 @@ impl AddScubaResponse for thrift::MegarepoAddBranchingTargetResult {}
 
 impl AddScubaResponse for thrift::MegarepoAddConfigResponse {}
 
+impl AddScubaResponse for thrift::MegarepoReadTargetConfigResponse {
+    fn add_scuba_response(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("response_megarepo_version", self.config.version.clone());
+    }
+}
+
+impl AddScubaResponse for thrift::MegarepoValidateConfigResponse {
+    fn add_scuba_response(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("response_megarepo_error_count", self.errors.len());
+    }
+}
+
 // Helper fn to report PollResponse types
 fn report_maybe_result<R: AddScubaResponse>(
     maybe_result: &Option<R>,
@@ -699,6 +699,14 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::MegarepoAddConfigParams,
         ) -> Result<thrift::MegarepoAddConfigResponse, service::MegarepoAddSyncTargetConfigExn>;
 
+        async fn megarepo_read_target_config(
+            params: thrift::MegarepoReadTargetConfigParams,
+        ) -> Result<thrift::MegarepoReadTargetConfigResponse, service::MegarepoReadTargetConfigExn>;
+
+        async fn megarepo_validate_config(
+            params: thrift::MegarepoValidateConfigParams,
+        ) -> Result<thrift::MegarepoValidateConfigResponse, service::MegarepoValidateConfigExn>;
+
         async fn megarepo_add_sync_target(
             params: thrift::MegarepoAddTargetParams,
         ) -> Result<thrift::MegarepoAddTargetToken, service::MegarepoAddSyncTargetExn>;
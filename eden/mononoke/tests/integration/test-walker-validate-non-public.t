@@ -84,6 +84,20 @@ Check we dont walk filenodes on a non-public commit.  Because filenodes is the o
   Seen,Loaded: 4,4
   * Type:Walked,Checks,Children HgChangeset:1,* HgFileEnvelope:1,* HgFileNode:1,* HgManifest:1,* (glob)
 
+Check --public-only walks the public commit as usual
+  $ mononoke_walker -L sizing scrub -q --public-only --walk-root=HgBonsaiMapping:${HGCOMMITC} -I deep -i bonsai -i derived_unodes -i derived_blame -i HgBonsaiMapping -X ChangesetToBonsaiParent -X UnodeFileToLinkedChangeset -X UnodeManifestToLinkedChangeset 2>&1 | strip_glog
+  Walking edge types [BlameToChangeset, ChangesetToUnodeMapping, HgBonsaiMappingToChangeset, UnodeFileToBlame, UnodeFileToUnodeFileParent, UnodeManifestToUnodeFileChild, UnodeManifestToUnodeManifestChild, UnodeManifestToUnodeManifestParent, UnodeMappingToRootUnodeManifest]
+  Walking node types [Blame, Changeset, HgBonsaiMapping, UnodeFile, UnodeManifest, UnodeMapping]
+  Seen,Loaded: 16,16
+  * Type:Walked,Checks,Children Blame:3,* Changeset:3,* HgBonsaiMapping:1,* UnodeFile:3,* UnodeManifest:3,* UnodeMapping:3,* (glob)
+
+Check --public-only does not expand the non-public commit, so neither its derived data nor its public parent are walked
+  $ mononoke_walker -L sizing scrub -q --public-only --walk-root=HgBonsaiMapping:${HGCOMMITCNEW} -I deep -i bonsai -i derived_unodes -i HgBonsaiMapping 2>&1 | strip_glog
+  Walking edge types [*] (glob)
+  Walking node types [*] (glob)
+  Seen,Loaded: 2,2
+  * Type:Walked,Checks,Children *Changeset:1,* HgBonsaiMapping:1,* (glob)
+
 validate, expect failures on phase info, and linknode as we now point to a non-public commit
   $ mononoke_walker -l validate validate -q --scuba-log-file scuba.json -I deep -I marker -b master_bookmark 2>&1 | strip_glog | sort
   Nodes,Pass,Fail:56,6,2; EdgesChecked:14; CheckType:Pass,Fail Total:6,2 ChangesetPhaseIsPublic:3,1 HgLinkNodePopulated:3,1
@@ -66,8 +66,8 @@ pub fn get_repo_id_from_key(key: &str) -> Result<Option<RepositoryId>, Error> {
         .transpose()
 }
 
-impl ScrubHandler for StatsScrubHandler {
-    fn on_repair(
+impl StatsScrubHandler {
+    fn record(
         &self,
         ctx: &CoreContext,
         blobstore_id: BlobstoreId,
@@ -75,9 +75,6 @@ impl ScrubHandler for StatsScrubHandler {
         is_repaired: bool,
         meta: &BlobstoreMetadata,
     ) {
-        self.inner
-            .on_repair(ctx, blobstore_id, key, is_repaired, meta);
-
         let ctime = match meta.ctime() {
             Some(ctime) => ScubaValue::from(ctime),
             None => ScubaValue::Null(NullScubaValue::Int),
@@ -129,6 +126,34 @@ impl ScrubHandler for StatsScrubHandler {
     }
 }
 
+impl ScrubHandler for StatsScrubHandler {
+    fn on_repair(
+        &self,
+        ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        key: &str,
+        is_repaired: bool,
+        meta: &BlobstoreMetadata,
+    ) {
+        self.inner
+            .on_repair(ctx, blobstore_id, key, is_repaired, meta);
+        self.record(ctx, blobstore_id, key, is_repaired, meta);
+    }
+
+    fn on_would_repair(
+        &self,
+        ctx: &CoreContext,
+        blobstore_ids: &[BlobstoreId],
+        key: &str,
+        meta: &BlobstoreMetadata,
+    ) {
+        self.inner.on_would_repair(ctx, blobstore_ids, key, meta);
+        for blobstore_id in blobstore_ids {
+            self.record(ctx, *blobstore_id, key, false, meta);
+        }
+    }
+}
+
 pub fn replace_blobconfig(
     blob_config: &mut BlobConfig,
     inner_blobstore_id: Option<u64>,
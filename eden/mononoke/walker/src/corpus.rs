@@ -65,6 +65,7 @@ const INFLIGHT_DIR: &str = "Inflight";
 // Output is the samples
 fn corpus_stream<InStream, SS>(
     scheduled_max: usize,
+    content_drain_max: usize,
     output_dir: Option<String>,
     s: InStream,
     sampler: Arc<CorpusSamplingHandler<CorpusSample>>,
@@ -98,7 +99,7 @@ where
             }
         }
     })
-    .try_buffer_unordered(scheduled_max)
+    .try_buffer_unordered(content_drain_max)
     // Dump the data to disk
     .map_ok(move |(walk_key, sample, mtime, stats)| {
         let node = walk_key.node;
@@ -194,6 +195,8 @@ fn dump_with_extension(node_type: NodeType) -> bool {
         NodeType::FastlogFile => false,
         NodeType::Fsnode => false,
         NodeType::FsnodeMapping => false,
+        NodeType::GitTree => false,
+        NodeType::GitTreeMapping => false,
         NodeType::SkeletonManifest => false,
         NodeType::SkeletonManifestMapping => false,
         NodeType::UnodeFile => false,
@@ -434,13 +437,18 @@ async fn run_one(
     let make_sink = {
         cloned!(command, job_params.quiet, sub_params.progress_state,);
         move |ctx: &CoreContext, repo_params: &RepoWalkParams| {
-            cloned!(ctx, repo_params.scheduled_max);
+            cloned!(
+                ctx,
+                repo_params.scheduled_max,
+                repo_params.content_drain_max
+            );
             async move |walk_output, _run_start, _chunk_num, _checkpoint_name| {
                 cloned!(ctx, sizing_progress_state);
                 let walk_progress = progress_stream(quiet, &progress_state, walk_output);
 
                 let corpus = corpus_stream(
                     scheduled_max,
+                    content_drain_max,
                     command.output_dir,
                     walk_progress,
                     command.sampler,
@@ -472,6 +480,7 @@ async fn run_one(
         required_node_data_types: hashset![NodeType::FileContent],
         always_emit_edge_types: HashSet::new(),
         keep_edge_paths: true,
+        include_scratch_bookmarks: false,
     };
 
     walk_exact_tail::<_, _, _, _, _, PathTrackingRoute<WrappedPath>>(
@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use itertools::Itertools;
+use mononoke_types::ContentId;
+use slog::{info, warn, Logger};
+use std::{collections::HashMap, sync::Mutex};
+
+pub const DEFAULT_DUPLICATE_CONTENT_MAX: usize = 10_000_000;
+
+struct DuplicateContentState {
+    // ContentId recomputed from the streamed bytes to the ContentIds walked with those bytes
+    by_hash: HashMap<ContentId, Vec<ContentId>>,
+    // Content seen once by_hash was full, so not checked for duplicates
+    untracked: u64,
+}
+
+/// Groups walked FileContent nodes by the ContentId computed from their bytes,
+/// so that content stored under more than one ContentId can be found.
+///
+/// Memory is bounded by remembering at most max_hashes distinct hashes.
+pub struct DuplicateContentTracker {
+    logger: Logger,
+    max_hashes: usize,
+    state: Mutex<DuplicateContentState>,
+}
+
+impl DuplicateContentTracker {
+    pub fn new(logger: Logger, max_hashes: usize) -> Self {
+        Self {
+            logger,
+            max_hashes,
+            state: Mutex::new(DuplicateContentState {
+                by_hash: HashMap::new(),
+                untracked: 0,
+            }),
+        }
+    }
+
+    /// Record that the content walked as content_id hashes to computed_id.
+    /// Returns false if the content could not be checked as the cap was hit.
+    pub fn record(&self, computed_id: ContentId, content_id: ContentId) -> bool {
+        let mut state = self.state.lock().expect("lock poisoned");
+        if let Some(ids) = state.by_hash.get_mut(&computed_id) {
+            if !ids.contains(&content_id) {
+                ids.push(content_id);
+            }
+            return true;
+        }
+        if state.by_hash.len() >= self.max_hashes {
+            if state.untracked == 0 {
+                warn!(
+                    self.logger,
+                    "Duplicate content limit of {} hashes reached, further content will not be checked",
+                    self.max_hashes
+                );
+            }
+            state.untracked += 1;
+            return false;
+        }
+        state.by_hash.insert(computed_id, vec![content_id]);
+        true
+    }
+
+    /// Groups of ContentIds with byte-identical content, keyed by the
+    /// ContentId computed from that content
+    pub fn duplicate_groups(&self) -> Vec<(ContentId, Vec<ContentId>)> {
+        let state = self.state.lock().expect("lock poisoned");
+        let mut groups: Vec<_> = state
+            .by_hash
+            .iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(computed_id, ids)| {
+                let mut ids = ids.clone();
+                ids.sort();
+                (*computed_id, ids)
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    pub fn report(&self) {
+        let groups = self.duplicate_groups();
+        for (computed_id, ids) in &groups {
+            info!(
+                self.logger,
+                "Duplicate content {}: {}",
+                computed_id,
+                ids.iter().join(",")
+            );
+        }
+        let untracked = self.state.lock().expect("lock poisoned").untracked;
+        info!(
+            self.logger,
+            "Found {} groups of duplicate content. {} file contents not checked due to the limit of {} hashes",
+            groups.len(),
+            untracked,
+            self.max_hashes,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use mononoke_types::FileContents;
+    use slog::o;
+
+    #[test]
+    fn test_duplicate_groups_and_cap() {
+        let logger = Logger::root(slog::Discard, o!());
+        let tracker = DuplicateContentTracker::new(logger, 2);
+        let [one, two, three] =
+            ["1", "2", "3"].map(|s| FileContents::content_id_for_bytes(&Bytes::from(s)));
+
+        assert!(tracker.record(one, one));
+        assert!(tracker.record(two, two));
+        // Seen again under the same id is not a duplicate
+        assert!(tracker.record(two, two));
+        assert!(tracker.record(one, three));
+        // A third distinct hash is over the cap
+        assert!(!tracker.record(three, three));
+
+        assert_eq!(tracker.duplicate_groups(), vec![(one, vec![one, three])]);
+        assert_eq!(tracker.state.lock().unwrap().untracked, 1);
+    }
+}
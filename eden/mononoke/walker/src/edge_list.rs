@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::walk::OutgoingEdge;
+
+use anyhow::{format_err, Error};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Records each edge the walk follows, one per line as
+/// `source stats_key \t EdgeType \t target stats_key`.
+/// Edges from the walk roots have "root" as their source.
+pub struct EdgeListWriter {
+    writer: Mutex<BufWriter<Box<dyn Write + Send>>>,
+}
+
+impl EdgeListWriter {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        Ok(Self::new(Box::new(File::create(path)?)))
+    }
+
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Mutex::new(BufWriter::new(writer)),
+        }
+    }
+
+    pub fn record(&self, source_key: &str, edge: &OutgoingEdge) -> Result<(), Error> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| format_err!("Edge list lock poisoned"))?;
+        writeln!(
+            writer,
+            "{}\t{}\t{}",
+            source_key,
+            edge.label,
+            edge.target.stats_key()
+        )?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<(), Error> {
+        self.writer
+            .lock()
+            .map_err(|_| format_err!("Edge list lock poisoned"))?
+            .flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{EdgeType, Node, UnitKey};
+    use mononoke_types::ContentId;
+    use std::fs;
+
+    #[test]
+    fn test_edge_list() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("repo.edges");
+
+        let writer = EdgeListWriter::create(&path)?;
+        let content_id = ContentId::from_bytes([1; 32])?;
+        let root_edge = OutgoingEdge::new(
+            EdgeType::RootToPublishedBookmarks,
+            Node::PublishedBookmarks(UnitKey()),
+        );
+        writer.record(&Node::Root(UnitKey()).stats_key(), &root_edge)?;
+        writer.record(
+            "some_source",
+            &OutgoingEdge::new(
+                EdgeType::FileContentToFileContentMetadata,
+                Node::FileContentMetadata(content_id),
+            ),
+        )?;
+        writer.flush()?;
+
+        let lines: Vec<String> = fs::read_to_string(&path)?
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "root\tRootToPublishedBookmarks\tpublished_bookmarks".to_string(),
+                format!(
+                    "some_source\tFileContentToFileContentMetadata\t{}",
+                    Node::FileContentMetadata(content_id).stats_key()
+                ),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use mononoke_types::ChangesetId;
+use slog::{info, Logger};
+use std::{collections::HashMap, sync::Mutex};
+
+#[derive(Default)]
+struct FootprintState {
+    by_changeset: HashMap<ChangesetId, u64>,
+    // Bytes reached before any bonsai changeset, e.g. when walking from hg roots
+    unattributed: u64,
+}
+
+/// Sums the bytes of walked nodes by the bonsai changeset they were reached from.
+///
+/// The walk visits each node once, so a blob reachable from several changesets
+/// is counted once, for the first changeset the walk reaches it from.
+pub struct ChangesetFootprint {
+    logger: Logger,
+    state: Mutex<FootprintState>,
+}
+
+impl ChangesetFootprint {
+    pub fn new(logger: Logger) -> Self {
+        Self {
+            logger,
+            state: Mutex::new(FootprintState::default()),
+        }
+    }
+
+    pub fn record(&self, changeset: Option<ChangesetId>, bytes: u64) {
+        let mut state = self.state.lock().expect("lock poisoned");
+        match changeset {
+            Some(cs_id) => *state.by_changeset.entry(cs_id).or_insert(0) += bytes,
+            None => state.unattributed += bytes,
+        }
+    }
+
+    /// Byte totals by changeset, sorted by ChangesetId
+    pub fn totals(&self) -> Vec<(ChangesetId, u64)> {
+        let state = self.state.lock().expect("lock poisoned");
+        let mut totals: Vec<_> = state
+            .by_changeset
+            .iter()
+            .map(|(cs_id, bytes)| (*cs_id, *bytes))
+            .collect();
+        totals.sort();
+        totals
+    }
+
+    pub fn report(&self) {
+        let totals = self.totals();
+        for (cs_id, bytes) in &totals {
+            info!(self.logger, "Changeset footprint {}: {}", cs_id, bytes);
+        }
+        let unattributed = self.state.lock().expect("lock poisoned").unattributed;
+        info!(
+            self.logger,
+            "Footprint of {} changesets totals {} bytes. {} bytes not attributed to a changeset",
+            totals.len(),
+            totals.iter().map(|(_, bytes)| bytes).sum::<u64>(),
+            unattributed,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Error;
+    use slog::o;
+
+    #[test]
+    fn test_footprint_totals() -> Result<(), Error> {
+        let logger = Logger::root(slog::Discard, o!());
+        let footprint = ChangesetFootprint::new(logger);
+        let one = ChangesetId::from_bytes([1; 32])?;
+        let two = ChangesetId::from_bytes([2; 32])?;
+
+        footprint.record(Some(two), 10);
+        footprint.record(Some(one), 5);
+        footprint.record(Some(two), 7);
+        footprint.record(None, 3);
+
+        assert_eq!(footprint.totals(), vec![(one, 5), (two, 17)]);
+        assert_eq!(footprint.state.lock().unwrap().unattributed, 3);
+        Ok(())
+    }
+}
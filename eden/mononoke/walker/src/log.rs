@@ -7,8 +7,10 @@
 
 /// Tags for slog usage
 pub const CHUNKING: &str = "chunking";
+pub const FRONTIER: &str = "frontier";
 pub const GRAPH: &str = "graph";
 pub const LOADED: &str = "loaded";
+pub const OUTCOME: &str = "outcome";
 pub const SIZING: &str = "sizing";
 pub const VALIDATE: &str = "validate";
 pub const SUPPRESS: &str = "suppress";
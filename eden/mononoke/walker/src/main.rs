@@ -17,18 +17,27 @@ use cmdlib::{args::CachelibSettings, helpers::block_execute};
 mod blobstore;
 mod checkpoint;
 mod corpus;
+mod duplicates;
+mod edge_list;
+mod footprint;
 #[macro_use]
 mod graph;
 mod log;
+mod output;
 mod pack;
+mod parquet_output;
 mod parse_node;
 mod progress;
+mod result_diff;
+mod resume;
 mod sampling;
 mod scrub;
 mod setup;
 mod sizing;
+mod spill;
 mod state;
 mod tail;
+mod unreachable;
 mod validate;
 mod walk;
 
@@ -52,6 +61,9 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
             sizing::compression_benefit(fb, logger.clone(), &matches, sub_m).boxed()
         }
         (setup::CORPUS, Some(sub_m)) => corpus::corpus(fb, logger.clone(), &matches, sub_m).boxed(),
+        (setup::DIFF_RESULTS, Some(sub_m)) => {
+            result_diff::diff_results(logger.clone(), sub_m).boxed()
+        }
         (setup::SCRUB, Some(sub_m)) => {
             scrub::scrub_objects(fb, logger.clone(), &matches, sub_m).boxed()
         }
@@ -0,0 +1,172 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::graph::{Node, NodeData};
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumString};
+
+/// Outcome of stepping to a node, as seen in NodeData
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsRefStr,
+    EnumString
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NodeStatus {
+    Ok,
+    Missing,
+    Error,
+    HashFailure,
+    ParentLinkageFailure,
+    RoundTripFailure,
+    AliasFailure,
+    OrphanedMapping,
+    CopyfromChainFailure,
+    FastlogChainFailure,
+    ContentSizeFailure,
+    DraftDerivedDataFailure,
+    ContentMetadataFailure,
+}
+
+/// Stable machine readable form of a walk result, one per visited node.
+/// Unlike the Debug output, field names and values don't change with the graph internals.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NodeRecord {
+    pub node_type: &'static str,
+    pub stats_key: String,
+    pub path: Option<String>,
+    pub status: NodeStatus,
+    /// Only set for content
+    pub size: Option<u64>,
+}
+
+impl NodeRecord {
+    pub fn new(node: &Node, node_data: Option<&NodeData>) -> Self {
+        let status = match node_data {
+            Some(NodeData::MissingAsData(_)) => NodeStatus::Missing,
+            Some(NodeData::ErrorAsData(_)) => NodeStatus::Error,
+            Some(NodeData::HashValidationFailureAsData(_)) => NodeStatus::HashFailure,
+            Some(NodeData::ParentLinkageFailureAsData(_)) => NodeStatus::ParentLinkageFailure,
+            Some(NodeData::RoundTripFailureAsData(_)) => NodeStatus::RoundTripFailure,
+            Some(NodeData::AliasFailureAsData(_)) => NodeStatus::AliasFailure,
+            Some(NodeData::OrphanedMappingAsData(_)) => NodeStatus::OrphanedMapping,
+            Some(NodeData::CopyfromChainFailureAsData(_)) => NodeStatus::CopyfromChainFailure,
+            Some(NodeData::FastlogChainFailureAsData(_)) => NodeStatus::FastlogChainFailure,
+            Some(NodeData::ContentSizeFailureAsData(_)) => NodeStatus::ContentSizeFailure,
+            Some(NodeData::DraftDerivedDataFailureAsData(_)) => NodeStatus::DraftDerivedDataFailure,
+            Some(NodeData::ContentMetadataFailureAsData(_)) => NodeStatus::ContentMetadataFailure,
+            _ => NodeStatus::Ok,
+        };
+        let size = match node_data {
+            // Streams are only sized once consumed, we never try to output their bytes
+            Some(NodeData::FileContent(content)) => content.known_size().map(|n| n as u64),
+            Some(NodeData::FileContentMetadata(Some(metadata))) => Some(metadata.total_size),
+            _ => None,
+        };
+        Self {
+            node_type: node.get_type().into(),
+            stats_key: node.stats_key(),
+            path: node.stats_path().map(|p| p.to_string()),
+            status,
+            size,
+        }
+    }
+
+    /// One line of JSON per record
+    pub fn to_json(&self) -> String {
+        // Only plain strings and numbers, so serialization can't fail
+        serde_json::to_string(self).expect("NodeRecord is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::FileContentData;
+    use futures::stream::{self, StreamExt};
+    use mononoke_types::ContentId;
+
+    #[test]
+    fn test_node_record_json() -> Result<(), anyhow::Error> {
+        let content_id = ContentId::from_bytes([1; 32])?;
+        let node = Node::FileContent(content_id);
+
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::FileContent(FileContentData::Consumed(42))),
+        );
+        assert_eq!(
+            record.to_json(),
+            format!(
+                r#"{{"node_type":"FileContent","stats_key":"{}","path":null,"status":"ok","size":42}}"#,
+                node.stats_key()
+            )
+        );
+
+        let stream = FileContentData::ContentStream(stream::empty().boxed());
+        let record = NodeRecord::new(&node, Some(&NodeData::FileContent(stream)));
+        assert_eq!(record.size, None);
+
+        let record = NodeRecord::new(&node, Some(&NodeData::MissingAsData(node.clone())));
+        assert_eq!(record.status, NodeStatus::Missing);
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::HashValidationFailureAsData(node.clone())),
+        );
+        assert!(record.to_json().contains(r#""status":"hash_failure""#));
+        assert_eq!(record.status.as_ref(), "hash_failure");
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::ParentLinkageFailureAsData(node.clone())),
+        );
+        assert!(record
+            .to_json()
+            .contains(r#""status":"parent_linkage_failure""#));
+        let record = NodeRecord::new(&node, Some(&NodeData::RoundTripFailureAsData(node.clone())));
+        assert!(record
+            .to_json()
+            .contains(r#""status":"round_trip_failure""#));
+        let record = NodeRecord::new(&node, Some(&NodeData::AliasFailureAsData(node.clone())));
+        assert_eq!(record.status.as_ref(), "alias_failure");
+        let record = NodeRecord::new(&node, Some(&NodeData::OrphanedMappingAsData(node.clone())));
+        assert_eq!(record.status.as_ref(), "orphaned_mapping");
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::CopyfromChainFailureAsData(node.clone())),
+        );
+        assert_eq!(record.status.as_ref(), "copyfrom_chain_failure");
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::FastlogChainFailureAsData(node.clone())),
+        );
+        assert_eq!(record.status.as_ref(), "fastlog_chain_failure");
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::ContentSizeFailureAsData(node.clone())),
+        );
+        assert_eq!(record.status.as_ref(), "content_size_failure");
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::DraftDerivedDataFailureAsData(node.clone())),
+        );
+        assert_eq!(record.status.as_ref(), "draft_derived_data_failure");
+        let record = NodeRecord::new(
+            &node,
+            Some(&NodeData::ContentMetadataFailureAsData(node.clone())),
+        );
+        assert_eq!(record.status.as_ref(), "content_metadata_failure");
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::graph::{Node, NodeData};
+use crate::output::NodeRecord;
+
+use anyhow::{bail, Error};
+use parquet::{
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{
+        properties::WriterProperties,
+        writer::{FileWriter, RowGroupWriter, SerializedFileWriter},
+    },
+    schema::parser::parse_message_type,
+};
+use std::{fs::File, path::Path, sync::Arc};
+
+/// Schema of the parquet output, one row per output node.
+///
+/// Columns are those of NodeRecord plus the node's sampling fingerprint, so the rows can be
+/// joined with the pack info logs. This is consumed by offline analysis, so only ever append
+/// columns, never rename, reorder or change the type of existing ones.
+///
+/// - node_type: the NodeType name, e.g. "FileContent"
+/// - stats_key: the blobstore key, or a descriptive key for nodes not in the blobstore
+/// - path: repo path the node was reached by, if tracked
+/// - status: NodeStatus in snake_case, e.g. "ok", "missing", "hash_failure"
+/// - size: bytes consumed for content streams, total size for content metadata
+/// - fingerprint: Node::sampling_fingerprint, u64 stored in an INT64
+const NODE_SCHEMA: &str = "
+message walk_node {
+    REQUIRED BINARY node_type (UTF8);
+    REQUIRED BINARY stats_key (UTF8);
+    OPTIONAL BINARY path (UTF8);
+    REQUIRED BINARY status (UTF8);
+    OPTIONAL INT64 size (UINT_64);
+    OPTIONAL INT64 fingerprint (UINT_64);
+}
+";
+
+/// Rows buffered before a row group is flushed to the file
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 64 * 1024;
+
+struct NodeRow {
+    record: NodeRecord,
+    fingerprint: Option<u64>,
+}
+
+/// Writes NodeRecords to a parquet file, buffering rows into row groups.
+/// Must be closed for the file footer to be written.
+pub struct ParquetNodeWriter {
+    writer: SerializedFileWriter<File>,
+    row_group_size: usize,
+    rows: Vec<NodeRow>,
+}
+
+impl ParquetNodeWriter {
+    pub fn create(path: &Path, row_group_size: usize) -> Result<Self, Error> {
+        let schema = Arc::new(parse_message_type(NODE_SCHEMA)?);
+        let props = Arc::new(WriterProperties::builder().build());
+        let writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+        Ok(Self {
+            writer,
+            row_group_size: row_group_size.max(1),
+            rows: Vec::with_capacity(row_group_size),
+        })
+    }
+
+    pub fn append(&mut self, node: &Node, node_data: Option<&NodeData>) -> Result<(), Error> {
+        self.rows.push(NodeRow {
+            record: NodeRecord::new(node, node_data),
+            fingerprint: node.sampling_fingerprint(),
+        });
+        if self.rows.len() >= self.row_group_size {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Flush any buffered rows and write the file footer
+    pub fn close(mut self) -> Result<(), Error> {
+        self.flush_row_group()?;
+        self.writer.close()?;
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> Result<(), Error> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let rows = &self.rows;
+        let mut row_group = self.writer.next_row_group()?;
+        let mut column_index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            match column_index {
+                0 => write_strings(
+                    &mut column,
+                    rows.iter().map(|r| Some(r.record.node_type)),
+                    false,
+                )?,
+                1 => write_strings(
+                    &mut column,
+                    rows.iter().map(|r| Some(r.record.stats_key.as_str())),
+                    false,
+                )?,
+                2 => write_strings(
+                    &mut column,
+                    rows.iter().map(|r| r.record.path.as_deref()),
+                    true,
+                )?,
+                3 => write_strings(
+                    &mut column,
+                    rows.iter().map(|r| Some(r.record.status.as_ref())),
+                    false,
+                )?,
+                4 => write_u64s(&mut column, rows.iter().map(|r| r.record.size))?,
+                5 => write_u64s(&mut column, rows.iter().map(|r| r.fingerprint))?,
+                _ => bail!("Unexpected column {} in parquet node schema", column_index),
+            }
+            row_group.close_column(column)?;
+            column_index += 1;
+        }
+        self.writer.close_row_group(row_group)?;
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+// Optional columns only carry the present values, with definition levels marking the nulls
+fn definition_levels<T>(values: impl Iterator<Item = Option<T>>) -> (Vec<T>, Vec<i16>) {
+    let mut present = Vec::new();
+    let mut def_levels = Vec::new();
+    for value in values {
+        match value {
+            Some(v) => {
+                present.push(v);
+                def_levels.push(1);
+            }
+            None => def_levels.push(0),
+        }
+    }
+    (present, def_levels)
+}
+
+fn write_strings<'a>(
+    column: &mut ColumnWriter,
+    values: impl Iterator<Item = Option<&'a str>>,
+    optional: bool,
+) -> Result<(), Error> {
+    let (values, def_levels) = definition_levels(values.map(|v| v.map(ByteArray::from)));
+    match column {
+        ColumnWriter::ByteArrayColumnWriter(typed) => {
+            typed.write_batch(&values, optional.then(|| def_levels.as_slice()), None)?;
+        }
+        _ => bail!("Expected a BINARY column in parquet node schema"),
+    }
+    Ok(())
+}
+
+fn write_u64s(
+    column: &mut ColumnWriter,
+    values: impl Iterator<Item = Option<u64>>,
+) -> Result<(), Error> {
+    // UINT_64 is stored as the same bits in an INT64
+    let (values, def_levels) = definition_levels(values.map(|v| v.map(|v| v as i64)));
+    match column {
+        ColumnWriter::Int64ColumnWriter(typed) => {
+            typed.write_batch(&values, Some(&def_levels), None)?;
+        }
+        _ => bail!("Expected an INT64 column in parquet node schema"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::FileContentData;
+    use mononoke_types::ContentId;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn test_parquet_row_groups() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("nodes.parquet");
+
+        let mut writer = ParquetNodeWriter::create(&path, 2)?;
+        for i in 0..5 {
+            let node = Node::FileContent(ContentId::from_bytes([i; 32])?);
+            let data = NodeData::FileContent(FileContentData::Consumed(i as usize));
+            writer.append(&node, Some(&data))?;
+        }
+        writer.close()?;
+
+        let reader = SerializedFileReader::new(File::open(&path)?)?;
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 5);
+        // Two full groups, then the remainder flushed on close
+        assert_eq!(metadata.num_row_groups(), 3);
+        assert_eq!(metadata.file_metadata().schema_descr().num_columns(), 6);
+        Ok(())
+    }
+}
@@ -6,8 +6,8 @@
  */
 
 use crate::graph::{
-    AliasKey, AliasType, ChangesetKey, FastlogKey, Node, NodeType, PathKey, UnitKey, UnodeFlags,
-    UnodeKey, WrappedPath,
+    AliasKey, AliasType, ChangesetKey, FastlogKey, GitTreeKey, Node, NodeType, PathKey, UnitKey,
+    UnodeFlags, UnodeKey, WrappedPath,
 };
 
 use anyhow::{format_err, Error};
@@ -17,10 +17,12 @@ use mononoke_types::{
     hash::{GitSha1, Sha1, Sha256},
     FileUnodeId, MPath, ManifestUnodeId,
 };
-use std::str::FromStr;
+use std::{io::BufRead, str::FromStr};
 use strum::IntoEnumIterator;
 
 const NODE_SEP: &str = ":";
+const ROOT_PATH: &str = "/";
+const PATH_ESCAPE: char = '%';
 
 fn check_and_build_path(node_type: NodeType, parts: &[&str]) -> Result<WrappedPath, Error> {
     if parts.len() < 2 {
@@ -29,9 +31,76 @@ fn check_and_build_path(node_type: NodeType, parts: &[&str]) -> Result<WrappedPa
             node_type
         ));
     }
-    let mpath = match parts[1..].join(NODE_SEP).as_str() {
-        "/" => None,
-        p => Some(MPath::new(p)?),
+    parse_path(&parts[1..].join(NODE_SEP))
+}
+
+fn push_escaped(escaped: &mut String, bytes: &[u8]) {
+    for b in bytes {
+        escaped.push_str(&format!("{}{:02X}", PATH_ESCAPE, b));
+    }
+}
+
+/// Paths are bytes, not necessarily UTF-8. Bytes that aren't UTF-8, control characters
+/// (so the path stays on one line) and the escape character itself are written as %XX.
+fn escape_path(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (valid, invalid_len) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(e) => {
+                let valid = std::str::from_utf8(&rest[..e.valid_up_to()])
+                    .expect("bytes before the error are valid UTF-8");
+                (valid, e.error_len().unwrap_or(rest.len() - valid.len()))
+            }
+        };
+        for c in valid.chars() {
+            if c == PATH_ESCAPE || c.is_control() {
+                push_escaped(&mut escaped, c.encode_utf8(&mut [0; 4]).as_bytes());
+            } else {
+                escaped.push(c);
+            }
+        }
+        let (invalid, after) = rest[valid.len()..].split_at(invalid_len);
+        push_escaped(&mut escaped, invalid);
+        rest = after;
+    }
+    escaped
+}
+
+fn unescape_path(s: &str) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, after)) = rest.split_first() {
+        if b as char == PATH_ESCAPE {
+            let byte = after
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format_err!("Invalid escape in path {:?}", s))?;
+            bytes.push(byte);
+            rest = &after[2..];
+        } else {
+            bytes.push(b);
+            rest = after;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Write a path so parse_path gets back exactly the same bytes, even if they aren't UTF-8
+pub fn format_path(path: &WrappedPath) -> String {
+    match path.as_ref() {
+        None => ROOT_PATH.to_string(),
+        Some(mpath) => escape_path(&mpath.to_vec()),
+    }
+}
+
+/// Parse a path written by format_path, so %XX escapes are decoded
+pub fn parse_path(s: &str) -> Result<WrappedPath, Error> {
+    let mpath = match s {
+        ROOT_PATH => None,
+        p => Some(MPath::new(unescape_path(p)?)?),
     };
     Ok(WrappedPath::from(mpath))
 }
@@ -97,6 +166,13 @@ impl FromStr for FastlogKey<FileUnodeId> {
     }
 }
 
+impl FromStr for GitTreeKey {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(GitTreeKey(GitSha1::from_str(s)?))
+    }
+}
+
 impl FromStr for UnodeFlags {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -174,6 +250,83 @@ pub fn parse_node(s: &str) -> Result<Node, Error> {
     Ok(node)
 }
 
+/// Parse one node per line, in the same format as parse_node, ignoring blank lines.
+/// Lines that don't parse are returned with their line number rather than failing
+/// the whole input, only a read error does that.
+pub fn parse_node_lines(
+    reader: impl BufRead,
+) -> Result<(Vec<(usize, Node)>, Vec<(usize, Error)>), Error> {
+    let mut nodes = Vec::new();
+    let mut invalid = Vec::new();
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse_node(line) {
+            Ok(node) => nodes.push((line_num + 1, node)),
+            Err(e) => invalid.push((line_num + 1, e)),
+        }
+    }
+    Ok((nodes, invalid))
+}
+
+/// Inverse of parse_node, so nodes can be written out and read back in.
+/// ChangesetKey::filenode_known_derived is not kept, it is false once parsed.
+pub fn format_node(node: &Node) -> String {
+    let key = match node {
+        Node::Root(_) => None,
+        // Bonsai
+        Node::Bookmark(k) => Some(k.to_string()),
+        Node::Changeset(k) => Some(k.inner.to_string()),
+        Node::BonsaiHgMapping(k) => Some(k.inner.to_string()),
+        Node::PhaseMapping(k) => Some(k.to_string()),
+        Node::PublishedBookmarks(_) => None,
+        // Hg
+        Node::HgBonsaiMapping(k) => Some(k.inner.to_string()),
+        Node::HgChangeset(k) => Some(k.inner.to_string()),
+        Node::HgChangesetViaBonsai(k) => Some(k.inner.to_string()),
+        Node::HgManifest(k) => Some(format!("{}{}{}", k.id, NODE_SEP, format_path(&k.path))),
+        Node::HgFileEnvelope(k) => Some(k.to_string()),
+        Node::HgFileNode(k) => Some(format!("{}{}{}", k.id, NODE_SEP, format_path(&k.path))),
+        Node::HgManifestFileNode(k) => {
+            Some(format!("{}{}{}", k.id, NODE_SEP, format_path(&k.path)))
+        }
+        // Content
+        Node::FileContent(k) => Some(k.to_string()),
+        Node::FileContentMetadata(k) => Some(k.to_string()),
+        Node::AliasContentMapping(AliasKey(alias)) => Some(match alias {
+            Alias::GitSha1(h) => format!("{}{}{}", AliasType::GitSha1.as_ref(), NODE_SEP, h),
+            Alias::Sha1(h) => format!("{}{}{}", AliasType::Sha1.as_ref(), NODE_SEP, h),
+            Alias::Sha256(h) => format!("{}{}{}", AliasType::Sha256.as_ref(), NODE_SEP, h),
+        }),
+        // Derived data
+        Node::Blame(k) => Some(k.to_string()),
+        Node::ChangesetInfo(k) => Some(k.to_string()),
+        Node::ChangesetInfoMapping(k) => Some(k.to_string()),
+        Node::DeletedManifest(k) => Some(k.to_string()),
+        Node::DeletedManifestMapping(k) => Some(k.to_string()),
+        Node::FastlogBatch(k) => Some(k.to_string()),
+        Node::FastlogDir(k) => Some(k.inner.to_string()),
+        Node::FastlogFile(k) => Some(k.inner.to_string()),
+        Node::Fsnode(k) => Some(k.to_string()),
+        Node::FsnodeMapping(k) => Some(k.to_string()),
+        Node::GitTree(k) => Some(k.0.to_string()),
+        Node::GitTreeMapping(k) => Some(k.to_string()),
+        Node::SkeletonManifest(k) => Some(k.to_string()),
+        Node::SkeletonManifestMapping(k) => Some(k.to_string()),
+        Node::UnodeFile(k) => Some(format!("{}{}{:b}", k.inner, NODE_SEP, k.flags.bits())),
+        Node::UnodeManifest(k) => Some(format!("{}{}{:b}", k.inner, NODE_SEP, k.flags.bits())),
+        Node::UnodeMapping(k) => Some(k.to_string()),
+    };
+    let node_type: &'static str = node.get_type().into();
+    match key {
+        Some(key) => format!("{}{}{}", node_type, NODE_SEP, key),
+        None => node_type.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -358,6 +511,19 @@ mod tests {
                     &parse_node(&format!("FsnodeMapping{}{}", NODE_SEP, SAMPLE_BLAKE2))?.get_type()
                 );
             }
+            NodeType::GitTree => {
+                assert_eq!(
+                    node_type,
+                    &parse_node(&format!("GitTree{}{}", NODE_SEP, SAMPLE_SHA1))?.get_type()
+                );
+            }
+            NodeType::GitTreeMapping => {
+                assert_eq!(
+                    node_type,
+                    &parse_node(&format!("GitTreeMapping{}{}", NODE_SEP, SAMPLE_BLAKE2))?
+                        .get_type()
+                );
+            }
             NodeType::SkeletonManifest => {
                 assert_eq!(
                     node_type,
@@ -412,4 +578,89 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn format_node_roundtrip() -> Result<(), Error> {
+        let samples = vec![
+            "Root".to_string(),
+            format!("Bookmark{}foo", NODE_SEP),
+            format!("Changeset{}{}", NODE_SEP, SAMPLE_BLAKE2),
+            "PublishedBookmarks".to_string(),
+            format!("HgChangeset{}{}", NODE_SEP, SAMPLE_SHA1),
+            format!("HgManifest{}{}{}/", NODE_SEP, SAMPLE_SHA1, NODE_SEP),
+            format!(
+                "HgFileNode{}{}{}{}",
+                NODE_SEP, SAMPLE_SHA1, NODE_SEP, SAMPLE_PATH
+            ),
+            format!("FileContent{}{}", NODE_SEP, SAMPLE_BLAKE2),
+            format!(
+                "AliasContentMapping{}Sha256{}{}",
+                NODE_SEP, NODE_SEP, SAMPLE_SHA256
+            ),
+            format!("FastlogDir{}{}", NODE_SEP, SAMPLE_BLAKE2),
+            format!("UnodeFile{}{}{}11", NODE_SEP, SAMPLE_BLAKE2, NODE_SEP),
+            format!("UnodeMapping{}{}", NODE_SEP, SAMPLE_BLAKE2),
+        ];
+        for sample in samples {
+            let node = parse_node(&sample)?;
+            assert_eq!(node, parse_node(&format_node(&node))?, "for {}", sample);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn format_path_roundtrip() -> Result<(), Error> {
+        let samples: Vec<&[u8]> = vec![
+            b"foo/bar",
+            b"caf\xc3\xa9/100%",
+            b"not/utf8\xff\xfe/\xc3",
+            b"tab\there:colon",
+        ];
+        for sample in samples {
+            let path = WrappedPath::from(Some(MPath::new(sample)?));
+            let formatted = format_path(&path);
+            assert!(!formatted.contains('\t'), "for {:?}", formatted);
+            assert_eq!(path, parse_path(&formatted)?, "for {:?}", formatted);
+
+            let node = Node::HgFileNode(PathKey {
+                id: HgFileNodeId::from_str(SAMPLE_SHA1)?,
+                path,
+            });
+            assert_eq!(node, parse_node(&format_node(&node))?);
+        }
+        assert_eq!(
+            format_path(&WrappedPath::from(Some(MPath::new(b"a%\xff")?))),
+            "a%25%FF"
+        );
+        assert!(parse_path("bad%G1").is_err());
+        assert!(parse_path("truncated%F").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_node_lines_reports_invalid() -> Result<(), Error> {
+        let input = format!(
+            "Bookmark{}foo\n\nNotANodeType{}foo\n  Changeset{}{}  \nChangeset{}bad\n",
+            NODE_SEP, NODE_SEP, NODE_SEP, SAMPLE_BLAKE2, NODE_SEP
+        );
+        let (nodes, invalid) = parse_node_lines(input.as_bytes())?;
+        assert_eq!(
+            nodes,
+            vec![
+                (1, parse_node(&format!("Bookmark{}foo", NODE_SEP))?),
+                (
+                    4,
+                    parse_node(&format!("Changeset{}{}", NODE_SEP, SAMPLE_BLAKE2))?
+                ),
+            ]
+        );
+        assert_eq!(
+            invalid
+                .iter()
+                .map(|(line_num, _)| *line_num)
+                .collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+        Ok(())
+    }
 }
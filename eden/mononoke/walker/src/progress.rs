@@ -5,7 +5,7 @@
  * GNU General Public License version 2.
  */
 
-use crate::graph::{Node, NodeType};
+use crate::graph::{Node, NodeData, NodeType};
 use crate::log;
 use crate::state::StepStats;
 use anyhow::Error;
@@ -30,10 +30,29 @@ define_stats! {
     walk_progress_errors: dynamic_timeseries("{}.progress.{}.errors", (subcommand: &'static str, repo: String); Rate, Sum),
     walk_progress_missing: dynamic_timeseries("{}.progress.{}.missing", (subcommand: &'static str, repo: String); Rate, Sum),
     walk_progress_hash_validation_failure: dynamic_timeseries("{}.progress.{}.hash_validation_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_parent_linkage_failure: dynamic_timeseries("{}.progress.{}.parent_linkage_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_round_trip_failure: dynamic_timeseries("{}.progress.{}.round_trip_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_alias_failure: dynamic_timeseries("{}.progress.{}.alias_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_orphaned_mapping: dynamic_timeseries("{}.progress.{}.orphaned_mapping", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_copyfrom_chain_failure: dynamic_timeseries("{}.progress.{}.copyfrom_chain_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_fastlog_chain_failure: dynamic_timeseries("{}.progress.{}.fastlog_chain_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_content_size_failure: dynamic_timeseries("{}.progress.{}.content_size_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_draft_derived_data_failure: dynamic_timeseries("{}.progress.{}.draft_derived_data_failure", (subcommand: &'static str, repo: String); Rate, Sum),
+    walk_progress_content_metadata_failure: dynamic_timeseries("{}.progress.{}.content_metadata_failure", (subcommand: &'static str, repo: String); Rate, Sum),
     walk_progress_walked_by_type: dynamic_timeseries("{}.progress.{}.{}.walked", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
     walk_progress_errors_by_type: dynamic_timeseries("{}.progress.{}.{}.errors", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
     walk_progress_missing_by_type: dynamic_timeseries("{}.progress.{}.{}.missing", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
     walk_progress_hash_validation_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.hash_validation_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_parent_linkage_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.parent_linkage_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_round_trip_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.round_trip_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_alias_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.alias_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_orphaned_mapping_by_type: dynamic_timeseries("{}.progress.{}.{}.orphaned_mapping", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_copyfrom_chain_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.copyfrom_chain_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_fastlog_chain_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.fastlog_chain_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_content_size_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.content_size_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_draft_derived_data_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.draft_derived_data_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_progress_content_metadata_failure_by_type: dynamic_timeseries("{}.progress.{}.{}.content_metadata_failure", (subcommand: &'static str, repo: String, node_type: String); Rate, Sum),
+    walk_outcome_by_type: dynamic_timeseries("{}.outcome.{}.{}.{}", (subcommand: &'static str, repo: String, node_type: String, outcome: &'static str); Rate, Sum),
 }
 
 pub trait ProgressRecorderUnprotected<SS> {
@@ -102,6 +121,15 @@ pub struct ProgressSummary {
     errors: u64,
     missing: u64,
     hash_validation_failure: u64,
+    parent_linkage_failure: u64,
+    round_trip_failure: u64,
+    alias_failure: u64,
+    orphaned_mapping: u64,
+    copyfrom_chain_failure: u64,
+    fastlog_chain_failure: u64,
+    content_size_failure: u64,
+    draft_derived_data_failure: u64,
+    content_metadata_failure: u64,
 }
 
 // Takes a summary type as a parameter. e.g. ProgressSummary
@@ -220,6 +248,78 @@ impl ProgressStateCountByType<StepStats, ProgressSummary> {
                 node_type.to_string(),
             ),
         );
+        STATS::walk_progress_parent_linkage_failure_by_type.add_value(
+            summary.parent_linkage_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_round_trip_failure_by_type.add_value(
+            summary.round_trip_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_alias_failure_by_type.add_value(
+            summary.alias_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_orphaned_mapping_by_type.add_value(
+            summary.orphaned_mapping as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_copyfrom_chain_failure_by_type.add_value(
+            summary.copyfrom_chain_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_fastlog_chain_failure_by_type.add_value(
+            summary.fastlog_chain_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_content_size_failure_by_type.add_value(
+            summary.content_size_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_draft_derived_data_failure_by_type.add_value(
+            summary.draft_derived_data_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
+        STATS::walk_progress_content_metadata_failure_by_type.add_value(
+            summary.content_metadata_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+                node_type.to_string(),
+            ),
+        );
     }
 
     pub fn report_progress_log(&mut self, mut delta_time: Option<Duration>) {
@@ -236,6 +336,15 @@ impl ProgressStateCountByType<StepStats, ProgressSummary> {
                     errors: ss.error_count as u64,
                     missing: ss.missing_count as u64,
                     hash_validation_failure: ss.hash_validation_failure_count as u64,
+                    parent_linkage_failure: ss.parent_linkage_failure_count as u64,
+                    round_trip_failure: ss.round_trip_failure_count as u64,
+                    alias_failure: ss.alias_failure_count as u64,
+                    orphaned_mapping: ss.orphaned_mapping_count as u64,
+                    copyfrom_chain_failure: ss.copyfrom_chain_failure_count as u64,
+                    fastlog_chain_failure: ss.fastlog_chain_failure_count as u64,
+                    content_size_failure: ss.content_size_failure_count as u64,
+                    draft_derived_data_failure: ss.draft_derived_data_failure_count as u64,
+                    content_metadata_failure: ss.content_metadata_failure_count as u64,
                 };
                 let delta = s - self
                     .reporting_stats
@@ -351,6 +460,78 @@ impl ProgressStateCountByType<StepStats, ProgressSummary> {
             ),
         );
 
+        STATS::walk_progress_parent_linkage_failure.add_value(
+            delta_summary.parent_linkage_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_round_trip_failure.add_value(
+            delta_summary.round_trip_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_alias_failure.add_value(
+            delta_summary.alias_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_orphaned_mapping.add_value(
+            delta_summary.orphaned_mapping as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_copyfrom_chain_failure.add_value(
+            delta_summary.copyfrom_chain_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_fastlog_chain_failure.add_value(
+            delta_summary.fastlog_chain_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_content_size_failure.add_value(
+            delta_summary.content_size_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_draft_derived_data_failure.add_value(
+            delta_summary.draft_derived_data_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
+        STATS::walk_progress_content_metadata_failure.add_value(
+            delta_summary.content_metadata_failure as i64,
+            (
+                self.params.subcommand_stats_key,
+                self.params.repo_stats_key.clone(),
+            ),
+        );
+
         self.reporting_stats.last_summary_by_type = summary_by_type;
         self.reporting_stats.last_summary = new_summary;
     }
@@ -381,6 +562,205 @@ impl ProgressReporterUnprotected for ProgressStateCountByType<StepStats, Progres
     }
 }
 
+/// How a walked node turned out, classified from its NodeData
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeOutcome {
+    Completed,
+    NotRequired,
+    Missing,
+    Error,
+    HashValidationFailure,
+    // Parent linkage, round trip, alias, orphaned mapping, copyfrom and fastlog chain,
+    // content size, draft derived data and content metadata checks
+    CheckFailure,
+}
+
+impl NodeOutcome {
+    pub fn new(node_data: Option<&NodeData>) -> Self {
+        match node_data {
+            Some(NodeData::ErrorAsData(_)) => NodeOutcome::Error,
+            Some(NodeData::MissingAsData(_)) => NodeOutcome::Missing,
+            Some(NodeData::HashValidationFailureAsData(_)) => NodeOutcome::HashValidationFailure,
+            Some(NodeData::ParentLinkageFailureAsData(_))
+            | Some(NodeData::RoundTripFailureAsData(_))
+            | Some(NodeData::AliasFailureAsData(_))
+            | Some(NodeData::OrphanedMappingAsData(_))
+            | Some(NodeData::CopyfromChainFailureAsData(_))
+            | Some(NodeData::FastlogChainFailureAsData(_))
+            | Some(NodeData::ContentSizeFailureAsData(_))
+            | Some(NodeData::DraftDerivedDataFailureAsData(_))
+            | Some(NodeData::ContentMetadataFailureAsData(_)) => NodeOutcome::CheckFailure,
+            // Nothing was loaded for the node, e.g. its data wasn't asked for
+            None | Some(NodeData::NotRequired) | Some(NodeData::OutsideChunk) => {
+                NodeOutcome::NotRequired
+            }
+            Some(_) => NodeOutcome::Completed,
+        }
+    }
+}
+
+#[derive(Add, Sub, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct OutcomeSummary {
+    pub completed: u64,
+    pub not_required: u64,
+    pub missing: u64,
+    pub errors: u64,
+    pub hash_validation_failure: u64,
+    pub check_failure: u64,
+}
+
+impl OutcomeSummary {
+    fn record(&mut self, outcome: NodeOutcome) {
+        let count = match outcome {
+            NodeOutcome::Completed => &mut self.completed,
+            NodeOutcome::NotRequired => &mut self.not_required,
+            NodeOutcome::Missing => &mut self.missing,
+            NodeOutcome::Error => &mut self.errors,
+            NodeOutcome::HashValidationFailure => &mut self.hash_validation_failure,
+            NodeOutcome::CheckFailure => &mut self.check_failure,
+        };
+        *count += 1;
+    }
+
+    // Stats key for each count
+    fn counts(&self) -> [(&'static str, u64); 6] {
+        [
+            ("completed", self.completed),
+            ("not_required", self.not_required),
+            ("missing", self.missing),
+            ("errors", self.errors),
+            ("hash_validation_failure", self.hash_validation_failure),
+            ("check_failure", self.check_failure),
+        ]
+    }
+}
+
+// Aggregate counterpart to the per node output, counts outcomes by NodeType.
+// Only a map update per node, so can always be on.
+pub struct OutcomeStateByType {
+    params: ProgressStateByTypeParams,
+    outcomes_by_type: HashMap<NodeType, OutcomeSummary>,
+    last_reported_by_type: HashMap<NodeType, OutcomeSummary>,
+    total_recorded: u64,
+    last_update: Instant,
+}
+
+impl OutcomeStateByType {
+    pub fn new(
+        fb: FacebookInit,
+        logger: Logger,
+        subcommand_stats_key: &'static str,
+        repo_stats_key: String,
+        included_types: HashSet<NodeType>,
+        options: ProgressOptions,
+    ) -> Self {
+        Self {
+            params: ProgressStateByTypeParams {
+                fb,
+                logger,
+                subcommand_stats_key,
+                repo_stats_key,
+                types_sorted_by_name: sort_by_string(included_types),
+                options,
+            },
+            outcomes_by_type: HashMap::new(),
+            last_reported_by_type: HashMap::new(),
+            total_recorded: 0,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn record_outcome(&mut self, n: &Node, node_data: Option<&NodeData>) {
+        self.total_recorded += 1;
+        self.outcomes_by_type
+            .entry(n.get_type())
+            .or_default()
+            .record(NodeOutcome::new(node_data));
+    }
+
+    /// Cumulative outcomes so far, for each type seen, ordered by type name
+    pub fn snapshot(&self) -> Vec<(NodeType, OutcomeSummary)> {
+        sort_by_string(self.outcomes_by_type.keys().copied())
+            .into_iter()
+            .map(|t| (t, self.outcomes_by_type[&t]))
+            .collect()
+    }
+
+    // Throttle by sample, then time
+    fn should_report_throttled(&mut self) -> bool {
+        if self.total_recorded % self.params.options.sample_rate == 0 {
+            let now = Instant::now();
+            if now.duration_since(self.last_update) >= self.params.options.interval {
+                self.last_update = now;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn report_outcomes(&mut self) {
+        let snapshot = self.snapshot();
+        for (node_type, summary) in &snapshot {
+            let last = self
+                .last_reported_by_type
+                .get(node_type)
+                .cloned()
+                .unwrap_or_default();
+            for (outcome, count) in (*summary - last).counts().iter() {
+                STATS::walk_outcome_by_type.add_value(
+                    *count as i64,
+                    (
+                        self.params.subcommand_stats_key,
+                        self.params.repo_stats_key.clone(),
+                        node_type.to_string(),
+                        *outcome,
+                    ),
+                );
+            }
+        }
+
+        let detail = &self
+            .params
+            .types_sorted_by_name
+            .iter()
+            .filter_map(|t| self.outcomes_by_type.get(t).map(|s| (t, s)))
+            .map(|(t, s)| {
+                format!(
+                    "{}:{},{},{},{},{},{}",
+                    t,
+                    s.completed,
+                    s.not_required,
+                    s.missing,
+                    s.errors,
+                    s.hash_validation_failure,
+                    s.check_failure
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        info!(
+            self.params.logger,
+            #log::OUTCOME,
+            "Type:Completed,NotRequired,Missing,Errors,HashValidationFailures,CheckFailures {}",
+            detail,
+        );
+
+        self.last_reported_by_type = snapshot.into_iter().collect();
+    }
+}
+
+impl ProgressReporterUnprotected for OutcomeStateByType {
+    fn report_progress(&mut self) {
+        self.report_outcomes();
+    }
+
+    fn report_throttled(&mut self) {
+        if self.should_report_throttled() {
+            self.report_outcomes();
+        }
+    }
+}
+
 pub trait ProgressRecorder<SS> {
     fn record_step(&self, n: &Node, ss: Option<&SS>);
     fn set_sample_builder(&self, s: MononokeScubaSampleBuilder);
@@ -430,6 +810,12 @@ where
     }
 }
 
+impl ProgressStateMutex<OutcomeStateByType> {
+    pub fn record_outcome(&self, n: &Node, node_data: Option<&NodeData>) {
+        self.inner.lock().unwrap().record_outcome(n, node_data)
+    }
+}
+
 impl<Inner> Clone for ProgressStateMutex<Inner> {
     fn clone(&self) -> Self {
         Self {
@@ -469,6 +855,32 @@ where
     })
 }
 
+// Count the outcome of each node by type, passing on all data unchanged
+pub fn outcome_stream<InStream, K, SS>(
+    quiet: bool,
+    outcome_state: &ProgressStateMutex<OutcomeStateByType>,
+    s: InStream,
+) -> impl Stream<Item = Result<(K, Option<NodeData>, SS), Error>>
+where
+    InStream: Stream<Item = Result<(K, Option<NodeData>, SS), Error>> + 'static + Send,
+    K: 'static,
+    // Make sure we can convert from K reference to Node reference
+    for<'b> &'b Node: From<&'b K>,
+{
+    let outcome_state = outcome_state.clone();
+    s.map_ok(move |(key, node_data, stats)| {
+        {
+            let k: &K = &key;
+            let n: &Node = k.into();
+            outcome_state.record_outcome(n, node_data.as_ref());
+            if !quiet {
+                outcome_state.report_throttled();
+            }
+        }
+        (key, node_data, stats)
+    })
+}
+
 // Final status summary, plus count of seen nodes
 pub async fn report_state<InStream, ND, SS>(ctx: CoreContext, s: InStream) -> Result<(), Error>
 where
@@ -489,3 +901,63 @@ where
     info!(ctx.logger(), #log::LOADED, "Seen,Loaded: {},{}", seen, loaded);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mononoke_types::ChangesetId;
+    use slog::{o, Discard};
+    use std::str::FromStr;
+
+    #[fbinit::test]
+    fn test_outcome_state_by_type(fb: FacebookInit) -> Result<(), Error> {
+        let mut state = OutcomeStateByType::new(
+            fb,
+            Logger::root(Discard, o!()),
+            "test",
+            "repo".to_string(),
+            HashSet::new(),
+            ProgressOptions {
+                sample_rate: 1,
+                interval: Duration::from_secs(1),
+            },
+        );
+        let cs_hex = "b847b8838bfe3ae13ea6f8ce2e341c51193587b8392494f6dbab7224b3b116bf";
+        let bookmark_node = NodeType::Bookmark.parse_node("main")?;
+        let changeset_node = NodeType::Changeset.parse_node(cs_hex)?;
+        let bookmark_data = NodeData::Bookmark(ChangesetId::from_str(cs_hex)?);
+        state.record_outcome(&bookmark_node, Some(&bookmark_data));
+        state.record_outcome(&changeset_node, Some(&NodeData::NotRequired));
+        state.record_outcome(
+            &changeset_node,
+            Some(&NodeData::MissingAsData(changeset_node.clone())),
+        );
+        state.record_outcome(
+            &changeset_node,
+            Some(&NodeData::RoundTripFailureAsData(changeset_node.clone())),
+        );
+
+        assert_eq!(
+            state.snapshot(),
+            vec![
+                (
+                    NodeType::Bookmark,
+                    OutcomeSummary {
+                        completed: 1,
+                        ..Default::default()
+                    }
+                ),
+                (
+                    NodeType::Changeset,
+                    OutcomeSummary {
+                        not_required: 1,
+                        missing: 1,
+                        check_failure: 1,
+                        ..Default::default()
+                    }
+                ),
+            ]
+        );
+        Ok(())
+    }
+}
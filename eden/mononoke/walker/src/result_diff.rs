@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::output::NodeStatus;
+use crate::setup::{DIFF_AFTER_ARG, DIFF_BEFORE_ARG};
+
+use anyhow::{format_err, Context, Error};
+use clap::ArgMatches;
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::RowAccessor,
+};
+use serde::{Deserialize, Serialize};
+use slog::{info, Logger};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Nodes are matched across runs by type and stats_key, as the stats_key alone can be
+// shared by nodes of different types, e.g. a changeset and its mappings
+type NodeKey = (String, String);
+
+// Column positions in the parquet node schema, see parquet_output.rs
+const PARQUET_NODE_TYPE_COLUMN: usize = 0;
+const PARQUET_STATS_KEY_COLUMN: usize = 1;
+const PARQUET_STATUS_COLUMN: usize = 3;
+
+/// The fields of a NodeRecord needed to compare runs, other fields are ignored
+#[derive(Deserialize)]
+struct LoadedRecord {
+    node_type: String,
+    stats_key: String,
+    status: NodeStatus,
+}
+
+/// A node seen in both runs whose status differs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StatusChange {
+    pub node_type: String,
+    pub stats_key: String,
+    pub before: NodeStatus,
+    pub after: NodeStatus,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ResultDiff {
+    pub changed: Vec<StatusChange>,
+    /// Nodes present in one run only, e.g. due to sampling or a changed walk
+    pub only_before: usize,
+    pub only_after: usize,
+}
+
+// A node can be output more than once, e.g. once per tail run. Any failure is kept
+// over an ok, so a failure seen in any record is compared.
+fn insert_record(records: &mut HashMap<NodeKey, NodeStatus>, record: LoadedRecord) {
+    let status = records
+        .entry((record.node_type, record.stats_key))
+        .or_insert(record.status);
+    if *status == NodeStatus::Ok {
+        *status = record.status;
+    }
+}
+
+fn load_json(path: &Path, records: &mut HashMap<NodeKey, NodeStatus>) -> Result<(), Error> {
+    let reader = BufReader::new(File::open(path)?);
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid node record at line {}", line_num + 1))?;
+        insert_record(records, record);
+    }
+    Ok(())
+}
+
+fn load_parquet(path: &Path, records: &mut HashMap<NodeKey, NodeStatus>) -> Result<(), Error> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    for row in reader.get_row_iter(None)? {
+        let status = row.get_string(PARQUET_STATUS_COLUMN)?;
+        let record = LoadedRecord {
+            node_type: row.get_string(PARQUET_NODE_TYPE_COLUMN)?.clone(),
+            stats_key: row.get_string(PARQUET_STATS_KEY_COLUMN)?.clone(),
+            status: NodeStatus::from_str(status)
+                .map_err(|_| format_err!("Unknown node status {:?}", status))?,
+        };
+        insert_record(records, record);
+    }
+    Ok(())
+}
+
+/// Load the node records of one run. Parquet files are recognised by their extension,
+/// anything else is read as the JSON lines output. A directory loads all of its parquet
+/// files, as the parquet sink writes one file per run and chunk.
+pub fn load_records(path: &Path) -> Result<HashMap<NodeKey, NodeStatus>, Error> {
+    let is_parquet = |path: &Path| path.extension().map_or(false, |ext| ext == "parquet");
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let mut files = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.retain(|p| is_parquet(p));
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut records = HashMap::new();
+    for file in files {
+        let loaded = if is_parquet(&file) {
+            load_parquet(&file, &mut records)
+        } else {
+            load_json(&file, &mut records)
+        };
+        loaded.with_context(|| format!("While loading walker results from {}", file.display()))?;
+    }
+    Ok(records)
+}
+
+/// Compare the node statuses of two runs, changes are sorted by node type then stats_key
+pub fn diff_records(
+    before: &HashMap<NodeKey, NodeStatus>,
+    after: &HashMap<NodeKey, NodeStatus>,
+) -> ResultDiff {
+    let mut changed = BTreeMap::new();
+    let mut only_before = 0;
+    for (key, before_status) in before {
+        match after.get(key) {
+            Some(after_status) if after_status != before_status => {
+                changed.insert(key, (*before_status, *after_status));
+            }
+            Some(_) => {}
+            None => only_before += 1,
+        }
+    }
+    let only_after = after.keys().filter(|k| !before.contains_key(k)).count();
+    ResultDiff {
+        changed: changed
+            .into_iter()
+            .map(|((node_type, stats_key), (before, after))| StatusChange {
+                node_type: node_type.clone(),
+                stats_key: stats_key.clone(),
+                before,
+                after,
+            })
+            .collect(),
+        only_before,
+        only_after,
+    }
+}
+
+// Subcommand entry point for comparing the output of two runs
+pub async fn diff_results<'a>(logger: Logger, sub_m: &'a ArgMatches<'a>) -> Result<(), Error> {
+    let path_arg = |name: &str| {
+        sub_m
+            .value_of(name)
+            .map(PathBuf::from)
+            .ok_or_else(|| format_err!("--{} is required", name))
+    };
+    let before = load_records(&path_arg(DIFF_BEFORE_ARG)?)?;
+    let after = load_records(&path_arg(DIFF_AFTER_ARG)?)?;
+
+    let diff = diff_records(&before, &after);
+    for change in &diff.changed {
+        // Only plain strings, so serialization can't fail
+        println!(
+            "{}",
+            serde_json::to_string(change).expect("StatusChange is always serializable")
+        );
+    }
+    info!(
+        logger,
+        "{} nodes changed status, {} only in before, {} only in after",
+        diff.changed.len(),
+        diff.only_before,
+        diff.only_after,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{FileContentData, Node, NodeData};
+    use crate::output::NodeRecord;
+    use crate::parquet_output::ParquetNodeWriter;
+    use mononoke_types::ContentId;
+    use std::io::Write;
+
+    #[test]
+    fn test_diff_json_and_parquet() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let nodes = (0..4u8)
+            .map(|i| Ok(Node::FileContent(ContentId::from_bytes([i; 32])?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let ok = NodeData::FileContent(FileContentData::Consumed(1));
+
+        // Before: all ok, except node 3 which is only in this run
+        let before_path = dir.path().join("before.json");
+        let mut before = File::create(&before_path)?;
+        for node in &nodes {
+            writeln!(before, "{}", NodeRecord::new(node, Some(&ok)).to_json())?;
+        }
+
+        // After: node 0 went missing, node 1 failed hash validation on its second visit
+        let after_dir = dir.path().join("after");
+        fs::create_dir(&after_dir)?;
+        let mut after = ParquetNodeWriter::create(&after_dir.join("run.parquet"), 2)?;
+        after.append(&nodes[0], Some(&NodeData::MissingAsData(nodes[0].clone())))?;
+        after.append(&nodes[1], Some(&ok))?;
+        after.append(
+            &nodes[1],
+            Some(&NodeData::HashValidationFailureAsData(nodes[1].clone())),
+        )?;
+        after.append(&nodes[2], Some(&ok))?;
+        after.close()?;
+
+        let diff = diff_records(&load_records(&before_path)?, &load_records(&after_dir)?);
+        let change = |node: &Node, after| StatusChange {
+            node_type: "FileContent".to_string(),
+            stats_key: node.stats_key(),
+            before: NodeStatus::Ok,
+            after,
+        };
+        let mut expected = vec![
+            change(&nodes[0], NodeStatus::Missing),
+            change(&nodes[1], NodeStatus::HashFailure),
+        ];
+        expected.sort_by(|a, b| a.stats_key.cmp(&b.stats_key));
+        assert_eq!(
+            diff,
+            ResultDiff {
+                changed: expected,
+                only_before: 1,
+                only_after: 0,
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::graph::{EdgeType, Node, NodeType, WrappedPath};
+use crate::parse_node::{format_node, format_path, parse_node, parse_path};
+use crate::walk::OutgoingEdge;
+
+use ahash::RandomState;
+use anyhow::{bail, format_err, Context, Error};
+use dashmap::DashMap;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+use strum::VariantNames;
+
+// Bump if the line format changes
+const RESUME_HEADER: &str = "walker_resume 2";
+const NODE_TYPES_TAG: &str = "node_types";
+const VISITED_TAG: &str = "visited";
+const PENDING_TAG: &str = "pending";
+// Optional line following a visited or pending line, for edges carrying a path
+const PATH_TAG: &str = "path";
+pub const FIELD_SEP: char = '\t';
+const NODE_TYPES_SEP: &str = ",";
+
+/// Records what a walk has visited and what it has queued but not yet visited,
+/// so that an interrupted walk can be picked up again without repeating finished work.
+#[derive(Default)]
+pub struct ResumeJournal {
+    visited: DashMap<OutgoingEdge, (), RandomState>,
+    pending: DashMap<OutgoingEdge, (), RandomState>,
+}
+
+impl ResumeJournal {
+    pub fn record_visit(&self, resolved: &OutgoingEdge, outgoing: &[OutgoingEdge]) {
+        self.pending.remove(resolved);
+        self.visited.insert(resolved.clone(), ());
+        self.record_pending(outgoing);
+    }
+
+    pub fn record_pending(&self, outgoing: &[OutgoingEdge]) {
+        for e in outgoing {
+            self.pending.insert(e.clone(), ());
+        }
+    }
+
+    pub fn snapshot(&self) -> ResumeState {
+        let visited: Vec<OutgoingEdge> = self.visited.iter().map(|e| e.key().clone()).collect();
+        // Steps to already visited nodes are dropped by the walk without a visit, so prune them here.
+        let visited_nodes: HashSet<&Node> = visited.iter().map(|e| &e.target).collect();
+        self.pending
+            .retain(|e, _| !visited_nodes.contains(&e.target));
+        let pending = self.pending.iter().map(|e| e.key().clone()).collect();
+        ResumeState { visited, pending }
+    }
+}
+
+/// Saved progress of a walk. Nodes are encoded with format_node so the file
+/// can be read back by parse_node in a later build, as long as the NodeType
+/// set has not changed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ResumeState {
+    pub visited: Vec<OutgoingEdge>,
+    pub pending: Vec<OutgoingEdge>,
+}
+
+/// Write an edge as a line tagged with tag, followed by a path line if the
+/// edge has a path. Also used to spill the walk frontier to disk.
+pub fn write_edge<W: Write>(w: &mut W, tag: &str, e: &OutgoingEdge) -> Result<(), Error> {
+    let label: &'static str = e.label.into();
+    writeln!(
+        w,
+        "{}{}{}{}{}",
+        tag,
+        FIELD_SEP,
+        label,
+        FIELD_SEP,
+        format_node(&e.target)
+    )?;
+    if let Some(path) = e.path.as_ref() {
+        writeln!(w, "{}{}{}", PATH_TAG, FIELD_SEP, format_path(path))?;
+    }
+    Ok(())
+}
+
+/// Parse the label and node fields of a line written by write_edge
+pub fn parse_edge(label: &str, node: &str) -> Result<OutgoingEdge, Error> {
+    Ok(OutgoingEdge::new(
+        EdgeType::from_str(label)?,
+        parse_node(node)?,
+    ))
+}
+
+/// If line is the path line of an edge written by write_edge, parse the path
+pub fn parse_path_line(line: &str) -> Option<Result<WrappedPath, Error>> {
+    // Paths can contain the separator, so take the rest of the line
+    line.strip_prefix(PATH_TAG)
+        .and_then(|rest| rest.strip_prefix(FIELD_SEP))
+        .map(parse_path)
+}
+
+fn node_types_line() -> String {
+    format!(
+        "{}{}{}",
+        NODE_TYPES_TAG,
+        FIELD_SEP,
+        NodeType::VARIANTS.join(NODE_TYPES_SEP)
+    )
+}
+
+impl ResumeState {
+    /// Write to a temporary file alongside path, then move it into place so
+    /// a crash while saving leaves the previous state intact.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)
+            .with_context(|| format!("Could not create resume state {}", tmp_path.display()))?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Could not move resume state to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Returns None if there is no saved state at path.
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(path)
+            .with_context(|| format!("Could not open resume state {}", path.display()))?;
+        Self::read(BufReader::new(file))
+            .with_context(|| format!("Could not load resume state {}", path.display()))
+            .map(Some)
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        writeln!(w, "{}", RESUME_HEADER)?;
+        writeln!(w, "{}", node_types_line())?;
+        for (tag, edges) in &[(VISITED_TAG, &self.visited), (PENDING_TAG, &self.pending)] {
+            for e in edges.iter() {
+                write_edge(w, tag, e)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: BufRead>(r: R) -> Result<Self, Error> {
+        let mut lines = r.lines();
+        match lines.next().transpose()? {
+            Some(header) if header == RESUME_HEADER => {}
+            header => bail!("Expected header {:?}, found {:?}", RESUME_HEADER, header),
+        }
+        match lines.next().transpose()? {
+            Some(node_types) if node_types == node_types_line() => {}
+            node_types => bail!(
+                "Resume state was saved with different node types, expected {:?}, found {:?}",
+                node_types_line(),
+                node_types
+            ),
+        }
+
+        let mut state = Self::default();
+        // Which list the last edge went to, so a following path line can be attached
+        let mut last: Option<&str> = None;
+        for line in lines {
+            let line = line?;
+            if let Some(path) = parse_path_line(&line) {
+                let edge = match last {
+                    Some(VISITED_TAG) => state.visited.last_mut(),
+                    Some(PENDING_TAG) => state.pending.last_mut(),
+                    _ => None,
+                }
+                .ok_or_else(|| format_err!("Path line {:?} without preceding edge", line))?;
+                edge.path = Some(path?);
+                continue;
+            }
+            let parts: Vec<_> = line.splitn(3, FIELD_SEP).collect();
+            match parts.as_slice() {
+                [tag, label, node] if *tag == VISITED_TAG || *tag == PENDING_TAG => {
+                    let edge = parse_edge(label, node)?;
+                    if *tag == VISITED_TAG {
+                        state.visited.push(edge);
+                        last = Some(VISITED_TAG);
+                    } else {
+                        state.pending.push(edge);
+                        last = Some(PENDING_TAG);
+                    }
+                }
+                _ => bail!("Unexpected line in resume state {:?}", line),
+            }
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::ChangesetKey;
+    use mercurial_types::HgFileNodeId;
+    use mononoke_types::{ChangesetId, MPath};
+
+    const SAMPLE_BLAKE2: &str = "b847b8838bfe3ae13ea6f8ce2d35a7b2260e2d4ec9ba4d8b2d2aaf1b04e5fb87";
+    const SAMPLE_SHA1: &str = "e797dcabeb8d6c5c3e4c0cdb0f0ee5e8c5e1d4a4";
+
+    fn sample_state() -> Result<ResumeState, Error> {
+        let bcs_id = ChangesetId::from_str(SAMPLE_BLAKE2)?;
+        let changeset = OutgoingEdge::new(
+            EdgeType::BookmarkToChangeset,
+            Node::Changeset(ChangesetKey {
+                inner: bcs_id,
+                filenode_known_derived: false,
+            }),
+        );
+        let envelope = OutgoingEdge::new_with_path(
+            EdgeType::HgManifestToHgFileEnvelope,
+            Node::HgFileEnvelope(HgFileNodeId::from_str(SAMPLE_SHA1)?),
+            // Paths needn't be UTF-8
+            Some(WrappedPath::from(Some(MPath::new(b"foo/bar\tbaz\xff")?))),
+        );
+        let root_path = OutgoingEdge::new_with_path(
+            EdgeType::HgManifestToHgFileEnvelope,
+            Node::HgFileEnvelope(HgFileNodeId::from_str(SAMPLE_SHA1)?),
+            Some(WrappedPath::Root),
+        );
+        Ok(ResumeState {
+            visited: vec![changeset],
+            pending: vec![envelope, root_path],
+        })
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<(), Error> {
+        let state = sample_state()?;
+        let mut buf = vec![];
+        state.write(&mut buf)?;
+        assert_eq!(state, ResumeState::read(buf.as_slice())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_types_mismatch() -> Result<(), Error> {
+        let mut buf = vec![];
+        sample_state()?.write(&mut buf)?;
+        let saved = String::from_utf8(buf)?.replacen("Root,", "", 1);
+        assert!(ResumeState::read(saved.as_bytes()).is_err());
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@
  */
 
 use crate::graph::{EdgeType, Node, NodeData, NodeType, WrappedPathHash, WrappedPathLike};
+use crate::resume::ResumeState;
 use crate::state::{InternedType, StepStats, WalkState};
 use crate::walk::{EmptyRoute, OutgoingEdge, StepRoute, TailingWalkVisitor, VisitOne, WalkVisitor};
 
@@ -133,6 +134,8 @@ pub struct PathTrackingRoute<P: WrappedPathLike> {
     /// When did this route see this path was updated.
     /// Taken from the last bonsai or hg changset stepped through.
     pub mtime: Option<DateTime>,
+    /// The last bonsai changeset stepped through, to attribute nodes to
+    pub changeset: Option<ChangesetId>,
 }
 
 // We don't hold these tracking so as to keep memory usage down in scrub
@@ -152,13 +155,22 @@ impl<P> PathTrackingRoute<P>
 where
     P: WrappedPathLike + Eq + Clone,
 {
-    fn evolve(route: Option<Self>, walk_item: &OutgoingEdge, mtime: Option<&DateTime>) -> Self {
+    fn evolve(
+        route: Option<Self>,
+        walk_item: &OutgoingEdge,
+        mtime: Option<&DateTime>,
+        changeset: Option<&ChangesetId>,
+    ) -> Self {
         let existing_path = route.as_ref().and_then(|r| r.path.as_ref());
         let existing_mtime = route.as_ref().and_then(|r| r.mtime.as_ref());
+        let existing_changeset = route.as_ref().and_then(|r| r.changeset.as_ref());
         let new_path = P::evolve_path(existing_path, &walk_item);
 
         // reuse same route if possible
-        if new_path == existing_path && (mtime.is_none() || mtime == existing_mtime) {
+        if new_path == existing_path
+            && (mtime.is_none() || mtime == existing_mtime)
+            && (changeset.is_none() || changeset == existing_changeset)
+        {
             if let Some(route) = route {
                 return route;
             }
@@ -166,7 +178,12 @@ where
 
         Self {
             path: new_path.cloned(),
-            mtime: mtime.cloned().or_else(|| route.and_then(|r| r.mtime)),
+            mtime: mtime
+                .cloned()
+                .or_else(|| route.as_ref().and_then(|r| r.mtime.clone())),
+            changeset: changeset
+                .cloned()
+                .or_else(|| route.and_then(|r| r.changeset)),
         }
     }
 }
@@ -190,6 +207,7 @@ impl<'a, P: WrappedPathLike> From<&'a WalkKeyOptPath<P>> for &'a Node {
 pub struct WalkPayloadMtime {
     pub data: Option<NodeData>,
     pub mtime: Option<DateTime>,
+    pub changeset: Option<ChangesetId>,
 }
 
 impl<T> TailingWalkVisitor for SamplingWalkVisitor<T> {
@@ -216,6 +234,14 @@ impl<T> TailingWalkVisitor for SamplingWalkVisitor<T> {
     fn num_deferred(&self) -> usize {
         self.inner.num_deferred()
     }
+
+    fn start_resume_journal(&mut self, previous: Option<ResumeState>) -> Vec<OutgoingEdge> {
+        self.inner.start_resume_journal(previous)
+    }
+
+    fn resume_state(&self) -> Option<ResumeState> {
+        self.inner.resume_state()
+    }
 }
 
 impl<T, P>
@@ -294,7 +320,12 @@ where
             _ => None,
         };
 
-        let route = PathTrackingRoute::evolve(route, &resolved, mtime);
+        let changeset = match &resolved.target {
+            Node::Changeset(k) => Some(k.inner),
+            _ => None,
+        };
+
+        let route = PathTrackingRoute::evolve(route, &resolved, mtime, changeset.as_ref());
         let ((n, nd, stats), _inner_route, outgoing) =
             self.inner
                 .visit(ctx, resolved, node_data, inner_route, outgoing);
@@ -308,6 +339,7 @@ where
                 WalkPayloadMtime {
                     data: nd,
                     mtime: route.mtime.clone(),
+                    changeset: route.changeset,
                 },
                 stats,
             ),
@@ -329,7 +361,7 @@ where
         Error,
     > {
         let inner_route = route.as_ref().map(|_| EmptyRoute {});
-        let route = PathTrackingRoute::evolve(route, walk_item, None);
+        let route = PathTrackingRoute::evolve(route, walk_item, None, None);
         let ((n, _nd, stats), _inner_route) =
             self.inner.defer_visit(bcs_id, walk_item, inner_route)?;
         Ok((
@@ -418,6 +450,7 @@ where
             WalkPayloadMtime {
                 data: nd,
                 mtime: None,
+                changeset: None,
             },
             stats,
         );
@@ -449,6 +482,7 @@ where
             WalkPayloadMtime {
                 data: nd,
                 mtime: None,
+                changeset: None,
             },
             stats,
         );
@@ -5,12 +5,15 @@
  * GNU General Public License version 2.
  */
 
+use crate::duplicates::{DuplicateContentTracker, DEFAULT_DUPLICATE_CONTENT_MAX};
 use crate::graph::{FileContentData, Node, NodeData, NodeType, WrappedPathHash, WrappedPathLike};
 use crate::log;
+use crate::output::NodeRecord;
 use crate::pack::{PackInfo, PackInfoLogOptions, PackInfoLogger};
+use crate::parquet_output::{ParquetNodeWriter, DEFAULT_ROW_GROUP_SIZE};
 use crate::progress::{
-    progress_stream, report_state, ProgressOptions, ProgressReporter, ProgressReporterUnprotected,
-    ProgressStateCountByType, ProgressStateMutex,
+    outcome_stream, progress_stream, report_state, OutcomeStateByType, ProgressOptions,
+    ProgressReporter, ProgressReporterUnprotected, ProgressStateCountByType, ProgressStateMutex,
 };
 use crate::sampling::{
     PathTrackingRoute, SamplingOptions, SamplingWalkVisitor, WalkKeyOptPath, WalkPayloadMtime,
@@ -18,11 +21,14 @@ use crate::sampling::{
 };
 use crate::setup::{
     parse_node_types, parse_pack_info_log_args, parse_progress_args, parse_sampling_args,
-    setup_common, JobWalkParams, OutputFormat, RepoSubcommandParams, EXCLUDE_OUTPUT_NODE_TYPE_ARG,
-    INCLUDE_OUTPUT_NODE_TYPE_ARG, LIMIT_DATA_FETCH_ARG, OUTPUT_FORMAT_ARG, SCRUB,
+    setup_common, JobWalkParams, OutputFormat, RepoSubcommandParams, DUPLICATE_CONTENT_MAX_ARG,
+    EXCLUDE_OUTPUT_NODE_TYPE_ARG, FIND_DUPLICATE_CONTENT_ARG, INCLUDE_OUTPUT_NODE_TYPE_ARG,
+    LIMIT_DATA_FETCH_ARG, OUTPUT_FORMAT_ARG, OUTPUT_PARQUET_DIR_ARG, SCRUB,
+    UNREACHABLE_KEYS_DIR_ARG, UNREACHABLE_KEY_LISTING_ARG,
 };
 use crate::sizing::SizingSample;
 use crate::tail::walk_exact_tail;
+use crate::unreachable::{check_walk_for_unreachable, UnreachableKeys};
 use crate::validate::TOTAL;
 use crate::walk::{EmptyRoute, RepoWalkParams, RepoWalkTypeParams};
 
@@ -31,7 +37,7 @@ use blobstore::BlobstoreGetData;
 use blobstore::SizeMetadata;
 use clap::ArgMatches;
 use cloned::cloned;
-use cmdlib::args::MononokeMatches;
+use cmdlib::args::{self, MononokeMatches};
 use context::CoreContext;
 use derive_more::{Add, Div, Mul, Sub};
 use fbinit::FacebookInit;
@@ -41,15 +47,17 @@ use futures::{
     TryFutureExt,
 };
 use metaconfig_types::BlobstoreId;
-use mononoke_types::datetime::DateTime;
+use mononoke_types::{datetime::DateTime, typed_hash::ContentIdContext};
+use multiplexedblob::BatchRepairOptions;
 use samplingblob::ComponentSamplingHandler;
 use slog::{info, Logger};
 use stats::prelude::*;
 use std::{
     collections::{HashMap, HashSet},
     fmt,
+    path::PathBuf,
     str::FromStr,
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
@@ -109,12 +117,15 @@ impl fmt::Display for ScrubStats {
 // Force load of leaf data like file contents that graph traversal did not need
 fn loading_stream<InStream, SS, L>(
     limit_data_fetch: bool,
-    scheduled_max: usize,
+    content_drain_max: usize,
     s: InStream,
     sampler: Arc<WalkSampleMapping<Node, ScrubSample>>,
     output_node_types: HashSet<NodeType>,
     output_format: OutputFormat,
+    parquet_writer: Option<Arc<Mutex<ParquetNodeWriter>>>,
     pack_info_logger: Option<L>,
+    duplicate_content: Option<Arc<DuplicateContentTracker>>,
+    unreachable_keys: Option<Arc<UnreachableKeys>>,
 ) -> impl Stream<Item = Result<(Node, Option<NodeData>, Option<ScrubStats>), Error>>
 where
     InStream: Stream<
@@ -131,16 +142,39 @@ where
         + Send,
     L: PackInfoLogger + 'static + Send,
 {
+    let json_node_types = if output_format == OutputFormat::Json {
+        output_node_types.clone()
+    } else {
+        HashSet::new()
+    };
+    let parquet_node_types = if parquet_writer.is_some() {
+        output_node_types.clone()
+    } else {
+        HashSet::new()
+    };
     s.map_ok(move |(walk_key, payload, _progress_stats)| {
         let mtime = payload.mtime;
         match payload.data {
-            Some(NodeData::FileContent(FileContentData::ContentStream(file_bytes_stream)))
-                if !limit_data_fetch =>
-            {
-                cloned!(sampler);
-                file_bytes_stream
-                    .try_fold(0, |acc, file_bytes| future::ok(acc + file_bytes.size()))
-                    .map_ok(move |num_bytes| {
+            Some(NodeData::FileContent(
+                content @ (FileContentData::ContentStream(_) | FileContentData::Buffered(_)),
+            )) if !limit_data_fetch => {
+                cloned!(sampler, duplicate_content);
+                let hasher = duplicate_content.as_ref().map(|_| ContentIdContext::new());
+                content
+                    .into_stream()
+                    .expect("content is not yet consumed")
+                    .try_fold((0, hasher), |(acc, mut hasher), file_bytes| {
+                        if let Some(hasher) = hasher.as_mut() {
+                            hasher.update(file_bytes.as_bytes());
+                        }
+                        future::ok((acc + file_bytes.size(), hasher))
+                    })
+                    .map_ok(move |(num_bytes, hasher)| {
+                        if let (Some(tracker), Some(hasher), Node::FileContent(content_id)) =
+                            (duplicate_content.as_ref(), hasher, &walk_key.node)
+                        {
+                            tracker.record(hasher.finish(), *content_id);
+                        }
                         let sample = sampler.complete_step(&walk_key.node);
                         (
                             walk_key,
@@ -162,6 +196,8 @@ where
                         OutputFormat::PrettyDebug => {
                             println!("Node {:?}: NodeData: {:#?}", walk_key.node, data_opt)
                         }
+                        // Output once any content stream is consumed, see below
+                        OutputFormat::Json => {}
                     }
                 }
                 let sample = data_opt
@@ -171,8 +207,28 @@ where
             }
         }
     })
-    .try_buffer_unordered(scheduled_max)
+    .try_buffer_unordered(content_drain_max)
+    .and_then(move |(walk_key, mtime, data_opt, sample)| {
+        // As with json, written once any content stream is consumed so its size is known
+        let res = match parquet_writer.as_ref() {
+            Some(writer) if parquet_node_types.contains(&walk_key.node.get_type()) => writer
+                .lock()
+                .expect("lock poisoned")
+                .append(&walk_key.node, data_opt.as_ref()),
+            _ => Ok(()),
+        };
+        future::ready(res.map(|()| (walk_key, mtime, data_opt, sample)))
+    })
     .map_ok(move |(walk_key, mtime, data_opt, sample)| {
+        if let Some(unreachable_keys) = unreachable_keys.as_ref() {
+            unreachable_keys.record(&walk_key.node);
+        }
+        if json_node_types.contains(&walk_key.node.get_type()) {
+            println!(
+                "{}",
+                NodeRecord::new(&walk_key.node, data_opt.as_ref()).to_json()
+            );
+        }
         let size = if let Some(sample) = sample {
             let size = ScrubStats::from(sample.as_ref());
             if let Some(logger) = pack_info_logger.as_ref() {
@@ -419,9 +475,16 @@ struct ScrubCommand {
     limit_data_fetch: bool,
     output_format: OutputFormat,
     output_node_types: HashSet<NodeType>,
+    parquet_output_dir: Option<PathBuf>,
     progress_options: ProgressOptions,
     sampling_options: SamplingOptions,
     pack_info_log_options: Option<PackInfoLogOptions>,
+    // Most distinct content hashes to track when finding duplicate content
+    duplicate_content_max: Option<usize>,
+    // Blobstore key listing to find unreachable derived data in, and where to write what is found
+    unreachable_keys_paths: Option<(PathBuf, PathBuf)>,
+    // Scrub repairs batched by the blobstore, flushed when each walk completes
+    batch_repair: Option<BatchRepairOptions>,
     sampler: Arc<WalkSampleMapping<Node, ScrubSample>>,
 }
 
@@ -466,9 +529,30 @@ pub async fn scrub_objects<'a>(
         limit_data_fetch: sub_m.is_present(LIMIT_DATA_FETCH_ARG),
         output_format,
         output_node_types,
+        parquet_output_dir: sub_m.value_of(OUTPUT_PARQUET_DIR_ARG).map(PathBuf::from),
         progress_options: parse_progress_args(&sub_m),
         sampling_options: parse_sampling_args(&sub_m, 1)?,
         pack_info_log_options: parse_pack_info_log_args(fb, &sub_m)?,
+        duplicate_content_max: if sub_m.is_present(FIND_DUPLICATE_CONTENT_ARG) {
+            Some(
+                args::get_usize_opt(&sub_m, DUPLICATE_CONTENT_MAX_ARG)
+                    .unwrap_or(DEFAULT_DUPLICATE_CONTENT_MAX),
+            )
+        } else {
+            None
+        },
+        unreachable_keys_paths: sub_m
+            .value_of(UNREACHABLE_KEY_LISTING_ARG)
+            .and_then(|listing| {
+                sub_m
+                    .value_of(UNREACHABLE_KEYS_DIR_ARG)
+                    .map(|dir| (PathBuf::from(listing), PathBuf::from(dir)))
+            }),
+        batch_repair: matches
+            .blobstore_options()
+            .scrub_options
+            .as_ref()
+            .and_then(|scrub_options| scrub_options.batch_repair.clone()),
         sampler: component_sampler,
     };
 
@@ -500,29 +584,105 @@ async fn run_one(
             command.sampling_options.node_types.clone(),
             command.progress_options,
         ));
+    let outcome_state = ProgressStateMutex::new(OutcomeStateByType::new(
+        fb,
+        repo_params.logger.clone(),
+        SCRUB,
+        repo_params.repo.name().clone(),
+        repo_params.include_node_types.clone(),
+        command.progress_options,
+    ));
+
+    let duplicate_content = command.duplicate_content_max.map(|max| {
+        Arc::new(DuplicateContentTracker::new(
+            repo_params.logger.clone(),
+            max,
+        ))
+    });
+
+    let unreachable_keys = match command.unreachable_keys_paths {
+        Some(_) => {
+            check_walk_for_unreachable(
+                &repo_params.walk_roots,
+                &repo_params.include_node_types,
+                &repo_params.include_edge_types,
+            )?;
+            Some(Arc::new(UnreachableKeys::new(
+                repo_params.logger.clone(),
+                repo_params.repo.get_repoid(),
+            )))
+        }
+        None => None,
+    };
 
     let make_sink = {
-        cloned!(command, job_params.quiet, sub_params.progress_state,);
+        cloned!(
+            command,
+            job_params.quiet,
+            sub_params.progress_state,
+            duplicate_content,
+            unreachable_keys
+        );
         move |ctx: &CoreContext, repo_params: &RepoWalkParams| {
             let repo_name = repo_params.repo.name().clone();
-            cloned!(ctx, repo_params.scheduled_max);
+            cloned!(ctx, repo_params.content_drain_max);
             async move |walk_output, run_start, chunk_num, checkpoint_name| {
                 let walk_progress = progress_stream(quiet, &progress_state, walk_output);
+                let parquet_writer = command
+                    .parquet_output_dir
+                    .as_ref()
+                    .map(|dir| {
+                        let file_name = format!(
+                            "{}_{}_{}.parquet",
+                            repo_name.replace('/', "_"),
+                            run_start.timestamp_seconds(),
+                            chunk_num
+                        );
+                        ParquetNodeWriter::create(&dir.join(file_name), DEFAULT_ROW_GROUP_SIZE)
+                    })
+                    .transpose()?
+                    .map(|writer| Arc::new(Mutex::new(writer)));
+                let unreachable_keys_file =
+                    format!("{}.unreachable_keys", repo_name.replace('/', "_"));
                 let loading = loading_stream(
                     command.limit_data_fetch,
-                    scheduled_max,
+                    content_drain_max,
                     walk_progress,
                     command.sampler,
                     command.output_node_types,
                     command.output_format,
+                    parquet_writer.clone(),
                     command
                         .pack_info_log_options
                         .map(|o| o.make_logger(repo_name, run_start, chunk_num, checkpoint_name)),
+                    duplicate_content.clone(),
+                    unreachable_keys.clone(),
                 );
                 let report_sizing = progress_stream(quiet, &sizing_progress_state, loading);
+                let report_outcomes = outcome_stream(quiet, &outcome_state, report_sizing);
 
-                report_state(ctx, report_sizing).await?;
+                report_state(ctx.clone(), report_outcomes).await?;
+                if let Some(batch_repair) = &command.batch_repair {
+                    batch_repair.flush(&ctx).await?;
+                }
+                if let Some(writer) = parquet_writer {
+                    // The stream has been consumed, so this is the last reference
+                    Arc::try_unwrap(writer)
+                        .map_err(|_| format_err!("Parquet writer still in use"))?
+                        .into_inner()
+                        .map_err(|_| format_err!("Parquet writer lock poisoned"))?
+                        .close()?;
+                }
+                if let Some(duplicate_content) = &duplicate_content {
+                    duplicate_content.report();
+                }
+                if let (Some(unreachable_keys), Some((listing, dir))) =
+                    (&unreachable_keys, &command.unreachable_keys_paths)
+                {
+                    unreachable_keys.report(listing, &dir.join(unreachable_keys_file))?;
+                }
                 sizing_progress_state.report_progress();
+                outcome_state.report_progress();
                 progress_state.report_progress();
                 Ok(())
             }
@@ -558,6 +718,8 @@ async fn run_one(
         required_node_data_types,
         always_emit_edge_types: HashSet::new(),
         keep_edge_paths: command.pack_info_log_options.is_some(),
+        // Draft data under scratch bookmarks is live too
+        include_scratch_bookmarks: unreachable_keys.is_some(),
     };
 
     if command.pack_info_log_options.is_some() {
@@ -7,18 +7,20 @@
 
 use crate::blobstore;
 use crate::checkpoint::{CheckpointsByName, SqlCheckpoints};
-use crate::graph::{EdgeType, Node, NodeType, SqlShardInfo};
+use crate::edge_list::EdgeListWriter;
+use crate::graph::{ActiveKeysPerShard, ChangesetKey, EdgeType, Node, NodeType, SqlShardInfo};
 use crate::log;
 use crate::pack::PackInfoLogOptions;
-use crate::parse_node::parse_node;
+use crate::parse_node::{parse_node, parse_node_lines};
 use crate::progress::{
     sort_by_string, ProgressOptions, ProgressStateCountByType, ProgressStateMutex, ProgressSummary,
 };
 use crate::sampling::SamplingOptions;
+use crate::spill::FrontierSpillParams;
 use crate::state::{InternedType, StepStats};
-use crate::tail::{ChunkingParams, ClearStateParams, TailParams};
+use crate::tail::{ChunkingParams, ClearStateParams, ResumeParams, TailParams};
 use crate::validate::{CheckType, REPO, WALK_TYPE};
-use crate::walk::{OutgoingEdge, RepoWalkParams};
+use crate::walk::{FrontierStats, OutgoingEdge, RepoWalkParams};
 
 use ::blobstore::Blobstore;
 use anyhow::{bail, format_err, Context, Error};
@@ -32,13 +34,16 @@ use cmdlib::args::{
     self, ArgType, CachelibSettings, MononokeClapApp, MononokeMatches, RepoRequirement,
     ResolvedRepo,
 };
+use context::CoreContext;
 use derived_data_filenodes::FilenodesOnlyPublic;
 use derived_data_manager::BonsaiDerivable as NewBonsaiDerivable;
 use fbinit::FacebookInit;
+use futures::future::try_join_all;
 use itertools::{process_results, Itertools};
 use maplit::hashset;
 use mercurial_derived_data::MappedHgChangesetId;
 use metaconfig_types::{MetadataDatabaseConfig, Redaction};
+use mononoke_types::{ChangesetId, MPath, Timestamp};
 use multiplexedblob::ScrubHandler;
 use newfilenodes::NewFilenodesBuilder;
 use once_cell::sync::Lazy;
@@ -51,7 +56,9 @@ use sql_ext::facebook::MysqlOptions;
 use std::{
     borrow::Borrow,
     collections::{HashMap, HashSet},
+    io,
     num::{NonZeroU32, NonZeroU64},
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
     time::Duration,
@@ -74,32 +81,132 @@ pub struct JobWalkParams {
     pub error_as_data_node_types: HashSet<NodeType>,
     pub error_as_data_edge_types: HashSet<EdgeType>,
     pub repo_count: usize,
+    pub max_concurrent_content_streams: Option<usize>,
+    /// If set, FileContent up to this many bytes is read into memory when stepped to,
+    /// so that each check wanting its bytes can see them. Larger content is streamed.
+    pub content_buffer_max_bytes: Option<usize>,
+    /// If set, only nodes in a 1 in N sample have their data kept and hashes validated
+    pub sample_rate: Option<u64>,
+    /// If set, only nodes in a 1 in N sample have their hashes validated, others are
+    /// still loaded and keep their data
+    pub validate_sample_rate: Option<u64>,
+    /// Most edges a single node may expand to, None is unlimited
+    pub max_outgoing_per_node: Option<usize>,
+    pub max_outgoing_action: MaxOutgoingAction,
+    /// Check that derived data parents agree with the bonsai parents
+    pub validate_parent_linkage: bool,
+    /// Check that hg changesets map to a bonsai that maps back to them
+    pub validate_hg_bonsai_round_trip: bool,
+    /// Check that all of a content's aliases map back to it
+    pub validate_content_aliases: bool,
+    /// Check that each FileContent streams as many bytes as its metadata says
+    pub validate_content_size: bool,
+    /// Check that each FileContentMetadata's size and hashes match its FileContent
+    pub validate_content_metadata: bool,
+    /// Check that the changeset each mapping node maps from still exists
+    pub check_orphaned_mappings: bool,
+    /// Check that draft changesets have no public-only derived data such as fastlog
+    pub validate_draft_derived_data: bool,
+    /// If set, follow each HgFileNode's copyfrom chain up to this length, checking it
+    /// terminates and every source exists
+    pub copyfrom_chain_max_length: Option<usize>,
+    /// If set, follow each FastlogDir and FastlogFile's previous batches up to this many
+    /// batches, checking they terminate and every batch exists
+    pub fastlog_chain_max_length: Option<usize>,
+    /// Only expand changesets that are public, skipping drafts and their derived data
+    pub public_only: bool,
+    /// If set, nodes with repo paths are only stepped to if under this prefix
+    pub path_prefix: Option<MPath>,
+    pub traversal_order: TraversalOrder,
+    /// If set, page the frontier out to disk once it holds more than this many edges
+    pub frontier_spill: Option<FrontierSpillParams>,
+}
+
+/// What to do when a node has more than max_outgoing_per_node edges
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(AsRefStr, EnumVariantNames, EnumString)]
+pub enum MaxOutgoingAction {
+    /// Keep the first max_outgoing_per_node edges and log a warning
+    Truncate,
+    /// Fail the walk
+    Fail,
+}
+
+/// How the walk frontier of edges waiting to be stepped to is drained.
+///
+/// Dfs keeps the frontier small, as each node's children are finished before
+/// moving on, so it is roughly bounded by depth times fan out. Bfs spreads
+/// load across the repo, but holds a whole level of the graph at once, so
+/// its frontier is unbounded on wide graphs. LowestFingerprintFirst gives a
+/// deterministic order across runs, with memory use similar to Bfs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(AsRefStr, EnumVariantNames, EnumString)]
+pub enum TraversalOrder {
+    /// Step to the most recently discovered edges first
+    Dfs,
+    /// Step to the least recently discovered edges first
+    Bfs,
+    /// Step to the edge whose target has the lowest sampling fingerprint
+    /// first. Nodes without a fingerprint go first.
+    LowestFingerprintFirst,
 }
 
 const PROGRESS_SAMPLE_RATE: u64 = 1000;
 const PROGRESS_SAMPLE_DURATION_S: u64 = 5;
+const DEFAULT_COPYFROM_CHAIN_MAX_LENGTH: usize = 1000;
+const DEFAULT_FASTLOG_CHAIN_MAX_LENGTH: usize = 1000;
 
 // Sub commands
 pub const SCRUB: &str = "scrub";
 pub const COMPRESSION_BENEFIT: &str = "compression-benefit";
 pub const VALIDATE: &str = "validate";
 pub const CORPUS: &str = "corpus";
+pub const DIFF_RESULTS: &str = "diff-results";
 
 // Subcommand args
 const QUIET_ARG: &str = "quiet";
 const ENABLE_REDACTION_ARG: &str = "enable-redaction";
 const SCHEDULED_MAX_ARG: &str = "scheduled-max";
+const MAX_CONCURRENT_CONTENT_STREAMS_ARG: &str = "max-concurrent-content-streams";
+const CONTENT_DRAIN_CONCURRENCY_ARG: &str = "content-drain-concurrency";
+const CONTENT_BUFFER_MAX_BYTES_ARG: &str = "content-buffer-max-bytes";
+const NODE_SAMPLE_RATE_ARG: &str = "node-sample-rate";
+const VALIDATE_SAMPLE_RATE_ARG: &str = "validate-sample-rate";
+const MAX_OUTGOING_PER_NODE_ARG: &str = "max-outgoing-per-node";
+const MAX_OUTGOING_ACTION_ARG: &str = "max-outgoing-action";
+const VALIDATE_PARENT_LINKAGE_ARG: &str = "validate-parent-linkage";
+const VALIDATE_HG_BONSAI_ROUND_TRIP_ARG: &str = "validate-hg-bonsai-round-trip";
+const VALIDATE_CONTENT_ALIASES_ARG: &str = "validate-content-aliases";
+const VALIDATE_CONTENT_SIZE_ARG: &str = "validate-content-size";
+const VALIDATE_CONTENT_METADATA_ARG: &str = "validate-content-metadata";
+const CHECK_ORPHANED_MAPPINGS_ARG: &str = "check-orphaned-mappings";
+const VALIDATE_DRAFT_DERIVED_DATA_ARG: &str = "validate-draft-derived-data";
+const VALIDATE_COPYFROM_CHAINS_ARG: &str = "validate-copyfrom-chains";
+const COPYFROM_CHAIN_MAX_LENGTH_ARG: &str = "copyfrom-chain-max-length";
+const VALIDATE_FASTLOG_CHAINS_ARG: &str = "validate-fastlog-chains";
+const FASTLOG_CHAIN_MAX_LENGTH_ARG: &str = "fastlog-chain-max-length";
+const PUBLIC_ONLY_ARG: &str = "public-only";
+const OUTPUT_EDGE_LIST_DIR_ARG: &str = "output-edge-list-dir";
+const PATH_PREFIX_ARG: &str = "path-prefix";
+const TRAVERSAL_ORDER_ARG: &str = "traversal-order";
+const FRONTIER_SPILL_THRESHOLD_ARG: &str = "frontier-spill-threshold";
+const FRONTIER_SPILL_DIR_ARG: &str = "frontier-spill-dir";
+const METADATA_ACTIVE_KEYS_PER_SHARD_ARG: &str = "metadata-active-keys-per-shard";
+const FILENODES_ACTIVE_KEYS_PER_SHARD_ARG: &str = "filenodes-active-keys-per-shard";
 const TAIL_INTERVAL_ARG: &str = "tail-interval";
 const ERROR_AS_DATA_NODE_TYPE_ARG: &str = "error-as-data-node-type";
 const ERROR_AS_DATA_EDGE_TYPE_ARG: &str = "error-as-data-edge-type";
 const EXCLUDE_NODE_TYPE_ARG: &str = "exclude-node-type";
 const INCLUDE_NODE_TYPE_ARG: &str = "include-node-type";
+const PRUNE_NODE_TYPE_ARG: &str = "prune-node-type";
 const EXCLUDE_EDGE_TYPE_ARG: &str = "exclude-edge-type";
 const INCLUDE_EDGE_TYPE_ARG: &str = "include-edge-type";
 const EXCLUDE_HASH_VALIDATION_NODE_TYPE_ARG: &str = "exclude-hash-validation-node-type";
 const INCLUDE_HASH_VALIDATION_NODE_TYPE_ARG: &str = "include-hash-validation-node-type";
 const BOOKMARK_ARG: &str = "bookmark";
 const WALK_ROOT_ARG: &str = "walk-root";
+const WALK_ROOTS_FROM_STDIN_ARG: &str = "walk-roots-from-stdin";
+const SEED_BOOKMARK_ARG: &str = "seed-bookmark";
 const CHUNK_BY_PUBLIC_ARG: &str = "chunk-by-public";
 const CHUNK_DIRECTION_ARG: &str = "chunk-direction";
 const CHUNK_SIZE_ARG: &str = "chunk-size";
@@ -112,6 +219,8 @@ const CHECKPOINT_NAME_ARG: &str = "checkpoint-name";
 const CHECKPOINT_PATH_ARG: &str = "checkpoint-path";
 const CHECKPOINT_SAMPLE_RATE_ARG: &str = "checkpoint-sample-rate";
 const STATE_MAX_AGE_ARG: &str = "state-max-age";
+const RESUME_STATE_DIR_ARG: &str = "resume-state-dir";
+const RESUME_STATE_SAVE_INTERVAL_ARG: &str = "resume-state-save-interval";
 const REPO_LOWER_BOUND: &str = "repo-lower-bound";
 const REPO_UPPER_BOUND: &str = "repo-upper-bound";
 const ALLOW_REMAINING_DEFERRED_ARG: &str = "allow-remaining-deferred";
@@ -121,6 +230,7 @@ const PROGRESS_SAMPLE_RATE_ARG: &str = "progress-sample-rate";
 const PROGRESS_INTERVAL_ARG: &str = "progress-interval";
 pub const LIMIT_DATA_FETCH_ARG: &str = "limit-data-fetch";
 pub const COMPRESSION_LEVEL_ARG: &str = "compression-level";
+pub const CHANGESET_FOOTPRINT_ARG: &str = "changeset-footprint";
 const SAMPLE_RATE_ARG: &str = "sample-rate";
 const SAMPLE_OFFSET_ARG: &str = "sample-offset";
 pub const EXCLUDE_CHECK_TYPE_ARG: &str = "exclude-check-type";
@@ -132,6 +242,13 @@ pub const EXCLUDE_OUTPUT_NODE_TYPE_ARG: &str = "exclude-output-node-type";
 pub const INCLUDE_OUTPUT_NODE_TYPE_ARG: &str = "include-output-node-type";
 pub const OUTPUT_FORMAT_ARG: &str = "output-format";
 pub const OUTPUT_DIR_ARG: &str = "output-dir";
+pub const OUTPUT_PARQUET_DIR_ARG: &str = "output-parquet-dir";
+pub const FIND_DUPLICATE_CONTENT_ARG: &str = "find-duplicate-content";
+pub const DUPLICATE_CONTENT_MAX_ARG: &str = "duplicate-content-max";
+pub const UNREACHABLE_KEY_LISTING_ARG: &str = "unreachable-key-listing";
+pub const UNREACHABLE_KEYS_DIR_ARG: &str = "unreachable-keys-dir";
+pub const DIFF_BEFORE_ARG: &str = "before";
+pub const DIFF_AFTER_ARG: &str = "after";
 const SCUBA_TABLE_ARG: &str = "scuba-table";
 const SCUBA_LOG_FILE_ARG: &str = "scuba-log-file";
 const BLOBSTORE_SAMPLING_MULTIPLIER: &str = "blobstore-sampling-multiplier";
@@ -149,6 +266,7 @@ const MARKER_VALUE_ARG: &str = "marker";
 const HG_VALUE_ARG: &str = "hg";
 const BONSAI_VALUE_ARG: &str = "bonsai";
 const CONTENT_META_VALUE_ARG: &str = "contentmeta";
+const MAPPING_VALUE_ARG: &str = "mapping";
 const ALL_VALUE_ARG: &str = "all";
 
 const DERIVED_PREFIX: &str = "derived_";
@@ -172,6 +290,7 @@ static NODE_TYPE_POSSIBLE_VALUES: Lazy<Vec<&'static str>> = Lazy::new(|| {
         DEFAULT_VALUE_ARG,
         DERIVED_VALUE_ARG,
         HG_VALUE_ARG,
+        MAPPING_VALUE_ARG,
     ];
     v.extend(
         DERIVED_DATA_INCLUDE_NODE_TYPES
@@ -182,8 +301,12 @@ static NODE_TYPE_POSSIBLE_VALUES: Lazy<Vec<&'static str>> = Lazy::new(|| {
     v
 });
 
-static NODE_HASH_VALIDATION_POSSIBLE_VALUES: Lazy<Vec<&'static str>> =
-    Lazy::new(|| vec![NodeType::HgFileEnvelope.into()]);
+static NODE_HASH_VALIDATION_POSSIBLE_VALUES: Lazy<Vec<&'static str>> = Lazy::new(|| {
+    NodeType::iter()
+        .filter(|t| t.supports_hash_validation())
+        .map(|t| t.into())
+        .collect()
+});
 
 /// Default to clearing out all except HgChangesets ( and bonsai Changsets as no option to clear those)
 const DEFAULT_CHUNK_CLEAR_INTERNED_TYPES: &[InternedType] = &[
@@ -209,6 +332,7 @@ const CHUNK_BY_PUBLIC_NODE_TYPES: &[NodeType] = &[
     NodeType::ChangesetInfoMapping,
     NodeType::DeletedManifestMapping,
     NodeType::FsnodeMapping,
+    NodeType::GitTreeMapping,
     NodeType::SkeletonManifestMapping,
     NodeType::UnodeMapping,
 ];
@@ -229,6 +353,7 @@ static EDGE_TYPE_POSSIBLE_VALUES: Lazy<Vec<&'static str>> = Lazy::new(|| {
         HG_VALUE_ARG,
         CONTENT_META_VALUE_ARG,
         MARKER_VALUE_ARG,
+        MAPPING_VALUE_ARG,
     ];
     v.extend(EdgeType::VARIANTS.iter());
     v
@@ -257,6 +382,17 @@ const DEFAULT_INCLUDE_NODE_TYPES: &[NodeType] = &[
 
 const BONSAI_INCLUDE_NODE_TYPES: &[NodeType] = &[NodeType::Bookmark, NodeType::Changeset];
 
+/// Mappings from a changeset to its derived data, None if underived
+pub const DERIVED_MAPPING_NODE_TYPES: &[NodeType] = &[
+    NodeType::BonsaiHgMapping,
+    NodeType::ChangesetInfoMapping,
+    NodeType::DeletedManifestMapping,
+    NodeType::FsnodeMapping,
+    NodeType::GitTreeMapping,
+    NodeType::SkeletonManifestMapping,
+    NodeType::UnodeMapping,
+];
+
 // Goes as far into history as it can
 const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     // Bonsai
@@ -270,6 +406,7 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::ChangesetToChangesetInfoMapping,
     EdgeType::ChangesetToDeletedManifestMapping,
     EdgeType::ChangesetToFsnodeMapping,
+    EdgeType::ChangesetToGitTreeMapping,
     EdgeType::ChangesetToSkeletonManifestMapping,
     EdgeType::ChangesetToUnodeMapping,
     // Hg
@@ -312,6 +449,8 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::FsnodeMappingToRootFsnode,
     EdgeType::FsnodeToChildFsnode,
     EdgeType::FsnodeToFileContent,
+    EdgeType::GitTreeMappingToRootGitTree,
+    EdgeType::GitTreeToGitTreeChild,
     EdgeType::SkeletonManifestMappingToRootSkeletonManifest,
     EdgeType::SkeletonManifestToSkeletonManifestChild,
     EdgeType::UnodeFileToBlame,
@@ -339,6 +478,7 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::ChangesetToChangesetInfoMapping,
     EdgeType::ChangesetToDeletedManifestMapping,
     EdgeType::ChangesetToFsnodeMapping,
+    EdgeType::ChangesetToGitTreeMapping,
     EdgeType::ChangesetToSkeletonManifestMapping,
     EdgeType::ChangesetToUnodeMapping,
     // Hg
@@ -367,6 +507,8 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::FsnodeToChildFsnode,
     EdgeType::FsnodeToFileContent,
     EdgeType::FsnodeMappingToRootFsnode,
+    EdgeType::GitTreeMappingToRootGitTree,
+    EdgeType::GitTreeToGitTreeChild,
     EdgeType::SkeletonManifestMappingToRootSkeletonManifest,
     EdgeType::SkeletonManifestToSkeletonManifestChild,
     EdgeType::UnodeFileToBlame,
@@ -423,11 +565,27 @@ const CONTENT_META_EDGE_TYPES: &[EdgeType] = &[
 pub enum OutputFormat {
     Debug,
     PrettyDebug,
+    /// One JSON NodeRecord per line
+    Json,
 }
 
 // Things like phases and obs markers will go here
 const MARKER_EDGE_TYPES: &[EdgeType] = &[EdgeType::ChangesetToPhaseMapping];
 
+// Walk changeset history and its derived data mappings, without loading the derived data itself
+const MAPPING_EDGE_TYPES: &[EdgeType] = &[
+    EdgeType::BookmarkToChangeset,
+    EdgeType::PublishedBookmarksToChangeset,
+    EdgeType::ChangesetToBonsaiParent,
+    EdgeType::ChangesetToBonsaiHgMapping,
+    EdgeType::ChangesetToChangesetInfoMapping,
+    EdgeType::ChangesetToDeletedManifestMapping,
+    EdgeType::ChangesetToFsnodeMapping,
+    EdgeType::ChangesetToGitTreeMapping,
+    EdgeType::ChangesetToSkeletonManifestMapping,
+    EdgeType::ChangesetToUnodeMapping,
+];
+
 static INCLUDE_NODE_TYPE_HELP: Lazy<String> = Lazy::new(|| {
     format!(
         "Graph node types we want to step to in the walk. Defaults to core Mononoke and Hg types: {:?}. See --{} for all possible values.",
@@ -544,23 +702,10 @@ pub fn parse_sampling_args(
     })
 }
 
-pub fn setup_toplevel_app<'a, 'b>(
-    app_name: &str,
-    cachelib_defaults: CachelibSettings,
-) -> MononokeClapApp<'a, 'b> {
-    let app_template = args::MononokeAppBuilder::new(app_name)
-        .with_arg_types(vec![ArgType::Scrub])
-        .with_blobstore_cachelib_attempt_zstd_default(false)
-        .with_blobstore_read_qps_default(NonZeroU32::new(20000))
-        .with_scrub_action_on_missing_write_mostly_default(Some(ScrubWriteMostly::SkipMissing))
-        .with_readonly_storage_default(ReadOnlyStorage(true))
-        .with_repo_required(RepoRequirement::AtLeastOne)
-        .with_fb303_args()
-        .with_cachelib_settings(cachelib_defaults);
-
+fn setup_scrub_subcommand<'a, 'b>() -> App<'a, 'b> {
     let scrub_objects =
         setup_subcommand_args(SubCommand::with_name(SCRUB).about("scrub, checks data is present by reading it and counting it. Combine with --enable-scrub-blobstore to check across a multiplex"));
-    let scrub_objects = add_sampling_args(scrub_objects)
+    add_sampling_args(scrub_objects)
         .arg(
             Arg::with_name(EXCLUDE_OUTPUT_NODE_TYPE_ARG)
                 .long(EXCLUDE_OUTPUT_NODE_TYPE_ARG)
@@ -593,6 +738,61 @@ pub fn setup_toplevel_app<'a, 'b>(
                 .required(false)
                 .help("Set the output format"),
         )
+        .arg(
+            Arg::with_name(OUTPUT_PARQUET_DIR_ARG)
+                .long(OUTPUT_PARQUET_DIR_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Directory to also write output nodes to as parquet, one file per run"),
+        )
+        .arg(
+            Arg::with_name(FIND_DUPLICATE_CONTENT_ARG)
+                .long(FIND_DUPLICATE_CONTENT_ARG)
+                .takes_value(false)
+                .required(false)
+                .conflicts_with(LIMIT_DATA_FETCH_ARG)
+                .help("Hash file content as it is streamed and report ContentIds whose content is byte-identical"),
+        )
+        .arg(
+            Arg::with_name(DUPLICATE_CONTENT_MAX_ARG)
+                .long(DUPLICATE_CONTENT_MAX_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(FIND_DUPLICATE_CONTENT_ARG)
+                .help("Most distinct content hashes to remember when finding duplicate content. Content seen after the limit is reached is counted but not checked."),
+        )
+        .arg(
+            Arg::with_name(UNREACHABLE_KEY_LISTING_ARG)
+                .long(UNREACHABLE_KEY_LISTING_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(UNREACHABLE_KEYS_DIR_ARG)
+                // Each of these shrinks the walk, so live keys would be reported as unreachable
+                .conflicts_with_all(&[
+                    CHUNK_BY_PUBLIC_ARG,
+                    TAIL_INTERVAL_ARG,
+                    PUBLIC_ONLY_ARG,
+                    PATH_PREFIX_ARG,
+                    PRUNE_NODE_TYPE_ARG,
+                    MAX_OUTGOING_PER_NODE_ARG,
+                    NODE_SAMPLE_RATE_ARG,
+                    EXCLUDE_EDGE_TYPE_ARG,
+                    BOOKMARK_ARG,
+                    SEED_BOOKMARK_ARG,
+                    WALK_ROOTS_FROM_STDIN_ARG,
+                    // A resumed walk skips what it visited before it was interrupted
+                    RESUME_STATE_DIR_ARG,
+                ])
+                .help("File listing the keys in the blobstore, one per line and with their repo prefix. Once the walk completes, the fsnode, unode and skeleton manifest keys in it that the walk did not reach are written to a file per repo in --unreachable-keys-dir. The walk must start from --walk-root PublishedBookmarks, which then also steps to scratch bookmarks, and be deep, as anything it misses is reported as unreachable. Take the listing before the walk starts, so derived data written during the walk is not in it."),
+        )
+        .arg(
+            Arg::with_name(UNREACHABLE_KEYS_DIR_ARG)
+                .long(UNREACHABLE_KEYS_DIR_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(UNREACHABLE_KEY_LISTING_ARG)
+                .help("Directory to write the unreachable derived data keys to, one file per repo with a key per line"),
+        )
         .arg(
             Arg::with_name(EXCLUDE_PACK_LOG_NODE_TYPE_ARG)
                 .long(EXCLUDE_PACK_LOG_NODE_TYPE_ARG)
@@ -630,7 +830,24 @@ pub fn setup_toplevel_app<'a, 'b>(
                 .required(false)
                 .requires(INCLUDE_PACK_LOG_NODE_TYPE_ARG)
                 .help("A log file to write Scuba pack info logs to (primarily useful in testing)"),
-        );
+        )
+}
+
+pub fn setup_toplevel_app<'a, 'b>(
+    app_name: &str,
+    cachelib_defaults: CachelibSettings,
+) -> MononokeClapApp<'a, 'b> {
+    let app_template = args::MononokeAppBuilder::new(app_name)
+        .with_arg_types(vec![ArgType::Scrub])
+        .with_blobstore_cachelib_attempt_zstd_default(false)
+        .with_blobstore_read_qps_default(NonZeroU32::new(20000))
+        .with_scrub_action_on_missing_write_mostly_default(Some(ScrubWriteMostly::SkipMissing))
+        .with_readonly_storage_default(ReadOnlyStorage(true))
+        .with_repo_required(RepoRequirement::AtLeastOne)
+        .with_fb303_args()
+        .with_cachelib_settings(cachelib_defaults);
+
+    let scrub_objects = setup_scrub_subcommand();
 
     let compression_benefit = setup_subcommand_args(
         SubCommand::with_name(COMPRESSION_BENEFIT).about("estimate compression benefit"),
@@ -641,6 +858,13 @@ pub fn setup_toplevel_app<'a, 'b>(
             .takes_value(true)
             .required(false)
             .help("Zstd compression level to use. 3 is the default"),
+    )
+    .arg(
+        Arg::with_name(CHANGESET_FOOTPRINT_ARG)
+            .long(CHANGESET_FOOTPRINT_ARG)
+            .takes_value(false)
+            .required(false)
+            .help("Also report the bytes of file content and other blobs each changeset is responsible for, as ChangesetId: bytes. Blobs reachable from several changesets are counted once, for the first changeset the walk reaches them from. Sample rate defaults to 1 so every node is counted."),
     );
     let compression_benefit = add_sampling_args(compression_benefit);
 
@@ -681,6 +905,24 @@ pub fn setup_toplevel_app<'a, 'b>(
             .help("Check types to include, defaults to all possible values"),
     );
 
+    // Compares output files, so takes none of the walk args
+    let diff_results = SubCommand::with_name(DIFF_RESULTS)
+        .about("compare the node statuses output by two runs, printing the nodes that changed as json")
+        .arg(
+            Arg::with_name(DIFF_BEFORE_ARG)
+                .long(DIFF_BEFORE_ARG)
+                .takes_value(true)
+                .required(true)
+                .help("Output of the earlier run: a json output file, a parquet file, or a directory of parquet files"),
+        )
+        .arg(
+            Arg::with_name(DIFF_AFTER_ARG)
+                .long(DIFF_AFTER_ARG)
+                .takes_value(true)
+                .required(true)
+                .help("Output of the later run, in any of the forms accepted for --before"),
+        );
+
     app_template.build()
         .about("Walks the mononoke commit and/or derived data graphs, with option of performing validations and modifications")
         .arg(
@@ -692,6 +934,7 @@ pub fn setup_toplevel_app<'a, 'b>(
         )
         .subcommand(compression_benefit)
         .subcommand(corpus)
+        .subcommand(diff_results)
         .subcommand(scrub_objects)
         .subcommand(validate)
 }
@@ -721,6 +964,196 @@ fn setup_subcommand_args<'a, 'b>(subcmd: App<'a, 'b>) -> App<'a, 'b> {
                 .required(false)
                 .help("Maximum number of walk step tasks to attempt to execute at once.  Default 4096."),
         )
+        .arg(
+            Arg::with_name(MAX_CONCURRENT_CONTENT_STREAMS_ARG)
+                .long(MAX_CONCURRENT_CONTENT_STREAMS_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of file content streams to have open at once in each repo walked, so the total across repos can be this times the number of repos. The walk waits for streams to be consumed before fetching more. Default is unlimited."),
+        )
+        .arg(
+            Arg::with_name(CONTENT_DRAIN_CONCURRENCY_ARG)
+                .long(CONTENT_DRAIN_CONCURRENCY_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of file content streams to drain at once. Default is the scheduled max."),
+        )
+        .arg(
+            Arg::with_name(CONTENT_BUFFER_MAX_BYTES_ARG)
+                .long(CONTENT_BUFFER_MAX_BYTES_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Read file contents of up to this many bytes into memory when stepping to them, so that content checks such as --validate-content-size, sizing and duplicate detection can all see their bytes. Larger contents are streamed once as usual. Default is to not buffer."),
+        )
+        .arg(
+            Arg::with_name(NODE_SAMPLE_RATE_ARG)
+                .long(NODE_SAMPLE_RATE_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Only keep node data and validate hashes for a deterministic 1 in N sample of nodes, chosen by their sampling fingerprint. Other nodes are still stepped through for their edges. Nodes without a fingerprint are always in the sample."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_SAMPLE_RATE_ARG)
+                .long(VALIDATE_SAMPLE_RATE_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Only validate hashes for a deterministic 1 in N sample of nodes, chosen by their sampling fingerprint, to scan cheaply for widespread corruption. Other nodes are still loaded and keep their data. Nodes without a fingerprint are always validated."),
+        )
+        .arg(
+            Arg::with_name(MAX_OUTGOING_PER_NODE_ARG)
+                .long(MAX_OUTGOING_PER_NODE_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of outgoing edges to expand from a single node, to bound the walk frontier on pathological manifests. Default is unlimited."),
+        )
+        .arg(
+            Arg::with_name(MAX_OUTGOING_ACTION_ARG)
+                .long(MAX_OUTGOING_ACTION_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(MAX_OUTGOING_PER_NODE_ARG)
+                .possible_values(MaxOutgoingAction::VARIANTS)
+                .default_value(MaxOutgoingAction::Fail.as_ref())
+                .help("Whether to truncate or fail when a node exceeds the outgoing edge limit"),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_PARENT_LINKAGE_ARG)
+                .long(VALIDATE_PARENT_LINKAGE_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("Check that the parents recorded in derived data (currently ChangesetInfo) match the bonsai changeset parents. Mismatches are reported as parent_linkage_failure."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_HG_BONSAI_ROUND_TRIP_ARG)
+                .long(VALIDATE_HG_BONSAI_ROUND_TRIP_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to HgChangesetViaBonsai, check that the bonsai it maps to maps back to the same hg changeset. Mismatches are reported as round_trip_failure."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_CONTENT_ALIASES_ARG)
+                .long(VALIDATE_CONTENT_ALIASES_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to FileContentMetadata, check its sha1, sha256 and git_sha1 aliases together. Any that are missing or map to other content are reported as one alias_failure."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_CONTENT_SIZE_ARG)
+                .long(VALIDATE_CONTENT_SIZE_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to FileContent, stream all of its bytes and compare the count with the total_size in its ContentMetadata. Truncated or overlong content, and content with no metadata, is reported as content_size_failure. The content is consumed by the check, so it is not available to later stages such as sizing, unless it is buffered by --content-buffer-max-bytes."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_CONTENT_METADATA_ARG)
+                .long(VALIDATE_CONTENT_METADATA_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to FileContentMetadata, stream its FileContent and recompute the size, sha1 and sha256. Metadata that disagrees with its content is reported as content_metadata_failure, listing the fields that differ."),
+        )
+        .arg(
+            Arg::with_name(CHECK_ORPHANED_MAPPINGS_ARG)
+                .long(CHECK_ORPHANED_MAPPINGS_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to a mapping node keyed by a bonsai changeset, such as UnodeMapping or FsnodeMapping, check the changeset still exists. Mappings whose changeset is gone are reported as orphaned_mapping. Use with --walk-root on the mapping nodes to find derived data left behind by stripped commits."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_DRAFT_DERIVED_DATA_ARG)
+                .long(VALIDATE_DRAFT_DERIVED_DATA_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to PhaseMapping for a changeset that is not public, check the changeset has no public-only derived data such as fastlog. Drafts that do are reported as draft_derived_data_failure. Include the PhaseMappingToChangeset edge type to also check the changeset exists."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_COPYFROM_CHAINS_ARG)
+                .long(VALIDATE_COPYFROM_CHAINS_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to HgFileNode, follow its chain of copyfrom sources. Chains with a missing source, a cycle, or more links than --copyfrom-chain-max-length are reported as copyfrom_chain_failure."),
+        )
+        .arg(
+            Arg::with_name(COPYFROM_CHAIN_MAX_LENGTH_ARG)
+                .long(COPYFROM_CHAIN_MAX_LENGTH_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(VALIDATE_COPYFROM_CHAINS_ARG)
+                .help("Most copyfrom links to follow from one HgFileNode before reporting the chain as too long. Default is 1000."),
+        )
+        .arg(
+            Arg::with_name(VALIDATE_FASTLOG_CHAINS_ARG)
+                .long(VALIDATE_FASTLOG_CHAINS_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to FastlogDir or FastlogFile, load every batch reachable through its previous batches. Chains with a missing batch, a cycle, or more batches than --fastlog-chain-max-length are reported as fastlog_chain_failure, separately from content failures."),
+        )
+        .arg(
+            Arg::with_name(FASTLOG_CHAIN_MAX_LENGTH_ARG)
+                .long(FASTLOG_CHAIN_MAX_LENGTH_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(VALIDATE_FASTLOG_CHAINS_ARG)
+                .help("Most previous batches to load from one FastlogDir or FastlogFile before reporting the chain as too long. Default is 1000."),
+        )
+        .arg(
+            Arg::with_name(PUBLIC_ONLY_ARG)
+                .long(PUBLIC_ONLY_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("When stepping to a Changeset, check its phase and do not expand it unless it is public. Draft changesets and the data derived from them are not walked."),
+        )
+        .arg(
+            Arg::with_name(OUTPUT_EDGE_LIST_DIR_ARG)
+                .long(OUTPUT_EDGE_LIST_DIR_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Directory to write each edge followed to, one tab separated file per repo. Roughly doubles output volume."),
+        )
+        .arg(
+            Arg::with_name(PATH_PREFIX_ARG)
+                .long(PATH_PREFIX_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Only step to nodes with repo paths under this prefix, e.g. for targeted repairs. Nodes without repo paths, such as changesets and mappings, are still walked to reach those under the prefix."),
+        )
+        .arg(
+            Arg::with_name(TRAVERSAL_ORDER_ARG)
+                .long(TRAVERSAL_ORDER_ARG)
+                .takes_value(true)
+                .required(false)
+                .possible_values(TraversalOrder::VARIANTS)
+                .default_value(TraversalOrder::Dfs.as_ref())
+                .help("Order to step through the walk frontier. Dfs bounds frontier memory, Bfs spreads load for cache warming but its frontier can grow to a whole level of the graph, LowestFingerprintFirst is deterministic across runs."),
+        )
+        .arg(
+            Arg::with_name(FRONTIER_SPILL_THRESHOLD_ARG)
+                .long(FRONTIER_SPILL_THRESHOLD_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Most edges to keep in the walk frontier in memory. Beyond this, edges are written out to files in batches and read back in order, so the walk result is unchanged. Only supported with Dfs and Bfs traversal orders, and for walks whose routes can be spilled, such as validate and scrub without path tracking. Default is to keep the whole frontier in memory."),
+        )
+        .arg(
+            Arg::with_name(FRONTIER_SPILL_DIR_ARG)
+                .long(FRONTIER_SPILL_DIR_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(FRONTIER_SPILL_THRESHOLD_ARG)
+                .help("Directory for frontier spill files. Default is the system temporary directory."),
+        )
+        .arg(
+            Arg::with_name(METADATA_ACTIVE_KEYS_PER_SHARD_ARG)
+                .long(METADATA_ACTIVE_KEYS_PER_SHARD_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of keys to have active at once against the metadata db. Must be greater than 0. Defaults to the mysql per key limit."),
+        )
+        .arg(
+            Arg::with_name(FILENODES_ACTIVE_KEYS_PER_SHARD_ARG)
+                .long(FILENODES_ACTIVE_KEYS_PER_SHARD_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of keys to have active at once against each filenodes shard. Must be greater than 0. Defaults to the mysql per key limit."),
+        )
         .arg(
             Arg::with_name(TAIL_INTERVAL_ARG)
                 .long(TAIL_INTERVAL_ARG)
@@ -760,6 +1193,17 @@ fn setup_subcommand_args<'a, 'b>(subcmd: App<'a, 'b>) -> App<'a, 'b> {
                 .possible_values(&NODE_TYPE_POSSIBLE_VALUES)
                 .help("Graph node types to exclude from walk. They are removed from the include node types."),
         )
+        .arg(
+            Arg::with_name(PRUNE_NODE_TYPE_ARG)
+                .long(PRUNE_NODE_TYPE_ARG)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .possible_values(&NODE_TYPE_POSSIBLE_VALUES)
+                .hide_possible_values(true)
+                .help("Graph node types never to step to, even as walk roots or from edges that are always emitted. Root can not be pruned."),
+        )
         .arg(
             Arg::with_name(INCLUDE_NODE_TYPE_ARG)
                 .long(INCLUDE_NODE_TYPE_ARG)
@@ -815,7 +1259,23 @@ fn setup_subcommand_args<'a, 'b>(subcmd: App<'a, 'b>) -> App<'a, 'b> {
                 .required(false)
                 .multiple(true)
                 .number_of_values(1)
-                .help("Root(s) to start traversal from in format <NodeType>:<node_key>, e.g. Bookmark:master or HgChangeset:7712b62acdc858689504945ac8965a303ded6626"),
+                .help("Root(s) to start traversal from in format <NodeType>:<node_key>, e.g. Bookmark:master or HgChangeset:7712b62acdc858689504945ac8965a303ded6626. Paths in node keys are percent-escaped where they are not UTF-8, or contain % or control characters"),
+        )
+        .arg(
+            Arg::with_name(WALK_ROOTS_FROM_STDIN_ARG)
+                .long(WALK_ROOTS_FROM_STDIN_ARG)
+                .takes_value(false)
+                .required(false)
+                .help("Also start traversal from the roots read from stdin, one per line in the same format as --walk-root. Invalid lines are logged with their line number and skipped."),
+        )
+        .arg(
+            Arg::with_name(SEED_BOOKMARK_ARG)
+                .long(SEED_BOOKMARK_ARG)
+                .takes_value(true)
+                .required(false)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Bookmark(s) to resolve to changesets at startup and start traversal from, as if stepping from the bookmark. Fails if any of them are not found."),
         )
         .arg(
             Arg::with_name(CHUNK_BY_PUBLIC_ARG)
@@ -936,6 +1396,21 @@ fn setup_subcommand_args<'a, 'b>(subcmd: App<'a, 'b>) -> App<'a, 'b> {
                 .default_value("432000")
                 .help("Max age of walk state held internally ot loaded from checkpoint that we will attempt to continue from, in seconds."),
         )
+        .arg(
+            Arg::with_name(RESUME_STATE_DIR_ARG)
+                .long(RESUME_STATE_DIR_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Directory to save visited nodes and pending edges to, so an interrupted walk can resume without repeating completed work. Removed once the walk completes."),
+        )
+        .arg(
+            Arg::with_name(RESUME_STATE_SAVE_INTERVAL_ARG)
+                .long(RESUME_STATE_SAVE_INTERVAL_ARG)
+                .takes_value(true)
+                .required(false)
+                .default_value("300")
+                .help("How often to save resume state during a walk, in seconds. It is also saved after each chunk."),
+        )
         .arg(
             Arg::with_name(REPO_LOWER_BOUND)
                 .long(REPO_LOWER_BOUND)
@@ -1168,10 +1643,22 @@ fn parse_tail_args<'a>(
         .map(Duration::from_secs)
         .unwrap();
 
+    // Can unwrap as there is clap default set
+    let save_interval = args::get_u64_opt(&sub_m, RESUME_STATE_SAVE_INTERVAL_ARG)
+        .map(Duration::from_secs)
+        .unwrap();
+    let resume = sub_m
+        .value_of(RESUME_STATE_DIR_ARG)
+        .map(|state_dir| ResumeParams {
+            state_dir: PathBuf::from(state_dir),
+            save_interval,
+        });
+
     Ok(TailParams {
         tail_secs,
         chunking,
         state_max_age,
+        resume,
     })
 }
 
@@ -1181,6 +1668,12 @@ fn parse_node_value(arg: &str) -> Result<HashSet<NodeType>, Error> {
         ALL_VALUE_ARG => HashSet::from_iter(NodeType::iter()),
         DEFAULT_VALUE_ARG => HashSet::from_iter(DEFAULT_INCLUDE_NODE_TYPES.iter().cloned()),
         BONSAI_VALUE_ARG => HashSet::from_iter(BONSAI_INCLUDE_NODE_TYPES.iter().cloned()),
+        MAPPING_VALUE_ARG => HashSet::from_iter(
+            BONSAI_INCLUDE_NODE_TYPES
+                .iter()
+                .chain(DERIVED_MAPPING_NODE_TYPES.iter())
+                .cloned(),
+        ),
         DERIVED_VALUE_ARG => {
             HashSet::from_iter(DERIVED_DATA_INCLUDE_NODE_TYPES.values().flatten().cloned())
         }
@@ -1235,6 +1728,7 @@ fn parse_edge_value(arg: &str) -> Result<HashSet<EdgeType>, Error> {
         CONTENT_META_VALUE_ARG => HashSet::from_iter(CONTENT_META_EDGE_TYPES.iter().cloned()),
         DEEP_VALUE_ARG => HashSet::from_iter(DEEP_INCLUDE_EDGE_TYPES.iter().cloned()),
         MARKER_VALUE_ARG => HashSet::from_iter(MARKER_EDGE_TYPES.iter().cloned()),
+        MAPPING_VALUE_ARG => HashSet::from_iter(MAPPING_EDGE_TYPES.iter().cloned()),
         HG_VALUE_ARG => HashSet::from_iter(HG_EDGE_TYPES.iter().cloned()),
         SHALLOW_VALUE_ARG => HashSet::from_iter(SHALLOW_INCLUDE_EDGE_TYPES.iter().cloned()),
         _ => EdgeType::from_str(arg)
@@ -1314,6 +1808,79 @@ pub async fn setup_common<'a>(
     let quiet = sub_m.is_present(QUIET_ARG);
     let common_config = cmdlib::args::load_common_config(config_store, &matches)?;
     let scheduled_max = args::get_usize_opt(&sub_m, SCHEDULED_MAX_ARG).unwrap_or(4096) as usize;
+    let max_concurrent_content_streams =
+        args::get_usize_opt(&sub_m, MAX_CONCURRENT_CONTENT_STREAMS_ARG);
+    let content_drain_concurrency = args::get_usize_opt(&sub_m, CONTENT_DRAIN_CONCURRENCY_ARG);
+    let content_buffer_max_bytes = args::get_usize_opt(&sub_m, CONTENT_BUFFER_MAX_BYTES_ARG);
+    if max_concurrent_content_streams == Some(0) {
+        bail!(
+            "--{} must be greater than 0",
+            MAX_CONCURRENT_CONTENT_STREAMS_ARG
+        );
+    }
+    if content_drain_concurrency == Some(0) {
+        bail!("--{} must be greater than 0", CONTENT_DRAIN_CONCURRENCY_ARG);
+    }
+    let sample_rate = args::get_u64_opt(&sub_m, NODE_SAMPLE_RATE_ARG);
+    if sample_rate == Some(0) {
+        bail!("--{} must be greater than 0", NODE_SAMPLE_RATE_ARG);
+    }
+    let validate_sample_rate = args::get_u64_opt(&sub_m, VALIDATE_SAMPLE_RATE_ARG);
+    if validate_sample_rate == Some(0) {
+        bail!("--{} must be greater than 0", VALIDATE_SAMPLE_RATE_ARG);
+    }
+    let max_outgoing_per_node = args::get_usize_opt(&sub_m, MAX_OUTGOING_PER_NODE_ARG);
+    let max_outgoing_action = sub_m
+        .value_of(MAX_OUTGOING_ACTION_ARG)
+        .map_or(Ok(MaxOutgoingAction::Fail), MaxOutgoingAction::from_str)?;
+    let validate_parent_linkage = sub_m.is_present(VALIDATE_PARENT_LINKAGE_ARG);
+    let validate_hg_bonsai_round_trip = sub_m.is_present(VALIDATE_HG_BONSAI_ROUND_TRIP_ARG);
+    let validate_content_aliases = sub_m.is_present(VALIDATE_CONTENT_ALIASES_ARG);
+    let validate_content_size = sub_m.is_present(VALIDATE_CONTENT_SIZE_ARG);
+    let validate_content_metadata = sub_m.is_present(VALIDATE_CONTENT_METADATA_ARG);
+    let check_orphaned_mappings = sub_m.is_present(CHECK_ORPHANED_MAPPINGS_ARG);
+    let validate_draft_derived_data = sub_m.is_present(VALIDATE_DRAFT_DERIVED_DATA_ARG);
+    let copyfrom_chain_max_length = if sub_m.is_present(VALIDATE_COPYFROM_CHAINS_ARG) {
+        Some(
+            args::get_usize_opt(&sub_m, COPYFROM_CHAIN_MAX_LENGTH_ARG)
+                .unwrap_or(DEFAULT_COPYFROM_CHAIN_MAX_LENGTH),
+        )
+    } else {
+        None
+    };
+    let fastlog_chain_max_length = if sub_m.is_present(VALIDATE_FASTLOG_CHAINS_ARG) {
+        Some(
+            args::get_usize_opt(&sub_m, FASTLOG_CHAIN_MAX_LENGTH_ARG)
+                .unwrap_or(DEFAULT_FASTLOG_CHAIN_MAX_LENGTH),
+        )
+    } else {
+        None
+    };
+    let public_only = sub_m.is_present(PUBLIC_ONLY_ARG);
+    let edge_list_dir = sub_m.value_of(OUTPUT_EDGE_LIST_DIR_ARG).map(PathBuf::from);
+    let path_prefix = sub_m
+        .value_of(PATH_PREFIX_ARG)
+        .map(MPath::new)
+        .transpose()?;
+    let traversal_order = sub_m
+        .value_of(TRAVERSAL_ORDER_ARG)
+        .map_or(Ok(TraversalOrder::Dfs), TraversalOrder::from_str)?;
+    let frontier_spill = match args::get_usize_opt(&sub_m, FRONTIER_SPILL_THRESHOLD_ARG) {
+        Some(0) => bail!("--{} must be greater than 0", FRONTIER_SPILL_THRESHOLD_ARG),
+        Some(_) if traversal_order == TraversalOrder::LowestFingerprintFirst => bail!(
+            "--{} is not supported with --{} {}",
+            FRONTIER_SPILL_THRESHOLD_ARG,
+            TRAVERSAL_ORDER_ARG,
+            traversal_order.as_ref()
+        ),
+        Some(max_in_memory) => Some(FrontierSpillParams {
+            max_in_memory,
+            dir: sub_m
+                .value_of(FRONTIER_SPILL_DIR_ARG)
+                .map_or_else(std::env::temp_dir, PathBuf::from),
+        }),
+        None => None,
+    };
     let inner_blobstore_id = args::get_u64_opt(&sub_m, INNER_BLOBSTORE_ID_ARG);
     let progress_options = parse_progress_args(sub_m);
 
@@ -1340,6 +1907,15 @@ pub async fn setup_common<'a>(
         &[],
     )?;
 
+    let prune_node_types = parse_node_values(sub_m.values_of(PRUNE_NODE_TYPE_ARG), &[])?;
+    if prune_node_types.contains(&NodeType::Root) {
+        return Err(format_err!(
+            "--{} can not include {}, the walk is seeded from it",
+            PRUNE_NODE_TYPE_ARG,
+            NodeType::Root
+        ));
+    }
+
     let mut walk_roots: Vec<OutgoingEdge> = vec![];
 
     if sub_m.is_present(BOOKMARK_ARG) {
@@ -1355,6 +1931,11 @@ pub async fn setup_common<'a>(
         walk_roots.append(&mut bookmarks);
     }
 
+    let seed_bookmarks: Vec<BookmarkName> = match sub_m.values_of(SEED_BOOKMARK_ARG) {
+        None => vec![],
+        Some(values) => values.map(BookmarkName::new).collect::<Result<_, _>>()?,
+    };
+
     if sub_m.is_present(WALK_ROOT_ARG) {
         let roots: Vec<_> = match sub_m.values_of(WALK_ROOT_ARG) {
             None => Err(format_err!("No root node passed to --{}", WALK_ROOT_ARG)),
@@ -1371,6 +1952,35 @@ pub async fn setup_common<'a>(
         walk_roots.append(&mut roots);
     }
 
+    if sub_m.is_present(WALK_ROOTS_FROM_STDIN_ARG) {
+        let (nodes, invalid) =
+            parse_node_lines(io::stdin().lock()).context("While reading walk roots from stdin")?;
+        let mut skipped = invalid.len();
+        for (line_num, error) in invalid {
+            warn!(
+                logger,
+                "Skipping walk root on stdin line {}: {:#}", line_num, error
+            );
+        }
+        for (line_num, node) in nodes {
+            match node.get_type().root_edge_type() {
+                Some(et) => walk_roots.push(OutgoingEdge::new(et, node)),
+                None => {
+                    warn!(
+                        logger,
+                        "Skipping walk root on stdin line {}: can't walk from a {} node",
+                        line_num,
+                        node.get_type()
+                    );
+                    skipped += 1;
+                }
+            }
+        }
+        if skipped > 0 {
+            warn!(logger, "Skipped {} invalid walk roots from stdin", skipped);
+        }
+    }
+
     let readonly_storage = matches.readonly_storage();
 
     let error_as_data_node_types = parse_node_types(
@@ -1400,6 +2010,18 @@ pub async fn setup_common<'a>(
     }
 
     let mysql_options = matches.mysql_options();
+    // A shard allowing no active keys would never make progress
+    let active_keys_limit = |arg: &str| -> Result<Option<usize>, Error> {
+        match args::get_usize_opt(&sub_m, arg) {
+            Some(0) => bail!("--{} must be greater than 0", arg),
+            Some(limit) => Ok(Some(limit)),
+            None => Ok(mysql_options.per_key_limit().filter(|limit| *limit > 0)),
+        }
+    };
+    let active_keys_per_shard = ActiveKeysPerShard {
+        metadata: active_keys_limit(METADATA_ACTIVE_KEYS_PER_SHARD_ARG)?,
+        filenodes: active_keys_limit(FILENODES_ACTIVE_KEYS_PER_SHARD_ARG)?,
+    };
     let blobstore_options = matches.blobstore_options();
     let storage_id = matches.value_of(STORAGE_ID_ARG);
     let enable_redaction = sub_m.is_present(ENABLE_REDACTION_ARG);
@@ -1520,11 +2142,13 @@ pub async fn setup_common<'a>(
             }
         };
 
-        if tail_params.chunking.is_none() && walk_roots.is_empty() {
+        if tail_params.chunking.is_none() && walk_roots.is_empty() && seed_bookmarks.is_empty() {
             bail!(
-                "No walk roots provided, pass with  --{}, --{} or --{}",
+                "No walk roots provided, pass with  --{}, --{}, --{}, --{} or --{}",
                 BOOKMARK_ARG,
                 WALK_ROOT_ARG,
+                WALK_ROOTS_FROM_STDIN_ARG,
+                SEED_BOOKMARK_ARG,
                 CHUNK_BY_PUBLIC_ARG,
             );
         }
@@ -1533,7 +2157,7 @@ pub async fn setup_common<'a>(
 
         let sql_shard_info = SqlShardInfo {
             filenodes: sql_factory.tier_info_shardable::<NewFilenodesBuilder>()?,
-            active_keys_per_shard: mysql_options.per_key_limit(),
+            active_keys_per_shard,
         };
 
         let one_repo = setup_repo(
@@ -1544,14 +2168,18 @@ pub async fn setup_common<'a>(
             scuba_builder.clone(),
             sql_shard_info.clone(),
             scheduled_max,
+            content_drain_concurrency,
             repo_count,
             &repo,
             walk_roots.clone(),
+            &seed_bookmarks,
             tail_params,
             include_edge_types.clone(),
             include_node_types.clone(),
             hash_validation_node_types.clone(),
+            prune_node_types.clone(),
             progress_options,
+            edge_list_dir.as_deref(),
         )
         .await?;
         per_repo.push(one_repo);
@@ -1564,11 +2192,60 @@ pub async fn setup_common<'a>(
             error_as_data_node_types,
             error_as_data_edge_types,
             repo_count,
+            max_concurrent_content_streams,
+            content_buffer_max_bytes,
+            sample_rate,
+            validate_sample_rate,
+            max_outgoing_per_node,
+            max_outgoing_action,
+            validate_parent_linkage,
+            validate_hg_bonsai_round_trip,
+            validate_content_aliases,
+            validate_content_size,
+            validate_content_metadata,
+            check_orphaned_mappings,
+            validate_draft_derived_data,
+            copyfrom_chain_max_length,
+            fastlog_chain_max_length,
+            public_only,
+            path_prefix,
+            traversal_order,
+            frontier_spill,
         },
         per_repo,
     ))
 }
 
+// Resolve bookmarks to the changesets they point at, failing if any are missing
+async fn resolve_seed_bookmarks(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bookmarks: &[BookmarkName],
+) -> Result<Vec<ChangesetId>, Error> {
+    let resolved = try_join_all(bookmarks.iter().map(|b| async move {
+        let bcs_id = repo.get_bonsai_bookmark(ctx.clone(), b).await?;
+        Ok::<_, Error>((b, bcs_id))
+    }))
+    .await?;
+    let missing: Vec<_> = resolved
+        .iter()
+        .filter(|(_, bcs_id)| bcs_id.is_none())
+        .map(|(b, _)| b.to_string())
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Bookmark(s) passed to --{} not found in {}: {}",
+            SEED_BOOKMARK_ARG,
+            repo.name(),
+            missing.join(", ")
+        );
+    }
+    Ok(resolved
+        .into_iter()
+        .filter_map(|(_, bcs_id)| bcs_id)
+        .collect())
+}
+
 // Setup for just one repo. Try and keep clap parsing out of here, should be done beforehand
 async fn setup_repo<'a>(
     walk_stats_key: &'static str,
@@ -1578,14 +2255,18 @@ async fn setup_repo<'a>(
     mut scuba_builder: MononokeScubaSampleBuilder,
     sql_shard_info: SqlShardInfo,
     scheduled_max: usize,
+    content_drain_concurrency: Option<usize>,
     repo_count: usize,
     resolved: &'a ResolvedRepo,
-    walk_roots: Vec<OutgoingEdge>,
+    mut walk_roots: Vec<OutgoingEdge>,
+    seed_bookmarks: &'a [BookmarkName],
     mut tail_params: TailParams,
     include_edge_types: HashSet<EdgeType>,
     mut include_node_types: HashSet<NodeType>,
     hash_validation_node_types: HashSet<NodeType>,
+    prune_node_types: HashSet<NodeType>,
     progress_options: ProgressOptions,
+    edge_list_dir: Option<&Path>,
 ) -> Result<(RepoSubcommandParams, RepoWalkParams), Error> {
     let logger = if repo_count > 1 {
         logger.new(o!("repo" => resolved.name.clone()))
@@ -1594,6 +2275,9 @@ async fn setup_repo<'a>(
     };
 
     let scheduled_max = scheduled_max / repo_count;
+    let content_drain_max = content_drain_concurrency
+        .map(|concurrency| (concurrency / repo_count).max(1))
+        .unwrap_or(scheduled_max);
     scuba_builder.add(REPO, resolved.name.clone());
 
     // Only walk derived node types that the repo is configured to contain
@@ -1605,12 +2289,36 @@ async fn setup_repo<'a>(
         }
     });
 
+    // Pruned node types are never stepped to, so don't report on them
+    include_node_types.retain(|t| !prune_node_types.contains(t));
+    walk_roots.retain(|e| !prune_node_types.contains(&e.label.outgoing_type()));
+
     let mut root_node_types: HashSet<_> =
         walk_roots.iter().map(|e| e.label.outgoing_type()).collect();
 
+    // Seed bookmarks are resolved once the repo is open, follow the same edges as
+    // stepping from the bookmark would
+    let seed_edge_types: Vec<_> = if seed_bookmarks.is_empty() {
+        vec![]
+    } else {
+        [
+            EdgeType::BookmarkToChangeset,
+            EdgeType::BookmarkToBonsaiHgMapping,
+        ]
+        .iter()
+        .copied()
+        .filter(|e| {
+            include_edge_types.contains(e) && !prune_node_types.contains(&e.outgoing_type())
+        })
+        .collect()
+    };
+    root_node_types.extend(seed_edge_types.iter().map(|e| e.outgoing_type()));
+
     if let Some(ref mut chunking) = tail_params.chunking {
         chunking.chunk_by.retain(|t| {
-            if let Some(t) = t.derived_data_name() {
+            if prune_node_types.contains(t) {
+                false
+            } else if let Some(t) = t.derived_data_name() {
                 resolved.config.derived_data_config.is_enabled(t)
             } else {
                 true
@@ -1655,6 +2363,36 @@ async fn setup_repo<'a>(
         .build(resolved.name.clone(), resolved.config.clone())
         .await?;
 
+    if !seed_edge_types.is_empty() {
+        let ctx = CoreContext::new_with_logger(fb, logger.clone());
+        let seeds = resolve_seed_bookmarks(&ctx, &repo, seed_bookmarks).await?;
+        for bcs_id in seeds {
+            for edge_type in &seed_edge_types {
+                let key = ChangesetKey {
+                    inner: bcs_id,
+                    filenode_known_derived: false, /* from bookmark we don't know if hg fully derived */
+                };
+                let node = match edge_type {
+                    EdgeType::BookmarkToBonsaiHgMapping => Node::BonsaiHgMapping(key),
+                    _ => Node::Changeset(key),
+                };
+                walk_roots.push(OutgoingEdge::new(*edge_type, node));
+            }
+        }
+    }
+
+    let edge_list = edge_list_dir
+        .map(|dir| {
+            let file_name = format!(
+                "{}_{}.edges",
+                resolved.name.replace('/', "_"),
+                Timestamp::now().timestamp_seconds()
+            );
+            EdgeListWriter::create(&dir.join(file_name))
+        })
+        .transpose()?
+        .map(Arc::new);
+
     Ok((
         RepoSubcommandParams {
             progress_state,
@@ -1665,12 +2403,16 @@ async fn setup_repo<'a>(
             repo,
             logger: logger.clone(),
             scheduled_max,
+            content_drain_max,
             sql_shard_info,
             walk_roots,
             include_node_types,
             include_edge_types,
             hash_validation_node_types,
+            prune_node_types,
             scuba_builder,
+            frontier_stats: Arc::new(FrontierStats::new()),
+            edge_list,
         },
     ))
 }
@@ -1698,4 +2440,56 @@ mod tests {
         let r = parse_node_values(m.values_of(INCLUDE_NODE_TYPE_ARG), &[]);
         assert!(r.is_err());
     }
+
+    #[test]
+    fn unreachable_key_listing_conflicts_with_resume() {
+        let listing_args = vec![
+            "scrub",
+            "--unreachable-key-listing",
+            "keys",
+            "--unreachable-keys-dir",
+            "out",
+        ];
+        assert!(setup_scrub_subcommand()
+            .get_matches_from_safe(listing_args.clone())
+            .is_ok());
+
+        let mut resumed = listing_args;
+        resumed.extend(&["--resume-state-dir", "state"]);
+        let err = setup_scrub_subcommand()
+            .get_matches_from_safe(resumed)
+            .unwrap_err();
+        assert_eq!(err.kind, clap::ErrorKind::ArgumentConflict);
+    }
+
+    #[fbinit::test]
+    async fn test_resolve_seed_bookmarks(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let bcs_id = ChangesetId::from_bytes([1; 32])?;
+        let main = BookmarkName::new("main")?;
+        let mut txn = repo.update_bookmark_transaction(ctx.clone());
+        txn.force_set(
+            &main,
+            bcs_id,
+            bookmarks::BookmarkUpdateReason::TestMove,
+            None,
+        )?;
+        assert!(txn.commit().await?);
+
+        let seeds = resolve_seed_bookmarks(&ctx, &repo, &[main.clone()]).await?;
+        assert_eq!(seeds, vec![bcs_id]);
+
+        let unknown = vec![
+            BookmarkName::new("release")?,
+            main,
+            BookmarkName::new("stable")?,
+        ];
+        let err = resolve_seed_bookmarks(&ctx, &repo, &unknown)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with(": release, stable"), "{}", err);
+        Ok(())
+    }
 }
@@ -5,10 +5,11 @@
  * GNU General Public License version 2.
  */
 
+use crate::footprint::ChangesetFootprint;
 use crate::graph::{FileContentData, Node, NodeData, NodeType, WrappedPath};
 use crate::progress::{
-    progress_stream, report_state, ProgressOptions, ProgressReporter, ProgressReporterUnprotected,
-    ProgressStateCountByType, ProgressStateMutex,
+    outcome_stream, progress_stream, report_state, OutcomeStateByType, ProgressOptions,
+    ProgressReporter, ProgressReporterUnprotected, ProgressStateCountByType, ProgressStateMutex,
 };
 use crate::sampling::{
     PathTrackingRoute, SamplingOptions, SamplingWalkVisitor, WalkKeyOptPath, WalkPayloadMtime,
@@ -16,7 +17,7 @@ use crate::sampling::{
 };
 use crate::setup::{
     parse_progress_args, parse_sampling_args, setup_common, JobWalkParams, RepoSubcommandParams,
-    COMPRESSION_BENEFIT, COMPRESSION_LEVEL_ARG,
+    CHANGESET_FOOTPRINT_ARG, COMPRESSION_BENEFIT, COMPRESSION_LEVEL_ARG,
 };
 use crate::tail::walk_exact_tail;
 use crate::walk::{RepoWalkParams, RepoWalkTypeParams};
@@ -87,19 +88,22 @@ fn try_compress(raw_data: &Bytes, compressor_type: CompressorType) -> Result<Siz
     Ok(SizingStats { raw, compressed })
 }
 
-// Force load of leaf data and check compression ratio
+// Force load of leaf data and check compression ratio. If footprint is set, the
+// file content's stream size and other nodes' raw blob sizes are attributed to
+// the changeset they were reached from.
 fn size_sampling_stream<InStream, InStats>(
-    scheduled_max: usize,
+    content_drain_max: usize,
     s: InStream,
     compressor_type: CompressorType,
     sampler: Arc<WalkSampleMapping<Node, SizingSample>>,
+    footprint: Option<Arc<ChangesetFootprint>>,
 ) -> impl Stream<Item = Result<(Node, Option<NodeData>, Option<SizingStats>), Error>>
 where
     InStream: Stream<
             Item = Result<
                 (
                     WalkKeyOptPath<WrappedPath>,
-                    Option<NodeData>,
+                    WalkPayloadMtime,
                     Option<InStats>,
                 ),
                 Error,
@@ -109,8 +113,10 @@ where
         + Send,
     InStats: 'static + Send,
 {
-    s.map_ok(move |(walk_key, data_opt, _stats_opt)| {
-        match (&walk_key.node, data_opt) {
+    s.map_ok(move |(walk_key, payload, _stats_opt)| {
+        // Sizing doesn't use mtime
+        let changeset = payload.changeset;
+        match (&walk_key.node, payload.data) {
             (Node::FileContent(_content_id), Some(NodeData::FileContent(fc)))
                 if sampler.is_sampling(&walk_key.node) =>
             {
@@ -118,14 +124,18 @@ where
                     FileContentData::Consumed(_num_loaded_bytes) => {
                         future::ok(_num_loaded_bytes).left_future()
                     }
+                    FileContentData::Buffered(bytes) => future::ok(bytes.len()).left_future(),
                     // Consume the stream to make sure we loaded all blobs
                     FileContentData::ContentStream(file_bytes_stream) => file_bytes_stream
                         .try_fold(0, |acc, file_bytes| future::ok(acc + file_bytes.size()))
                         .right_future(),
                 }
                 .and_then({
-                    cloned!(sampler);
+                    cloned!(sampler, footprint);
                     move |fs_stream_size| {
+                        if let Some(footprint) = &footprint {
+                            footprint.record(changeset, fs_stream_size as u64);
+                        }
                         // Report the blobstore sizes in sizing stats, more accurate than stream sizes, as headers included
                         let sizes = sampler
                             .complete_step(&walk_key.node)
@@ -168,11 +178,15 @@ where
                     })
                     .transpose();
 
+                if let (Some(footprint), Ok(Some(sizes))) = (&footprint, &sizes) {
+                    footprint.record(changeset, sizes.raw);
+                }
+
                 future::ready(sizes.map(|sizes| (walk_key.node, data_opt, sizes))).right_future()
             }
         }
     })
-    .try_buffer_unordered(scheduled_max)
+    .try_buffer_unordered(content_drain_max)
 }
 
 impl ProgressStateCountByType<SizingStats, SizingStats> {
@@ -282,6 +296,7 @@ impl SamplingHandler for WalkSampleMapping<Node, SizingSample> {
 #[derive(Clone)]
 struct SizingCommand {
     compression_level: i32,
+    changeset_footprint: bool,
     progress_options: ProgressOptions,
     sampling_options: SamplingOptions,
     sampler: Arc<WalkSampleMapping<Node, SizingSample>>,
@@ -314,10 +329,15 @@ pub async fn compression_benefit<'a>(
     )
     .await?;
 
+    let changeset_footprint = sub_m.is_present(CHANGESET_FOOTPRINT_ARG);
+    // Footprints need every node, so only sample if asked to
+    let default_sample_rate = if changeset_footprint { 1 } else { 100 };
+
     let command = SizingCommand {
         compression_level: args::get_i32_opt(&sub_m, COMPRESSION_LEVEL_ARG).unwrap_or(3),
+        changeset_footprint,
         progress_options: parse_progress_args(&sub_m),
-        sampling_options: parse_sampling_args(&sub_m, 100)?,
+        sampling_options: parse_sampling_args(&sub_m, default_sample_rate)?,
         sampler,
     };
 
@@ -349,30 +369,54 @@ async fn run_one(
             command.sampling_options.node_types.clone(),
             command.progress_options,
         ));
+    let outcome_state = ProgressStateMutex::new(OutcomeStateByType::new(
+        fb,
+        repo_params.logger.clone(),
+        COMPRESSION_BENEFIT,
+        repo_params.repo.name().clone(),
+        repo_params.include_node_types.clone(),
+        command.progress_options,
+    ));
+
+    let footprint = if command.changeset_footprint {
+        Some(Arc::new(ChangesetFootprint::new(
+            repo_params.logger.clone(),
+        )))
+    } else {
+        None
+    };
 
     let make_sink = {
-        cloned!(command, job_params.quiet, sub_params.progress_state,);
+        cloned!(
+            command,
+            job_params.quiet,
+            sub_params.progress_state,
+            footprint
+        );
         move |ctx: &CoreContext, repo_params: &RepoWalkParams| {
-            cloned!(ctx, repo_params.scheduled_max);
+            cloned!(ctx, repo_params.content_drain_max);
             async move |walk_output, _run_start, _chunk_num, _checkpoint_name| {
-                cloned!(ctx, sizing_progress_state);
-                // Sizing doesn't use mtime, so remove it from payload
-                let walk_progress = progress_stream(quiet, &progress_state, walk_output).map_ok(
-                    |(key, payload, stats): (_, WalkPayloadMtime, _)| (key, payload.data, stats),
-                );
+                cloned!(ctx, sizing_progress_state, outcome_state);
+                let walk_progress = progress_stream(quiet, &progress_state, walk_output);
 
                 let compressor = size_sampling_stream(
-                    scheduled_max,
+                    content_drain_max,
                     walk_progress,
                     CompressorType::Zstd {
                         level: command.compression_level,
                     },
                     command.sampler,
+                    footprint.clone(),
                 );
                 let report_sizing = progress_stream(quiet, &sizing_progress_state, compressor);
+                let report_outcomes = outcome_stream(quiet, &outcome_state, report_sizing);
 
-                report_state(ctx, report_sizing).await?;
+                report_state(ctx, report_outcomes).await?;
+                if let Some(footprint) = &footprint {
+                    footprint.report();
+                }
                 sizing_progress_state.report_progress();
+                outcome_state.report_progress();
                 progress_state.report_progress();
                 Ok(())
             }
@@ -397,6 +441,7 @@ async fn run_one(
         required_node_data_types: hashset![NodeType::FileContent],
         always_emit_edge_types: HashSet::new(),
         keep_edge_paths: true,
+        include_scratch_bookmarks: false,
     };
 
     walk_exact_tail::<_, _, _, _, _, PathTrackingRoute<WrappedPath>>(
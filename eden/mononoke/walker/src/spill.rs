@@ -0,0 +1,309 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::resume::{parse_edge, parse_path_line, write_edge, FIELD_SEP};
+use crate::setup::TraversalOrder;
+use crate::walk::OutgoingEdge;
+
+use anyhow::{bail, format_err, Context, Error};
+use std::{
+    cmp,
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+const ROUTE_TAG: &str = "route";
+const EDGE_TAG: &str = "edge";
+
+/// When and where to spill the walk frontier to disk
+#[derive(Clone, Debug)]
+pub struct FrontierSpillParams {
+    /// Most edges to keep in memory before spilling
+    pub max_in_memory: usize,
+    /// Directory for the spill files, which are removed as soon as they are closed
+    pub dir: PathBuf,
+}
+
+type Item<Route> = (Option<Route>, OutgoingEdge);
+
+// A batch of frontier items written out in frontier order
+struct Segment {
+    file: File,
+    len: usize,
+}
+
+/// A Dfs or Bfs frontier that keeps at most max_in_memory edges in memory, plus
+/// one batch being paged in or out. Overflow is written to files in batches and
+/// read back when the in memory part runs out, so edges pop in the same order
+/// as with the in memory frontier.
+///
+/// Edges are encoded like resume state, so ChangesetKey::filenode_known_derived
+/// is false once paged back in. That only means the filenode derivation check is
+/// repeated.
+pub struct SpillingFrontier<Route> {
+    order: TraversalOrder,
+    params: FrontierSpillParams,
+    batch_size: usize,
+    encode: fn(&Route) -> String,
+    decode: fn(&str) -> Result<Route, Error>,
+    // Popped from the front
+    memory: VecDeque<Item<Route>>,
+    // Dfs: a stack, the last segment was spilled most recently and is paged in first.
+    // Bfs: a queue, the first segment is paged in first.
+    spilled: VecDeque<Segment>,
+    spilled_len: usize,
+    // Bfs only: items pushed while older ones are spilled, waiting to fill a segment
+    tail: VecDeque<Item<Route>>,
+}
+
+impl<Route> SpillingFrontier<Route> {
+    pub fn new(
+        order: TraversalOrder,
+        params: FrontierSpillParams,
+        encode: fn(&Route) -> String,
+        decode: fn(&str) -> Result<Route, Error>,
+    ) -> Result<Self, Error> {
+        match order {
+            TraversalOrder::Dfs | TraversalOrder::Bfs => {}
+            TraversalOrder::LowestFingerprintFirst => {
+                bail!("Frontier spilling is not supported with {:?}", order)
+            }
+        }
+        if params.max_in_memory == 0 {
+            bail!("Frontier spilling needs room for at least one edge in memory");
+        }
+        // Page in and out half of the in memory limit at a time, so that a push
+        // just after a spill or a pop just after a page in doesn't go to disk again
+        let batch_size = cmp::max(params.max_in_memory / 2, 1);
+        Ok(Self {
+            order,
+            params,
+            batch_size,
+            encode,
+            decode,
+            memory: VecDeque::new(),
+            spilled: VecDeque::new(),
+            spilled_len: 0,
+            tail: VecDeque::new(),
+        })
+    }
+
+    pub fn push(&mut self, item: Item<Route>) -> Result<(), Error> {
+        match self.order {
+            TraversalOrder::Bfs => {
+                if self.spilled.is_empty() && self.tail.is_empty() {
+                    self.memory.push_back(item);
+                    if self.memory.len() > self.params.max_in_memory {
+                        // Everything after the in memory limit goes through the tail,
+                        // so that it stays behind what is already queued
+                        let item = self.memory.pop_back().expect("memory can't be empty");
+                        self.tail.push_back(item);
+                    }
+                } else {
+                    self.tail.push_back(item);
+                    if self.tail.len() >= self.batch_size {
+                        let batch: Vec<_> = self.tail.drain(..).collect();
+                        let segment = self.write_segment(&batch)?;
+                        self.spilled.push_back(segment);
+                    }
+                }
+            }
+            _ => {
+                self.memory.push_front(item);
+                if self.memory.len() > self.params.max_in_memory {
+                    // The back of the stack is furthest from being popped
+                    let split_at = self.memory.len() - self.batch_size;
+                    let batch: Vec<_> = self.memory.drain(split_at..).collect();
+                    let segment = self.write_segment(&batch)?;
+                    self.spilled.push_back(segment);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn push_retry(&mut self, item: Item<Route>) {
+        self.memory.push_front(item);
+    }
+
+    pub fn pop(&mut self) -> Result<Option<Item<Route>>, Error> {
+        if self.memory.is_empty() {
+            let segment = match self.order {
+                TraversalOrder::Bfs if !self.spilled.is_empty() => self.spilled.pop_front(),
+                TraversalOrder::Bfs => {
+                    self.memory.extend(self.tail.drain(..));
+                    None
+                }
+                _ => self.spilled.pop_back(),
+            };
+            if let Some(segment) = segment {
+                self.memory = self.read_segment(segment)?;
+            }
+        }
+        Ok(self.memory.pop_front())
+    }
+
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.spilled_len + self.tail.len()
+    }
+
+    fn write_segment(&mut self, batch: &[Item<Route>]) -> Result<Segment, Error> {
+        let file = tempfile::tempfile_in(&self.params.dir).with_context(|| {
+            format!(
+                "Could not create frontier spill file in {}",
+                self.params.dir.display()
+            )
+        })?;
+        let mut writer = BufWriter::new(file);
+        for (route, edge) in batch {
+            if let Some(route) = route {
+                writeln!(writer, "{}{}{}", ROUTE_TAG, FIELD_SEP, (self.encode)(route))?;
+            }
+            write_edge(&mut writer, EDGE_TAG, edge)?;
+        }
+        let file = writer
+            .into_inner()
+            .map_err(|e| format_err!("Could not write frontier spill file: {}", e))?;
+        self.spilled_len += batch.len();
+        Ok(Segment {
+            file,
+            len: batch.len(),
+        })
+    }
+
+    fn read_segment(&mut self, mut segment: Segment) -> Result<VecDeque<Item<Route>>, Error> {
+        segment.file.seek(SeekFrom::Start(0))?;
+        let mut items = VecDeque::with_capacity(segment.len);
+        let mut route = None;
+        for line in BufReader::new(segment.file).lines() {
+            let line = line.context("Could not read frontier spill file")?;
+            if let Some(path) = parse_path_line(&line) {
+                let (_, edge) = items
+                    .back_mut()
+                    .ok_or_else(|| format_err!("Path line {:?} without preceding edge", line))?;
+                edge.path = Some(path?);
+                continue;
+            }
+            let parts: Vec<_> = line.splitn(3, FIELD_SEP).collect();
+            match parts.as_slice() {
+                [ROUTE_TAG, ..] => {
+                    route = Some((self.decode)(&line[ROUTE_TAG.len() + 1..])?);
+                }
+                [EDGE_TAG, label, node] => {
+                    items.push_back((route.take(), parse_edge(label, node)?));
+                }
+                _ => bail!("Unexpected line in frontier spill file {:?}", line),
+            }
+        }
+        if items.len() != segment.len {
+            bail!(
+                "Frontier spill file has {} edges, expected {}",
+                items.len(),
+                segment.len
+            );
+        }
+        self.spilled_len -= segment.len;
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{EdgeType, Node, WrappedPath};
+    use crate::walk::{EmptyRoute, StepRoute};
+    use mercurial_types::HgFileNodeId;
+    use mononoke_types::MPath;
+    use std::cell::RefCell;
+    use std::str::FromStr;
+
+    fn edges() -> Result<Vec<OutgoingEdge>, Error> {
+        (0..20u8)
+            .map(|i| {
+                let id = HgFileNodeId::from_str(&format!("{:040x}", i))?;
+                let path = match i % 3 {
+                    0 => None,
+                    1 => Some(WrappedPath::Root),
+                    _ => Some(WrappedPath::from(Some(MPath::new(format!(
+                        "dir\t{}/f",
+                        i
+                    ))?))),
+                };
+                Ok(OutgoingEdge::new_with_path(
+                    EdgeType::HgManifestToHgFileEnvelope,
+                    Node::HgFileEnvelope(id),
+                    path,
+                ))
+            })
+            .collect()
+    }
+
+    // Pushes all edges, popping one after every few pushes, then drains
+    fn drain(
+        mut push: impl FnMut(Item<EmptyRoute>) -> Result<(), Error>,
+        mut pop: impl FnMut() -> Result<Option<Item<EmptyRoute>>, Error>,
+    ) -> Result<Vec<(bool, OutgoingEdge)>, Error> {
+        let mut popped = vec![];
+        for (i, edge) in edges()?.into_iter().enumerate() {
+            let route = if i == 0 { None } else { Some(EmptyRoute()) };
+            push((route, edge))?;
+            if i % 4 == 3 {
+                popped.extend(pop()?);
+            }
+        }
+        while let Some(item) = pop()? {
+            popped.push(item);
+        }
+        Ok(popped
+            .into_iter()
+            .map(|(route, edge)| (route.is_some(), edge))
+            .collect())
+    }
+
+    #[test]
+    fn test_spilling_frontier_order() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        for order in &[TraversalOrder::Dfs, TraversalOrder::Bfs] {
+            let in_memory = RefCell::new(VecDeque::new());
+            let expected = drain(
+                |item| {
+                    match order {
+                        TraversalOrder::Bfs => in_memory.borrow_mut().push_back(item),
+                        _ => in_memory.borrow_mut().push_front(item),
+                    }
+                    Ok(())
+                },
+                || Ok(in_memory.borrow_mut().pop_front()),
+            )?;
+
+            let params = FrontierSpillParams {
+                max_in_memory: 3,
+                dir: dir.path().to_path_buf(),
+            };
+            let frontier = RefCell::new(SpillingFrontier::new(
+                *order,
+                params,
+                EmptyRoute::spill_encode,
+                EmptyRoute::spill_decode,
+            )?);
+            let spilled = drain(
+                |item| {
+                    let mut frontier = frontier.borrow_mut();
+                    frontier.push(item)?;
+                    assert!(frontier.memory.len() <= 3);
+                    Ok(())
+                },
+                || frontier.borrow_mut().pop(),
+            )?;
+            assert_eq!(expected, spilled, "{:?}", order);
+            assert_eq!(frontier.borrow().len(), 0);
+        }
+        Ok(())
+    }
+}
@@ -5,9 +5,13 @@
  * GNU General Public License version 2.
  */
 
-use crate::graph::{EdgeType, Node, NodeData, NodeType, UnodeFlags, WrappedPath, WrappedPathHash};
+use crate::graph::{
+    EdgeType, GitTreeKey, Node, NodeData, NodeType, NodeTypeSet, UnodeFlags, WrappedPath,
+    WrappedPathHash,
+};
 use crate::log;
 use crate::progress::sort_by_string;
+use crate::resume::{ResumeJournal, ResumeState};
 use crate::walk::{
     expand_checked_nodes, EmptyRoute, OutgoingEdge, TailingWalkVisitor, VisitOne, WalkVisitor,
 };
@@ -46,6 +50,15 @@ pub struct StepStats {
     pub error_count: usize,
     pub missing_count: usize,
     pub hash_validation_failure_count: usize,
+    pub parent_linkage_failure_count: usize,
+    pub round_trip_failure_count: usize,
+    pub alias_failure_count: usize,
+    pub orphaned_mapping_count: usize,
+    pub copyfrom_chain_failure_count: usize,
+    pub fastlog_chain_failure_count: usize,
+    pub content_size_failure_count: usize,
+    pub draft_derived_data_failure_count: usize,
+    pub content_metadata_failure_count: usize,
     pub num_expanded_new: usize,
     pub visited_of_type: usize,
 }
@@ -58,6 +71,22 @@ impl Add<StepStats> for StepStats {
             missing_count: self.missing_count + other.missing_count,
             hash_validation_failure_count: self.hash_validation_failure_count
                 + other.hash_validation_failure_count,
+            parent_linkage_failure_count: self.parent_linkage_failure_count
+                + other.parent_linkage_failure_count,
+            round_trip_failure_count: self.round_trip_failure_count
+                + other.round_trip_failure_count,
+            alias_failure_count: self.alias_failure_count + other.alias_failure_count,
+            orphaned_mapping_count: self.orphaned_mapping_count + other.orphaned_mapping_count,
+            copyfrom_chain_failure_count: self.copyfrom_chain_failure_count
+                + other.copyfrom_chain_failure_count,
+            fastlog_chain_failure_count: self.fastlog_chain_failure_count
+                + other.fastlog_chain_failure_count,
+            content_size_failure_count: self.content_size_failure_count
+                + other.content_size_failure_count,
+            draft_derived_data_failure_count: self.draft_derived_data_failure_count
+                + other.draft_derived_data_failure_count,
+            content_metadata_failure_count: self.content_metadata_failure_count
+                + other.content_metadata_failure_count,
             num_expanded_new: self.num_expanded_new + other.num_expanded_new,
             visited_of_type: cmp::max(self.visited_of_type, other.visited_of_type),
         }
@@ -166,7 +195,7 @@ struct UnodeInterned<T> {
 
 pub struct WalkState {
     // Params
-    include_node_types: HashSet<NodeType>,
+    include_node_types: NodeTypeSet,
     include_edge_types: HashSet<EdgeType>,
     always_emit_edge_types: HashSet<EdgeType>,
     enable_derive: bool,
@@ -207,6 +236,8 @@ pub struct WalkState {
     visited_fastlog_file: StateMap<InternedId<FileUnodeId>>,
     visited_fsnode: StateMap<FsnodeId>,
     visited_fsnode_mapping: StateMap<InternedId<ChangesetId>>,
+    visited_git_tree: StateMap<GitTreeKey>,
+    visited_git_tree_mapping: StateMap<InternedId<ChangesetId>>,
     visited_skeleton_manifest: StateMap<SkeletonManifestId>,
     visited_skeleton_manifest_mapping: StateMap<InternedId<ChangesetId>>,
     visited_unode_file: StateMap<UnodeInterned<FileUnodeId>>,
@@ -214,6 +245,8 @@ pub struct WalkState {
     visited_unode_mapping: StateMap<InternedId<ChangesetId>>,
     // Count
     visit_count: [AtomicUsize; NodeType::COUNT],
+    // Resume
+    resume_journal: Option<ResumeJournal>,
 }
 
 impl WalkState {
@@ -227,7 +260,7 @@ impl WalkState {
         let fac = RandomState::default();
         Self {
             // Params
-            include_node_types,
+            include_node_types: NodeTypeSet::from(&include_node_types),
             include_edge_types,
             always_emit_edge_types,
             enable_derive,
@@ -268,6 +301,8 @@ impl WalkState {
             visited_fastlog_file: StateMap::with_hasher(fac.clone()),
             visited_fsnode: StateMap::with_hasher(fac.clone()),
             visited_fsnode_mapping: StateMap::with_hasher(fac.clone()),
+            visited_git_tree: StateMap::with_hasher(fac.clone()),
+            visited_git_tree_mapping: StateMap::with_hasher(fac.clone()),
             visited_skeleton_manifest: StateMap::with_hasher(fac.clone()),
             visited_skeleton_manifest_mapping: StateMap::with_hasher(fac.clone()),
             visited_unode_file: StateMap::with_hasher(fac.clone()),
@@ -275,6 +310,8 @@ impl WalkState {
             visited_unode_mapping: StateMap::with_hasher(fac),
             // Count
             visit_count: array_init(|_i| AtomicUsize::new(0)),
+            // Resume
+            resume_journal: None,
         }
     }
 
@@ -353,6 +390,12 @@ impl WalkState {
             (Node::FsnodeMapping(bcs_id), Some(_)) => {
                 self.record(&self.visited_fsnode_mapping, &self.bcs_ids.interned(bcs_id));
             }
+            (Node::GitTreeMapping(bcs_id), Some(_)) => {
+                self.record(
+                    &self.visited_git_tree_mapping,
+                    &self.bcs_ids.interned(bcs_id),
+                );
+            }
             (Node::SkeletonManifestMapping(bcs_id), Some(_)) => {
                 self.record(
                     &self.visited_skeleton_manifest_mapping,
@@ -371,7 +414,7 @@ impl WalkState {
         outgoing_edge.label.incoming_type().is_none()
             || (self
                 .include_node_types
-                .contains(&outgoing_edge.target.get_type())
+                .contains(outgoing_edge.target.get_type())
                 && self.include_edge_types.contains(&outgoing_edge.label))
     }
 
@@ -464,6 +507,8 @@ impl WalkState {
             NodeType::FastlogFile => self.visited_fastlog_file.clear(),
             NodeType::Fsnode => self.visited_fsnode.clear(),
             NodeType::FsnodeMapping => self.visited_fsnode_mapping.clear(),
+            NodeType::GitTree => self.visited_git_tree.clear(),
+            NodeType::GitTreeMapping => self.visited_git_tree_mapping.clear(),
             NodeType::SkeletonManifest => self.visited_skeleton_manifest.clear(),
             NodeType::SkeletonManifestMapping => self.visited_skeleton_manifest_mapping.clear(),
             NodeType::UnodeFile => self.visited_unode_file.clear(),
@@ -614,6 +659,15 @@ impl WalkState {
                     true
                 }
             }
+            (Node::GitTree(_), true) => true,
+            (Node::GitTree(k), false) => self.record(&self.visited_git_tree, &k),
+            (Node::GitTreeMapping(bcs_id), _) => {
+                if let Some(id) = self.bcs_ids.get(bcs_id) {
+                    !self.visited_git_tree_mapping.contains_key(&id) // Does not insert, see record_resolved_visit
+                } else {
+                    true
+                }
+            }
             (Node::SkeletonManifest(_), true) => true,
             (Node::SkeletonManifest(id), false) => {
                 self.record(&self.visited_skeleton_manifest, &id)
@@ -878,6 +932,28 @@ impl TailingWalkVisitor for WalkState {
     fn num_deferred(&self) -> usize {
         self.deferred_bcs.len()
     }
+
+    fn start_resume_journal(&mut self, previous: Option<ResumeState>) -> Vec<OutgoingEdge> {
+        let journal = ResumeJournal::default();
+        let pending = if let Some(previous) = previous {
+            for visited in previous.visited {
+                // NotRequired is enough for record_resolved_visit to mark the mapping types.
+                self.needs_visit(&visited);
+                self.record_resolved_visit(&visited, Some(&NodeData::NotRequired));
+                journal.record_visit(&visited, &[]);
+            }
+            journal.record_pending(&previous.pending);
+            previous.pending
+        } else {
+            vec![]
+        };
+        self.resume_journal = Some(journal);
+        pending
+    }
+
+    fn resume_state(&self) -> Option<ResumeState> {
+        self.resume_journal.as_ref().map(|j| j.snapshot())
+    }
 }
 
 impl WalkVisitor<(Node, Option<NodeData>, Option<StepStats>), EmptyRoute> for WalkState {
@@ -939,6 +1015,10 @@ impl WalkVisitor<(Node, Option<NodeData>, Option<StepStats>), EmptyRoute> for Wa
 
         self.record_resolved_visit(&resolved, node_data.as_ref());
 
+        if let Some(resume_journal) = self.resume_journal.as_ref() {
+            resume_journal.record_visit(&resolved, &outgoing);
+        }
+
         // Stats
         let num_expanded_new = outgoing.len() + queued_roots;
         let node = resolved.target;
@@ -947,6 +1027,15 @@ impl WalkVisitor<(Node, Option<NodeData>, Option<StepStats>), EmptyRoute> for Wa
             error_count: 0,
             missing_count: 0,
             hash_validation_failure_count: 0,
+            parent_linkage_failure_count: 0,
+            round_trip_failure_count: 0,
+            alias_failure_count: 0,
+            orphaned_mapping_count: 0,
+            copyfrom_chain_failure_count: 0,
+            fastlog_chain_failure_count: 0,
+            content_size_failure_count: 0,
+            draft_derived_data_failure_count: 0,
+            content_metadata_failure_count: 0,
             num_expanded_new,
             visited_of_type: self.get_visit_count(&node.get_type()),
         };
@@ -963,6 +1052,42 @@ impl WalkVisitor<(Node, Option<NodeData>, Option<StepStats>), EmptyRoute> for Wa
                 stats.hash_validation_failure_count += 1;
                 None
             }
+            Some(NodeData::ParentLinkageFailureAsData(_key)) => {
+                stats.parent_linkage_failure_count += 1;
+                None
+            }
+            Some(NodeData::RoundTripFailureAsData(_key)) => {
+                stats.round_trip_failure_count += 1;
+                None
+            }
+            Some(NodeData::AliasFailureAsData(_key)) => {
+                stats.alias_failure_count += 1;
+                None
+            }
+            Some(NodeData::OrphanedMappingAsData(_key)) => {
+                stats.orphaned_mapping_count += 1;
+                None
+            }
+            Some(NodeData::CopyfromChainFailureAsData(_key)) => {
+                stats.copyfrom_chain_failure_count += 1;
+                None
+            }
+            Some(NodeData::FastlogChainFailureAsData(_key)) => {
+                stats.fastlog_chain_failure_count += 1;
+                None
+            }
+            Some(NodeData::ContentSizeFailureAsData(_key)) => {
+                stats.content_size_failure_count += 1;
+                None
+            }
+            Some(NodeData::DraftDerivedDataFailureAsData(_key)) => {
+                stats.draft_derived_data_failure_count += 1;
+                None
+            }
+            Some(NodeData::ContentMetadataFailureAsData(_key)) => {
+                stats.content_metadata_failure_count += 1;
+                None
+            }
             Some(d) => Some(d),
             None => None,
         };
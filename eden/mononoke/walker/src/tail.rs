@@ -8,6 +8,7 @@
 use crate::checkpoint::{Checkpoint, CheckpointsByName};
 use crate::graph::{ChangesetKey, Node, NodeType};
 use crate::log;
+use crate::resume::ResumeState;
 use crate::setup::JobWalkParams;
 use crate::state::InternedType;
 use crate::walk::{
@@ -29,10 +30,12 @@ use futures::{
 };
 use mercurial_derived_data::MappedHgChangesetId;
 use mononoke_types::{ChangesetId, RepositoryId, Timestamp};
-use slog::{info, Logger};
+use slog::{info, warn, Logger};
 use std::{
     cmp::{max, min},
     collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 use strum::IntoEnumIterator;
@@ -60,6 +63,7 @@ fn roots_for_chunk(
                 NodeType::ChangesetInfoMapping => Node::ChangesetInfoMapping(id),
                 NodeType::DeletedManifestMapping => Node::DeletedManifestMapping(id),
                 NodeType::FsnodeMapping => Node::FsnodeMapping(id),
+                NodeType::GitTreeMapping => Node::GitTreeMapping(id),
                 NodeType::SkeletonManifestMapping => Node::SkeletonManifestMapping(id),
                 NodeType::UnodeMapping => Node::UnodeMapping(id),
                 _ => bail!("Unsupported root type for chunking {:?}", r),
@@ -94,11 +98,27 @@ pub struct ChunkingParams {
     pub repo_upper_bound_override: Option<u64>,
 }
 
+#[derive(Clone, Debug)]
+pub struct ResumeParams {
+    pub state_dir: PathBuf,
+    pub save_interval: Duration,
+}
+
 #[derive(Clone, Debug)]
 pub struct TailParams {
     pub tail_secs: Option<u64>,
     pub chunking: Option<ChunkingParams>,
     pub state_max_age: Duration,
+    pub resume: Option<ResumeParams>,
+}
+
+const RESUME_STATE_EXTENSION: &str = "walk_resume";
+
+fn save_resume_state<V: TailingWalkVisitor>(visitor: &V, path: &Path) -> Result<(), Error> {
+    if let Some(state) = visitor.resume_state() {
+        state.save(path)?;
+    }
+    Ok(())
 }
 
 // Represent that only one end of the bound is optional, depending on direction
@@ -199,6 +219,30 @@ where
 
     let mut state_start = Timestamp::now();
 
+    // Pick up from the visits saved by a previous run that did not finish
+    let resume_path = tail_params.resume.as_ref().map(|resume| {
+        resume.state_dir.join(format!(
+            "{}.{}",
+            repo_params.repo.name(),
+            RESUME_STATE_EXTENSION
+        ))
+    });
+    let mut resume_roots = if let Some(resume_path) = resume_path.as_ref() {
+        let previous = ResumeState::load(resume_path)?;
+        if let Some(previous) = previous.as_ref() {
+            info!(
+                repo_params.logger,
+                "Resuming from {} with {} visited and {} pending",
+                resume_path.display(),
+                previous.visited.len(),
+                previous.pending.len()
+            );
+        }
+        visitor.start_resume_journal(previous)
+    } else {
+        vec![]
+    };
+
     let with_hg = repo_params.include_node_types.iter().any(|n| {
         let n = n.derived_data_name();
         n == Some(MappedHgChangesetId::NAME) || n == Some(FilenodesOnlyPublic::NAME)
@@ -442,8 +486,12 @@ where
                 repo_params.walk_roots.extend(chunk_roots);
             }
             repo_params.walk_roots.extend(extra_roots);
+            repo_params.walk_roots.extend(resume_roots.drain(..));
 
             cloned!(ctx, job_params, make_run, type_params);
+            let quiet = job_params.quiet;
+            let frontier_stats = repo_params.frontier_stats.clone();
+            let edge_list = repo_params.edge_list.clone();
             let make_sink = make_run(&ctx, &repo_params);
 
             // Walk needs clonable visitor, so wrap in Arc for its duration
@@ -455,9 +503,55 @@ where
                 .as_ref()
                 .and_then(|chunking| chunking.checkpoints.as_ref())
                 .map(|v| v.name().to_string());
-            make_sink(walk_output, run_start, chunk_num, cp_name).await?;
+            let resume_saver = match (tail_params.resume.as_ref(), resume_path.as_ref()) {
+                (Some(resume), Some(resume_path)) => {
+                    cloned!(arc_v, logger, resume_path);
+                    let mut interval = tokio::time::interval(resume.save_interval);
+                    Some(tokio::spawn(async move {
+                        // First tick completes immediately
+                        interval.tick().await;
+                        loop {
+                            interval.tick().await;
+                            cloned!(arc_v, resume_path);
+                            let saved = tokio::task::spawn_blocking(move || {
+                                save_resume_state(arc_v.as_ref(), &resume_path)
+                            })
+                            .await;
+                            match saved {
+                                Ok(Ok(())) => {}
+                                Ok(Err(e)) => warn!(logger, "Could not save resume state: {:?}", e),
+                                Err(e) => warn!(logger, "Could not save resume state: {:?}", e),
+                            }
+                        }
+                    }))
+                }
+                _ => None,
+            };
+            let walk_result = make_sink(walk_output, run_start, chunk_num, cp_name).await;
+            // Make sure the saver has let go of the visitor before unwrapping it
+            if let Some(resume_saver) = resume_saver {
+                resume_saver.abort();
+                let _ = resume_saver.await;
+            }
+            walk_result?;
+            if let Some(edge_list) = edge_list.as_ref() {
+                edge_list.flush()?;
+            }
+            if !quiet {
+                for count in frontier_stats.snapshot() {
+                    info!(
+                        logger, #log::FRONTIER,
+                        "Frontier {}: current {} (~{} bytes), peak {}",
+                        count.node_type.as_ref(), count.current, count.current_bytes, count.peak
+                    );
+                }
+            }
             visitor = Arc::try_unwrap(arc_v).map_err(|_| anyhow!("could not unwrap visitor"))?;
 
+            if let Some(resume_path) = resume_path.as_ref() {
+                save_resume_state(&visitor, resume_path)?;
+            }
+
             if let Some(chunking) = tail_params.chunking.as_ref() {
                 info!(logger, #log::LOADED, "Deferred: {}", visitor.num_deferred());
                 if let Some(clear_state) = chunking.clear_state.as_ref() {
@@ -515,6 +609,14 @@ where
             );
         };
 
+        // Walk completed, so next run starts afresh
+        if let Some(resume_path) = resume_path.as_ref() {
+            if resume_path.exists() {
+                fs::remove_file(resume_path)?;
+            }
+            visitor.start_resume_journal(None);
+        }
+
         match tail_secs {
             Some(interval) => {
                 let start = Instant::now();
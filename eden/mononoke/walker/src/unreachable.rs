@@ -0,0 +1,273 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::graph::{EdgeType, Node, NodeType};
+use crate::walk::OutgoingEdge;
+
+use anyhow::{bail, Context, Error};
+use mononoke_types::{
+    FileUnodeId, FsnodeId, ManifestUnodeId, MononokeId, RepositoryId, SkeletonManifestId,
+};
+use slog::{info, Logger};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Derived data whose blobs are keyed by their own hash, so nothing else refers to them once no
+/// walk reaches them. Root mappings are keyed by changeset instead, and are orphans rather than
+/// garbage once their changeset is gone, see --check-orphaned-mappings.
+pub const UNREACHABLE_NODE_TYPES: &[NodeType] = &[
+    NodeType::Fsnode,
+    NodeType::SkeletonManifest,
+    NodeType::UnodeFile,
+    NodeType::UnodeManifest,
+];
+
+// Node types a walk passes through to get to UNREACHABLE_NODE_TYPES
+const PATH_NODE_TYPES: &[NodeType] = &[
+    NodeType::PublishedBookmarks,
+    NodeType::Changeset,
+    NodeType::FsnodeMapping,
+    NodeType::SkeletonManifestMapping,
+    NodeType::UnodeMapping,
+];
+
+// Every edge by which a live UNREACHABLE_NODE_TYPES blob can be referenced, from the public
+// bookmarks back through history. Leaving any out would report live blobs as unreachable.
+const REQUIRED_EDGE_TYPES: &[EdgeType] = &[
+    EdgeType::PublishedBookmarksToChangeset,
+    EdgeType::ChangesetToBonsaiParent,
+    EdgeType::ChangesetToFsnodeMapping,
+    EdgeType::ChangesetToSkeletonManifestMapping,
+    EdgeType::ChangesetToUnodeMapping,
+    EdgeType::FsnodeMappingToRootFsnode,
+    EdgeType::FsnodeToChildFsnode,
+    EdgeType::SkeletonManifestMappingToRootSkeletonManifest,
+    EdgeType::SkeletonManifestToSkeletonManifestChild,
+    EdgeType::UnodeMappingToRootUnodeManifest,
+    EdgeType::UnodeManifestToUnodeManifestChild,
+    EdgeType::UnodeManifestToUnodeFileChild,
+    EdgeType::UnodeManifestToUnodeManifestParent,
+    EdgeType::UnodeFileToUnodeFileParent,
+];
+
+/// Check a walk would reach every live key of UNREACHABLE_NODE_TYPES, so none is reported as
+/// unreachable. It must start from all the bookmarks, scratch included, and step through every
+/// edge that can lead to them. Options that otherwise shrink the walk are rejected when parsing args.
+pub fn check_walk_for_unreachable(
+    walk_roots: &[OutgoingEdge],
+    include_node_types: &HashSet<NodeType>,
+    include_edge_types: &HashSet<EdgeType>,
+) -> Result<(), Error> {
+    if walk_roots.is_empty()
+        || walk_roots
+            .iter()
+            .any(|root| root.target.get_type() != NodeType::PublishedBookmarks)
+    {
+        bail!(
+            "Finding unreachable keys needs a walk from all bookmarks, with {} as the only walk root",
+            NodeType::PublishedBookmarks
+        );
+    }
+    let missing_node_types: Vec<_> = UNREACHABLE_NODE_TYPES
+        .iter()
+        .chain(PATH_NODE_TYPES.iter())
+        .filter(|t| !include_node_types.contains(t))
+        .collect();
+    if !missing_node_types.is_empty() {
+        bail!(
+            "Finding unreachable keys needs node types {:?} to be walked",
+            missing_node_types
+        );
+    }
+    let missing_edge_types: Vec<_> = REQUIRED_EDGE_TYPES
+        .iter()
+        .filter(|t| !include_edge_types.contains(t))
+        .collect();
+    if !missing_edge_types.is_empty() {
+        bail!(
+            "Finding unreachable keys needs edge types {:?} to be walked",
+            missing_edge_types
+        );
+    }
+    Ok(())
+}
+
+fn is_checked_key(key: &str) -> bool {
+    [
+        FsnodeId::blobstore_key_prefix(),
+        SkeletonManifestId::blobstore_key_prefix(),
+        FileUnodeId::blobstore_key_prefix(),
+        ManifestUnodeId::blobstore_key_prefix(),
+    ]
+    .iter()
+    .any(|prefix| key.starts_with(prefix))
+}
+
+/// Records the blobstore keys of the derived data reached by a walk, so that a listing of the
+/// blobstore can be checked for derived data blobs that no walk reaches.
+///
+/// The listing must be taken before the walk starts. Derived data written after the walk has
+/// passed its changesets is not reached, so a later listing would report it as unreachable.
+///
+/// Every reached key is kept, so memory grows with the repo's derived data.
+pub struct UnreachableKeys {
+    logger: Logger,
+    repo_prefix: String,
+    reachable: Mutex<HashSet<String>>,
+}
+
+impl UnreachableKeys {
+    pub fn new(logger: Logger, repo_id: RepositoryId) -> Self {
+        Self {
+            logger,
+            repo_prefix: repo_id.prefix(),
+            reachable: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn record(&self, node: &Node) {
+        if UNREACHABLE_NODE_TYPES.contains(&node.get_type()) {
+            let mut reachable = self.reachable.lock().expect("lock poisoned");
+            reachable.insert(node.stats_key());
+        }
+    }
+
+    /// Keys from a blobstore listing, one per line, that are this repo's derived data and were
+    /// not reached. Keys are returned as listed, with their repo prefix, in listing order.
+    pub fn unreachable(&self, listing: impl BufRead) -> Result<Vec<String>, Error> {
+        let reachable = self.reachable.lock().expect("lock poisoned");
+        let mut unreachable = vec![];
+        for line in listing.lines() {
+            let line = line?;
+            let key = line.trim();
+            match key.strip_prefix(&self.repo_prefix) {
+                Some(unprefixed) if is_checked_key(unprefixed) => {
+                    if !reachable.contains(unprefixed) {
+                        unreachable.push(key.to_string());
+                    }
+                }
+                // Another repo's key, or not derived data checked here
+                _ => {}
+            }
+        }
+        Ok(unreachable)
+    }
+
+    /// Write the unreachable keys from the listing at listing_path to output_path, one per line
+    pub fn report(&self, listing_path: &Path, output_path: &Path) -> Result<(), Error> {
+        let listing = File::open(listing_path)
+            .with_context(|| format!("While opening key listing {}", listing_path.display()))?;
+        let unreachable = self.unreachable(BufReader::new(listing))?;
+        let mut output = BufWriter::new(File::create(output_path)?);
+        for key in &unreachable {
+            writeln!(output, "{}", key)?;
+        }
+        output.flush()?;
+        info!(
+            self.logger,
+            "Found {} unreachable derived data keys, {} were reachable. Written to {}",
+            unreachable.len(),
+            self.reachable.lock().expect("lock poisoned").len(),
+            output_path.display(),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ChangesetKey, UnitKey, UnodeFlags, UnodeKey};
+    use bookmarks::BookmarkName;
+    use mononoke_types::{ChangesetId, ContentId};
+    use slog::o;
+
+    #[test]
+    fn test_unreachable_keys() -> Result<(), Error> {
+        let logger = Logger::root(slog::Discard, o!());
+        let repo_id = RepositoryId::new(1);
+        let keys = UnreachableKeys::new(logger, repo_id);
+
+        let reached = FsnodeId::from_bytes([1; 32])?;
+        let garbage = FsnodeId::from_bytes([2; 32])?;
+        let garbage_unode = FileUnodeId::from_bytes([3; 32])?;
+        keys.record(&Node::Fsnode(reached));
+        keys.record(&Node::UnodeFile(UnodeKey {
+            inner: FileUnodeId::from_bytes([4; 32])?,
+            flags: UnodeFlags::NONE,
+        }));
+        // Not derived data, so not recorded
+        keys.record(&Node::Changeset(ChangesetKey {
+            inner: ChangesetId::from_bytes([5; 32])?,
+            filenode_known_derived: false,
+        }));
+
+        let prefixed = |key: String| format!("{}{}", repo_id.prefix(), key);
+        let listing = [
+            prefixed(reached.blobstore_key()),
+            prefixed(garbage.blobstore_key()),
+            prefixed(FileUnodeId::from_bytes([4; 32])?.blobstore_key()),
+            prefixed(garbage_unode.blobstore_key()),
+            // Content isn't derived data
+            prefixed(ContentId::from_bytes([6; 32])?.blobstore_key()),
+            // Another repo's garbage
+            format!(
+                "{}{}",
+                RepositoryId::new(2).prefix(),
+                garbage.blobstore_key()
+            ),
+        ]
+        .join("\n");
+
+        assert_eq!(
+            keys.unreachable(listing.as_bytes())?,
+            vec![
+                prefixed(garbage.blobstore_key()),
+                prefixed(garbage_unode.blobstore_key()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_walk_for_unreachable() -> Result<(), Error> {
+        let node_types: HashSet<_> = UNREACHABLE_NODE_TYPES
+            .iter()
+            .chain(PATH_NODE_TYPES.iter())
+            .copied()
+            .collect();
+        let edge_types: HashSet<_> = REQUIRED_EDGE_TYPES.iter().copied().collect();
+        let all_bookmarks = vec![OutgoingEdge::new(
+            EdgeType::RootToPublishedBookmarks,
+            Node::PublishedBookmarks(UnitKey()),
+        )];
+        check_walk_for_unreachable(&all_bookmarks, &node_types, &edge_types)?;
+
+        // No roots, or a single bookmark, would miss the other bookmarks' data
+        assert!(check_walk_for_unreachable(&[], &node_types, &edge_types).is_err());
+        let one_bookmark = vec![OutgoingEdge::new(
+            EdgeType::RootToBookmark,
+            Node::Bookmark(BookmarkName::new("main")?),
+        )];
+        assert!(check_walk_for_unreachable(&one_bookmark, &node_types, &edge_types).is_err());
+
+        // Without history, older derived data would be reported
+        let mut shallow = edge_types.clone();
+        shallow.remove(&EdgeType::ChangesetToBonsaiParent);
+        assert!(check_walk_for_unreachable(&all_bookmarks, &node_types, &shallow).is_err());
+
+        let mut no_mappings = node_types.clone();
+        no_mappings.remove(&NodeType::UnodeMapping);
+        assert!(check_walk_for_unreachable(&all_bookmarks, &no_mappings, &edge_types).is_err());
+        Ok(())
+    }
+}
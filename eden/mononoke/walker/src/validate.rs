@@ -8,19 +8,22 @@
 // This module allows the implementation of validating checks over the mononoke graph
 // Currently checks are added by
 //  1. Add a CheckType variant
-//  2. Add CheckType::node_type() and CheckType::enum_type() cases for the new variant
+//  2. Add CheckType::node_types() and CheckType::enum_type() cases for the new variant
 //  3. Add a new validation method
 //  4. Add the method to the match/case in ValidatingVisitor::visit()
 
 use crate::graph::{EdgeType, Node, NodeData, NodeType, WrappedPath};
 use crate::log;
+use crate::parse_node::{format_node, parse_node};
 use crate::progress::{
-    progress_stream, report_state, sort_by_string, ProgressOptions, ProgressRecorder,
-    ProgressRecorderUnprotected, ProgressReporter, ProgressReporterUnprotected, ProgressStateMutex,
+    outcome_stream, progress_stream, report_state, sort_by_string, OutcomeStateByType,
+    ProgressOptions, ProgressRecorder, ProgressRecorderUnprotected, ProgressReporter,
+    ProgressReporterUnprotected, ProgressStateMutex,
 };
+use crate::resume::{ResumeState, FIELD_SEP};
 use crate::setup::{
-    parse_progress_args, setup_common, JobWalkParams, RepoSubcommandParams, EXCLUDE_CHECK_TYPE_ARG,
-    INCLUDE_CHECK_TYPE_ARG, VALIDATE,
+    parse_progress_args, setup_common, JobWalkParams, RepoSubcommandParams,
+    DERIVED_MAPPING_NODE_TYPES, EXCLUDE_CHECK_TYPE_ARG, INCLUDE_CHECK_TYPE_ARG, VALIDATE,
 };
 use crate::state::{InternedType, StepStats, WalkState};
 use crate::tail::walk_exact_tail;
@@ -29,7 +32,7 @@ use crate::walk::{
     VisitOne, WalkVisitor,
 };
 
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use async_trait::async_trait;
 use bonsai_hg_mapping::{BonsaiHgMapping, BonsaiHgMappingEntry};
 use bulkops::Direction;
@@ -129,6 +132,7 @@ enum CheckType {
     ChangesetPhaseIsPublic,
     HgLinkNodePopulated,
     FileContentIsLfs,
+    DerivedMappingPresent,
 }
 }
 
@@ -138,13 +142,15 @@ impl CheckType {
             CheckType::ChangesetPhaseIsPublic => "bonsai_phase_is_public",
             CheckType::HgLinkNodePopulated => "hg_link_node_populated",
             CheckType::FileContentIsLfs => "file_content_is_lfs",
+            CheckType::DerivedMappingPresent => "derived_mapping_present",
         }
     }
-    pub fn node_type(&self) -> NodeType {
+    pub fn node_types(&self) -> &'static [NodeType] {
         match self {
-            CheckType::ChangesetPhaseIsPublic => NodeType::PhaseMapping,
-            CheckType::HgLinkNodePopulated => NodeType::HgFileNode,
-            CheckType::FileContentIsLfs => NodeType::FileContentMetadata,
+            CheckType::ChangesetPhaseIsPublic => &[NodeType::PhaseMapping],
+            CheckType::HgLinkNodePopulated => &[NodeType::HgFileNode],
+            CheckType::FileContentIsLfs => &[NodeType::FileContentMetadata],
+            CheckType::DerivedMappingPresent => DERIVED_MAPPING_NODE_TYPES,
         }
     }
 }
@@ -194,12 +200,15 @@ impl ValidatingVisitor {
                 enable_derive,
                 chunk_direction,
             ),
-            checks_by_node_type: include_checks
-                .into_iter()
-                .group_by(|c| c.node_type())
-                .into_iter()
-                .map(|(key, group)| (key, HashSet::from_iter(group)))
-                .collect(),
+            checks_by_node_type: include_checks.into_iter().fold(
+                HashMap::new(),
+                |mut acc: HashMap<NodeType, HashSet<CheckType>>, check| {
+                    for node_type in check.node_types() {
+                        acc.entry(*node_type).or_default().insert(check);
+                    }
+                    acc
+                },
+            ),
             lfs_threshold,
         }
     }
@@ -371,6 +380,42 @@ fn check_file_content_is_lfs(
     }
 }
 
+// The changeset a derived data mapping node is for
+fn mapping_changeset_id(node: &Node) -> Option<ChangesetId> {
+    match node {
+        Node::BonsaiHgMapping(key) => Some(key.inner),
+        Node::ChangesetInfoMapping(bcs_id)
+        | Node::DeletedManifestMapping(bcs_id)
+        | Node::FsnodeMapping(bcs_id)
+        | Node::GitTreeMapping(bcs_id)
+        | Node::SkeletonManifestMapping(bcs_id)
+        | Node::UnodeMapping(bcs_id) => Some(*bcs_id),
+        _ => None,
+    }
+}
+
+fn check_derived_mapping_present(
+    node_data: Option<&NodeData>,
+    route: Option<&ValidateRoute>,
+) -> CheckStatus {
+    match node_data {
+        Some(NodeData::BonsaiHgMapping(Some(_)))
+        | Some(NodeData::ChangesetInfoMapping(Some(_)))
+        | Some(NodeData::DeletedManifestMapping(Some(_)))
+        | Some(NodeData::FsnodeMapping(Some(_)))
+        | Some(NodeData::GitTreeMapping(Some(_)))
+        | Some(NodeData::SkeletonManifestMapping(Some(_)))
+        | Some(NodeData::UnodeMapping(Some(_))) => CheckStatus::Pass(None),
+        // Underived, or unexpected node type
+        _ => CheckStatus::Fail(ValidateInfo::new(
+            route.map(|r| r.src_node.clone()),
+            None,
+            None,
+            None,
+        )),
+    }
+}
+
 #[derive(AddAssign, Clone, Copy, Default, Debug)]
 struct CheckStats {
     pass: u64,
@@ -428,6 +473,25 @@ impl StepRoute for ValidateRoute {
     fn via_node(&self) -> Option<&Node> {
         self.via.last()
     }
+
+    const SPILLABLE: bool = true;
+
+    // The source node then the via nodes, formatted nodes have no FIELD_SEP in them
+    fn spill_encode(&self) -> String {
+        std::iter::once(&self.src_node)
+            .chain(self.via.iter())
+            .map(format_node)
+            .join(&FIELD_SEP.to_string())
+    }
+
+    fn spill_decode(s: &str) -> Result<Self, Error> {
+        let mut nodes = s.split(FIELD_SEP).map(parse_node);
+        let src_node = nodes
+            .next()
+            .ok_or_else(|| format_err!("Route {:?} has no source node", s))??;
+        let via = nodes.collect::<Result<_, _>>()?;
+        Ok(Self { src_node, via })
+    }
 }
 
 impl TailingWalkVisitor for ValidatingVisitor {
@@ -454,6 +518,14 @@ impl TailingWalkVisitor for ValidatingVisitor {
     fn num_deferred(&self) -> usize {
         self.inner.num_deferred()
     }
+
+    fn start_resume_journal(&mut self, previous: Option<ResumeState>) -> Vec<OutgoingEdge> {
+        self.inner.start_resume_journal(previous)
+    }
+
+    fn resume_state(&self) -> Option<ResumeState> {
+        self.inner.resume_state()
+    }
 }
 
 impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), ValidateRoute>
@@ -514,6 +586,9 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), ValidateRoute>
                                 CheckStatus::Pass(None)
                             }
                         }
+                        CheckType::DerivedMappingPresent => {
+                            check_derived_mapping_present(node_data.as_ref(), route.as_ref())
+                        }
                     };
                     match &status {
                         CheckStatus::Pass(_) => pass += 1,
@@ -600,6 +675,8 @@ struct ValidateProgressState {
     checked_nodes: u64,
     passed_nodes: u64,
     failed_nodes: u64,
+    // Changesets found without each mapping type since the last report
+    underived: HashMap<NodeType, Vec<ChangesetId>>,
     throttle_options: ProgressOptions,
     last_update: Instant,
 }
@@ -626,6 +703,7 @@ impl ValidateProgressState {
             checked_nodes: 0,
             passed_nodes: 0,
             failed_nodes: 0,
+            underived: HashMap::new(),
             throttle_options,
             last_update: now,
         }
@@ -656,6 +734,21 @@ impl ValidateProgressState {
         );
     }
 
+    fn report_underived(&mut self) {
+        for node_type in sort_by_string(self.underived.keys().cloned()) {
+            if let Some(mut bcs_ids) = self.underived.remove(&node_type) {
+                bcs_ids.sort();
+                info!(
+                    self.logger,
+                    #log::VALIDATE,
+                    "Underived {}: {}",
+                    node_type,
+                    bcs_ids.iter().map(|id| id.to_string()).join(" "),
+                );
+            }
+        }
+    }
+
     fn report_progress_stats(&self) {
         // Per check type
         for (k, v) in self.stats_by_type.iter() {
@@ -773,6 +866,14 @@ impl ProgressRecorderUnprotected<CheckData> for ValidateProgressState {
                         STATS::walker_validate
                             .add_value(1, (self.repo_stats_key.clone(), k.stats_key(), FAIL));
                         stats.fail += 1;
+                        if k == CheckType::DerivedMappingPresent {
+                            if let Some(bcs_id) = mapping_changeset_id(resolved_node) {
+                                self.underived
+                                    .entry(resolved_node.get_type())
+                                    .or_default()
+                                    .push(bcs_id);
+                            }
+                        }
                         (Some(validate_info), 1)
                     }
                 };
@@ -818,6 +919,7 @@ impl ProgressReporterUnprotected for ValidateProgressState {
     fn report_progress(&mut self) {
         self.report_progress_log();
         self.report_progress_stats();
+        self.report_underived();
     }
 
     fn report_throttled(&mut self) {
@@ -840,8 +942,11 @@ struct ValidateCommand {
 
 impl ValidateCommand {
     fn apply_repo(&mut self, repo_params: &RepoWalkParams) {
-        self.include_check_types
-            .retain(|t| repo_params.include_node_types.contains(&t.node_type()));
+        self.include_check_types.retain(|t| {
+            t.node_types()
+                .iter()
+                .any(|n| repo_params.include_node_types.contains(n))
+        });
     }
 }
 
@@ -894,15 +999,24 @@ async fn run_one(
         command.include_check_types.clone(),
         command.progress_options,
     ));
+    let outcome_state = ProgressStateMutex::new(OutcomeStateByType::new(
+        fb,
+        repo_params.logger.clone(),
+        VALIDATE,
+        repo_params.repo.name().clone(),
+        repo_params.include_node_types.clone(),
+        command.progress_options,
+    ));
 
     cloned!(job_params.quiet, sub_params.progress_state);
     let make_sink = move |ctx: &CoreContext, repo_params: &RepoWalkParams| {
         cloned!(ctx);
         validate_progress_state.set_sample_builder(repo_params.scuba_builder.clone());
         async move |walk_output, _run_start, _chunk_num, _checkpoint_name| {
-            cloned!(ctx, progress_state, validate_progress_state);
+            cloned!(ctx, progress_state, validate_progress_state, outcome_state);
+            let walk_outcomes = outcome_stream(quiet, &outcome_state, walk_output);
             let walk_progress =
-                progress_stream(quiet, &progress_state, walk_output).map_ok(|(n, d, s)| {
+                progress_stream(quiet, &progress_state, walk_outcomes).map_ok(|(n, d, s)| {
                     // swap stats and data round
                     (n, s, d)
                 });
@@ -911,6 +1025,7 @@ async fn run_one(
 
             report_state(ctx, validate_progress).await?;
             progress_state.report_progress();
+            outcome_state.report_progress();
             validate_progress_state.report_progress();
             Ok(())
         }
@@ -928,6 +1043,12 @@ async fn run_one(
         required_node_data_types.insert(NodeType::FileContentMetadata);
         keep_edge_paths = true;
     }
+    if command
+        .include_check_types
+        .contains(&CheckType::DerivedMappingPresent)
+    {
+        required_node_data_types.extend(DERIVED_MAPPING_NODE_TYPES.iter().cloned());
+    }
 
     let stateful_visitor = ValidatingVisitor::new(
         repo_params.repo.name().clone(),
@@ -948,6 +1069,7 @@ async fn run_one(
         required_node_data_types,
         always_emit_edge_types,
         keep_edge_paths,
+        include_scratch_bookmarks: false,
     };
 
     walk_exact_tail(
@@ -961,3 +1083,43 @@ async fn run_one(
     )
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ChangesetKey, PathKey};
+    use mercurial_types::HgManifestId;
+
+    #[test]
+    fn test_validate_route_spill_roundtrip() -> Result<(), Error> {
+        let changeset = Node::Changeset(ChangesetKey {
+            inner: ChangesetId::from_bytes([1; 32])?,
+            filenode_known_derived: false,
+        });
+        let hg_changeset = Node::HgChangeset(ChangesetKey {
+            inner: HgChangesetId::from_str("e797dcabdd6d16ec4ae614165178b60d7054305b")?,
+            filenode_known_derived: false,
+        });
+        // A path with the separator in it
+        let manifest = Node::HgManifest(PathKey {
+            id: HgManifestId::from_str("a2fde1c6b2bd7aa2cd0f8ba1b6a8b2f3e86e4b3c")?,
+            path: WrappedPath::from(Some(MPath::new("dir\tname")?)),
+        });
+
+        let route = ValidateRoute::next_route(None, changeset);
+        let route = ValidateRoute::next_route(Some(route), hg_changeset);
+        let route = ValidateRoute::next_route(Some(route), manifest);
+        assert_eq!(route.via.len(), 2);
+
+        let decoded = ValidateRoute::spill_decode(&route.spill_encode())?;
+        assert_eq!(decoded.src_node, route.src_node);
+        assert_eq!(decoded.via, route.via);
+
+        // With no via nodes
+        let route = ValidateRoute::next_route(None, decoded.src_node);
+        let decoded = ValidateRoute::spill_decode(&route.spill_encode())?;
+        assert_eq!(decoded.src_node, route.src_node);
+        assert!(decoded.via.is_empty());
+        Ok(())
+    }
+}
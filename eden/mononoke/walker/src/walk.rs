@@ -5,26 +5,30 @@
  * GNU General Public License version 2.
  */
 
+use crate::edge_list::EdgeListWriter;
 use crate::graph::{
-    AliasKey, ChangesetKey, EdgeType, FastlogKey, FileContentData, HashValidationError, Node,
-    NodeData, NodeType, PathKey, SqlShardInfo, UnodeFlags, UnodeKey, UnodeManifestEntry,
-    WrappedPath,
+    AliasKey, ChangesetKey, EdgeType, FastlogKey, FileContentData, GitTreeKey, HashValidationError,
+    Node, NodeData, NodeType, NodeTypeSet, PathKey, SqlShardInfo, UnitKey, UnodeFlags, UnodeKey,
+    UnodeManifestEntry, WrappedPath,
 };
 use crate::log;
-use crate::setup::JobWalkParams;
+use crate::resume::ResumeState;
+use crate::setup::{JobWalkParams, MaxOutgoingAction, TraversalOrder};
+use crate::spill::{FrontierSpillParams, SpillingFrontier};
 use crate::state::InternedType;
 use crate::validate::{add_node_to_scuba, CHECK_FAIL, CHECK_TYPE, EDGE_TYPE, ERROR_MSG};
 
 use anyhow::{format_err, Context, Error};
+use array_init::array_init;
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 use blame::BlameRoot;
 use blobrepo::BlobRepo;
 use blobrepo_hg::BlobRepoHg;
-use blobstore::{Loadable, LoadableError};
+use blobstore::{Blobstore, Loadable, LoadableError};
 use bonsai_hg_mapping::{BonsaiHgMapping, BonsaiHgMappingEntry};
 use bookmarks::{BookmarkKind, BookmarkName, BookmarkPagination, BookmarkPrefix, Freshness};
-use bounded_traversal::limited_by_key_shardable;
+use bounded_traversal::{limited_by_key_shardable_with_frontier, Frontier};
 use changeset_info::ChangesetInfo;
 use cloned::cloned;
 use context::CoreContext;
@@ -38,36 +42,75 @@ use filestore::{self, Alias};
 use fsnodes::RootFsnodeId;
 use futures::{
     future::{self, FutureExt, TryFutureExt},
-    stream::{Stream, TryStreamExt},
+    stream::{self, BoxStream, Stream, StreamExt, TryStreamExt},
 };
+use git_types::{Tree, TreeHandle};
 use itertools::{Either, Itertools};
 use manifest::{Entry, Manifest};
 use mercurial_derived_data::MappedHgChangesetId;
 use mercurial_types::{FileBytes, HgChangesetId, HgFileNodeId, HgManifestId, RepoPath};
 use mononoke_types::{
-    blame::BlameMaybeRejected, fsnode::FsnodeEntry, skeleton_manifest::SkeletonManifestEntry,
-    unode::UnodeEntry, BlameId, ChangesetId, ContentId, DeletedManifestId, FastlogBatchId,
-    FileUnodeId, FsnodeId, MPath, ManifestUnodeId, RepositoryId, SkeletonManifestId,
+    blame::BlameMaybeRejected,
+    fsnode::FsnodeEntry,
+    hash::{Sha1, Sha256},
+    skeleton_manifest::SkeletonManifestEntry,
+    unode::UnodeEntry,
+    BlameId, ChangesetId, ContentId, ContentMetadata, DeletedManifestId, FastlogBatch,
+    FastlogBatchId, FileUnodeId, FsnodeId, MPath, ManifestUnodeId, RepositoryId,
+    SkeletonManifestId,
 };
 use phases::{HeadsFetcher, Phase, Phases};
 use scuba_ext::MononokeScubaSampleBuilder;
+use serde::Serialize;
 use skeleton_manifest::RootSkeletonManifestId;
 use slog::{info, warn, Logger};
+use stats::prelude::*;
 use std::{
-    collections::{HashMap, HashSet},
-    fmt::Debug,
+    cmp::{self, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt::{self, Debug},
     iter::{IntoIterator, Iterator},
-    sync::Arc,
+    mem::size_of,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
+use strum::{EnumCount, IntoEnumIterator};
 use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use unodes::RootUnodeManifestId;
 
+define_stats! {
+    prefix = "mononoke.walker";
+    content_streams_high_water_mark: dynamic_singleton_counter("{}.content_streams.high_water_mark", (repo: String)),
+    truncated_edges: dynamic_timeseries("{}.truncated_edges.{}", (repo: String, node_type: &'static str); Rate, Sum),
+}
+
 pub trait StepRoute: Debug {
     /// Where we stepped from, useful for immediate reproductions with --walk-root
     fn source_node(&self) -> Option<&Node>;
 
     /// What the check thinks is an interesting node on the route to here (e.g. the affected changeset)
     fn via_node(&self) -> Option<&Node>;
+
+    /// Whether routes can be written out with spill_encode and read back with spill_decode,
+    /// so that the walk frontier can be spilled to disk
+    const SPILLABLE: bool = false;
+
+    fn spill_encode(&self) -> String {
+        String::new()
+    }
+
+    fn spill_decode(_s: &str) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Err(format_err!(
+            "{} can't be spilled",
+            std::any::type_name::<Self>()
+        ))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -80,6 +123,16 @@ impl StepRoute for EmptyRoute {
     fn via_node(&self) -> Option<&Node> {
         None
     }
+
+    const SPILLABLE: bool = true;
+
+    fn spill_encode(&self) -> String {
+        String::new()
+    }
+
+    fn spill_decode(_s: &str) -> Result<Self, Error> {
+        Ok(EmptyRoute())
+    }
 }
 
 // Holds type of edge and target Node that we want to load in next step(s)
@@ -113,6 +166,8 @@ impl OutgoingEdge {
 pub enum ErrorKind {
     #[error("Could not step to {1:?} via {2} in repo {0}")]
     NotTraversable(String, OutgoingEdge, String),
+    #[error("{0:?} has {1} outgoing edges, more than the limit of {2}")]
+    TooManyOutgoingEdges(Node, usize, usize),
 }
 
 // Simpler visitor trait used inside each step to decide
@@ -206,6 +261,13 @@ pub trait TailingWalkVisitor {
     fn end_chunks(&mut self, logger: &Logger, contiguous_bounds: bool) -> Result<(), Error>;
 
     fn num_deferred(&self) -> usize;
+
+    // Start recording visits so the walk can be resumed. Marks any previously visited nodes as done,
+    // and returns the edges that were still pending so they can be walked again.
+    fn start_resume_journal(&mut self, previous: Option<ResumeState>) -> Vec<OutgoingEdge>;
+
+    // Snapshot of the visits recorded since start_resume_journal, None if not recording.
+    fn resume_state(&self) -> Option<ResumeState>;
 }
 
 // Data found for this node, plus next steps
@@ -220,10 +282,59 @@ enum StepError {
     Missing(String),
     #[error("Hash validation failure: {0}")]
     HashValidationFailure(Error),
+    #[error("Parent linkage failure: {0}")]
+    ParentLinkageFailure(Error),
+    #[error("Hg to bonsai round trip failure: {0}")]
+    RoundTripFailure(Error),
+    #[error("Alias failure for {0}: {1}")]
+    AliasFailure(ContentId, AliasReport),
+    #[error("Mapping's changeset {0} does not exist")]
+    OrphanedMapping(ChangesetId),
+    #[error("Copyfrom chain failure for {0}: {1}")]
+    CopyfromChainFailure(HgFileNodeId, CopyfromChainFailure),
+    #[error("Fastlog chain failure for {0}: {1}")]
+    FastlogChainFailure(String, FastlogChainFailure),
+    #[error("Content size failure for {0}: {1}")]
+    ContentSizeFailure(ContentId, ContentSizeFailure),
+    #[error("Content metadata failure for {0}: {1}")]
+    ContentMetadataFailure(ContentId, MetadataDiscrepancy),
+    #[error("Draft changeset {0} has public-only derived data {1}")]
+    DraftDerivedDataFailure(ChangesetId, &'static str),
     #[error(transparent)]
     Other(#[from] Error),
 }
 
+// Why following a filenode's copyfrom sources didn't reach the end of the chain
+#[derive(Debug, Error)]
+enum CopyfromChainFailure {
+    #[error("copyfrom source {1} at {0} is missing")]
+    Dangling(RepoPath, HgFileNodeId),
+    #[error("copyfrom source {1} at {0} is already in the chain")]
+    Cycle(RepoPath, HgFileNodeId),
+    #[error("chain has more than {0} copyfrom links")]
+    TooLong(usize),
+}
+
+// Why loading a fastlog's previous batches didn't reach the root of the chain
+#[derive(Debug, Error)]
+enum FastlogChainFailure {
+    #[error("previous batch {0} is missing")]
+    Dangling(FastlogBatchId),
+    #[error("previous batch {0} is already in the chain")]
+    Cycle(FastlogBatchId),
+    #[error("chain has more than {0} previous batches")]
+    TooLong(usize),
+}
+
+// Why a content's streamed bytes couldn't be matched against its metadata
+#[derive(Debug, Error)]
+enum ContentSizeFailure {
+    #[error("metadata says {expected} bytes but {actual} were streamed")]
+    Mismatch { expected: u64, actual: u64 },
+    #[error("no metadata to check the {0} streamed bytes against")]
+    MissingMetadata(u64),
+}
+
 impl From<LoadableError> for StepError {
     fn from(error: LoadableError) -> Self {
         match error {
@@ -302,6 +413,7 @@ async fn published_bookmarks_step<V: VisitOne>(
 
 async fn bonsai_phase_step<V: VisitOne>(
     ctx: &CoreContext,
+    repo: &BlobRepo,
     checker: &Checker<V>,
     bcs_id: &ChangesetId,
 ) -> Result<StepOutput, StepError> {
@@ -310,11 +422,31 @@ async fn bonsai_phase_step<V: VisitOne>(
     } else {
         None
     };
+
+    // Fastlog is only derived for public commits, see bonsai_to_unode_mapping_step.
+    // Never derive here, it's the stored state that is being checked.
+    if checker.validate_draft_derived_data
+        && maybe_phase.is_none()
+        && is_derived::<RootFastlog>(ctx, repo, *bcs_id, false).await?
+    {
+        return Err(StepError::DraftDerivedDataFailure(
+            *bcs_id,
+            RootFastlog::NAME,
+        ));
+    }
+
+    let mut edges = vec![];
+    checker.add_edge(&mut edges, EdgeType::PhaseMappingToChangeset, || {
+        Node::Changeset(ChangesetKey {
+            inner: *bcs_id,
+            filenode_known_derived: false,
+        })
+    });
     Ok(StepOutput::Done(
         checker.step_data(NodeType::PhaseMapping, || {
             NodeData::PhaseMapping(maybe_phase)
         }),
-        vec![],
+        edges,
     ))
 }
 
@@ -417,6 +549,14 @@ async fn fastlog_dir_step<V: VisitOne>(
         }
     }
 
+    if let (Some(max_length), Some(log)) = (checker.fastlog_chain_max_length, &log) {
+        let start = match path {
+            Some(path) => format!("dir unode {} at {}", id.inner, path),
+            None => format!("dir unode {}", id.inner),
+        };
+        check_fastlog_chain(ctx, repo, max_length, start, log).await?;
+    }
+
     Ok(StepOutput::Done(
         checker.step_data(NodeType::FastlogDir, || NodeData::FastlogDir(log)),
         edges,
@@ -461,12 +601,52 @@ async fn fastlog_file_step<V: VisitOne>(
         }
     }
 
+    if let (Some(max_length), Some(log)) = (checker.fastlog_chain_max_length, &log) {
+        let start = match path {
+            Some(path) => format!("file unode {} at {}", id.inner, path),
+            None => format!("file unode {}", id.inner),
+        };
+        check_fastlog_chain(ctx, repo, max_length, start, log).await?;
+    }
+
     Ok(StepOutput::Done(
         checker.step_data(NodeType::FastlogFile, || NodeData::FastlogFile(log)),
         edges,
     ))
 }
 
+// The previous batches are loaded here rather than walked, so that a broken chain is
+// reported against the unode whose history it would corrupt.
+async fn check_fastlog_chain(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    max_length: usize,
+    start: String,
+    log: &FastlogBatch,
+) -> Result<(), StepError> {
+    let failure = |kind| StepError::FastlogChainFailure(start.clone(), kind);
+    // Batches are never shared between chains, so seeing one twice means a cycle
+    let mut seen = HashSet::new();
+    let mut pending: Vec<FastlogBatchId> = log.previous_batches().iter().cloned().collect();
+    while let Some(id) = pending.pop() {
+        if seen.len() >= max_length {
+            return Err(failure(FastlogChainFailure::TooLong(max_length)));
+        }
+        if !seen.insert(id) {
+            return Err(failure(FastlogChainFailure::Cycle(id)));
+        }
+        let batch = match id.load(ctx, repo.blobstore()).await {
+            Ok(batch) => batch,
+            Err(LoadableError::Missing(_)) => {
+                return Err(failure(FastlogChainFailure::Dangling(id)));
+            }
+            Err(LoadableError::Error(err)) => return Err(StepError::Other(err)),
+        };
+        pending.extend(batch.previous_batches().iter().cloned());
+    }
+    Ok(())
+}
+
 async fn bonsai_changeset_info_mapping_step<V: VisitOne>(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -507,6 +687,19 @@ async fn changeset_info_step<V: VisitOne>(
     let info = maybe_derived::<ChangesetInfo>(ctx, repo, bcs_id, enable_derive).await?;
 
     if let Some(info) = info {
+        if checker.validate_parent_linkage {
+            let bcs = bcs_id.load(ctx, repo.blobstore()).await?;
+            let info_parents: Vec<_> = info.parents().collect();
+            let bcs_parents: Vec<_> = bcs.parents().collect();
+            if info_parents != bcs_parents {
+                return Err(StepError::ParentLinkageFailure(format_err!(
+                    "ChangesetInfo for {} has parents {:?} but bonsai has {:?}",
+                    bcs_id,
+                    info_parents,
+                    bcs_parents
+                )));
+            }
+        }
         let mut edges = vec![];
         for parent_id in info.parents() {
             checker.add_edge(
@@ -537,6 +730,11 @@ async fn bonsai_changeset_step<V: VisitOne>(
 ) -> Result<StepOutput, StepError> {
     let bcs_id = &key.inner;
 
+    // Don't load or expand drafts, so nothing derived from them is walked either
+    if checker.public_only && !checker.is_public(ctx, bcs_id).await? {
+        return Ok(StepOutput::Done(NodeData::NotRequired, vec![]));
+    }
+
     // Get the data, and add direct file data for this bonsai changeset
     let bcs = bcs_id.load(ctx, repo.blobstore()).await?;
 
@@ -565,6 +763,10 @@ async fn bonsai_changeset_step<V: VisitOne>(
     checker.add_edge(&mut edges, EdgeType::ChangesetToFsnodeMapping, || {
         Node::FsnodeMapping(*bcs_id)
     });
+    // Git tree mapping is 1:1, from there it expands by directory like fsnodes
+    checker.add_edge(&mut edges, EdgeType::ChangesetToGitTreeMapping, || {
+        Node::GitTreeMapping(*bcs_id)
+    });
     // Skeleton manifest mapping is 1:1 but from their expands less than unodes
     checker.add_edge(
         &mut edges,
@@ -615,23 +817,278 @@ async fn file_content_step<V: VisitOne>(
     checker: &Checker<V>,
     id: ContentId,
 ) -> Result<StepOutput, StepError> {
-    let maybe_s = filestore::fetch(repo.get_blobstore(), ctx, &id.into()).await?;
-    let s = match maybe_s {
-        Some(s) => s.map_ok(FileBytes),
-        None => {
-            return Err(StepError::Missing(format!("missing content for {}", id)));
-        }
+    // Acquire before fetching so the walk waits rather than opening more streams
+    let permit = match &checker.content_stream_limiter {
+        Some(limiter) => Some(limiter.acquire(&ctx).await?),
+        None => None,
+    };
+    let content = match checker.content_buffer_max_bytes {
+        Some(max_bytes) => buffer_content(&ctx, repo, id, max_bytes).await?,
+        None => FileContentData::ContentStream(fetch_content(&ctx, repo, id).await?),
+    };
+    if checker.validate_content_size {
+        let content = match content {
+            // Buffered content is checked without reading it again, so is still
+            // available to later stages
+            FileContentData::Buffered(bytes) => {
+                compare_content_size(&ctx, repo, id, bytes.len() as u64).await?;
+                FileContentData::Buffered(bytes)
+            }
+            content => {
+                let s = content.into_stream().expect("content was just fetched");
+                FileContentData::Consumed(check_content_size(&ctx, repo, id, s).await?)
+            }
+        };
+        drop(permit);
+        return Ok(StepOutput::Done(
+            checker.step_data(NodeType::FileContent, || NodeData::FileContent(content)),
+            vec![],
+        ));
+    }
+    let content = match content {
+        FileContentData::ContentStream(s) => FileContentData::ContentStream(
+            s.map_ok(move |b| {
+                // The permit is released when the stream is dropped
+                let _permit = &permit;
+                b
+            })
+            .boxed(),
+        ),
+        // Nothing left to read
+        content => content,
     };
 
     // We don't force file loading here, content may not be needed
     Ok(StepOutput::Done(
-        checker.step_data(NodeType::FileContent, || {
-            NodeData::FileContent(FileContentData::ContentStream(Box::pin(s)))
-        }),
+        checker.step_data(NodeType::FileContent, || NodeData::FileContent(content)),
         vec![],
     ))
 }
 
+async fn fetch_content(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+) -> Result<BoxStream<'static, Result<FileBytes, Error>>, StepError> {
+    let s = filestore::fetch(repo.get_blobstore(), ctx.clone(), &id.into())
+        .await?
+        .ok_or_else(|| StepError::Missing(format!("missing content for {}", id)))?;
+    Ok(s.map_ok(FileBytes).boxed())
+}
+
+// Reads content into memory while it is no more than max_bytes, so that more than one
+// check can see it. Larger content is returned as a stream that replays the chunks
+// already read before continuing with the rest, so it is still only fetched once.
+async fn buffer_content(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+    max_bytes: usize,
+) -> Result<FileContentData, StepError> {
+    let mut s = fetch_content(ctx, repo, id).await?;
+    let mut chunks = vec![];
+    let mut num_bytes = 0;
+    while let Some(chunk) = s.try_next().await? {
+        num_bytes += chunk.size();
+        chunks.push(chunk);
+        if num_bytes > max_bytes {
+            let read = stream::iter(chunks.into_iter().map(Ok));
+            return Ok(FileContentData::ContentStream(read.chain(s).boxed()));
+        }
+    }
+    let bytes = match chunks.len() {
+        1 => chunks.pop().expect("one chunk").into_bytes(),
+        _ => chunks
+            .iter()
+            .map(|chunk| chunk.as_bytes().as_ref())
+            .collect::<Vec<_>>()
+            .concat()
+            .into(),
+    };
+    Ok(FileContentData::Buffered(bytes))
+}
+
+// Streams the whole content, so that truncated chunks are caught even when the
+// metadata itself is intact, and returns the number of bytes read.
+async fn check_content_size(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+    s: BoxStream<'static, Result<FileBytes, Error>>,
+) -> Result<usize, StepError> {
+    let actual = s
+        .try_fold(0u64, |acc, b| future::ok::<_, Error>(acc + b.size() as u64))
+        .await?;
+    compare_content_size(ctx, repo, id, actual).await
+}
+
+// Compares the number of bytes read from the content with its metadata
+async fn compare_content_size(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+    actual: u64,
+) -> Result<usize, StepError> {
+    // Read-only, as this is a check and shouldn't fill in missing metadata
+    let metadata = filestore::get_metadata_readonly(repo.blobstore(), ctx, &id.into()).await?;
+    match metadata {
+        // Empty files are checked the same way: their blob exists but streams no bytes
+        Some(Some(metadata)) if metadata.total_size == actual => Ok(actual as usize),
+        Some(Some(metadata)) => Err(StepError::ContentSizeFailure(
+            id,
+            ContentSizeFailure::Mismatch {
+                expected: metadata.total_size,
+                actual,
+            },
+        )),
+        Some(None) | None => Err(StepError::ContentSizeFailure(
+            id,
+            ContentSizeFailure::MissingMetadata(actual),
+        )),
+    }
+}
+
+/// How one alias mapping of a content compares to the content's metadata
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AliasStatus {
+    Ok,
+    Missing,
+    // Mapping exists but points at a different content
+    Wrong,
+}
+
+/// All the aliases of one content checked together, so a partial backfill shows as one record
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+struct AliasReport {
+    sha1: AliasStatus,
+    sha256: AliasStatus,
+    git_sha1: AliasStatus,
+}
+
+impl AliasReport {
+    fn is_ok(&self) -> bool {
+        [self.sha1, self.sha256, self.git_sha1]
+            .iter()
+            .all(|s| *s == AliasStatus::Ok)
+    }
+}
+
+impl fmt::Display for AliasReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
+
+async fn alias_status(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+    alias: Alias,
+) -> Result<AliasStatus, Error> {
+    match alias.load(ctx, repo.blobstore()).await {
+        Ok(content_id) if content_id == id => Ok(AliasStatus::Ok),
+        Ok(_) => Ok(AliasStatus::Wrong),
+        Err(LoadableError::Missing(_)) => Ok(AliasStatus::Missing),
+        Err(LoadableError::Error(e)) => Err(e),
+    }
+}
+
+async fn check_content_aliases(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+    metadata: &ContentMetadata,
+) -> Result<(), StepError> {
+    let (sha1, sha256, git_sha1) = future::try_join3(
+        alias_status(ctx, repo, id, Alias::Sha1(metadata.sha1)),
+        alias_status(ctx, repo, id, Alias::Sha256(metadata.sha256)),
+        alias_status(ctx, repo, id, Alias::GitSha1(metadata.git_sha1.sha1())),
+    )
+    .await?;
+    let report = AliasReport {
+        sha1,
+        sha256,
+        git_sha1,
+    };
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(StepError::AliasFailure(id, report))
+    }
+}
+
+/// A metadata field whose value differs from the one recomputed from the content
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+struct FieldMismatch {
+    metadata: String,
+    content: String,
+}
+
+impl FieldMismatch {
+    fn check<T: PartialEq + fmt::Display>(metadata: T, content: T) -> Option<Self> {
+        if metadata == content {
+            None
+        } else {
+            Some(Self {
+                metadata: metadata.to_string(),
+                content: content.to_string(),
+            })
+        }
+    }
+}
+
+/// The fields of a content's metadata that disagree with the content itself
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+struct MetadataDiscrepancy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_size: Option<FieldMismatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha1: Option<FieldMismatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<FieldMismatch>,
+}
+
+impl MetadataDiscrepancy {
+    fn new(metadata: &ContentMetadata, size: u64, sha1: Sha1, sha256: Sha256) -> Self {
+        Self {
+            total_size: FieldMismatch::check(metadata.total_size, size),
+            sha1: FieldMismatch::check(metadata.sha1, sha1),
+            sha256: FieldMismatch::check(metadata.sha256, sha256),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for MetadataDiscrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
+
+// Goes back from the metadata to the content it describes, so that metadata which drifted
+// from its content is caught, not just content that disagrees with its metadata.
+async fn check_content_metadata(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+    metadata: &ContentMetadata,
+) -> Result<(), StepError> {
+    let s = fetch_content(ctx, repo, id).await?;
+    let (size, sha1, sha256) = filestore::size_and_hashes(s.map_ok(FileBytes::into_bytes)).await?;
+    let discrepancy = MetadataDiscrepancy::new(metadata, size, sha1, sha256);
+    if discrepancy.is_ok() {
+        Ok(())
+    } else {
+        Err(StepError::ContentMetadataFailure(id, discrepancy))
+    }
+}
+
 async fn file_content_metadata_step<V: VisitOne>(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -649,6 +1106,12 @@ async fn file_content_metadata_step<V: VisitOne>(
 
     match metadata_opt {
         Some(Some(metadata)) => {
+            if checker.validate_content_aliases {
+                check_content_aliases(ctx, repo, id, &metadata).await?;
+            }
+            if checker.validate_content_metadata {
+                check_content_metadata(ctx, repo, id, &metadata).await?;
+            }
             let mut edges = vec![];
             checker.add_edge(&mut edges, EdgeType::FileContentMetadataToSha1Alias, || {
                 Node::AliasContentMapping(AliasKey(Alias::Sha1(metadata.sha1)))
@@ -812,6 +1275,12 @@ async fn hg_changeset_via_bonsai_step<'a, V: VisitOne>(
         return Ok(StepOutput::Deferred(bcs_id));
     }
 
+    if checker.validate_hg_bonsai_round_trip {
+        checker
+            .check_hg_bonsai_round_trip(ctx, &input_key.inner, bcs_id)
+            .await?;
+    }
+
     let bonsai_key = ChangesetKey {
         inner: bcs_id,
         filenode_known_derived: input_key.filenode_known_derived,
@@ -909,6 +1378,7 @@ async fn file_node_step_impl<V: VisitOne, F, D>(
     linknode_mapping_edge: EdgeType,
     parent_edge: EdgeType,
     copyfrom_edge: EdgeType,
+    copyfrom_chain_max_length: Option<usize>,
     build_file_node: F,
     build_data: D,
 ) -> Result<StepOutput, StepError>
@@ -929,6 +1399,18 @@ where
             return Ok(StepOutput::Deferred(bcs_id));
         }
 
+        if let Some(max_length) = copyfrom_chain_max_length {
+            check_copyfrom_chain(
+                &ctx,
+                repo,
+                max_length,
+                repo_path.clone(),
+                hg_file_node_id,
+                file_node_info.copyfrom.clone(),
+            )
+            .await?;
+        }
+
         // Validate hg link node
         checker.add_edge(&mut edges, linknode_edge, || {
             Node::HgChangesetViaBonsai(ChangesetKey {
@@ -973,6 +1455,44 @@ where
     ))
 }
 
+// The copyfrom sources are loaded here rather than walked, as the walk would only see a
+// cycle as a node it had already visited.
+async fn check_copyfrom_chain(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    max_length: usize,
+    repo_path: RepoPath,
+    hg_file_node_id: HgFileNodeId,
+    mut copyfrom: Option<(RepoPath, HgFileNodeId)>,
+) -> Result<(), StepError> {
+    let failure = |kind| StepError::CopyfromChainFailure(hg_file_node_id, kind);
+    let mut seen = HashSet::new();
+    seen.insert((repo_path, hg_file_node_id));
+    while let Some((source_path, source_id)) = copyfrom {
+        // seen holds the starting filenode plus one entry per link followed
+        if seen.len() > max_length {
+            return Err(failure(CopyfromChainFailure::TooLong(max_length)));
+        }
+        if !seen.insert((source_path.clone(), source_id)) {
+            return Err(failure(CopyfromChainFailure::Cycle(source_path, source_id)));
+        }
+        let source_info = repo
+            .get_filenode_opt(ctx.clone(), &source_path, source_id)
+            .await?
+            .do_not_handle_disabled_filenodes()?;
+        copyfrom = match source_info {
+            Some(source_info) => source_info.copyfrom,
+            None => {
+                return Err(failure(CopyfromChainFailure::Dangling(
+                    source_path,
+                    source_id,
+                )));
+            }
+        };
+    }
+    Ok(())
+}
+
 async fn hg_file_node_step<V: VisitOne>(
     ctx: CoreContext,
     repo: &BlobRepo,
@@ -995,6 +1515,7 @@ async fn hg_file_node_step<V: VisitOne>(
         EdgeType::HgFileNodeToLinkedHgBonsaiMapping,
         EdgeType::HgFileNodeToHgParentFileNode,
         EdgeType::HgFileNodeToHgCopyfromFileNode,
+        checker.copyfrom_chain_max_length,
         Node::HgFileNode,
         NodeData::HgFileNode,
     )
@@ -1023,6 +1544,7 @@ async fn hg_manifest_file_node_step<V: VisitOne>(
         EdgeType::HgManifestFileNodeToLinkedHgBonsaiMapping,
         EdgeType::HgManifestFileNodeToHgParentFileNode,
         EdgeType::HgManifestFileNodeToHgCopyfromFileNode,
+        None,
         Node::HgManifestFileNode,
         NodeData::HgManifestFileNode,
     )
@@ -1039,7 +1561,7 @@ async fn hg_manifest_step<V: VisitOne>(
     let hgmanifest = hg_manifest_id.load(ctx, repo.blobstore()).await?;
     let (manifests, filenodes): (Vec<_>, Vec<_>) =
         hgmanifest.list().partition_map(|(name, entry)| {
-            let path_opt = WrappedPath::from(Some(MPath::join_opt_element(path.as_ref(), &name)));
+            let path_opt = path.join_element(&name);
             match entry {
                 Entry::Leaf((_, filenode_id)) => Either::Right((path_opt, filenode_id)),
                 Entry::Tree(manifest_id) => Either::Left((path_opt, manifest_id)),
@@ -1186,11 +1708,7 @@ async fn fsnode_step<V: VisitOne>(
                     &mut dir_edges,
                     EdgeType::FsnodeToChildFsnode,
                     || Node::Fsnode(*fsnode_id),
-                    || {
-                        path.map(|p| {
-                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child)))
-                        })
-                    },
+                    || path.map(|p| p.join_element(child)),
                 );
             }
             FsnodeEntry::File(file) => {
@@ -1198,11 +1716,7 @@ async fn fsnode_step<V: VisitOne>(
                     &mut content_edges,
                     EdgeType::FsnodeToFileContent,
                     || Node::FileContent(*file.content_id()),
-                    || {
-                        path.map(|p| {
-                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child)))
-                        })
-                    },
+                    || path.map(|p| p.join_element(child)),
                 );
             }
         }
@@ -1421,11 +1935,7 @@ async fn unode_manifest_step<V: VisitOne>(
                     &mut edges,
                     EdgeType::UnodeManifestToUnodeManifestChild,
                     || Node::UnodeManifest(UnodeKey { inner: *id, flags }),
-                    || {
-                        path.map(|p| {
-                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child)))
-                        })
-                    },
+                    || path.map(|p| p.join_element(child)),
                 );
             }
             UnodeEntry::File(id) => {
@@ -1433,11 +1943,7 @@ async fn unode_manifest_step<V: VisitOne>(
                     &mut file_edges,
                     EdgeType::UnodeManifestToUnodeFileChild,
                     || Node::UnodeFile(UnodeKey { inner: *id, flags }),
-                    || {
-                        path.map(|p| {
-                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child)))
-                        })
-                    },
+                    || path.map(|p| p.join_element(child)),
                 );
             }
         }
@@ -1483,11 +1989,7 @@ async fn deleted_manifest_step<V: VisitOne>(
             &mut edges,
             EdgeType::DeletedManifestToDeletedManifestChild,
             || Node::DeletedManifest(*deleted_manifest_id),
-            || {
-                path.map(|p| {
-                    WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child_path)))
-                })
-            },
+            || path.map(|p| p.join_element(child_path)),
         );
     }
 
@@ -1533,6 +2035,73 @@ async fn deleted_manifest_mapping_step<V: VisitOne>(
     }
 }
 
+async fn git_tree_step<V: VisitOne>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    checker: &Checker<V>,
+    key: &GitTreeKey,
+    path: Option<&WrappedPath>,
+) -> Result<StepOutput, StepError> {
+    // The key only has the object id, not the full TreeHandle, so load via its blobstore key
+    let blobstore_key = TreeHandle::blobstore_key_for_sha1(&key.0);
+    let tree = match repo.blobstore().get(ctx, &blobstore_key).await? {
+        Some(bytes) => Tree::try_from(bytes.into_bytes())?,
+        None => return Err(StepError::Missing(blobstore_key)),
+    };
+    let mut edges = vec![];
+
+    for (child_path, entry) in tree.list() {
+        match entry {
+            Entry::Tree(subtree) => {
+                checker.add_edge_with_path(
+                    &mut edges,
+                    EdgeType::GitTreeToGitTreeChild,
+                    || Node::GitTree(GitTreeKey(subtree.oid().sha1())),
+                    || path.map(|p| p.join_element(&child_path)),
+                );
+            }
+            Entry::Leaf(_) => {}
+        }
+    }
+
+    Ok(StepOutput::Done(
+        checker.step_data(NodeType::GitTree, || NodeData::GitTree(Some(tree))),
+        edges,
+    ))
+}
+
+async fn git_tree_mapping_step<V: VisitOne>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    checker: &Checker<V>,
+    bcs_id: ChangesetId,
+    enable_derive: bool,
+) -> Result<StepOutput, StepError> {
+    let root_tree = maybe_derived::<TreeHandle>(ctx, repo, bcs_id, enable_derive).await?;
+
+    if let Some(root_tree) = root_tree {
+        let mut edges = vec![];
+
+        checker.add_edge_with_path(
+            &mut edges,
+            EdgeType::GitTreeMappingToRootGitTree,
+            || Node::GitTree(GitTreeKey(root_tree.oid().sha1())),
+            || Some(WrappedPath::Root),
+        );
+        Ok(StepOutput::Done(
+            checker.step_data(NodeType::GitTreeMapping, || {
+                NodeData::GitTreeMapping(Some(root_tree))
+            }),
+            edges,
+        ))
+    } else {
+        Ok(StepOutput::Done(
+            checker.step_data(NodeType::GitTreeMapping, || NodeData::GitTreeMapping(None)),
+            vec![],
+        ))
+    }
+}
+
 async fn skeleton_manifest_step<V: VisitOne>(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -1550,11 +2119,7 @@ async fn skeleton_manifest_step<V: VisitOne>(
                     &mut edges,
                     EdgeType::SkeletonManifestToSkeletonManifestChild,
                     || Node::SkeletonManifest(*subdir.id()),
-                    || {
-                        path.map(|p| {
-                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child_path)))
-                        })
-                    },
+                    || path.map(|p| p.join_element(child_path)),
                 );
             }
             SkeletonManifestEntry::File => {}
@@ -1629,11 +2194,129 @@ pub fn expand_checked_nodes(children: &mut Vec<OutgoingEdge>) -> () {
     }
 }
 
+// Bounds how many FileContent streams can be open at once, and records the peak seen
+struct ContentStreamLimiter {
+    semaphore: Arc<Semaphore>,
+    open: Arc<AtomicUsize>,
+    high_water_mark: AtomicUsize,
+    repo_name: String,
+}
+
+// Held by an open content stream, returning its slot to the limiter when dropped
+struct ContentStreamPermit {
+    _permit: OwnedSemaphorePermit,
+    open: Arc<AtomicUsize>,
+}
+
+impl Drop for ContentStreamPermit {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl ContentStreamLimiter {
+    fn new(max_open: usize, repo_name: String) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_open)),
+            open: Arc::new(AtomicUsize::new(0)),
+            high_water_mark: AtomicUsize::new(0),
+            repo_name,
+        }
+    }
+
+    async fn acquire(&self, ctx: &CoreContext) -> Result<ContentStreamPermit, Error> {
+        let permit = self.semaphore.clone().acquire_owned().await?;
+        let open = self.open.fetch_add(1, Ordering::AcqRel) + 1;
+        let previous_max = self.high_water_mark.fetch_max(open, Ordering::AcqRel);
+        if open > previous_max {
+            STATS::content_streams_high_water_mark.set_value(
+                ctx.fb,
+                open as i64,
+                (self.repo_name.clone(),),
+            );
+        }
+        Ok(ContentStreamPermit {
+            _permit: permit,
+            open: self.open.clone(),
+        })
+    }
+}
+
+/// Per NodeType counts of edges enqueued for walking but not yet stepped to.
+/// Used to see which types dominate the frontier when memory use is high.
+pub struct FrontierStats {
+    current: [AtomicUsize; NodeType::COUNT],
+    peak: [AtomicUsize; NodeType::COUNT],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrontierCount {
+    pub node_type: NodeType,
+    pub current: usize,
+    pub peak: usize,
+    /// Estimate of the memory held by the current edges
+    pub current_bytes: usize,
+}
+
+impl Default for FrontierStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrontierStats {
+    pub fn new() -> Self {
+        Self {
+            current: array_init(|_i| AtomicUsize::new(0)),
+            peak: array_init(|_i| AtomicUsize::new(0)),
+        }
+    }
+
+    fn enqueued(&self, t: NodeType) {
+        let current = self.current[t as usize].fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak[t as usize].fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn dequeued(&self, t: NodeType) {
+        self.current[t as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Counts for the types that have been enqueued at some point
+    pub fn snapshot(&self) -> Vec<FrontierCount> {
+        NodeType::iter()
+            .filter_map(|t| {
+                let peak = self.peak[t as usize].load(Ordering::Relaxed);
+                if peak == 0 {
+                    return None;
+                }
+                let current = self.current[t as usize].load(Ordering::Relaxed);
+                Some(FrontierCount {
+                    node_type: t,
+                    current,
+                    peak,
+                    current_bytes: current * size_of::<OutgoingEdge>(),
+                })
+            })
+            .collect()
+    }
+}
+
 struct Checker<V: VisitOne> {
     include_edge_types: HashSet<EdgeType>,
-    hash_validation_node_types: HashSet<NodeType>,
+    prune_node_types: NodeTypeSet,
+    hash_validation_node_types: NodeTypeSet,
+    validate_parent_linkage: bool,
+    validate_hg_bonsai_round_trip: bool,
+    validate_content_aliases: bool,
+    validate_content_size: bool,
+    validate_content_metadata: bool,
+    check_orphaned_mappings: bool,
+    validate_draft_derived_data: bool,
+    copyfrom_chain_max_length: Option<usize>,
+    fastlog_chain_max_length: Option<usize>,
+    public_only: bool,
     always_emit_edge_types: HashSet<EdgeType>,
-    required_node_data_types: HashSet<NodeType>,
+    required_node_data_types: NodeTypeSet,
     keep_edge_paths: bool,
     visitor: V,
     phases_store: Arc<dyn Phases>,
@@ -1642,6 +2325,15 @@ struct Checker<V: VisitOne> {
     with_blame: bool,
     with_fastlog: bool,
     with_filenodes: bool,
+    content_stream_limiter: Option<ContentStreamLimiter>,
+    content_buffer_max_bytes: Option<usize>,
+    sample_rate: Option<u64>,
+    validate_sample_rate: Option<u64>,
+    max_outgoing_per_node: Option<usize>,
+    max_outgoing_action: MaxOutgoingAction,
+    frontier_stats: Arc<FrontierStats>,
+    edge_list: Option<Arc<EdgeListWriter>>,
+    path_prefix: Option<MPath>,
 }
 
 impl<V: VisitOne> Checker<V> {
@@ -1683,6 +2375,32 @@ impl<V: VisitOne> Checker<V> {
             .await
     }
 
+    // Goes to the mapping store rather than the visitor's cache, as it is the
+    // stored mapping that is being checked
+    async fn check_hg_bonsai_round_trip(
+        &self,
+        ctx: &CoreContext,
+        hg_cs_id: &HgChangesetId,
+        bcs_id: ChangesetId,
+    ) -> Result<(), StepError> {
+        let round_trip: Vec<_> = self
+            .bonsai_hg_mapping
+            .get(ctx, self.repo_id, bcs_id.into())
+            .await?
+            .into_iter()
+            .map(|entry| entry.hg_cs_id)
+            .collect();
+        if round_trip != [*hg_cs_id] {
+            return Err(StepError::RoundTripFailure(format_err!(
+                "{} maps to {} which maps back to {:?}",
+                hg_cs_id,
+                bcs_id,
+                round_trip
+            )));
+        }
+        Ok(())
+    }
+
     // Convience method around make_edge
     fn add_edge<N>(&self, edges: &mut Vec<OutgoingEdge>, edge_type: EdgeType, node_fn: N)
     where
@@ -1714,9 +2432,15 @@ impl<V: VisitOne> Checker<V> {
     where
         N: FnOnce() -> Node,
     {
+        if self.prune_node_types.contains(edge_type.outgoing_type()) {
+            return None;
+        }
         let always_emit = self.always_emit_edge_types.contains(&edge_type);
         if always_emit || self.include_edge_types.contains(&edge_type) {
             let outgoing = OutgoingEdge::new(edge_type, node_fn());
+            if !in_path_prefix(self.path_prefix.as_ref(), &outgoing.target, None) {
+                return None;
+            }
             if always_emit || self.visitor.needs_visit(&outgoing) {
                 return Some(outgoing);
             }
@@ -1735,12 +2459,24 @@ impl<V: VisitOne> Checker<V> {
         N: FnOnce() -> Node,
         P: FnOnce() -> Option<WrappedPath>,
     {
+        if self.prune_node_types.contains(edge_type.outgoing_type()) {
+            return None;
+        }
         let always_emit = self.always_emit_edge_types.contains(&edge_type);
         if always_emit || self.include_edge_types.contains(&edge_type) {
+            let target = node_fn();
+            let path = if self.keep_edge_paths || self.path_prefix.is_some() {
+                path_fn()
+            } else {
+                None
+            };
+            if !in_path_prefix(self.path_prefix.as_ref(), &target, path.as_ref()) {
+                return None;
+            }
             let outgoing = if self.keep_edge_paths {
-                OutgoingEdge::new_with_path(edge_type, node_fn(), path_fn())
+                OutgoingEdge::new_with_path(edge_type, target, path)
             } else {
-                OutgoingEdge::new(edge_type, node_fn())
+                OutgoingEdge::new(edge_type, target)
             };
             if always_emit || self.visitor.needs_visit(&outgoing) {
                 return Some(outgoing);
@@ -1754,7 +2490,7 @@ impl<V: VisitOne> Checker<V> {
     where
         D: FnOnce() -> NodeData,
     {
-        if self.required_node_data_types.contains(&t) {
+        if self.required_node_data_types.contains(t) {
             data_fn()
         } else {
             NodeData::NotRequired
@@ -1762,6 +2498,181 @@ impl<V: VisitOne> Checker<V> {
     }
 }
 
+// Goes to the changesets table, as mappings are found by walking roots that need not
+// be reachable from any bookmark
+async fn check_mapping_not_orphaned(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: ChangesetId,
+) -> Result<(), StepError> {
+    if repo.changeset_exists_by_bonsai(ctx.clone(), bcs_id).await? {
+        Ok(())
+    } else {
+        Err(StepError::OrphanedMapping(bcs_id))
+    }
+}
+
+// Whether an edge to target stays within the path prefix. The path set on the edge is used
+// if there is one, else the target's own path. Targets that can't have a repo path, or
+// whose path isn't known here, are kept as they may lead to nodes under the prefix.
+fn in_path_prefix(prefix: Option<&MPath>, target: &Node, path: Option<&WrappedPath>) -> bool {
+    let prefix = match prefix {
+        Some(prefix) => prefix,
+        None => return true,
+    };
+    if !target.get_type().allow_repo_path() {
+        return true;
+    }
+    path.or_else(|| target.stats_path())
+        .map_or(true, |path| path.is_on_path_to(prefix))
+}
+
+// An edge waiting in the frontier, ordered by its target's sampling fingerprint. The sequence
+// number breaks ties in insertion order so the walk order is deterministic.
+struct FingerprintEntry<Route> {
+    fingerprint: u64,
+    seq: u64,
+    item: (Option<Route>, OutgoingEdge),
+}
+
+impl<Route> PartialEq for FingerprintEntry<Route> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl<Route> Eq for FingerprintEntry<Route> {}
+
+impl<Route> PartialOrd for FingerprintEntry<Route> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Route> Ord for FingerprintEntry<Route> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (self.fingerprint, self.seq).cmp(&(other.fingerprint, other.seq))
+    }
+}
+
+// Edges waiting to be stepped to, drained according to the TraversalOrder
+enum WalkFrontier<Route> {
+    Dfs(VecDeque<(Option<Route>, OutgoingEdge)>),
+    Bfs(VecDeque<(Option<Route>, OutgoingEdge)>),
+    LowestFingerprintFirst {
+        heap: BinaryHeap<Reverse<FingerprintEntry<Route>>>,
+        // Items that could not be scheduled yet go ahead of the heap
+        retry: VecDeque<(Option<Route>, OutgoingEdge)>,
+        next_seq: u64,
+    },
+    // Dfs or Bfs with overflow paged out to disk
+    Spilling(SpillingFrontier<Route>, FrontierError),
+}
+
+// The first error paging the frontier out or in. The Frontier trait can't return
+// errors, so it is kept here for the walk stream to fail with.
+type FrontierError = Arc<Mutex<Option<Error>>>;
+
+fn record_frontier_error(frontier_error: &FrontierError, error: Error) {
+    frontier_error
+        .lock()
+        .expect("lock poisoned")
+        .get_or_insert(error);
+}
+
+impl<Route> WalkFrontier<Route> {
+    fn new(order: TraversalOrder, roots: Vec<(Option<Route>, OutgoingEdge)>) -> Self {
+        match order {
+            TraversalOrder::Dfs => WalkFrontier::Dfs(roots.into()),
+            TraversalOrder::Bfs => WalkFrontier::Bfs(roots.into()),
+            TraversalOrder::LowestFingerprintFirst => {
+                let mut frontier = WalkFrontier::LowestFingerprintFirst {
+                    heap: BinaryHeap::new(),
+                    retry: VecDeque::new(),
+                    next_seq: 0,
+                };
+                for root in roots {
+                    frontier.push(root);
+                }
+                frontier
+            }
+        }
+    }
+
+    fn spilling(
+        order: TraversalOrder,
+        roots: Vec<(Option<Route>, OutgoingEdge)>,
+        params: FrontierSpillParams,
+        frontier_error: FrontierError,
+    ) -> Result<Self, Error>
+    where
+        Route: StepRoute,
+    {
+        let mut frontier = WalkFrontier::Spilling(
+            SpillingFrontier::new(order, params, Route::spill_encode, Route::spill_decode)?,
+            frontier_error,
+        );
+        for root in roots {
+            frontier.push(root);
+        }
+        Ok(frontier)
+    }
+}
+
+impl<Route> Frontier<(Option<Route>, OutgoingEdge)> for WalkFrontier<Route> {
+    fn push(&mut self, item: (Option<Route>, OutgoingEdge)) {
+        match self {
+            WalkFrontier::Dfs(queue) => queue.push_front(item),
+            WalkFrontier::Bfs(queue) => queue.push_back(item),
+            WalkFrontier::LowestFingerprintFirst { heap, next_seq, .. } => {
+                let fingerprint = item.1.target.sampling_fingerprint().unwrap_or(0);
+                heap.push(Reverse(FingerprintEntry {
+                    fingerprint,
+                    seq: *next_seq,
+                    item,
+                }));
+                *next_seq += 1;
+            }
+            WalkFrontier::Spilling(frontier, frontier_error) => {
+                if let Err(error) = frontier.push(item) {
+                    record_frontier_error(frontier_error, error);
+                }
+            }
+        }
+    }
+
+    fn push_retry(&mut self, item: (Option<Route>, OutgoingEdge)) {
+        match self {
+            WalkFrontier::Dfs(queue) | WalkFrontier::Bfs(queue) => queue.push_front(item),
+            WalkFrontier::LowestFingerprintFirst { retry, .. } => retry.push_front(item),
+            WalkFrontier::Spilling(frontier, _) => frontier.push_retry(item),
+        }
+    }
+
+    fn pop(&mut self) -> Option<(Option<Route>, OutgoingEdge)> {
+        match self {
+            WalkFrontier::Dfs(queue) | WalkFrontier::Bfs(queue) => queue.pop_front(),
+            WalkFrontier::LowestFingerprintFirst { heap, retry, .. } => retry
+                .pop_front()
+                .or_else(|| heap.pop().map(|Reverse(entry)| entry.item)),
+            WalkFrontier::Spilling(frontier, frontier_error) => {
+                frontier.pop().unwrap_or_else(|error| {
+                    record_frontier_error(frontier_error, error);
+                    None
+                })
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            WalkFrontier::Dfs(queue) | WalkFrontier::Bfs(queue) => queue.len(),
+            WalkFrontier::LowestFingerprintFirst { heap, retry, .. } => heap.len() + retry.len(),
+            WalkFrontier::Spilling(frontier, _) => frontier.len(),
+        }
+    }
+}
+
 // Parameters that vary per repo but can be setup in common conde
 #[derive(Clone)]
 pub struct RepoWalkParams {
@@ -1769,11 +2680,18 @@ pub struct RepoWalkParams {
     pub logger: Logger,
     pub scuba_builder: MononokeScubaSampleBuilder,
     pub scheduled_max: usize,
+    /// How many file content streams to drain at once
+    pub content_drain_max: usize,
     pub sql_shard_info: SqlShardInfo,
     pub walk_roots: Vec<OutgoingEdge>,
     pub include_node_types: HashSet<NodeType>,
     pub include_edge_types: HashSet<EdgeType>,
     pub hash_validation_node_types: HashSet<NodeType>,
+    /// Node types that are never stepped to, even if always emitted
+    pub prune_node_types: HashSet<NodeType>,
+    pub frontier_stats: Arc<FrontierStats>,
+    /// Where to record the edges followed, if anywhere
+    pub edge_list: Option<Arc<EdgeListWriter>>,
 }
 
 // Parameters that vary per repo but are set differently by scrub, validate etc.
@@ -1782,6 +2700,45 @@ pub struct RepoWalkTypeParams {
     pub always_emit_edge_types: HashSet<EdgeType>,
     pub required_node_data_types: HashSet<NodeType>,
     pub keep_edge_paths: bool,
+    /// Also walk from scratch bookmarks wherever the published bookmarks are walked from.
+    /// They don't count as public heads, so their changesets are still draft.
+    pub include_scratch_bookmarks: bool,
+}
+
+/// The bookmarks the PublishedBookmarks node steps to, and the public heads phases are worked
+/// out from. Scratch bookmarks are only ever stepped to, as their changesets are still draft.
+async fn list_walk_bookmarks(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    include_scratch_bookmarks: bool,
+) -> Result<(HashMap<BookmarkName, ChangesetId>, Vec<ChangesetId>), Error> {
+    let kinds = if include_scratch_bookmarks {
+        BookmarkKind::ALL
+    } else {
+        BookmarkKind::ALL_PUBLISHING
+    };
+    let bookmarks: Vec<_> = repo
+        .bookmarks()
+        .list(
+            ctx,
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            kinds,
+            &BookmarkPagination::FromStart,
+            std::u64::MAX,
+        )
+        .try_collect()
+        .await?;
+    let public_heads = bookmarks
+        .iter()
+        .filter(|(book, _)| book.kind != BookmarkKind::Scratch)
+        .map(|(_, csid)| *csid)
+        .collect();
+    let walked = bookmarks
+        .into_iter()
+        .map(|(book, csid)| (book.name, csid))
+        .collect();
+    Ok((walked, public_heads))
 }
 
 /// Walk the graph from one or more starting points,  providing stream of data for later reduction
@@ -1798,46 +2755,45 @@ where
     Route: 'static + Send + Clone + StepRoute,
 {
     // Build lookups
-    let published_bookmarks = repo_params
-        .repo
-        .bookmarks()
-        .list(
-            ctx.clone(),
-            Freshness::MostRecent,
-            &BookmarkPrefix::empty(),
-            BookmarkKind::ALL_PUBLISHING,
-            &BookmarkPagination::FromStart,
-            std::u64::MAX,
-        )
-        .map_ok(|(book, csid)| (book.name, csid))
-        .try_collect::<HashMap<_, _>>();
+    let bookmarks = list_walk_bookmarks(
+        ctx.clone(),
+        repo_params.repo.clone(),
+        type_params.include_scratch_bookmarks,
+    );
 
     // Roots were not stepped to from elsewhere, so their Option<Route> is None.
     let walk_roots: Vec<(Option<Route>, OutgoingEdge)> = repo_params
         .walk_roots
         .iter()
+        .filter(|e| {
+            !repo_params
+                .prune_node_types
+                .contains(&e.label.outgoing_type())
+        })
         .map(|e| (None, e.clone()))
         .collect();
+    for (_via, e) in &walk_roots {
+        repo_params.frontier_stats.enqueued(e.target.get_type());
+    }
 
     async move {
-        let published_bookmarks = Arc::new(published_bookmarks.await?);
-        let heads_fetcher: HeadsFetcher = Arc::new({
-            cloned!(published_bookmarks);
-            move |_ctx: &CoreContext| {
-                future::ok(published_bookmarks.iter().map(|(_, csid)| *csid).collect()).boxed()
-            }
-        });
+        let (published_bookmarks, public_heads) = bookmarks.await?;
+        let published_bookmarks = Arc::new(published_bookmarks);
+        let heads_fetcher: HeadsFetcher =
+            Arc::new(move |_ctx: &CoreContext| future::ok(public_heads.clone()).boxed());
 
         cloned!(
             repo_params.repo,
             repo_params.include_edge_types,
             repo_params.hash_validation_node_types,
             repo_params.include_node_types,
+            repo_params.prune_node_types,
             repo_params.sql_shard_info,
         );
 
-        let mut required_node_data_types = type_params.required_node_data_types;
-        required_node_data_types.extend(hash_validation_node_types.clone());
+        let hash_validation_node_types = NodeTypeSet::from(&hash_validation_node_types);
+        let required_node_data_types = NodeTypeSet::from(&type_params.required_node_data_types)
+            .union(&hash_validation_node_types);
         let checker = Arc::new(Checker {
             with_blame: repo_params.include_node_types.contains(&NodeType::Blame),
             with_fastlog: include_node_types
@@ -1848,7 +2804,18 @@ where
                     || e.outgoing_type() == NodeType::HgManifestFileNode
             }),
             include_edge_types,
+            prune_node_types: NodeTypeSet::from(&prune_node_types),
             hash_validation_node_types,
+            validate_parent_linkage: job_params.validate_parent_linkage,
+            validate_hg_bonsai_round_trip: job_params.validate_hg_bonsai_round_trip,
+            validate_content_aliases: job_params.validate_content_aliases,
+            validate_content_size: job_params.validate_content_size,
+            validate_content_metadata: job_params.validate_content_metadata,
+            check_orphaned_mappings: job_params.check_orphaned_mappings,
+            validate_draft_derived_data: job_params.validate_draft_derived_data,
+            copyfrom_chain_max_length: job_params.copyfrom_chain_max_length,
+            fastlog_chain_max_length: job_params.fastlog_chain_max_length,
+            public_only: job_params.public_only,
             always_emit_edge_types: type_params.always_emit_edge_types,
             keep_edge_paths: type_params.keep_edge_paths,
             visitor: visitor.clone(),
@@ -1860,12 +2827,44 @@ where
             ),
             bonsai_hg_mapping: repo.get_bonsai_hg_mapping().clone(),
             repo_id: repo.get_repoid(),
+            content_stream_limiter: job_params
+                .max_concurrent_content_streams
+                .map(|max_open| ContentStreamLimiter::new(max_open, repo.name().clone())),
+            content_buffer_max_bytes: job_params.content_buffer_max_bytes,
+            sample_rate: job_params.sample_rate,
+            validate_sample_rate: job_params.validate_sample_rate,
+            max_outgoing_per_node: job_params.max_outgoing_per_node,
+            max_outgoing_action: job_params.max_outgoing_action,
+            frontier_stats: repo_params.frontier_stats.clone(),
+            edge_list: repo_params.edge_list.clone(),
+            path_prefix: job_params.path_prefix.clone(),
         });
 
-        Ok(limited_by_key_shardable(
+        let frontier_error = FrontierError::default();
+        let frontier = match job_params.frontier_spill.clone() {
+            Some(spill_params) if Route::SPILLABLE => WalkFrontier::spilling(
+                job_params.traversal_order,
+                walk_roots,
+                spill_params,
+                frontier_error.clone(),
+            )?,
+            Some(_) => {
+                warn!(
+                    repo_params.logger,
+                    "This walk's routes can't be spilled, keeping the frontier in memory"
+                );
+                WalkFrontier::new(job_params.traversal_order, walk_roots)
+            }
+            None => WalkFrontier::new(job_params.traversal_order, walk_roots),
+        };
+
+        let walk = limited_by_key_shardable_with_frontier(
             repo_params.scheduled_max,
-            walk_roots,
+            frontier,
             move |(via, walk_item): (Option<Route>, OutgoingEdge)| {
+                repo_params
+                    .frontier_stats
+                    .dequeued(walk_item.target.get_type());
                 cloned!(repo_params.sql_shard_info);
                 let shard_key = walk_item.target.sql_shard(&sql_shard_info);
                 let ctx = if let Some(ctx) =
@@ -1914,21 +2913,37 @@ where
             move |(_route, edge)| {
                 (
                     &edge.target,
-                    sql_shard_info
-                        .active_keys_per_shard
-                        .as_ref()
-                        .and_then(|per_shard| {
-                            edge.target
-                                .sql_shard(&sql_shard_info)
-                                .map(|v| (v, *per_shard))
-                        }),
+                    edge.target.sql_shard(&sql_shard_info).and_then(|shard| {
+                        sql_shard_info
+                            .active_keys_limit(&shard)
+                            .map(|per_shard| (shard, per_shard))
+                    }),
                 )
             },
-        ))
+        );
+        Ok(fail_on_frontier_error(walk, frontier_error))
     }
     .try_flatten_stream()
 }
 
+// Once the frontier has failed the walk can't continue in order without the lost edges,
+// so fail the stream, including if the failure was what made it end
+fn fail_on_frontier_error<T>(
+    walk: impl Stream<Item = Result<T, Error>>,
+    frontier_error: FrontierError,
+) -> impl Stream<Item = Result<T, Error>> {
+    let take_error = move || frontier_error.lock().expect("lock poisoned").take();
+    let end_take_error = take_error.clone();
+    walk.map(move |res| match take_error() {
+        Some(error) => Err(error),
+        None => res,
+    })
+    .chain(
+        stream::once(future::lazy(move |_| end_take_error()))
+            .filter_map(|error| future::ready(error.map(Err))),
+    )
+}
+
 async fn walk_one<V, VOut, Route>(
     ctx: CoreContext,
     via: Option<Route>,
@@ -1958,6 +2973,9 @@ where
     if via.is_none() {
         // record stats for the walk_roots
         visitor.visit(&ctx, walk_item.clone(), None, None, vec![walk_item.clone()]);
+        if let Some(edge_list) = checker.edge_list.as_ref() {
+            edge_list.record(&Node::Root(UnitKey()).stats_key(), &walk_item)?;
+        }
     }
 
     let step_result = match walk_item.target.clone() {
@@ -1979,7 +2997,7 @@ where
         Node::BonsaiHgMapping(bcs_id) => {
             bonsai_to_hg_mapping_step(&ctx, &repo, &checker, bcs_id, enable_derive).await
         }
-        Node::PhaseMapping(bcs_id) => bonsai_phase_step(&ctx, &checker, &bcs_id).await,
+        Node::PhaseMapping(bcs_id) => bonsai_phase_step(&ctx, &repo, &checker, &bcs_id).await,
         Node::PublishedBookmarks(_) => {
             published_bookmarks_step(published_bookmarks.clone(), &checker).await
         }
@@ -2045,6 +3063,12 @@ where
         Node::FsnodeMapping(bcs_id) => {
             bonsai_to_fsnode_mapping_step(&ctx, &repo, &checker, bcs_id, enable_derive).await
         }
+        Node::GitTree(key) => {
+            git_tree_step(&ctx, &repo, &checker, &key, walk_item.path.as_ref()).await
+        }
+        Node::GitTreeMapping(bcs_id) => {
+            git_tree_mapping_step(&ctx, &repo, &checker, bcs_id, enable_derive).await
+        }
         Node::SkeletonManifest(id) => {
             skeleton_manifest_step(&ctx, &repo, &checker, &id, walk_item.path.as_ref()).await
         }
@@ -2062,13 +3086,34 @@ where
         }
     };
 
+    // An orphaned mapping is reported in place of whatever its step found
+    let step_result = match walk_item.target.mapped_changeset() {
+        Some(bcs_id) if checker.check_orphaned_mappings => {
+            check_mapping_not_orphaned(&ctx, &repo, bcs_id)
+                .await
+                .and(step_result)
+        }
+        _ => step_result,
+    };
+
     let edge_label = walk_item.label;
     let node_type = walk_item.target.get_type();
 
+    let in_sample = checker
+        .sample_rate
+        .map_or(true, |sample_rate| walk_item.target.in_sample(sample_rate));
+
     // Run hash validation if needed
     let step_result = match step_result {
+        // Outside the sample we only want the edges
+        Ok(StepOutput::Done(_node_data, children)) if !in_sample => {
+            Ok(StepOutput::Done(NodeData::NotRequired, children))
+        }
         Ok(StepOutput::Done(node_data, children)) => {
-            if checker.hash_validation_node_types.contains(&node_type) {
+            let in_validate_sample = checker
+                .validate_sample_rate
+                .map_or(true, |sample_rate| walk_item.target.in_sample(sample_rate));
+            if in_validate_sample && checker.hash_validation_node_types.contains(node_type) {
                 let f = walk_item
                     .target
                     .validate_hash(ctx.clone(), repo.clone(), &node_data);
@@ -2110,6 +3155,15 @@ where
             let check_type = match e {
                 StepError::Missing(_) => "missing",
                 StepError::HashValidationFailure(_) => "hash_validation_failure",
+                StepError::ParentLinkageFailure(_) => "parent_linkage_failure",
+                StepError::RoundTripFailure(_) => "round_trip_failure",
+                StepError::AliasFailure(..) => "alias_failure",
+                StepError::OrphanedMapping(_) => "orphaned_mapping",
+                StepError::CopyfromChainFailure(..) => "copyfrom_chain_failure",
+                StepError::FastlogChainFailure(..) => "fastlog_chain_failure",
+                StepError::ContentSizeFailure(..) => "content_size_failure",
+                StepError::ContentMetadataFailure(..) => "content_metadata_failure",
+                StepError::DraftDerivedDataFailure(..) => "draft_derived_data_failure",
                 StepError::Other(_) => "step",
             };
 
@@ -2134,6 +3188,42 @@ where
                             NodeData::HashValidationFailureAsData(walk_item.target.clone()),
                             vec![],
                         )),
+                        StepError::ParentLinkageFailure(_s) => Ok(StepOutput::Done(
+                            NodeData::ParentLinkageFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::RoundTripFailure(_s) => Ok(StepOutput::Done(
+                            NodeData::RoundTripFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::AliasFailure(..) => Ok(StepOutput::Done(
+                            NodeData::AliasFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::OrphanedMapping(_) => Ok(StepOutput::Done(
+                            NodeData::OrphanedMappingAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::CopyfromChainFailure(..) => Ok(StepOutput::Done(
+                            NodeData::CopyfromChainFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::FastlogChainFailure(..) => Ok(StepOutput::Done(
+                            NodeData::FastlogChainFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::ContentSizeFailure(..) => Ok(StepOutput::Done(
+                            NodeData::ContentSizeFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::DraftDerivedDataFailure(..) => Ok(StepOutput::Done(
+                            NodeData::DraftDerivedDataFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
+                        StepError::ContentMetadataFailure(..) => Ok(StepOutput::Done(
+                            NodeData::ContentMetadataFailureAsData(walk_item.target.clone()),
+                            vec![],
+                        )),
                         StepError::Other(_e) => Ok(StepOutput::Done(
                             NodeData::ErrorAsData(walk_item.target.clone()),
                             vec![],
@@ -2151,12 +3241,46 @@ where
         ErrorKind::NotTraversable(repo.name().clone(), walk_item.clone(), format!("{:?}", via))
     })?;
 
+    let source_key = checker
+        .edge_list
+        .as_ref()
+        .map(|_| walk_item.target.stats_key());
     let (vout, via, next) = match step_output {
         StepOutput::Deferred(bcs_id) => {
             let (vout, via) = visitor.defer_visit(&bcs_id, &walk_item, via)?;
             (vout, via, vec![])
         }
-        StepOutput::Done(node_data, children) => {
+        StepOutput::Done(node_data, mut children) => {
+            // Bound the frontier from any one node
+            if let Some(max) = checker
+                .max_outgoing_per_node
+                .filter(|max| children.len() > *max)
+            {
+                match checker.max_outgoing_action {
+                    MaxOutgoingAction::Fail => {
+                        return Err(ErrorKind::TooManyOutgoingEdges(
+                            walk_item.target,
+                            children.len(),
+                            max,
+                        )
+                        .into());
+                    }
+                    MaxOutgoingAction::Truncate => {
+                        let truncated = children.len() - max;
+                        warn!(
+                            logger,
+                            "Truncating {} of {} outgoing edges from {:?}",
+                            truncated,
+                            children.len(),
+                            walk_item.target
+                        );
+                        STATS::truncated_edges
+                            .add_value(truncated as i64, (repo.name().clone(), node_type.into()));
+                        children.truncate(max);
+                    }
+                }
+            }
+
             // make sure steps are valid.  would be nice if this could be static
             for c in &children {
                 if c.label.outgoing_type() != c.target.get_type() {
@@ -2174,7 +3298,358 @@ where
             visitor.visit(&ctx, walk_item, Some(node_data), via, children)
         }
     };
+    for e in &next {
+        checker.frontier_stats.enqueued(e.target.get_type());
+    }
+    if let (Some(edge_list), Some(source_key)) = (checker.edge_list.as_ref(), source_key) {
+        for e in &next {
+            edge_list.record(&source_key, e)?;
+        }
+    }
     let via = Some(via);
     let next = next.into_iter().map(move |e| (via.clone(), e));
     Ok(Some((vout, next)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bookmarks::BookmarkUpdateReason;
+    use maplit::hashmap;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_frontier_spill_failure_fails_walk() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let frontier_error = FrontierError::default();
+        // Spill files can't be created in a missing directory
+        let params = FrontierSpillParams {
+            max_in_memory: 1,
+            dir: dir.path().join("missing"),
+        };
+        let mut frontier = WalkFrontier::<EmptyRoute>::spilling(
+            TraversalOrder::Dfs,
+            vec![],
+            params,
+            frontier_error.clone(),
+        )?;
+        for i in 0..3u8 {
+            let id = HgFileNodeId::from_str(&format!("{:040x}", i))?;
+            frontier.push((
+                None,
+                OutgoingEdge::new(
+                    EdgeType::HgManifestToHgFileEnvelope,
+                    Node::HgFileEnvelope(id),
+                ),
+            ));
+        }
+        assert!(frontier_error.lock().unwrap().is_some());
+
+        // The error fails the walk rather than it ending early as if complete
+        let walk = stream::iter(vec![Ok(1), Ok(2)]);
+        let res: Vec<_> = fail_on_frontier_error(walk, frontier_error.clone())
+            .collect()
+            .await;
+        assert_eq!(res.len(), 2);
+        assert!(res[0].is_err());
+        assert_eq!(res[1].as_ref().ok(), Some(&2));
+
+        // and the same if it is only found once the walk has ended
+        frontier.push((
+            None,
+            OutgoingEdge::new(
+                EdgeType::HgManifestToHgFileEnvelope,
+                Node::HgFileEnvelope(HgFileNodeId::from_str(&format!("{:040x}", 3))?),
+            ),
+        ));
+        let walk = stream::iter(Vec::<Result<u32, Error>>::new());
+        let res: Vec<_> = fail_on_frontier_error(walk, frontier_error).collect().await;
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_frontier_stats() {
+        let stats = FrontierStats::new();
+        assert!(stats.snapshot().is_empty());
+
+        stats.enqueued(NodeType::Fsnode);
+        stats.enqueued(NodeType::Fsnode);
+        stats.enqueued(NodeType::FileContent);
+        stats.dequeued(NodeType::Fsnode);
+
+        assert_eq!(
+            stats.snapshot(),
+            vec![
+                FrontierCount {
+                    node_type: NodeType::FileContent,
+                    current: 1,
+                    peak: 1,
+                    current_bytes: size_of::<OutgoingEdge>(),
+                },
+                FrontierCount {
+                    node_type: NodeType::Fsnode,
+                    current: 1,
+                    peak: 2,
+                    current_bytes: size_of::<OutgoingEdge>(),
+                },
+            ]
+        );
+    }
+
+    #[fbinit::test]
+    async fn test_list_walk_bookmarks(fb: fbinit::FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let main = BookmarkName::new("main")?;
+        let scratch = BookmarkName::new("scratch/branch")?;
+        let public_cs = ChangesetId::from_bytes([1; 32])?;
+        let draft_cs = ChangesetId::from_bytes([2; 32])?;
+        let mut txn = repo.update_bookmark_transaction(ctx.clone());
+        txn.force_set(&main, public_cs, BookmarkUpdateReason::TestMove, None)?;
+        txn.create_scratch(&scratch, draft_cs)?;
+        txn.commit().await?;
+
+        let (walked, public_heads) = list_walk_bookmarks(ctx.clone(), repo.clone(), false).await?;
+        assert_eq!(walked, hashmap! { main.clone() => public_cs });
+        assert_eq!(public_heads, vec![public_cs]);
+
+        // Listing unreachable keys walks the scratch bookmark too, so its commit's manifests
+        // are reached rather than reported, but it is still not a public head
+        let (walked, public_heads) = list_walk_bookmarks(ctx, repo, true).await?;
+        assert_eq!(walked, hashmap! { main => public_cs, scratch => draft_cs });
+        assert_eq!(public_heads, vec![public_cs]);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_content_stream_limiter(fb: fbinit::FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let limiter = ContentStreamLimiter::new(2, "repo".to_string());
+        let first = limiter.acquire(&ctx).await?;
+        let second = limiter.acquire(&ctx).await?;
+        assert_eq!(limiter.high_water_mark.load(Ordering::Acquire), 2);
+
+        // At the cap, the next stream waits for an open one to be consumed
+        assert!(limiter.acquire(&ctx).now_or_never().is_none());
+        drop(first);
+        let third = limiter
+            .acquire(&ctx)
+            .now_or_never()
+            .expect("a slot was freed")?;
+        assert_eq!(limiter.open.load(Ordering::Acquire), 2);
+
+        // The peak is kept once streams are consumed
+        drop(second);
+        drop(third);
+        assert_eq!(limiter.open.load(Ordering::Acquire), 0);
+        assert_eq!(limiter.high_water_mark.load(Ordering::Acquire), 2);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_buffer_content(fb: fbinit::FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let contents = bytes::Bytes::from("hello world!");
+        // Stored in chunks, so buffering has to join them
+        let config = filestore::FilestoreConfig {
+            chunk_size: Some(5),
+            concurrency: 1,
+        };
+        let metadata = filestore::store(
+            repo.blobstore(),
+            config,
+            &ctx,
+            &filestore::StoreRequest::new(contents.len() as u64),
+            stream::once(future::ok(contents.clone())),
+        )
+        .await?;
+        let id = metadata.content_id;
+
+        let content = buffer_content(&ctx, &repo, id, contents.len()).await?;
+        assert_eq!(content.known_size(), Some(contents.len()));
+        match content {
+            FileContentData::Buffered(bytes) => assert_eq!(bytes, contents),
+            other => panic!("Expected buffered content, got {:?}", other),
+        }
+
+        // Over the limit it is streamed, including the chunks read while checking
+        let content = buffer_content(&ctx, &repo, id, 6).await?;
+        assert_eq!(content.known_size(), None);
+        let streamed: Vec<u8> = content
+            .into_stream()
+            .expect("content not consumed")
+            .try_fold(vec![], |mut acc, b| {
+                acc.extend_from_slice(b.as_bytes());
+                future::ok(acc)
+            })
+            .await?;
+        assert_eq!(streamed, contents.as_ref());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_check_content_metadata(fb: fbinit::FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty()?;
+        let contents = bytes::Bytes::from("hello world!");
+        let metadata = filestore::store(
+            repo.blobstore(),
+            filestore::FilestoreConfig::default(),
+            &ctx,
+            &filestore::StoreRequest::new(contents.len() as u64),
+            stream::once(future::ok(contents.clone())),
+        )
+        .await?;
+        let id = metadata.content_id;
+        check_content_metadata(&ctx, &repo, id, &metadata).await?;
+
+        // Metadata that drifted from its content, only the fields that differ are reported
+        let drifted = ContentMetadata {
+            total_size: 5,
+            sha1: Sha1::from_byte_array([1; 20]),
+            ..metadata.clone()
+        };
+        match check_content_metadata(&ctx, &repo, id, &drifted).await {
+            Err(StepError::ContentMetadataFailure(failed_id, discrepancy)) => {
+                assert_eq!(failed_id, id);
+                assert!(discrepancy.sha256.is_none());
+                assert_eq!(
+                    discrepancy.to_string(),
+                    format!(
+                        r#"{{"total_size":{{"metadata":"5","content":"12"}},"sha1":{{"metadata":"{}","content":"{}"}}}}"#,
+                        drifted.sha1, metadata.sha1
+                    )
+                );
+            }
+            other => panic!("Expected content metadata failure, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_alias_report() {
+        let report = AliasReport {
+            sha1: AliasStatus::Ok,
+            sha256: AliasStatus::Missing,
+            git_sha1: AliasStatus::Wrong,
+        };
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            r#"{"sha1":"ok","sha256":"missing","git_sha1":"wrong"}"#
+        );
+        let report = AliasReport {
+            sha256: AliasStatus::Ok,
+            git_sha1: AliasStatus::Ok,
+            ..report
+        };
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_in_path_prefix() -> Result<(), Error> {
+        let prefix = MPath::new("fbcode/eden")?;
+        let path =
+            |p: &str| -> Result<WrappedPath, Error> { Ok(WrappedPath::from(MPath::new_opt(p)?)) };
+        let manifest = |p: &str| -> Result<Node, Error> {
+            Ok(Node::HgManifest(PathKey::new(
+                HgManifestId::from_str("e797dcabdd6d16ec4ae614165178b60d7054305b")?,
+                path(p)?,
+            )))
+        };
+
+        // No prefix keeps everything
+        assert!(in_path_prefix(None, &manifest("www")?, None));
+
+        let prefix = Some(&prefix);
+        // Path-less nodes are always kept to reach the nodes under the prefix
+        let changeset = NodeType::Changeset
+            .parse_node("b847b8838bfe3ae13ea6f8ce2e341c51193587b8392494f6dbab7224b3b116bf")?;
+        assert!(in_path_prefix(prefix, &changeset, None));
+
+        // Manifests on the way down to the prefix and under it are kept
+        assert!(in_path_prefix(prefix, &manifest("")?, None));
+        assert!(in_path_prefix(prefix, &manifest("fbcode")?, None));
+        assert!(in_path_prefix(prefix, &manifest("fbcode/eden")?, None));
+        assert!(in_path_prefix(
+            prefix,
+            &manifest("fbcode/eden/mononoke")?,
+            None
+        ));
+        // Others are pruned
+        assert!(!in_path_prefix(prefix, &manifest("www")?, None));
+        assert!(!in_path_prefix(prefix, &manifest("fbcode/edenapi")?, None));
+
+        // The path on the edge is used for nodes with no path of their own
+        let content = NodeType::FileContent
+            .parse_node("b847b8838bfe3ae13ea6f8ce2e341c51193587b8392494f6dbab7224b3b116bf")?;
+        assert!(in_path_prefix(prefix, &content, None));
+        assert!(in_path_prefix(
+            prefix,
+            &content,
+            Some(&path("fbcode/eden/README")?)
+        ));
+        assert!(!in_path_prefix(
+            prefix,
+            &content,
+            Some(&path("www/README")?)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_frontier_order() -> Result<(), Error> {
+        let edges = [
+            "b847b8838bfe3ae13ea6f8ce2e341c51193587b8392494f6dbab7224b3b116bf",
+            "1111111111111111111111111111111111111111111111111111111111111111",
+            "2222222222222222222222222222222222222222222222222222222222222222",
+            "3333333333333333333333333333333333333333333333333333333333333333",
+        ]
+        .iter()
+        .map(|id| {
+            let target = NodeType::Changeset.parse_node(id)?;
+            Ok(OutgoingEdge::new(EdgeType::BookmarkToChangeset, target))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+        let drain = |order: TraversalOrder| {
+            let mut frontier = WalkFrontier::<()>::new(order, vec![(None, edges[0].clone())]);
+            for edge in &edges[1..] {
+                frontier.push((None, edge.clone()));
+            }
+            assert_eq!(frontier.len(), edges.len());
+            let mut drained = vec![];
+            while let Some((_route, edge)) = frontier.pop() {
+                drained.push(edge);
+            }
+            drained
+        };
+
+        let mut dfs = edges[1..].to_vec();
+        dfs.reverse();
+        dfs.push(edges[0].clone());
+        assert_eq!(drain(TraversalOrder::Dfs), dfs);
+
+        assert_eq!(drain(TraversalOrder::Bfs), edges);
+
+        let mut by_fingerprint = edges.clone();
+        by_fingerprint.sort_by_key(|e| e.target.sampling_fingerprint());
+        assert_eq!(
+            drain(TraversalOrder::LowestFingerprintFirst),
+            by_fingerprint
+        );
+
+        // Retried items go ahead of the rest
+        let mut frontier = WalkFrontier::<()>::new(TraversalOrder::LowestFingerprintFirst, vec![]);
+        frontier.push((None, by_fingerprint[0].clone()));
+        frontier.push_retry((None, by_fingerprint[1].clone()));
+        assert_eq!(
+            frontier.pop().map(|(_, e)| e),
+            Some(by_fingerprint[1].clone())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use cpython::*;
+
+py_class!(pub class cancellationtoken |py| {
+    data flag: Arc<AtomicBool>;
+
+    def __new__(_cls) -> PyResult<Self> {
+        Self::create_instance(py, Arc::new(AtomicBool::new(false)))
+    }
+
+    /// cancel()
+    ///
+    /// Ask fetches using this token to stop at the next entry. Safe to call
+    /// from another thread (e.g. a signal handler) while a fetch is running.
+    def cancel(&self) -> PyResult<PyNone> {
+        self.flag(py).store(true, Ordering::Relaxed);
+        Ok(PyNone)
+    }
+
+    def cancelled(&self) -> PyResult<bool> {
+        Ok(self.flag(py).load(Ordering::Relaxed))
+    }
+});
+
+impl cancellationtoken {
+    /// The shared flag, for polling without holding the GIL.
+    pub fn shared_flag(&self, py: Python) -> Arc<AtomicBool> {
+        self.flag(py).clone()
+    }
+}
@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::num::NonZeroU64;
 use std::sync::Arc;
 use std::time::Duration;
@@ -22,6 +24,7 @@ use cpython_ext::ResultPyErrExt;
 use edenapi::Builder;
 use edenapi::EdenApi;
 use edenapi_types::AnyFileContentId;
+use edenapi_types::BonsaiChangesetId;
 use edenapi_types::CommitGraphEntry;
 use edenapi_types::CommitHashLookupResponse;
 use edenapi_types::CommitHashToLocationResponse;
@@ -52,7 +55,9 @@ use revisionstore::EdenApiTreeStore;
 use types::HgId;
 use types::RepoPathBuf;
 
+use crate::cancel::cancellationtoken;
 use crate::pyext::EdenApiPyExt;
+use crate::stats::downloadstats;
 use crate::stats::stats;
 
 // Python wrapper around an EdenAPI client.
@@ -62,6 +67,8 @@ use crate::stats::stats;
 // the `EdenApiPyExt` trait.
 py_class!(pub class client |py| {
     data inner: Arc<dyn EdenApi>;
+    // Capabilities don't change during a session, so are only fetched once per repo.
+    data capabilities_cache: RefCell<HashMap<String, Vec<String>>>;
 
     def __new__(
         _cls,
@@ -75,26 +82,46 @@ py_class!(pub class client |py| {
             .build()
             .map_pyerr(py)?;
 
-        client::create_instance(py, inner)
+        client::create_instance(py, inner, RefCell::new(HashMap::new()))
     }
 
     def health(&self) -> PyResult<PyDict> {
         self.inner(py).clone().health_py(py)
     }
 
+    /// capabilities(repo: str) -> [str]
+    ///
+    /// Optional features the server supports for the repo, such as
+    /// "segmented-changelog". Fetched on first use and cached for the
+    /// lifetime of the client.
     def capabilities(&self, repo: String) -> PyResult<Vec<String>> {
+        if let Some(caps) = self.capabilities_cache(py).borrow().get(&repo) {
+            return Ok(caps.clone());
+        }
         let client = self.inner(py).clone();
         let caps = py
             .allow_threads(|| {
-                block_unless_interrupted(async move {
-                    client.capabilities(repo).await
+                block_unless_interrupted({
+                    let repo = repo.clone();
+                    async move { client.capabilities(repo).await }
                 })
             })
             .map_pyerr(py)?
             .map_pyerr(py)?;
+        self.capabilities_cache(py)
+            .borrow_mut()
+            .insert(repo, caps.clone());
         Ok(caps)
     }
 
+    /// downloadstats() -> downloadstats
+    ///
+    /// Bytes downloaded and requests sent by this client so far for each
+    /// kind of data fetched.
+    def downloadstats(&self) -> PyResult<downloadstats> {
+        downloadstats::new(py, self.inner(py).download_stats())
+    }
+
     def files(
         &self,
         repo: String,
@@ -103,6 +130,19 @@ py_class!(pub class client |py| {
         self.inner(py).clone().files_py(py, repo, keys)
     }
 
+    /// get_files_git(repo: str, keys: [(path: str, git_sha1: str)]) -> [FileEntry]
+    ///
+    /// Fetch files addressed by git sha1 hex rather than hg node. The git sha1
+    /// is used as the node, so this only works for repos that store git sha1s
+    /// as their node ids.
+    def get_files_git(
+        &self,
+        repo: String,
+        keys: Vec<(PyPathBuf, String)>
+    ) -> PyResult<TStream<anyhow::Result<Serde<FileEntry>>>> {
+        self.inner(py).clone().files_git_py(py, repo, keys)
+    }
+
     def filesattrs(
         &self,
         repo: String,
@@ -126,14 +166,30 @@ py_class!(pub class client |py| {
         self.inner(py).clone().history_py(py, repo, keys, length)
     }
 
+    /// get_history_entries(repo: str, keys: [(path: str, node: bytes)], depth: int = None)
+    ///     -> [(path: str, node: bytes, p1: bytes, p2: bytes, linknode: bytes, copyfrom: str | None)]
+    ///
+    /// Fetch file history as plain tuples rather than a stream of entries, for
+    /// tooling that analyzes history without storing it. Missing parents are
+    /// null nodes. `depth` limits how many entries are fetched per file.
+    def get_history_entries(
+        &self,
+        repo: String,
+        keys: Vec<(PyPathBuf, Serde<HgId>)>,
+        depth: Option<u32> = None
+    ) -> PyResult<Vec<(PyPathBuf, PyBytes, PyBytes, PyBytes, PyBytes, Option<PyPathBuf>)>> {
+        self.inner(py).clone().history_entries_py(py, repo, keys, depth)
+    }
+
     def storetrees(
         &self,
         store: PyObject,
         repo: String,
         keys: Vec<(PyPathBuf, Serde<HgId>)>,
-        attributes: Option<Serde<TreeAttributes>> = None
+        attributes: Option<Serde<TreeAttributes>> = None,
+        cancel: Option<cancellationtoken> = None
     ) -> PyResult<stats> {
-        self.inner(py).clone().storetrees_py(py, store, repo, keys, attributes.map(|a| a.0))
+        self.inner(py).clone().storetrees_py(py, store, repo, keys, attributes.map(|a| a.0), cancel)
     }
 
     def trees(
@@ -457,6 +513,21 @@ py_class!(pub class client |py| {
             .map_pyerr(py)
             .map(|responses| Serde(responses.into_iter().map(|r| r.mutation).collect()))
     }
+
+    /// Look up the bonsai changeset ids for the given hg commits.
+    /// Returns a list of (hgid, bonsai) pairs, with bonsai set to None for
+    /// commits the server has no mapping for.
+    def get_bonsai_mappings(
+        &self,
+        repo: String,
+        hgids: Serde<Vec<HgId>>,
+    ) -> PyResult<Serde<Vec<(HgId, Option<BonsaiChangesetId>)>>> {
+        let inner = self.inner(py).clone();
+        py.allow_threads(|| block_unless_interrupted(inner.commit_bonsai_mappings(repo, hgids.0)))
+            .map_pyerr(py)?
+            .map_pyerr(py)
+            .map(|responses| Serde(responses.into_iter().map(|r| (r.hgid, r.bonsai)).collect()))
+    }
 });
 
 impl ExtractInnerRef for client {
@@ -11,6 +11,7 @@
 
 use cpython::*;
 
+mod cancel;
 mod client;
 mod pyext;
 mod pytypes;
@@ -24,5 +25,7 @@ pub fn init_module(py: Python, package: &str) -> PyResult<PyModule> {
     let m = PyModule::new(py, &name)?;
     m.add_class::<client::client>(py)?;
     m.add_class::<stats::stats>(py)?;
+    m.add_class::<stats::downloadstats>(py)?;
+    m.add_class::<cancel::cancellationtoken>(py)?;
     Ok(m)
 }
@@ -8,6 +8,8 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use anyhow::bail;
@@ -56,6 +58,7 @@ use edenapi_types::TreeEntry;
 use edenapi_types::UploadHgChangeset;
 use edenapi_types::UploadSnapshotResponse;
 use edenapi_types::UploadToken;
+use edenapi_types::WireHistoryEntry;
 use futures::prelude::*;
 use futures::stream;
 use progress_model::ProgressBar;
@@ -65,14 +68,17 @@ use revisionstore::HgIdMutableDeltaStore;
 use revisionstore::StoreKey;
 use revisionstore::StoreResult;
 use types::HgId;
+use types::Key;
 use types::RepoPathBuf;
 
+use crate::cancel::cancellationtoken;
 use crate::pytypes::PyStats;
 use crate::stats::stats;
 use crate::util::as_deltastore;
 use crate::util::meta_to_dict;
 use crate::util::to_contentid;
 use crate::util::to_keys;
+use crate::util::to_keys_from_git_sha1;
 use crate::util::to_keys_with_parents;
 use crate::util::to_path;
 use crate::util::to_trees_upload_items;
@@ -105,6 +111,21 @@ pub trait EdenApiPyExt: EdenApi {
         Ok(entries.map_ok(Serde).map_err(Into::into).into())
     }
 
+    fn files_git_py(
+        self: Arc<Self>,
+        py: Python,
+        repo: String,
+        keys: Vec<(PyPathBuf, String)>,
+    ) -> PyResult<TStream<anyhow::Result<Serde<FileEntry>>>> {
+        let keys = to_keys_from_git_sha1(py, &keys)?;
+        let entries = py
+            .allow_threads(|| block_unless_interrupted(self.files(repo, keys)))
+            .map_pyerr(py)?
+            .map_pyerr(py)?
+            .entries;
+        Ok(entries.map_ok(Serde).map_err(Into::into).into())
+    }
+
     fn history_py(
         self: Arc<Self>,
         py: Python,
@@ -121,6 +142,52 @@ pub trait EdenApiPyExt: EdenApi {
         Ok(entries.map_ok(Serde).map_err(Into::into).into())
     }
 
+    /// Fetch history and collect it as `(path, node, p1, p2, linknode, copyfrom)`
+    /// tuples, so callers can inspect it without writing it to a store.
+    fn history_entries_py(
+        self: Arc<Self>,
+        py: Python,
+        repo: String,
+        keys: Vec<(PyPathBuf, Serde<HgId>)>,
+        length: Option<u32>,
+    ) -> PyResult<
+        Vec<(
+            PyPathBuf,         /* path */
+            PyBytes,           /* node */
+            PyBytes,           /* p1 */
+            PyBytes,           /* p2 */
+            PyBytes,           /* linknode */
+            Option<PyPathBuf>, /* copyfrom */
+        )>,
+    > {
+        let keys = to_keys(py, &keys)?;
+        let entries = py
+            .allow_threads(|| {
+                block_unless_interrupted(async move {
+                    self.history(repo, keys, length).await?.flatten().await
+                })
+            })
+            .map_pyerr(py)?
+            .map_pyerr(py)?;
+
+        Ok(entries
+            .into_iter()
+            .map(|entry| {
+                let path = PyPathBuf::from(entry.key.path.clone());
+                let wire = WireHistoryEntry::from(entry);
+                let (p1, p2) = wire.parents.into_nodes();
+                (
+                    path,
+                    PyBytes::new(py, wire.node.as_ref()),
+                    PyBytes::new(py, p1.as_ref()),
+                    PyBytes::new(py, p2.as_ref()),
+                    PyBytes::new(py, wire.linknode.as_ref()),
+                    wire.copyfrom.map(PyPathBuf::from),
+                )
+            })
+            .collect())
+    }
+
     fn storetrees_py(
         self: Arc<Self>,
         py: Python,
@@ -128,11 +195,13 @@ pub trait EdenApiPyExt: EdenApi {
         repo: String,
         keys: Vec<(PyPathBuf, Serde<HgId>)>,
         attributes: Option<TreeAttributes>,
+        cancel: Option<cancellationtoken>,
     ) -> PyResult<stats> {
         let keys = to_keys(py, &keys)?;
         let store = as_deltastore(py, store)?;
+        let cancel = cancel.map(|c| c.shared_flag(py));
 
-        let stats = py
+        let (stats, failed) = py
             .allow_threads(|| {
                 block_unless_interrupted(async move {
                     let prog = ProgressBar::register_new(
@@ -141,13 +210,16 @@ pub trait EdenApiPyExt: EdenApi {
                         "trees",
                     );
                     let response = self.trees(repo, keys, attributes).await?;
-                    write_trees(response, store, prog).await
+                    write_trees(response, store, prog, cancel).await
                 })
             })
             .map_pyerr(py)?
             .map_pyerr(py)?;
 
-        stats::new(py, stats)
+        match stats {
+            Some(stats) => stats::with_failed(py, stats, failed),
+            None => stats::cancelled_with_failed(py, failed),
+        }
     }
 
     fn trees_py(
@@ -831,14 +903,34 @@ pub trait EdenApiPyExt: EdenApi {
 
 impl<T: EdenApi + ?Sized> EdenApiPyExt for T {}
 
+/// Write the fetched trees to the store. Trees that the server failed to
+/// return are collected rather than ending the fetch, so they can be retried.
+/// Returns None for the stats if cancelled, as they are only known once the
+/// response completes. Dropping the response aborts the remaining requests.
 async fn write_trees(
     mut response: Response<Result<TreeEntry, EdenApiServerError>>,
     store: Arc<dyn HgIdMutableDeltaStore>,
     prog: Arc<ProgressBar>,
-) -> Result<Stats, EdenApiError> {
-    while let Some(Ok(entry)) = response.entries.try_next().await? {
-        store.add_tree(&entry)?;
-        prog.increase_position(1);
+    cancel: Option<Arc<AtomicBool>>,
+) -> Result<(Option<Stats>, Vec<Key>), EdenApiError> {
+    let mut failed = Vec::new();
+    let is_cancelled = || {
+        cancel
+            .as_ref()
+            .map_or(false, |flag| flag.load(Ordering::Relaxed))
+    };
+    while let Some(entry) = response.entries.try_next().await? {
+        if is_cancelled() {
+            return Ok((None, failed));
+        }
+        match entry {
+            Ok(entry) => {
+                store.add_tree(&entry)?;
+                prog.increase_position(1);
+            }
+            Err(EdenApiServerError { key: Some(key), .. }) => failed.push(key),
+            Err(e) => return Err(e.into()),
+        }
     }
-    response.stats.await
+    Ok((Some(response.stats.await?), failed))
 }
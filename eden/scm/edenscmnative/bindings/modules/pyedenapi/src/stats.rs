@@ -6,10 +6,19 @@
  */
 
 use cpython::*;
+use cpython_ext::convert::Serde;
+use cpython_ext::PyPathBuf;
+use edenapi::CategoryStats;
+use edenapi::DownloadStats;
+use edenapi::FetchCategory;
 use edenapi::Stats;
+use types::HgId;
+use types::Key;
 
 py_class!(pub class stats |py| {
     data stats: Stats;
+    data failed: Vec<Key>;
+    data was_cancelled: bool;
 
     def __str__(&self) -> PyResult<String> {
         Ok(self.stats(py).to_string())
@@ -42,10 +51,76 @@ py_class!(pub class stats |py| {
     def bytes_per_second(&self) -> PyResult<f64> {
         Ok(self.stats(py).bytes_per_second())
     }
+
+    /// failed() -> [(path: str, node: bytes)]
+    ///
+    /// Keys that the server failed to return, so they can be retried.
+    def failed(&self) -> PyResult<Vec<(PyPathBuf, Serde<HgId>)>> {
+        Ok(self
+            .failed(py)
+            .iter()
+            .map(|key| (key.path.clone().into(), Serde(key.hgid)))
+            .collect())
+    }
+
+    /// cancelled() -> bool
+    ///
+    /// Whether the fetch was cancelled before completing. Transfer stats of a
+    /// cancelled fetch are not available, so are all zero.
+    def cancelled(&self) -> PyResult<bool> {
+        Ok(*self.was_cancelled(py))
+    }
 });
 
 impl stats {
     pub fn new(py: Python, stats: Stats) -> PyResult<Self> {
+        Self::with_failed(py, stats, Vec::new())
+    }
+
+    pub fn with_failed(py: Python, stats: Stats, failed: Vec<Key>) -> PyResult<Self> {
+        Self::create_instance(py, stats, failed, false)
+    }
+
+    pub fn cancelled_with_failed(py: Python, failed: Vec<Key>) -> PyResult<Self> {
+        Self::create_instance(py, Stats::default(), failed, true)
+    }
+}
+
+py_class!(pub class downloadstats |py| {
+    data stats: DownloadStats;
+
+    /// downloaded(category: str) -> int
+    ///
+    /// Bytes downloaded for the category, one of "files", "history" or "trees".
+    def downloaded(&self, category: &str) -> PyResult<usize> {
+        Ok(self.category(py, category)?.downloaded)
+    }
+
+    /// requests(category: str) -> int
+    ///
+    /// Requests sent for the category, one of "files", "history" or "trees".
+    def requests(&self, category: &str) -> PyResult<usize> {
+        Ok(self.category(py, category)?.requests)
+    }
+});
+
+impl downloadstats {
+    pub fn new(py: Python, stats: DownloadStats) -> PyResult<Self> {
         Self::create_instance(py, stats)
     }
+
+    fn category(&self, py: Python, category: &str) -> PyResult<CategoryStats> {
+        let category = match category {
+            "files" => FetchCategory::Files,
+            "history" => FetchCategory::History,
+            "trees" => FetchCategory::Trees,
+            _ => {
+                return Err(PyErr::new::<exc::ValueError, _>(
+                    py,
+                    format!("unknown download category: {}", category),
+                ));
+            }
+        };
+        Ok(self.stats(py).get(category).clone())
+    }
 }
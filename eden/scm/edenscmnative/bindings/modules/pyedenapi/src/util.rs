@@ -15,6 +15,7 @@ use cpython_ext::PyPathBuf;
 use cpython_ext::ResultPyErrExt;
 use edenapi::ResponseMeta;
 use edenapi_types::ContentId;
+use edenapi_types::Sha1;
 use edenapi_types::UploadTreeEntry;
 use pyrevisionstore::mutabledeltastore;
 use revisionstore::HgIdMutableDeltaStore;
@@ -40,6 +41,15 @@ pub fn to_key(py: Python, path: &PyPath, hgid: HgId) -> PyResult<Key> {
     Ok(Key::new(path, hgid))
 }
 
+/// Build a key for content addressed by git sha1. There is no git sha1 to hg
+/// node lookup, the git sha1 is carried in place of the hg node, so this only
+/// finds files in repos that store git sha1s as their node ids, as git-backed
+/// repos do. Malformed hex raises the hash parsing error.
+pub fn to_key_from_git_sha1(py: Python, path: &PyPath, git_sha1: &str) -> PyResult<Key> {
+    let sha1: Sha1 = git_sha1.parse().map_pyerr(py)?;
+    to_key(py, path, HgId::from_byte_array(sha1.into_byte_array()))
+}
+
 pub fn to_key_with_parents(
     py: Python,
     path: &PyPath,
@@ -74,6 +84,15 @@ pub fn to_keys<'a>(
         .collect()
 }
 
+pub fn to_keys_from_git_sha1<'a>(
+    py: Python,
+    keys: impl IntoIterator<Item = &'a (PyPathBuf, String)>,
+) -> PyResult<Vec<Key>> {
+    keys.into_iter()
+        .map(|(path, git_sha1)| to_key_from_git_sha1(py, path, git_sha1))
+        .collect()
+}
+
 pub fn to_keys_with_parents<'a>(
     py: Python,
     keys: impl IntoIterator<Item = &'a (PyPathBuf, Serde<HgId>, Serde<HgId>, Serde<HgId>)>,
@@ -111,3 +130,32 @@ pub fn meta_to_dict(py: Python, meta: &ResponseMeta) -> PyResult<PyDict> {
     dict.set_item(py, "content_encoding", &meta.content_encoding)?;
     Ok(dict)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_key_from_git_sha1() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let path = PyPath::from_str("dir/file");
+        let hex = "e797dcabdd6d16ec4ae614165178b60d7054305b";
+
+        let key = to_key_from_git_sha1(py, path, hex).unwrap();
+        assert_eq!(key.hgid.to_hex(), hex);
+        assert_eq!(key.path.as_str(), "dir/file");
+
+        // Too short, and not hex, raise the parsing error rather than panicking
+        for malformed in &["e797dcab", "z797dcabdd6d16ec4ae614165178b60d7054305b"] {
+            let mut err = to_key_from_git_sha1(py, path, malformed).unwrap_err();
+            let msg = err
+                .instance(py)
+                .str(py)
+                .unwrap()
+                .to_string_lossy(py)
+                .into_owned();
+            assert!(msg.contains("is not a 20-byte hex string"), "{}", msg);
+        }
+    }
+}
@@ -50,6 +50,10 @@ enum class RustFileType {
 template<typename T = void, typename E = void>
 struct RustResult;
 
+/// The outcome of uploading files. Keys whose content the server did not
+/// accept are listed as failed, the rest were uploaded.
+struct RustFileUploadOutcome;
+
 struct RustString;
 
 template<typename T = void>
@@ -69,6 +73,18 @@ struct RustKey {
   uint8_t hgid[20];
 };
 
+/// A file to upload: the key it is stored under and its content, both
+/// borrowed from the caller for the duration of the call.
+struct RustFileUpload {
+  RustKey key;
+  const uint8_t *data;
+  size_t data_len;
+};
+
+struct RustFileUploadFetch {
+  RustResult<RustFileUploadOutcome, RustError> *ptr;
+};
+
 struct RustTreeAttributes {
   bool manifest_blob;
   bool parents;
@@ -131,6 +147,23 @@ RustTreeEntryFetch rust_edenapi_trees_blocking(RustClient *client,
                                                size_t keys_len,
                                                RustTreeAttributes attrs);
 
+/// Upload the content of `files`, returning how many were uploaded and the
+/// keys of any that failed. Content the server already has counts as uploaded.
+RustFileUploadFetch rust_edenapi_upload_files_blocking(RustClient *client,
+                                                       const uint8_t *repo,
+                                                       size_t repo_len,
+                                                       const RustFileUpload *files,
+                                                       size_t files_len);
+
+/// Methods for FileUploadOutcome
+size_t rust_fileuploadoutcome_uploaded_files(const RustFileUploadOutcome *o);
+
+size_t rust_fileuploadoutcome_uploaded_bytes(const RustFileUploadOutcome *o);
+
+size_t rust_fileuploadoutcome_failed_len(const RustFileUploadOutcome *o);
+
+const RustApiKey *rust_fileuploadoutcome_failed_get(const RustFileUploadOutcome *o, size_t idx);
+
 /// Methods for ApiKey
 RustHgId rust_key_get_hgid(const RustApiKey *k);
 
@@ -198,6 +231,8 @@ void rust_edenapiclient_free(RustEdenApiClient v);
 
 void rust_treeentryfetch_free(RustTreeEntryFetch v);
 
+void rust_fileuploadfetch_free(RustFileUploadFetch v);
+
 uintptr_t rust_ownedstring_len(const RustOwnedString *s);
 
 const uint8_t *rust_ownedstring_ptr(const RustOwnedString *s);
@@ -236,6 +271,14 @@ RustOwnedString rust_result_treechildentry_err_display(const RustResult<RustTree
 
 RustOwnedString rust_result_treechildentry_err_debug(const RustResult<RustTreeChildEntry, RustEdenApiServerError> *r);
 
+const RustFileUploadOutcome *rust_result_fileuploadoutcome_ok(const RustResult<RustFileUploadOutcome, RustError> *r);
+
+bool rust_result_fileuploadoutcome_is_err(const RustResult<RustFileUploadOutcome, RustError> *r);
+
+RustOwnedString rust_result_fileuploadoutcome_err_display(const RustResult<RustFileUploadOutcome, RustError> *r);
+
+RustOwnedString rust_result_fileuploadoutcome_err_debug(const RustResult<RustFileUploadOutcome, RustError> *r);
+
 size_t rust_vec_treeentry_len(const RustVec<RustResult<RustTreeEntry, RustEdenApiServerError>> *v);
 
 const RustResult<RustTreeEntry, RustEdenApiServerError> *rust_vec_treeentry_get(const RustVec<RustResult<RustTreeEntry, RustEdenApiServerError>> *v,
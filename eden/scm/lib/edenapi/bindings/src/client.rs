@@ -5,21 +5,33 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use anyhow::Context;
 use anyhow::Error;
+use async_runtime::block_unless_interrupted;
 use edenapi::BlockingResponse;
 use edenapi::Builder;
 use edenapi::EdenApi;
+use edenapi_ext::calc_contentid;
+use edenapi_types::AnyFileContentId;
+use edenapi_types::AnyId;
 use edenapi_types::EdenApiServerError;
 use edenapi_types::TreeEntry;
+use futures::StreamExt;
 use libc::size_t;
+use minibytes::Bytes;
 use types::Key as ApiKey;
 
 use crate::ptr_len_to_slice;
 use crate::types::TreeAttributes;
 use crate::EdenApiClient;
+use crate::FileUpload;
+use crate::FileUploadFetch;
+use crate::FileUploadOutcome;
 use crate::Key;
 use crate::TreeEntryFetch;
 
@@ -80,3 +92,80 @@ pub extern "C" fn rust_edenapi_trees_blocking(
 ) -> TreeEntryFetch {
     edenapi_trees_blocking(client, repo, repo_len, keys, keys_len, attrs).into()
 }
+
+fn edenapi_upload_files_blocking(
+    client: *mut Client,
+    repo: *const u8,
+    repo_len: size_t,
+    files: *const FileUpload,
+    files_len: size_t,
+) -> Result<FileUploadOutcome, Error> {
+    assert!(!client.is_null());
+    let client: &Client = unsafe { &*client };
+    let repo = unsafe { ptr_len_to_slice(repo, repo_len) }?;
+    let repo: &str = std::str::from_utf8(repo)?;
+    let files: &[FileUpload] = unsafe { std::slice::from_raw_parts(files, files_len) };
+    let files = files
+        .iter()
+        .map(|f| {
+            let (key, data) = f.to_owned_parts()?;
+            let content_id = calc_contentid(&data);
+            Ok((key, content_id, Bytes::from(data)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    // Each distinct content is uploaded once, however many keys share it
+    let mut upload_data = BTreeMap::new();
+    for (_key, content_id, data) in &files {
+        upload_data
+            .entry(*content_id)
+            .or_insert_with(|| data.clone());
+    }
+    let upload_data = upload_data
+        .into_iter()
+        .map(|(content_id, data)| (AnyFileContentId::ContentId(content_id), data))
+        .collect();
+
+    // Collect every entry rather than stopping at the first error, so the
+    // contents that were uploaded can be told apart from those that were not.
+    let response =
+        block_unless_interrupted(client.process_files_upload(repo.to_string(), upload_data, None))
+            .context("transfer interrupted by user")??;
+    let entries: Vec<_> = block_unless_interrupted(response.entries.collect::<Vec<_>>())
+        .context("transfer interrupted by user")?;
+    let uploaded: HashSet<_> = entries
+        .into_iter()
+        .filter_map(|entry| match entry.ok()?.data.id {
+            AnyId::AnyFileContentId(AnyFileContentId::ContentId(id)) => Some(id),
+            _ => None,
+        })
+        .collect();
+
+    let mut outcome = FileUploadOutcome {
+        uploaded_files: 0,
+        uploaded_bytes: 0,
+        failed: Vec::new(),
+    };
+    for (key, content_id, data) in files {
+        if uploaded.contains(&content_id) {
+            outcome.uploaded_files += 1;
+            outcome.uploaded_bytes += data.len();
+        } else {
+            outcome.failed.push(key);
+        }
+    }
+    Ok(outcome)
+}
+
+/// Upload the content of `files`, returning how many were uploaded and the
+/// keys of any that failed. Content the server already has counts as uploaded.
+#[no_mangle]
+pub extern "C" fn rust_edenapi_upload_files_blocking(
+    client: *mut Client,
+    repo: *const u8,
+    repo_len: size_t,
+    files: *const FileUpload,
+    files_len: size_t,
+) -> FileUploadFetch {
+    edenapi_upload_files_blocking(client, repo, repo_len, files, files_len).into()
+}
@@ -23,13 +23,16 @@ pub use crate::opaque::ApiKey;
 pub use crate::opaque::EdenApiError;
 pub use crate::opaque::EdenApiServerError;
 pub use crate::opaque::FileMetadata;
+pub use crate::opaque::FileUploadOutcome;
 pub use crate::opaque::TreeChildEntry;
 pub use crate::opaque::TreeEntry;
 pub use crate::owned::EdenApiClient;
+pub use crate::owned::FileUploadFetch;
 pub use crate::owned::OwnedString;
 pub use crate::owned::TreeEntryFetch;
 pub use crate::types::ContentId;
 pub use crate::types::FileType;
+pub use crate::types::FileUpload;
 pub use crate::types::HgId;
 pub use crate::types::Key;
 pub use crate::types::Parents;
@@ -21,6 +21,14 @@ pub use edenapi_types::TreeEntry;
 use libc::size_t;
 pub use types::Key as ApiKey;
 
+/// The outcome of uploading files. Keys whose content the server did not
+/// accept are listed as failed, the rest were uploaded.
+pub struct FileUploadOutcome {
+    pub uploaded_files: usize,
+    pub uploaded_bytes: usize,
+    pub failed: Vec<ApiKey>,
+}
+
 use crate::ContentId;
 use crate::FileType;
 use crate::HgId;
@@ -28,6 +36,35 @@ use crate::Parents;
 use crate::Sha1;
 use crate::Sha256;
 
+/// Methods for FileUploadOutcome
+#[no_mangle]
+pub extern "C" fn rust_fileuploadoutcome_uploaded_files(o: *const FileUploadOutcome) -> size_t {
+    assert!(!o.is_null());
+    let o = unsafe { &*o };
+    o.uploaded_files
+}
+#[no_mangle]
+pub extern "C" fn rust_fileuploadoutcome_uploaded_bytes(o: *const FileUploadOutcome) -> size_t {
+    assert!(!o.is_null());
+    let o = unsafe { &*o };
+    o.uploaded_bytes
+}
+#[no_mangle]
+pub extern "C" fn rust_fileuploadoutcome_failed_len(o: *const FileUploadOutcome) -> size_t {
+    assert!(!o.is_null());
+    let o = unsafe { &*o };
+    o.failed.len()
+}
+#[no_mangle]
+pub extern "C" fn rust_fileuploadoutcome_failed_get(
+    o: *const FileUploadOutcome,
+    idx: size_t,
+) -> *const ApiKey {
+    assert!(!o.is_null());
+    let o = unsafe { &*o };
+    &o.failed[idx]
+}
+
 /// Methods for ApiKey
 #[no_mangle]
 pub extern "C" fn rust_key_get_hgid(k: *const ApiKey) -> HgId {
@@ -14,6 +14,7 @@ use anyhow::Error;
 use edenapi::EdenApi;
 
 use crate::EdenApiServerError;
+use crate::FileUploadOutcome;
 use crate::TreeEntry;
 
 type Client = Arc<dyn EdenApi>;
@@ -70,6 +71,32 @@ pub extern "C" fn rust_treeentryfetch_free(v: TreeEntryFetch) {
     drop(v);
 }
 
+#[repr(C)]
+pub struct FileUploadFetch {
+    ptr: *mut Result<FileUploadOutcome, Error>,
+}
+
+impl From<Result<FileUploadOutcome, Error>> for FileUploadFetch {
+    fn from(v: Result<FileUploadOutcome, Error>) -> Self {
+        let boxed = Box::new(v);
+        Self {
+            ptr: Box::into_raw(boxed),
+        }
+    }
+}
+
+impl Drop for FileUploadFetch {
+    fn drop(&mut self) {
+        let boxed = unsafe { Box::from_raw(self.ptr) };
+        drop(boxed);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rust_fileuploadfetch_free(v: FileUploadFetch) {
+    drop(v);
+}
+
 /// A wrapper type for a Box<String>. When into_raw_parts is stabilized, the Box / extra allocation
 /// can be removed.
 #[repr(C)]
@@ -12,6 +12,7 @@ use anyhow::Error;
 use edenapi::EdenApi;
 
 use crate::EdenApiServerError;
+use crate::FileUploadOutcome;
 use crate::OwnedString;
 use crate::TreeChildEntry;
 use crate::TreeEntry;
@@ -172,3 +173,48 @@ pub extern "C" fn rust_result_treechildentry_err_debug(
     let r = unsafe { &*r };
     r.unwrap_err_debug().into()
 }
+
+// Monomorphization for Result<FileUploadOutcome, Error>
+#[no_mangle]
+pub extern "C" fn rust_result_fileuploadoutcome_ok(
+    r: *const Result<FileUploadOutcome, Error>,
+) -> *const FileUploadOutcome {
+    assert!(!r.is_null());
+    let r = unsafe { &*r };
+    if let Ok(ref v) = r {
+        v
+    } else {
+        ptr::null()
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rust_result_fileuploadoutcome_is_err(
+    r: *const Result<FileUploadOutcome, Error>,
+) -> bool {
+    assert!(!r.is_null());
+    let r = unsafe { &*r };
+    if r.is_err() {
+        true
+    } else {
+        false
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rust_result_fileuploadoutcome_err_display(
+    r: *const Result<FileUploadOutcome, Error>,
+) -> OwnedString {
+    assert!(!r.is_null());
+    let r = unsafe { &*r };
+    r.unwrap_err_display().into()
+}
+
+#[no_mangle]
+pub extern "C" fn rust_result_fileuploadoutcome_err_debug(
+    r: *const Result<FileUploadOutcome, Error>,
+) -> OwnedString {
+    assert!(!r.is_null());
+    let r = unsafe { &*r };
+    r.unwrap_err_debug().into()
+}
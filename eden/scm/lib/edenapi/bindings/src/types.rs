@@ -47,6 +47,25 @@ impl TryFrom<&Key> for ApiKey {
     }
 }
 
+/// A file to upload: the key it is stored under and its content, both
+/// borrowed from the caller for the duration of the call.
+#[repr(C)]
+pub struct FileUpload {
+    key: Key,
+    data: *const u8,
+    data_len: size_t,
+}
+
+impl FileUpload {
+    // Copies the key and content, leaving the caller responsible for
+    // deallocating its own.
+    pub(crate) fn to_owned_parts(&self) -> Result<(ApiKey, Vec<u8>)> {
+        let key = ApiKey::try_from(&self.key)?;
+        let data = unsafe { ptr_len_to_slice(self.data, self.data_len)? };
+        Ok((key, data.to_vec()))
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub enum FileType {
@@ -122,6 +122,7 @@ pub struct HttpClientBuilder {
     max_history: Option<usize>,
     max_location_to_hash: Option<usize>,
     max_commit_mutations: Option<usize>,
+    max_commit_bonsai_mappings: Option<usize>,
     timeout: Option<Duration>,
     debug: bool,
     correlator: Option<String>,
@@ -198,6 +199,7 @@ impl HttpClientBuilder {
         let max_history = get_config(config, "edenapi", "maxhistory")?;
         let max_location_to_hash = get_config(config, "edenapi", "maxlocationtohash")?;
         let max_commit_mutations = get_config(config, "edenapi", "maxcommitmutations")?;
+        let max_commit_bonsai_mappings = get_config(config, "edenapi", "maxcommitbonsaimappings")?;
         let timeout = get_config(config, "edenapi", "timeout")?.map(Duration::from_secs);
         let debug = get_config(config, "edenapi", "debug")?.unwrap_or_default();
         let http_version =
@@ -241,6 +243,7 @@ impl HttpClientBuilder {
             max_history,
             max_location_to_hash,
             max_commit_mutations,
+            max_commit_bonsai_mappings,
             timeout,
             debug,
             correlator: None,
@@ -426,6 +429,7 @@ pub(crate) struct Config {
     pub(crate) max_history: Option<usize>,
     pub(crate) max_location_to_hash: Option<usize>,
     pub(crate) max_commit_mutations: Option<usize>,
+    pub(crate) max_commit_bonsai_mappings: Option<usize>,
     pub(crate) timeout: Option<Duration>,
     pub(crate) debug: bool,
     pub(crate) correlator: Option<String>,
@@ -455,6 +459,7 @@ impl TryFrom<HttpClientBuilder> for Config {
             max_history,
             max_location_to_hash,
             max_commit_mutations,
+            max_commit_bonsai_mappings,
             timeout,
             debug,
             correlator,
@@ -496,6 +501,7 @@ impl TryFrom<HttpClientBuilder> for Config {
             max_history,
             max_location_to_hash,
             max_commit_mutations,
+            max_commit_bonsai_mappings,
             timeout,
             debug,
             correlator,
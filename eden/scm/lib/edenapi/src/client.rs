@@ -25,6 +25,8 @@ use edenapi_types::BonsaiChangesetContent;
 use edenapi_types::BookmarkEntry;
 use edenapi_types::BookmarkRequest;
 use edenapi_types::CloneData;
+use edenapi_types::CommitBonsaiMappingRequest;
+use edenapi_types::CommitBonsaiMappingResponse;
 use edenapi_types::CommitGraphEntry;
 use edenapi_types::CommitGraphRequest;
 use edenapi_types::CommitHashLookupRequest;
@@ -86,6 +88,7 @@ use itertools::Itertools;
 use metrics::Counter;
 use metrics::EntranceGuard;
 use minibytes::Bytes;
+use parking_lot::Mutex;
 use percent_encoding::utf8_percent_encode;
 use percent_encoding::AsciiSet;
 use percent_encoding::NON_ALPHANUMERIC;
@@ -105,6 +108,8 @@ use crate::retryable::RetryableFiles;
 use crate::retryable::RetryableStreamRequest;
 use crate::retryable::RetryableTrees;
 use crate::types::wire::pull::PullFastForwardRequest;
+use crate::DownloadStats;
+use crate::FetchCategory;
 
 /// All non-alphanumeric characters (except hypens, underscores, and periods)
 /// found in the repo's name will be percent-encoded before being used in URLs.
@@ -134,6 +139,7 @@ mod paths {
     pub const COMMIT_HASH_LOOKUP: &str = "commit/hash_lookup";
     pub const COMMIT_GRAPH: &str = "commit/graph";
     pub const COMMIT_MUTATIONS: &str = "commit/mutations";
+    pub const COMMIT_BONSAI_MAPPING: &str = "commit/bonsai_mapping";
     pub const BOOKMARKS: &str = "bookmarks";
     pub const SET_BOOKMARK: &str = "bookmarks/set";
     pub const LAND_STACK: &str = "land";
@@ -156,6 +162,7 @@ pub struct Client {
 pub struct ClientInner {
     config: Config,
     client: HttpClient,
+    download_stats: Arc<Mutex<DownloadStats>>,
 }
 
 impl Client {
@@ -164,7 +171,11 @@ impl Client {
         let client = http_client("edenapi")
             .verbose(config.debug)
             .max_concurrent_requests(config.max_requests.unwrap_or(0));
-        let inner = Arc::new(ClientInner { config, client });
+        let inner = Arc::new(ClientInner {
+            config,
+            client,
+            download_stats: Default::default(),
+        });
         Self { inner }
     }
 
@@ -258,6 +269,20 @@ impl Client {
             .collect()
     }
 
+    /// Count the given requests and the bytes they download towards the
+    /// client's totals for `category`.
+    fn track_downloads(&self, requests: &mut [Request], category: FetchCategory) {
+        self.inner.download_stats.lock().get_mut(category).requests += requests.len();
+        for req in requests {
+            let download_stats = self.inner.download_stats.clone();
+            req.ctx_mut()
+                .event_listeners()
+                .on_download_bytes(move |_req, n| {
+                    download_stats.lock().get_mut(category).downloaded += n;
+                });
+        }
+    }
+
     /// Fetch data from the server without Wire to Api conversion.
     ///
     /// Concurrently performs all of the given HTTP requests, each of
@@ -398,11 +423,12 @@ impl Client {
         let guards = vec![FILES_INFLIGHT.entrance_guard(keys.len())];
 
         let url = self.build_url(paths::FILES, Some(&repo))?;
-        let requests = self.prepare_requests(&url, keys, self.config().max_files, |keys| {
+        let mut requests = self.prepare_requests(&url, keys, self.config().max_files, |keys| {
             let req = FileRequest { keys, reqs: vec![] };
             self.log_request(&req, "files");
             req
         })?;
+        self.track_downloads(&mut requests, FetchCategory::Files);
 
         Ok(self.fetch_guard::<FileEntry>(requests, guards)?)
     }
@@ -420,7 +446,7 @@ impl Client {
         }
 
         let url = self.build_url(paths::TREES, Some(&repo))?;
-        let requests = self.prepare_requests(&url, keys, self.config().max_trees, |keys| {
+        let mut requests = self.prepare_requests(&url, keys, self.config().max_trees, |keys| {
             let req = TreeRequest {
                 keys,
                 attributes: attributes.clone().unwrap_or_default(),
@@ -428,6 +454,7 @@ impl Client {
             self.log_request(&req, "trees");
             req
         })?;
+        self.track_downloads(&mut requests, FetchCategory::Trees);
 
         Ok(self.fetch::<Result<TreeEntry, EdenApiServerError>>(requests)?)
     }
@@ -446,11 +473,12 @@ impl Client {
         let guards = vec![FILES_ATTRS_INFLIGHT.entrance_guard(reqs.len())];
 
         let url = self.build_url(paths::FILES, Some(&repo))?;
-        let requests = self.prepare_requests(&url, reqs, self.config().max_files, |reqs| {
+        let mut requests = self.prepare_requests(&url, reqs, self.config().max_files, |reqs| {
             let req = FileRequest { reqs, keys: vec![] };
             self.log_request(&req, "files");
             req
         })?;
+        self.track_downloads(&mut requests, FetchCategory::Files);
 
         Ok(self.fetch_guard::<FileEntry>(requests, guards)?)
     }
@@ -532,6 +560,10 @@ impl Client {
 
 #[async_trait]
 impl EdenApi for Client {
+    fn download_stats(&self) -> DownloadStats {
+        self.inner.download_stats.lock().clone()
+    }
+
     async fn health(&self) -> Result<ResponseMeta, EdenApiError> {
         let url = self.build_url(paths::HEALTH_CHECK, None)?;
 
@@ -591,11 +623,13 @@ impl EdenApi for Client {
         }
 
         let url = self.build_url(paths::HISTORY, Some(&repo))?;
-        let requests = self.prepare_requests(&url, keys, self.config().max_history, |keys| {
-            let req = HistoryRequest { keys, length };
-            self.log_request(&req, "history");
-            req
-        })?;
+        let mut requests =
+            self.prepare_requests(&url, keys, self.config().max_history, |keys| {
+                let req = HistoryRequest { keys, length };
+                self.log_request(&req, "history");
+                req
+            })?;
+        self.track_downloads(&mut requests, FetchCategory::History);
 
         let Response { entries, stats } = self.fetch::<HistoryResponseChunk>(requests)?;
 
@@ -1214,6 +1248,28 @@ impl EdenApi for Client {
         self.fetch_vec_with_retry::<CommitMutationsResponse>(requests)
             .await
     }
+
+    async fn commit_bonsai_mappings(
+        &self,
+        repo: String,
+        hgids: Vec<HgId>,
+    ) -> Result<Vec<CommitBonsaiMappingResponse>, EdenApiError> {
+        tracing::info!("Requesting bonsai mappings for {} commits", hgids.len());
+        let url = self.build_url(paths::COMMIT_BONSAI_MAPPING, Some(&repo))?;
+        let requests = self.prepare_requests(
+            &url,
+            hgids,
+            self.config().max_commit_bonsai_mappings,
+            |hgids| {
+                let req = CommitBonsaiMappingRequest { hgids };
+                self.log_request(&req, "commit_bonsai_mapping");
+                req
+            },
+        )?;
+
+        self.fetch_vec_with_retry::<CommitBonsaiMappingResponse>(requests)
+            .await
+    }
 }
 
 /// Split up a collection of keys into batches of at most `batch_size`.
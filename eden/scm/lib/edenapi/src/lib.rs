@@ -14,7 +14,10 @@ mod retryable;
 pub use configmodel;
 pub use edenapi_trait::api;
 pub use edenapi_trait::errors;
+pub use edenapi_trait::CategoryStats;
+pub use edenapi_trait::DownloadStats;
 pub use edenapi_trait::Entries;
+pub use edenapi_trait::FetchCategory;
 pub use edenapi_trait::Response;
 pub use edenapi_trait::ResponseMeta;
 pub use edenapi_types as types;
@@ -14,6 +14,7 @@ use edenapi_types::AnyId;
 use edenapi_types::BonsaiChangesetContent;
 use edenapi_types::BookmarkEntry;
 use edenapi_types::CloneData;
+use edenapi_types::CommitBonsaiMappingResponse;
 use edenapi_types::CommitGraphEntry;
 use edenapi_types::CommitHashLookupResponse;
 use edenapi_types::CommitHashToLocationResponse;
@@ -48,6 +49,7 @@ use types::Key;
 use crate::errors::EdenApiError;
 use crate::response::Response;
 use crate::response::ResponseMeta;
+use crate::stats::DownloadStats;
 
 #[async_trait]
 pub trait EdenApi: Send + Sync + 'static {
@@ -55,6 +57,12 @@ pub trait EdenApi: Send + Sync + 'static {
         Err(EdenApiError::NotSupported)
     }
 
+    /// Bytes downloaded and requests sent by this client so far, broken down
+    /// by the kind of data fetched.
+    fn download_stats(&self) -> DownloadStats {
+        DownloadStats::default()
+    }
+
     async fn capabilities(&self, repo: String) -> Result<Vec<String>, EdenApiError> {
         let _ = repo;
         Err(EdenApiError::NotSupported)
@@ -314,4 +322,15 @@ pub trait EdenApi: Send + Sync + 'static {
         let _ = (repo, commits);
         Err(EdenApiError::NotSupported)
     }
+
+    /// Look up the bonsai changeset for each of the given hg changesets.
+    /// There is one response per hgid, with no bonsai if the server has no mapping.
+    async fn commit_bonsai_mappings(
+        &self,
+        repo: String,
+        hgids: Vec<HgId>,
+    ) -> Result<Vec<CommitBonsaiMappingResponse>, EdenApiError> {
+        let _ = (repo, hgids);
+        Err(EdenApiError::NotSupported)
+    }
 }
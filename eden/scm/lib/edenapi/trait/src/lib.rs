@@ -8,6 +8,7 @@
 pub mod api;
 pub mod errors;
 pub mod response;
+pub mod stats;
 
 // Re-export for convenience.
 pub use configmodel;
@@ -19,5 +20,8 @@ pub use crate::errors::EdenApiError;
 pub use crate::response::Entries;
 pub use crate::response::Response;
 pub use crate::response::ResponseMeta;
+pub use crate::stats::CategoryStats;
+pub use crate::stats::DownloadStats;
+pub use crate::stats::FetchCategory;
 
 pub type Result<T> = std::result::Result<T, EdenApiError>;
@@ -0,0 +1,47 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+/// The kinds of data fetch whose transfers are accounted separately.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FetchCategory {
+    Files,
+    History,
+    Trees,
+}
+
+/// Transfer totals for one `FetchCategory`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CategoryStats {
+    pub downloaded: usize,
+    pub requests: usize,
+}
+
+/// Transfer totals for each `FetchCategory` over the lifetime of a client.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DownloadStats {
+    pub files: CategoryStats,
+    pub history: CategoryStats,
+    pub trees: CategoryStats,
+}
+
+impl DownloadStats {
+    pub fn get(&self, category: FetchCategory) -> &CategoryStats {
+        match category {
+            FetchCategory::Files => &self.files,
+            FetchCategory::History => &self.history,
+            FetchCategory::Trees => &self.trees,
+        }
+    }
+
+    pub fn get_mut(&mut self, category: FetchCategory) -> &mut CategoryStats {
+        match category {
+            FetchCategory::Files => &mut self.files,
+            FetchCategory::History => &mut self.history,
+            FetchCategory::Trees => &mut self.trees,
+        }
+    }
+}
@@ -639,6 +639,43 @@ impl Arbitrary for CommitMutationsResponse {
     }
 }
 
+#[auto_wire]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CommitBonsaiMappingRequest {
+    #[id(1)]
+    pub hgids: Vec<HgId>,
+}
+
+/// The bonsai changeset for an hg changeset, or None if the server has no
+/// mapping for it, e.g. because the commit is not derived yet.
+#[auto_wire]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CommitBonsaiMappingResponse {
+    #[id(1)]
+    pub hgid: HgId,
+    #[id(2)]
+    pub bonsai: Option<BonsaiChangesetId>,
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for CommitBonsaiMappingRequest {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            hgids: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "for-tests"))]
+impl Arbitrary for CommitBonsaiMappingResponse {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            hgid: Arbitrary::arbitrary(g),
+            bonsai: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
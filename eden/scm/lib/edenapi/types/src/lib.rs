@@ -66,6 +66,8 @@ pub use crate::bookmark::SetBookmarkRequest;
 pub use crate::commit::make_hash_lookup_request;
 pub use crate::commit::BonsaiChangesetContent;
 pub use crate::commit::BonsaiFileChange;
+pub use crate::commit::CommitBonsaiMappingRequest;
+pub use crate::commit::CommitBonsaiMappingResponse;
 pub use crate::commit::CommitGraphEntry;
 pub use crate::commit::CommitGraphRequest;
 pub use crate::commit::CommitHashLookupRequest;
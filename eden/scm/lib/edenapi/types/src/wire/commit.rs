@@ -22,6 +22,8 @@ use crate::commit::CommitHashToLocationRequestBatch;
 use crate::commit::CommitHashToLocationResponse;
 use crate::commit::EphemeralPrepareResponse;
 pub use crate::commit::WireBonsaiExtra;
+pub use crate::commit::WireCommitBonsaiMappingRequest;
+pub use crate::commit::WireCommitBonsaiMappingResponse;
 pub use crate::commit::WireCommitGraphEntry;
 pub use crate::commit::WireCommitGraphRequest;
 pub use crate::commit::WireCommitLocationToHashRequest;
@@ -477,5 +479,7 @@ mod tests {
         WireFetchSnapshotResponse,
         WireCommitMutationsRequest,
         WireCommitMutationsResponse,
+        WireCommitBonsaiMappingRequest,
+        WireCommitBonsaiMappingResponse,
     );
 }